@@ -0,0 +1,545 @@
+use crate::{json::JsonValue, text::SourceText, typeck::TypeTable};
+
+use super::{
+    lexer::TextSpan, ASTExpression, ASTExpressionKind, ASTNodeId, ASTPattern, ASTPatternKind, ASTStatement,
+    ASTStatementKind, ASTTypeExpression, ASTTypeExpressionKind, Ast,
+};
+
+// Serializes the tree as JSON so tools can map source ranges to node ids
+// (and the inferred types recorded for them) without re-parsing
+pub struct ASTJsonDumper<'a> {
+    text: &'a SourceText,
+    types: &'a TypeTable,
+}
+
+impl<'a> ASTJsonDumper<'a> {
+    pub fn new(text: &'a SourceText, types: &'a TypeTable) -> Self {
+        Self { text, types }
+    }
+
+    pub fn dump(&self, ast: &Ast) -> JsonValue {
+        JsonValue::object(vec![
+            ("file", JsonValue::string(self.text.file_name())),
+            (
+                "statements",
+                JsonValue::Array(ast.statements.iter().map(|stmt| self.statement(stmt)).collect()),
+            ),
+        ])
+    }
+
+    fn statement(&self, stmt: &ASTStatement) -> JsonValue {
+        let (kind, mut fields) = match &stmt.kind {
+            ASTStatementKind::Expression(expr) => ("expression", vec![("expression", self.expression(expr))]),
+            ASTStatementKind::LetStatement(let_statement) => (
+                "let",
+                vec![
+                    ("pattern", self.pattern(&let_statement.pattern)),
+                    (
+                        "type_annotation",
+                        let_statement
+                            .type_annotation
+                            .as_ref()
+                            .map_or(JsonValue::Null, |annotation| self.type_expression(annotation)),
+                    ),
+                    ("initializer", self.expression(&let_statement.initializer)),
+                ],
+            ),
+            ASTStatementKind::Struct(struct_statement) => {
+                let fields = struct_statement
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        JsonValue::object(vec![
+                            ("name", JsonValue::string(&field.name.span.literal)),
+                            ("type_annotation", self.type_expression(&field.type_annotation)),
+                        ])
+                    })
+                    .collect();
+                (
+                    "struct",
+                    vec![
+                        ("name", JsonValue::string(&struct_statement.name.span.literal)),
+                        ("fields", JsonValue::Array(fields)),
+                    ],
+                )
+            }
+            ASTStatementKind::Enum(enum_statement) => {
+                let variants = enum_statement
+                    .variants
+                    .iter()
+                    .map(|variant| {
+                        JsonValue::object(vec![
+                            ("name", JsonValue::string(&variant.name.span.literal)),
+                            (
+                                "payload",
+                                JsonValue::Array(variant.payload.iter().map(|ty| self.type_expression(ty)).collect()),
+                            ),
+                        ])
+                    })
+                    .collect();
+                (
+                    "enum",
+                    vec![
+                        ("name", JsonValue::string(&enum_statement.name.span.literal)),
+                        ("variants", JsonValue::Array(variants)),
+                    ],
+                )
+            }
+            ASTStatementKind::For(for_statement) => (
+                "for",
+                vec![
+                    ("variable", JsonValue::string(&for_statement.variable.span.literal)),
+                    ("iterable", self.expression(&for_statement.iterable)),
+                    (
+                        "body",
+                        JsonValue::Array(for_statement.body.iter().map(|stmt| self.statement(stmt)).collect()),
+                    ),
+                ],
+            ),
+        };
+        fields.splice(0..0, self.header(stmt.id, kind, &stmt.span));
+        JsonValue::object(fields)
+    }
+
+    fn expression(&self, expr: &ASTExpression) -> JsonValue {
+        let (kind, mut fields) = match &expr.kind {
+            ASTExpressionKind::Number(number) => ("number", vec![("value", JsonValue::Number(number.number))]),
+            ASTExpressionKind::Binary(binary) => (
+                "binary",
+                vec![
+                    ("operator", JsonValue::string(&binary.operator.token.span.literal)),
+                    ("left", self.expression(&binary.left)),
+                    ("right", self.expression(&binary.right)),
+                ],
+            ),
+            ASTExpressionKind::Parenthesized(parenthesized) => (
+                "parenthesized",
+                vec![("expression", self.expression(&parenthesized.expression))],
+            ),
+            ASTExpressionKind::Variable(variable) => {
+                ("variable", vec![("name", JsonValue::string(variable.identifier()))])
+            }
+            ASTExpressionKind::Assignment(assignment) => (
+                "assignment",
+                vec![
+                    ("name", JsonValue::string(&assignment.identifier.span.literal)),
+                    ("expression", self.expression(&assignment.expression)),
+                ],
+            ),
+            ASTExpressionKind::String(string) => ("string", vec![("value", JsonValue::string(&string.value))]),
+            ASTExpressionKind::Map(map) => {
+                let entries = map
+                    .entries
+                    .iter()
+                    .map(|entry| {
+                        JsonValue::object(vec![("key", self.expression(&entry.key)), ("value", self.expression(&entry.value))])
+                    })
+                    .collect();
+                ("map", vec![("entries", JsonValue::Array(entries))])
+            }
+            ASTExpressionKind::List(list) => ("list", vec![("elements", self.expressions(&list.elements))]),
+            ASTExpressionKind::Index(index) => (
+                "index",
+                vec![("target", self.expression(&index.target)), ("index", self.expression(&index.index))],
+            ),
+            ASTExpressionKind::IndexAssignment(assignment) => (
+                "index_assignment",
+                vec![
+                    ("target", self.expression(&assignment.target)),
+                    ("index", self.expression(&assignment.index)),
+                    ("expression", self.expression(&assignment.expression)),
+                ],
+            ),
+            ASTExpressionKind::Slice(slice) => {
+                let bound = |bound: &Option<Box<ASTExpression>>| {
+                    bound.as_ref().map_or(JsonValue::Null, |bound| self.expression(bound))
+                };
+                (
+                    "slice",
+                    vec![
+                        ("target", self.expression(&slice.target)),
+                        ("start", bound(&slice.start)),
+                        ("end", bound(&slice.end)),
+                    ],
+                )
+            }
+            ASTExpressionKind::Call(call) => (
+                "call",
+                vec![
+                    ("callee", JsonValue::string(&call.callee.span.literal)),
+                    ("arguments", self.expressions(&call.arguments)),
+                ],
+            ),
+            ASTExpressionKind::Struct(struct_expr) => {
+                let fields = struct_expr
+                    .fields
+                    .iter()
+                    .map(|field| {
+                        JsonValue::object(vec![
+                            ("name", JsonValue::string(&field.name.span.literal)),
+                            ("value", self.expression(&field.value)),
+                        ])
+                    })
+                    .collect();
+                (
+                    "struct",
+                    vec![
+                        ("name", JsonValue::string(&struct_expr.name.span.literal)),
+                        ("fields", JsonValue::Array(fields)),
+                    ],
+                )
+            }
+            ASTExpressionKind::Field(field) => (
+                "field",
+                vec![
+                    ("target", self.expression(&field.target)),
+                    ("field", JsonValue::string(&field.field.span.literal)),
+                ],
+            ),
+            ASTExpressionKind::FieldAssignment(assignment) => (
+                "field_assignment",
+                vec![
+                    ("target", self.expression(&assignment.target)),
+                    ("field", JsonValue::string(&assignment.field.span.literal)),
+                    ("expression", self.expression(&assignment.expression)),
+                ],
+            ),
+            ASTExpressionKind::Match(match_expr) => {
+                let arms = match_expr
+                    .arms
+                    .iter()
+                    .map(|arm| {
+                        JsonValue::object(vec![("pattern", self.pattern(&arm.pattern)), ("body", self.expression(&arm.body))])
+                    })
+                    .collect();
+                (
+                    "match",
+                    vec![("scrutinee", self.expression(&match_expr.scrutinee)), ("arms", JsonValue::Array(arms))],
+                )
+            }
+            ASTExpressionKind::Tuple(tuple) => ("tuple", vec![("elements", self.expressions(&tuple.elements))]),
+            ASTExpressionKind::Range(range) => (
+                "range",
+                vec![
+                    ("start", self.expression(&range.start)),
+                    ("end", self.expression(&range.end)),
+                    ("inclusive", JsonValue::Bool(range.inclusive())),
+                ],
+            ),
+            ASTExpressionKind::Error(_) => ("error", vec![]),
+        };
+        let inferred = self
+            .types
+            .get(expr.id)
+            .map_or(JsonValue::Null, |ty| JsonValue::string(&ty.to_string()));
+        fields.insert(0, ("type", inferred));
+        fields.splice(0..0, self.header(expr.id, kind, &expr.span));
+        JsonValue::object(fields)
+    }
+
+    fn expressions(&self, exprs: &[ASTExpression]) -> JsonValue {
+        JsonValue::Array(exprs.iter().map(|expr| self.expression(expr)).collect())
+    }
+
+    fn pattern(&self, pattern: &ASTPattern) -> JsonValue {
+        let (kind, mut fields) = match &pattern.kind {
+            ASTPatternKind::Wildcard => ("wildcard", vec![]),
+            ASTPatternKind::Identifier(identifier) => {
+                ("identifier", vec![("name", JsonValue::string(&identifier.span.literal))])
+            }
+            ASTPatternKind::Number(number) => ("number", vec![("value", JsonValue::Number(*number))]),
+            ASTPatternKind::String(value) => ("string", vec![("value", JsonValue::string(value))]),
+            ASTPatternKind::Variant(name, payload) => (
+                "variant",
+                vec![
+                    ("name", JsonValue::string(&name.span.literal)),
+                    ("payload", self.patterns(payload)),
+                ],
+            ),
+            ASTPatternKind::Tuple(elements) => ("tuple", vec![("elements", self.patterns(elements))]),
+            ASTPatternKind::List(elements) => ("list", vec![("elements", self.patterns(elements))]),
+            ASTPatternKind::Struct(name, fields, rest) => {
+                let fields = fields
+                    .iter()
+                    .map(|field| {
+                        JsonValue::object(vec![
+                            ("name", JsonValue::string(&field.name.span.literal)),
+                            ("pattern", self.pattern(&field.pattern)),
+                        ])
+                    })
+                    .collect();
+                (
+                    "struct",
+                    vec![
+                        ("name", JsonValue::string(&name.span.literal)),
+                        ("fields", JsonValue::Array(fields)),
+                        ("rest", JsonValue::Bool(*rest)),
+                    ],
+                )
+            }
+        };
+        let inferred = self
+            .types
+            .get(pattern.id)
+            .map_or(JsonValue::Null, |ty| JsonValue::string(&ty.to_string()));
+        fields.insert(0, ("type", inferred));
+        fields.splice(0..0, self.header(pattern.id, kind, &pattern.span));
+        JsonValue::object(fields)
+    }
+
+    fn patterns(&self, patterns: &[ASTPattern]) -> JsonValue {
+        JsonValue::Array(patterns.iter().map(|pattern| self.pattern(pattern)).collect())
+    }
+
+    fn type_expression(&self, type_expression: &ASTTypeExpression) -> JsonValue {
+        let (kind, mut fields) = match &type_expression.kind {
+            ASTTypeExpressionKind::Named(name) => ("named", vec![("name", JsonValue::string(&name.span.literal))]),
+            ASTTypeExpressionKind::Array(element) => ("array", vec![("element", self.type_expression(element))]),
+            ASTTypeExpressionKind::Map(key, value) => (
+                "map",
+                vec![("key", self.type_expression(key)), ("value", self.type_expression(value))],
+            ),
+            ASTTypeExpressionKind::Function(parameters, return_type) => (
+                "function",
+                vec![
+                    (
+                        "parameters",
+                        JsonValue::Array(parameters.iter().map(|p| self.type_expression(p)).collect()),
+                    ),
+                    ("return", self.type_expression(return_type)),
+                ],
+            ),
+            ASTTypeExpressionKind::Optional(inner) => ("optional", vec![("inner", self.type_expression(inner))]),
+            ASTTypeExpressionKind::Tuple(elements) => (
+                "tuple",
+                vec![(
+                    "elements",
+                    JsonValue::Array(elements.iter().map(|element| self.type_expression(element)).collect()),
+                )],
+            ),
+            ASTTypeExpressionKind::Error(_) => ("error", vec![]),
+        };
+        fields.splice(0..0, self.header(type_expression.id, kind, &type_expression.span));
+        JsonValue::object(fields)
+    }
+
+    // Fields shared by every node: id, kind, byte span and 1-based line/column range
+    fn header(&self, id: ASTNodeId, kind: &'static str, span: &TextSpan) -> Vec<(&'static str, JsonValue)> {
+        let position = |pos: usize| {
+            let (line, column) = self.text.line_column(pos);
+            JsonValue::object(vec![
+                ("line", JsonValue::Number(line as i64)),
+                ("column", JsonValue::Number(column as i64)),
+            ])
+        };
+        vec![
+            ("id", JsonValue::Number(id.0 as i64)),
+            ("kind", JsonValue::string(kind)),
+            (
+                "span",
+                JsonValue::object(vec![
+                    ("start", JsonValue::Number(span.start as i64)),
+                    ("end", JsonValue::Number(span.end as i64)),
+                ]),
+            ),
+            (
+                "range",
+                JsonValue::object(vec![("start", position(span.start)), ("end", position(span.end))]),
+            ),
+        ]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{collections::HashSet, iter::Peekable, str::Chars};
+
+    use crate::{compilation_unit::CompilationUnit, json::JsonValue, lints::LintConfig, text::SourceText};
+
+    use super::ASTJsonDumper;
+
+    // Statements, expressions, patterns and type expressions of each kind
+    const PROGRAM: &str = "\
+struct Point { x: int, y: [string] }
+enum Shape { Circle(int), Pair((int, int)) }
+let p: Point = Point { x: 1, y: [\"é\"] }
+let m: {string: int} = { \"a\": 1 }
+let (a, _) = (p.x * 2 + 1, m[\"a\"])
+for i in 0..a { a = (a - i) / 2 }
+match Circle(a) { Circle(r) => r, Pair(_) => 0 }";
+
+    // Reads back what `JsonValue` writes, failing on anything else
+    struct Reader<'a> {
+        chars: Peekable<Chars<'a>>,
+    }
+
+    impl Reader<'_> {
+        fn parse(text: &str) -> JsonValue {
+            let mut reader = Reader { chars: text.chars().peekable() };
+            let value = reader.value();
+            assert_eq!(reader.chars.next(), None, "trailing characters");
+            value
+        }
+
+        fn expect(&mut self, expected: char) {
+            assert_eq!(self.chars.next(), Some(expected));
+        }
+
+        fn value(&mut self) -> JsonValue {
+            match self.chars.peek().copied() {
+                Some('{') => {
+                    let mut fields = Vec::new();
+                    self.sequence('{', '}', |reader| {
+                        let key = reader.string();
+                        reader.expect(':');
+                        fields.push((key, reader.value()));
+                    });
+                    JsonValue::Object(fields)
+                }
+                Some('[') => {
+                    let mut values = Vec::new();
+                    self.sequence('[', ']', |reader| values.push(reader.value()));
+                    JsonValue::Array(values)
+                }
+                Some('"') => JsonValue::String(self.string()),
+                Some(c) if c == '-' || c.is_ascii_digit() => {
+                    let mut number = String::new();
+                    while let Some(c) = self.chars.next_if(|c| *c == '-' || c.is_ascii_digit()) {
+                        number.push(c);
+                    }
+                    JsonValue::Number(number.parse().unwrap())
+                }
+                _ => {
+                    let word: String = std::iter::from_fn(|| self.chars.next_if(char::is_ascii_alphabetic)).collect();
+                    match word.as_str() {
+                        "null" => JsonValue::Null,
+                        "true" => JsonValue::Bool(true),
+                        "false" => JsonValue::Bool(false),
+                        _ => panic!("unexpected `{}`", word),
+                    }
+                }
+            }
+        }
+
+        fn sequence(&mut self, open: char, close: char, mut item: impl FnMut(&mut Self)) {
+            self.expect(open);
+            if self.chars.next_if_eq(&close).is_some() {
+                return;
+            }
+            loop {
+                item(self);
+                match self.chars.next() {
+                    Some(',') => continue,
+                    Some(c) if c == close => return,
+                    c => panic!("expected `,` or `{}`, found {:?}", close, c),
+                }
+            }
+        }
+
+        fn string(&mut self) -> String {
+            self.expect('"');
+            let mut value = String::new();
+            loop {
+                match self.chars.next().expect("unterminated string") {
+                    '"' => return value,
+                    '\\' => match self.chars.next() {
+                        Some('n') => value.push('\n'),
+                        Some('r') => value.push('\r'),
+                        Some('t') => value.push('\t'),
+                        Some('u') => {
+                            let hex: String = self.chars.by_ref().take(4).collect();
+                            value.push(char::from_u32(u32::from_str_radix(&hex, 16).unwrap()).unwrap());
+                        }
+                        Some(c) => value.push(c),
+                        None => panic!("unterminated escape"),
+                    },
+                    c => value.push(c),
+                }
+            }
+        }
+    }
+
+    fn field<'v>(value: &'v JsonValue, name: &str) -> &'v JsonValue {
+        match value {
+            JsonValue::Object(fields) => &fields.iter().find(|(key, _)| key == name).unwrap().1,
+            _ => panic!("expected an object with `{}`", name),
+        }
+    }
+
+    fn number(value: &JsonValue) -> usize {
+        match value {
+            JsonValue::Number(number) => *number as usize,
+            _ => panic!("expected a number"),
+        }
+    }
+
+    fn position(value: &JsonValue) -> (usize, usize) {
+        (number(field(value, "line")), number(field(value, "column")))
+    }
+
+    // Every object carrying an `id`, outermost first
+    fn nodes<'v>(value: &'v JsonValue, found: &mut Vec<&'v JsonValue>) {
+        match value {
+            JsonValue::Object(fields) => {
+                if fields.iter().any(|(key, _)| key == "id") {
+                    found.push(value);
+                }
+                fields.iter().for_each(|(_, value)| nodes(value, found));
+            }
+            JsonValue::Array(values) => values.iter().for_each(|value| nodes(value, found)),
+            _ => {}
+        }
+    }
+
+    fn dump(input: &str) -> (SourceText, String) {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        assert!(!unit.has_errors());
+        let dumped = ASTJsonDumper::new(&unit.text, &unit.types).dump(&unit.ast).to_string();
+        (unit.text, dumped)
+    }
+
+    #[test]
+    fn gives_every_node_a_unique_id_and_the_range_of_its_span() {
+        let (text, dumped) = dump(PROGRAM);
+        let tree = Reader::parse(&dumped);
+        let mut found = Vec::new();
+        nodes(&tree, &mut found);
+
+        let mut ids = HashSet::new();
+        let mut kinds = HashSet::new();
+        for node in &found {
+            assert!(ids.insert(number(field(node, "id"))), "id reused by {}", node);
+            let span = field(node, "span");
+            let (start, end) = (number(field(span, "start")), number(field(span, "end")));
+            let range = field(node, "range");
+            assert_eq!(position(field(range, "start")), text.line_column(start), "{}", node);
+            assert_eq!(position(field(range, "end")), text.line_column(end), "{}", node);
+            if let JsonValue::String(kind) = field(node, "kind") {
+                kinds.insert(kind.as_str());
+            }
+        }
+        // A type expression, a pattern, a statement and an expression
+        for kind in ["named", "tuple", "wildcard", "identifier", "variant", "let", "for", "binary", "match"] {
+            assert!(kinds.contains(kind), "no `{}` node", kind);
+        }
+    }
+
+    #[test]
+    fn spans_binary_expressions_from_their_first_token_to_their_last() {
+        let (text, dumped) = dump(PROGRAM);
+        let tree = Reader::parse(&dumped);
+        let mut found = Vec::new();
+        nodes(&tree, &mut found);
+
+        let span_text = |node: &JsonValue| {
+            let span = field(node, "span");
+            text.text()[number(field(span, "start"))..number(field(span, "end"))].to_string()
+        };
+        let binaries: Vec<_> = found
+            .iter()
+            .filter(|node| matches!(field(node, "kind"), JsonValue::String(kind) if kind == "binary"))
+            .map(|node| span_text(node))
+            .collect();
+        assert_eq!(binaries, ["p.x * 2 + 1", "p.x * 2", "(a - i) / 2", "a - i"]);
+    }
+}
@@ -1,50 +1,386 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
-use crate::ast::{ASTBinaryOperatorKind, ASTVisitor};
+use crate::{
+    ast::{ASTBinaryOperatorKind, ASTExpressionKind, ASTPattern, ASTPatternKind, ASTVisitor},
+    diagnostics::DiagnosticsBagCell,
+    runtime::{self, AccessError, Builtin, Map, Value},
+};
 
 pub struct ASTEvaluator {
-    pub last_value: Option<i64>,
-    pub variables: HashMap<String, i64>
+    pub last_value: Option<Value>,
+    scopes: Vec<HashMap<String, Value>>, // Innermost last; match arms and loop bodies push one
+    structs: HashMap<String, Vec<String>>, // Field names of each struct, in declaration order
+    variants: HashSet<String>,
+    failed: bool, // Set by the first runtime error, which stops the program
+    diagnostics_bag: DiagnosticsBagCell,
 }
 
 impl ASTEvaluator {
-    pub fn new() -> Self {
-        Self { last_value: None, variables: HashMap::new() }
+    pub fn new(diagnostics_bag: DiagnosticsBagCell) -> Self {
+        Self {
+            last_value: None,
+            scopes: vec![HashMap::new()],
+            structs: HashMap::new(),
+            variants: HashSet::new(),
+            failed: false,
+            diagnostics_bag,
+        }
+    }
+
+    fn value(&self) -> Value {
+        self.last_value.clone().unwrap()
+    }
+
+    fn variable(&self, name: &str) -> Option<&Value> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    // Assigns to the innermost variable of that name; the type checker makes
+    // sure there is one
+    fn assign(&mut self, name: &str, value: Value) {
+        if let Some(variable) = self.scopes.iter_mut().rev().find_map(|scope| scope.get_mut(name)) {
+            *variable = value;
+        }
+    }
+
+    // Whether `value` matches `pattern`, collecting the variables it binds.
+    // When `lengths_checked`, as in `let`, a list of another length than its
+    // pattern is a runtime error rather than a mismatch.
+    fn matches(
+        &mut self,
+        pattern: &ASTPattern,
+        value: &Value,
+        bindings: &mut Vec<(String, Value)>,
+        lengths_checked: bool,
+    ) -> bool {
+        match &pattern.kind {
+            ASTPatternKind::Wildcard => true,
+            ASTPatternKind::Identifier(identifier) if self.variants.contains(&identifier.span.literal) => {
+                runtime::is_variant(value, &identifier.span.literal).as_bool()
+            }
+            ASTPatternKind::Identifier(identifier) => {
+                bindings.push((identifier.span.literal.clone(), value.clone()));
+                true
+            }
+            ASTPatternKind::Number(number) => runtime::equals(value, &Value::Int(*number)).as_bool(),
+            ASTPatternKind::String(string) => runtime::equals(value, &Value::string(string)).as_bool(),
+            ASTPatternKind::Variant(name, payload) => {
+                runtime::is_variant(value, &name.span.literal).as_bool()
+                    && payload.iter().enumerate().all(|(index, pattern)| {
+                        self.matches(pattern, &runtime::payload(value, index), bindings, lengths_checked)
+                    })
+            }
+            ASTPatternKind::Tuple(elements) => elements.iter().enumerate().all(|(index, pattern)| {
+                self.matches(pattern, &runtime::element(value, index), bindings, lengths_checked)
+            }),
+            ASTPatternKind::List(elements) => {
+                let length_matches = match lengths_checked {
+                    true => self.check_access(runtime::check_length(value, elements.len()), &pattern.span).is_some(),
+                    false => runtime::length(value) == elements.len() as i64,
+                };
+                length_matches
+                    && elements.iter().enumerate().all(|(index, pattern)| {
+                        let element = value.as_list().borrow()[index].clone();
+                        self.matches(pattern, &element, bindings, lengths_checked)
+                    })
+            }
+            ASTPatternKind::Struct(_, fields, _) => fields.iter().all(|field| {
+                let value = runtime::field(value, &field.name.span.literal);
+                self.matches(&field.pattern, &value, bindings, lengths_checked)
+            }),
+        }
+    }
+
+    // The value of a failed list or map operation is never observed, as with
+    // arithmetic
+    fn check_access<T>(&mut self, result: Result<T, AccessError>, span: &super::lexer::TextSpan) -> Option<T> {
+        match result {
+            Ok(value) => Some(value),
+            Err(error) => {
+                self.failed = true;
+                self.diagnostics_bag.borrow_mut().report_access_error(&error, span);
+                self.last_value = Some(Value::Int(0));
+                None
+            }
+        }
     }
 }
 
 impl ASTVisitor for ASTEvaluator {
+    fn visit_statement(&mut self, stmt: &super::ASTStatement) {
+        if !self.failed {
+            self.do_visit_statement(stmt)
+        }
+    }
+
     fn visit_number_expression(&mut self, number: &super::ASTNumberExpression) {
-        self.last_value = Some(number.number);
+        self.last_value = Some(Value::Int(number.number));
     }
 
-    fn visit_binary_expression(&mut self, binary_expr: &super::ASTBinaryExpression) {
+    // Binary expressions are evaluated here, where their span is known for
+    // reporting runtime errors
+    fn visit_expression(&mut self, expr: &super::ASTExpression) {
+        let ASTExpressionKind::Binary(binary_expr) = &expr.kind else {
+            return self.do_visit_expression(expr);
+        };
         self.visit_expression(&binary_expr.left);
-        let left = self.last_value.unwrap();
+        let left = self.value();
         self.visit_expression(&binary_expr.right);
-        let right = self.last_value.unwrap();
-        self.last_value = Some(
+        let right = self.value();
+        if self.failed {
+            return;
+        }
+        let (left, right) = (left.as_int(), right.as_int());
+        let result = match binary_expr.operator.kind {
+            ASTBinaryOperatorKind::Add => left.checked_add(right),
+            ASTBinaryOperatorKind::Subtract => left.checked_sub(right),
+            ASTBinaryOperatorKind::Multiply => left.checked_mul(right),
+            ASTBinaryOperatorKind::Divide => left.checked_div(right),
+        };
+        if result.is_none() {
+            self.failed = true;
+            let mut diagnostics_bag = self.diagnostics_bag.borrow_mut();
             match binary_expr.operator.kind {
-                ASTBinaryOperatorKind::Add => left + right,
-                ASTBinaryOperatorKind::Subtract => left - right,
-                ASTBinaryOperatorKind::Multiply => left * right,
-                ASTBinaryOperatorKind::Divide => left / right,
+                ASTBinaryOperatorKind::Divide if right == 0 => diagnostics_bag.report_division_by_zero(&expr.span, None),
+                _ => diagnostics_bag.report_arithmetic_overflow(
+                    &binary_expr.operator.token.span.literal,
+                    left,
+                    right,
+                    &expr.span,
+                ),
             }
-        )
+        }
+        // A failed operation still yields a value so the rest of the
+        // expression can unwind; it is never observed
+        self.last_value = Some(Value::Int(result.unwrap_or_default()));
     }
-    
-    fn visit_error(&mut self, span: &super::lexer::TextSpan) {
+
+    fn visit_error(&mut self, _span: &super::lexer::TextSpan) {
         todo!()
     }
 
+    // The type checker makes sure the pattern matches, but for the length of
+    // lists
     fn visit_let_statement(&mut self, let_statement: &super::ASTLetStatement) {
         self.visit_expression(&let_statement.initializer);
-        self.variables.insert(let_statement.identifier.span.literal.clone(), self.last_value.unwrap());
+        if self.failed {
+            return;
+        }
+        let mut bindings = Vec::new();
+        if self.matches(&let_statement.pattern, &self.value(), &mut bindings, true) {
+            self.scopes.last_mut().unwrap().extend(bindings);
+        }
+    }
+
+    fn visit_struct_statement(&mut self, struct_statement: &super::ASTStructStatement) {
+        let fields = struct_statement.fields.iter().map(|field| field.name.span.literal.clone()).collect();
+        self.structs.insert(struct_statement.name.span.literal.clone(), fields);
+    }
+
+    fn visit_enum_statement(&mut self, enum_statement: &super::ASTEnumStatement) {
+        for variant in &enum_statement.variants {
+            self.variants.insert(variant.name.span.literal.clone());
+        }
     }
-    
+
+    fn visit_assignment_expression(&mut self, assignment_expr: &super::ASTAssignmentExpression) {
+        self.visit_expression(&assignment_expr.expression);
+        self.assign(&assignment_expr.identifier.span.literal, self.value());
+    }
+
     fn visit_variable_expression(&mut self, variable_expression: &super::ASTVariableExpression) {
         let literal = &variable_expression.identifier.span.literal;
-        self.last_value = Some(*self.variables.get(literal).unwrap())
+        self.last_value = Some(match self.variable(literal) {
+            Some(value) => value.clone(),
+            None => Value::variant(literal, Vec::new()),
+        })
+    }
+
+    // Arms are tried in order; the type checker makes sure one matches
+    fn visit_match_expression(&mut self, match_expr: &super::ASTMatchExpression) {
+        self.visit_expression(&match_expr.scrutinee);
+        if self.failed {
+            return;
+        }
+        let value = self.value();
+        for arm in &match_expr.arms {
+            let mut bindings = Vec::new();
+            if !self.matches(&arm.pattern, &value, &mut bindings, false) {
+                continue;
+            }
+            // Bindings are only visible in the arm
+            self.scopes.push(bindings.into_iter().collect());
+            self.visit_expression(&arm.body);
+            self.scopes.pop();
+            return;
+        }
+        unreachable!("the type checker rejects matches that are not exhaustive")
+    }
+
+    // Each item gets a fresh scope holding the loop variable. The loop is a
+    // statement, so the value of the program is left as it was.
+    fn visit_for_statement(&mut self, for_statement: &super::ASTForStatement) {
+        let last_value = self.last_value.take();
+        self.visit_expression(&for_statement.iterable);
+        let items = runtime::iterate(&self.value());
+        for index in 0..runtime::length(&items) {
+            if self.failed {
+                break;
+            }
+            let item = runtime::get(&items, &Value::Int(index))
+                .unwrap_or_else(|_| unreachable!("indexes are below the number of items"));
+            self.scopes.push(HashMap::from([(for_statement.variable.span.literal.clone(), item)]));
+            for statement in &for_statement.body {
+                self.visit_statement(statement);
+            }
+            self.scopes.pop();
+        }
+        self.last_value = last_value;
+    }
+
+    fn visit_range_expression(&mut self, range_expr: &super::ASTRangeExpression) {
+        self.visit_expression(&range_expr.start);
+        let start = self.value().as_int();
+        self.visit_expression(&range_expr.end);
+        let end = self.value().as_int();
+        self.last_value = Some(Value::Range { start, end, inclusive: range_expr.inclusive() });
+    }
+
+    fn visit_tuple_expression(&mut self, tuple_expr: &super::ASTTupleExpression) {
+        let mut elements = Vec::new();
+        for element in &tuple_expr.elements {
+            self.visit_expression(element);
+            elements.push(self.value());
+        }
+        self.last_value = Some(Value::tuple(elements));
+    }
+
+    fn visit_list_expression(&mut self, list_expr: &super::ASTListExpression) {
+        let mut elements = Vec::new();
+        for element in &list_expr.elements {
+            self.visit_expression(element);
+            elements.push(self.value());
+        }
+        self.last_value = Some(Value::list(elements));
+    }
+
+    fn visit_string_expression(&mut self, string: &super::ASTStringExpression) {
+        self.last_value = Some(Value::string(&string.value));
+    }
+
+    fn visit_map_expression(&mut self, map_expr: &super::ASTMapExpression) {
+        let mut map = Map::new();
+        for entry in &map_expr.entries {
+            self.visit_expression(&entry.key);
+            let key = self.value();
+            self.visit_expression(&entry.value);
+            if !self.failed {
+                map.insert(&key, self.value());
+            }
+        }
+        self.last_value = Some(Value::map(map));
+    }
+
+    fn visit_index_expression(&mut self, index_expr: &super::ASTIndexExpression) {
+        self.visit_expression(&index_expr.target);
+        let target = self.value();
+        self.visit_expression(&index_expr.index);
+        let index = self.value();
+        if self.failed {
+            return;
+        }
+        if let Some(element) = self.check_access(runtime::get(&target, &index), &index_expr.index.span) {
+            self.last_value = Some(element);
+        }
+    }
+
+    // The value stays the one assigned, as with variables
+    fn visit_index_assignment_expression(&mut self, assignment_expr: &super::ASTIndexAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        let target = self.value();
+        self.visit_expression(&assignment_expr.index);
+        let index = self.value();
+        self.visit_expression(&assignment_expr.expression);
+        let value = self.value();
+        if self.failed {
+            return;
+        }
+        self.check_access(runtime::set(&target, &index, value), &assignment_expr.index.span);
+    }
+
+    fn visit_slice_expression(&mut self, slice_expr: &super::ASTSliceExpression) {
+        self.visit_expression(&slice_expr.target);
+        let list = self.value();
+        let mut bounds = [None, None];
+        for (bound, expr) in bounds.iter_mut().zip([&slice_expr.start, &slice_expr.end]) {
+            if let Some(expr) = expr {
+                self.visit_expression(expr);
+                *bound = Some(self.value());
+            }
+        }
+        if self.failed {
+            return;
+        }
+        let start = bounds[0].as_ref().map_or(0, Value::as_int);
+        let end = bounds[1].as_ref().map_or_else(|| runtime::length(&list), Value::as_int);
+        if let Some(slice) = self.check_access(runtime::slice(&list, start, end), &slice_expr.range) {
+            self.last_value = Some(slice);
+        }
+    }
+
+    // Fields are evaluated in source order and stored in declaration order
+    fn visit_struct_expression(&mut self, struct_expr: &super::ASTStructExpression) {
+        let mut values = HashMap::new();
+        for field in &struct_expr.fields {
+            self.visit_expression(&field.value);
+            values.insert(field.name.span.literal.as_str(), self.value());
+        }
+        let name = &struct_expr.name.span.literal;
+        let fields = self.structs[name].iter().map(|field| (field.as_str(), values[field.as_str()].clone())).collect();
+        self.last_value = Some(Value::record(name, fields));
+    }
+
+    fn visit_field_expression(&mut self, field_expr: &super::ASTFieldExpression) {
+        self.visit_expression(&field_expr.target);
+        if !self.failed {
+            self.last_value = Some(runtime::field(&self.value(), &field_expr.field.span.literal));
+        }
+    }
+
+    fn visit_field_assignment_expression(&mut self, assignment_expr: &super::ASTFieldAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        let target = self.value();
+        self.visit_expression(&assignment_expr.expression);
+        if !self.failed {
+            runtime::set_field(&target, &assignment_expr.field.span.literal, self.value());
+        }
+    }
+
+    fn visit_call_expression(&mut self, call_expr: &super::ASTCallExpression) {
+        let mut arguments = Vec::new();
+        for argument in &call_expr.arguments {
+            self.visit_expression(argument);
+            arguments.push(self.value());
+        }
+        if self.failed {
+            return;
+        }
+        let callee = &call_expr.callee.span.literal;
+        if self.variants.contains(callee) {
+            self.last_value = Some(Value::variant(callee, arguments));
+            return;
+        }
+        let builtin = Builtin::from_name(callee).expect("calls are resolved by the type checker");
+        let result = match builtin {
+            Builtin::Len => Some(Value::Int(runtime::length(&arguments[0]))),
+            Builtin::Push => Some(Value::Int(runtime::push(&arguments[0], arguments[1].clone()))),
+            Builtin::Pop => self.check_access(runtime::pop(&arguments[0]), &call_expr.arguments[0].span),
+            Builtin::Keys => Some(runtime::keys(&arguments[0])),
+            Builtin::Values => Some(runtime::values(&arguments[0])),
+            Builtin::Has => Some(runtime::has(&arguments[0], &arguments[1])),
+        };
+        if result.is_some() {
+            self.last_value = result;
+        }
     }
-    
 }
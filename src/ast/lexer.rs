@@ -3,6 +3,7 @@ use std::fmt::Display;
 #[derive(Debug, PartialEq, Clone)]
 pub enum TokenKind {
     Number(i64),
+    String(String), // Value of a `"..."` literal, escapes resolved
     Plus,
     Minus,
     Asterisk,
@@ -12,9 +13,28 @@ pub enum TokenKind {
     Eof,
     Bad,
     Whitespace,
+    LeftBracket,
+    RightBracket,
+    LeftBrace,
+    RightBrace,
+    Colon,
+    Comma,
+    Question,
+    Arrow,
     Let,
+    Fn,
+    Struct,
+    Enum,
+    Match,
+    For,
+    In,
     Id,
-    Equals
+    Equals,
+    FatArrow,
+    Dot,
+    DotDot,
+    DotDotEquals,
+    Pragma, // `#allow(...)`-style directive, up to the end of the line
 }
 
 /* DISPLAY */
@@ -23,18 +43,38 @@ impl Display for TokenKind {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             TokenKind::Number(_) => write!(f, "Number"),
+            TokenKind::String(_) => write!(f, "String"),
             TokenKind::Plus => write!(f, "+"),
             TokenKind::Minus => write!(f, "-"),
             TokenKind::Asterisk => write!(f, "*"),
             TokenKind::Slash => write!(f, "/"),
             TokenKind::LeftParen => write!(f, "("),
             TokenKind::RightParen => write!(f, ")"),
+            TokenKind::LeftBracket => write!(f, "["),
+            TokenKind::RightBracket => write!(f, "]"),
+            TokenKind::LeftBrace => write!(f, "{{"),
+            TokenKind::RightBrace => write!(f, "}}"),
+            TokenKind::Colon => write!(f, ":"),
+            TokenKind::Comma => write!(f, ","),
+            TokenKind::Question => write!(f, "?"),
+            TokenKind::Arrow => write!(f, "->"),
             TokenKind::Bad => write!(f, "Bad"),
             TokenKind::Whitespace => write!(f, "Whitespace"),
             TokenKind::Eof => write!(f, "Eof"),
             TokenKind::Let => write!(f, "Let"),
+            TokenKind::Fn => write!(f, "Fn"),
+            TokenKind::Struct => write!(f, "Struct"),
+            TokenKind::Enum => write!(f, "Enum"),
+            TokenKind::Match => write!(f, "Match"),
+            TokenKind::For => write!(f, "For"),
+            TokenKind::In => write!(f, "In"),
             TokenKind::Id => write!(f, "Identifier"),
             TokenKind::Equals => write!(f, "="),
+            TokenKind::FatArrow => write!(f, "=>"),
+            TokenKind::Dot => write!(f, "."),
+            TokenKind::DotDot => write!(f, ".."),
+            TokenKind::DotDotEquals => write!(f, "..="),
+            TokenKind::Pragma => write!(f, "Pragma"),
         }
     }
 }
@@ -86,7 +126,7 @@ impl Token {
 
 pub struct Lexer<'a> {
     input: &'a str,
-    cur_pos: usize,
+    cur_pos: usize, // Byte offset, always on a char boundary
 }
 
 impl<'a> Lexer<'a> {
@@ -99,28 +139,38 @@ impl<'a> Lexer<'a> {
         if self.cur_pos == self.input.len() {
             let eof_char: char = '\0';
             self.cur_pos += 1;
-            return Some(Token::new(TokenKind::Eof, TextSpan::new(0, 0, eof_char.to_string())))
+            return Some(Token::new(TokenKind::Eof, TextSpan::new(self.input.len(), self.input.len(), eof_char.to_string())))
         }
 
         let c = self.current_char();
         c.map(|c| {
             let start = self.cur_pos;
-            let mut kind = TokenKind::Bad;
-            if Self::is_number_start(&c) {
+            let kind = if Self::is_number_start(&c) {
                 let number: i64 = self.consume_number();
-                kind = TokenKind::Number(number)
+                TokenKind::Number(number)
+            } else if c == '"' {
+                self.consume_string()
             } else if Self::is_whitespace(&c) {
                 self.consume();
-                kind = TokenKind::Whitespace
+                TokenKind::Whitespace
+            } else if Self::is_pragma_start(&c) {
+                self.consume_line();
+                TokenKind::Pragma
             } else if Self::is_identifier_start(&c) {
                 let identifier = self.consume_identifier();
-                kind = match identifier.as_str() {
+                match identifier.as_str() {
                     "let" => TokenKind::Let,
+                    "fn" => TokenKind::Fn,
+                    "struct" => TokenKind::Struct,
+                    "enum" => TokenKind::Enum,
+                    "match" => TokenKind::Match,
+                    "for" => TokenKind::For,
+                    "in" => TokenKind::In,
                     _ => TokenKind::Id
                 }
             } else {
-                kind = self.consume_punctuation();
-            }
+                self.consume_punctuation()
+            };
 
             let end = self.cur_pos;
             let literal = self.input[start..end].to_string();
@@ -134,24 +184,29 @@ impl<'a> Lexer<'a> {
     }
 
     fn is_number_start(c: &char) -> bool {
-        c.is_digit(10) // return bool
+        c.is_ascii_digit() // return bool
+    }
+
+    fn is_pragma_start(c: &char) -> bool {
+        *c == '#'
     }
 
     fn is_identifier_start(c: &char) -> bool {
-        c.is_alphabetic()
+        c.is_alphabetic() || *c == '_'
+    }
+
+    fn is_identifier_continue(c: &char) -> bool {
+        Self::is_identifier_start(c) || c.is_ascii_digit()
     }
 
     fn current_char(&self) -> Option<char> {
-        self.input.chars().nth(self.cur_pos) // return Option<char>
+        self.input.get(self.cur_pos..)?.chars().next() // return Option<char>
     }
 
     fn consume(&mut self) -> Option<char> {
-        if self.cur_pos >= self.input.len() {
-            return None
-        }
-        let c = self.current_char();
-        self.cur_pos += 1;
-        c // return Option<char>
+        let c = self.current_char()?;
+        self.cur_pos += c.len_utf8();
+        Some(c) // return Option<char>
     }
 
     fn consume_number(&mut self) -> i64 {
@@ -167,24 +222,80 @@ impl<'a> Lexer<'a> {
         number // return i64
     }
 
+    // Strings end on the same line; an unterminated one is a bad token
+    fn consume_string(&mut self) -> TokenKind {
+        self.consume();
+        let mut value = String::new();
+        while let Some(c) = self.current_char() {
+            if c == '\n' {
+                break;
+            }
+            self.consume();
+            match c {
+                '"' => return TokenKind::String(value),
+                '\\' => match self.consume() {
+                    Some('n') => value.push('\n'),
+                    Some('t') => value.push('\t'),
+                    Some(escaped) => value.push(escaped),
+                    None => break,
+                },
+                _ => value.push(c),
+            }
+        }
+        TokenKind::Bad
+    }
+
     fn consume_punctuation(&mut self) -> TokenKind {
         let c = self.consume().unwrap();
         match c {
             '+' => TokenKind::Plus,
+            '-' if self.current_char() == Some('>') => {
+                self.consume();
+                TokenKind::Arrow
+            }
             '-' => TokenKind::Minus,
             '*' => TokenKind::Asterisk,
             '/' => TokenKind::Slash,
             '(' => TokenKind::LeftParen,
             ')' => TokenKind::RightParen,
+            '{' => TokenKind::LeftBrace,
+            '}' => TokenKind::RightBrace,
+            '[' => TokenKind::LeftBracket,
+            ']' => TokenKind::RightBracket,
+            ':' => TokenKind::Colon,
+            ',' => TokenKind::Comma,
+            '?' => TokenKind::Question,
+            '=' if self.current_char() == Some('>') => {
+                self.consume();
+                TokenKind::FatArrow
+            }
             '=' => TokenKind::Equals,
+            '.' if self.current_char() == Some('.') => {
+                self.consume();
+                if self.current_char() == Some('=') {
+                    self.consume();
+                    return TokenKind::DotDotEquals;
+                }
+                TokenKind::DotDot
+            }
+            '.' => TokenKind::Dot,
             _ => TokenKind::Bad
         }
     }
 
+    fn consume_line(&mut self) {
+        while let Some(c) = self.current_char() {
+            if c == '\n' {
+                break;
+            }
+            self.consume().unwrap();
+        }
+    }
+
     fn consume_identifier(&mut self) -> String {
         let mut identifier = String::new();
         while let Some(c) = self.current_char() {
-            if !Self::is_identifier_start(&c) {
+            if !Self::is_identifier_continue(&c) {
                 break;
             }
             self.consume().unwrap();
@@ -195,3 +306,44 @@ impl<'a> Lexer<'a> {
 }
 
 /* LEXER */
+
+#[cfg(test)]
+mod tests {
+    use super::{Lexer, Token, TokenKind};
+
+    fn lex(input: &str) -> Vec<Token> {
+        let mut lexer = Lexer::new(input);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token);
+        }
+        tokens
+    }
+
+    #[test]
+    fn spans_are_byte_offsets_into_the_input() {
+        let input = "let s = \"héllo\" # é\n1";
+        let tokens = lex(input);
+        for token in &tokens[..tokens.len() - 1] {
+            assert_eq!(token.span.literal, input[token.span.start..token.span.end]);
+        }
+        let eof = tokens.last().unwrap();
+        assert_eq!((eof.kind.clone(), eof.span.start), (TokenKind::Eof, input.len()));
+    }
+
+    #[test]
+    fn lexes_non_ascii_strings() {
+        let tokens = lex("\"héllo\"");
+        assert_eq!(tokens[0].kind, TokenKind::String("héllo".to_string()));
+        assert_eq!((tokens[0].span.start, tokens[0].span.end), (0, 8));
+    }
+
+    #[test]
+    fn lexes_non_ascii_comments_up_to_the_end_of_the_line() {
+        let kinds: Vec<_> = lex("# é\n1").into_iter().map(|token| token.kind).collect();
+        assert_eq!(
+            kinds,
+            [TokenKind::Pragma, TokenKind::Whitespace, TokenKind::Number(1), TokenKind::Eof]
+        );
+    }
+}
@@ -1,7 +1,15 @@
+use std::{
+    fmt::Display,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
 use termion::color;
 
-use self::lexer::{TextSpan, Token};
+use crate::typeck::TypeTable;
+
+use self::lexer::{TextSpan, Token, TokenKind};
 
+pub mod dump;
 pub mod evaluator;
 pub mod lexer;
 pub mod parser;
@@ -27,8 +35,15 @@ impl Ast {
         }
     }
 
-    pub fn visualize(&self) -> () {
-        let mut printer = ASTPrinter::new();
+    pub fn visit_mut(&mut self, visitor: &mut dyn ASTVisitorMut) {
+        for stmt in &mut self.statements {
+            visitor.visit_statement_mut(stmt)
+        }
+    }
+
+    // Let bindings are annotated with the inferred types found in `types`
+    pub fn visualize(&self, types: &TypeTable) {
+        let mut printer = ASTPrinter::new(Some(types));
         self.visit(&mut printer);
         println!("{}", printer.result)
     }
@@ -43,11 +58,28 @@ pub trait ASTVisitor {
             ASTStatementKind::LetStatement(expr) => {
                 self.visit_let_statement(expr);
             }
+            ASTStatementKind::Struct(struct_statement) => {
+                self.visit_struct_statement(struct_statement);
+            }
+            ASTStatementKind::Enum(enum_statement) => {
+                self.visit_enum_statement(enum_statement);
+            }
+            ASTStatementKind::For(for_statement) => {
+                self.visit_for_statement(for_statement);
+            }
         }
     }
     fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
         self.visit_expression(&let_statement.initializer)
     }
+    fn visit_struct_statement(&mut self, _struct_statement: &ASTStructStatement) {}
+    fn visit_enum_statement(&mut self, _enum_statement: &ASTEnumStatement) {}
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.visit_expression(&for_statement.iterable);
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+    }
     fn visit_statement(&mut self, stmt: &ASTStatement) {
         self.do_visit_statement(stmt)
     }
@@ -59,6 +91,20 @@ pub trait ASTVisitor {
             ASTExpressionKind::Binary(expr) => self.visit_binary_expression(expr),
             ASTExpressionKind::Parenthesized(expr) => self.visit_parenthesized_expression(expr),
             ASTExpressionKind::Variable(expr) => self.visit_variable_expression(expr),
+            ASTExpressionKind::Assignment(expr) => self.visit_assignment_expression(expr),
+            ASTExpressionKind::String(string) => self.visit_string_expression(string),
+            ASTExpressionKind::List(expr) => self.visit_list_expression(expr),
+            ASTExpressionKind::Map(expr) => self.visit_map_expression(expr),
+            ASTExpressionKind::Index(expr) => self.visit_index_expression(expr),
+            ASTExpressionKind::IndexAssignment(expr) => self.visit_index_assignment_expression(expr),
+            ASTExpressionKind::Slice(expr) => self.visit_slice_expression(expr),
+            ASTExpressionKind::Call(expr) => self.visit_call_expression(expr),
+            ASTExpressionKind::Struct(expr) => self.visit_struct_expression(expr),
+            ASTExpressionKind::Field(expr) => self.visit_field_expression(expr),
+            ASTExpressionKind::FieldAssignment(expr) => self.visit_field_assignment_expression(expr),
+            ASTExpressionKind::Match(expr) => self.visit_match_expression(expr),
+            ASTExpressionKind::Tuple(expr) => self.visit_tuple_expression(expr),
+            ASTExpressionKind::Range(expr) => self.visit_range_expression(expr),
             ASTExpressionKind::Error(span) => self.visit_error(span),
         }
     }
@@ -76,9 +122,99 @@ pub trait ASTVisitor {
 
     // Parenthesized Expression
     fn visit_parenthesized_expression(&mut self, expr: &ASTParenthesizedExpression) {
-        self.do_visit_expression(&expr.expression)
+        self.visit_expression(&expr.expression)
+    }
+
+    // Assignment Expression
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    // List Expression
+    fn visit_list_expression(&mut self, list_expr: &ASTListExpression) {
+        for element in &list_expr.elements {
+            self.visit_expression(element);
+        }
+    }
+
+    // Map Expression
+    fn visit_map_expression(&mut self, map_expr: &ASTMapExpression) {
+        for entry in &map_expr.entries {
+            self.visit_expression(&entry.key);
+            self.visit_expression(&entry.value);
+        }
+    }
+
+    // Index Expression
+    fn visit_index_expression(&mut self, index_expr: &ASTIndexExpression) {
+        self.visit_expression(&index_expr.target);
+        self.visit_expression(&index_expr.index)
     }
 
+    // Index Assignment Expression
+    fn visit_index_assignment_expression(&mut self, assignment_expr: &ASTIndexAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        self.visit_expression(&assignment_expr.index);
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    // Slice Expression
+    fn visit_slice_expression(&mut self, slice_expr: &ASTSliceExpression) {
+        self.visit_expression(&slice_expr.target);
+        for bound in [&slice_expr.start, &slice_expr.end].into_iter().flatten() {
+            self.visit_expression(bound);
+        }
+    }
+
+    // Call Expression
+    fn visit_call_expression(&mut self, call_expr: &ASTCallExpression) {
+        for argument in &call_expr.arguments {
+            self.visit_expression(argument);
+        }
+    }
+
+    // Struct Expression
+    fn visit_struct_expression(&mut self, struct_expr: &ASTStructExpression) {
+        for field in &struct_expr.fields {
+            self.visit_expression(&field.value);
+        }
+    }
+
+    // Field Expression
+    fn visit_field_expression(&mut self, field_expr: &ASTFieldExpression) {
+        self.visit_expression(&field_expr.target)
+    }
+
+    // Field Assignment Expression
+    fn visit_field_assignment_expression(&mut self, assignment_expr: &ASTFieldAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    // Match Expression
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        self.visit_expression(&match_expr.scrutinee);
+        for arm in &match_expr.arms {
+            self.visit_expression(&arm.body);
+        }
+    }
+
+    // Tuple Expression
+    fn visit_tuple_expression(&mut self, tuple_expr: &ASTTupleExpression) {
+        for element in &tuple_expr.elements {
+            self.visit_expression(element);
+        }
+    }
+
+    // Range Expression
+    fn visit_range_expression(&mut self, range_expr: &ASTRangeExpression) {
+        self.visit_expression(&range_expr.start);
+        self.visit_expression(&range_expr.end)
+    }
+
+    // String Expression
+    fn visit_string_expression(&mut self, _string: &ASTStringExpression) {}
+
     // Error
     fn visit_error(&mut self, span: &TextSpan);
 
@@ -89,31 +225,209 @@ pub trait ASTVisitor {
     fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression);
 }
 
-pub struct ASTPrinter {
-    indent: usize,
+// Like `ASTVisitor`, but every node is borrowed mutably so a pass can rewrite
+// the tree in place. Nodes are replaced from `visit_expression_mut`, which sees
+// the whole `ASTExpression` (id and span included); every method walks the
+// children by default, so a pass only overrides the nodes it rewrites.
+pub trait ASTVisitorMut {
+    fn do_visit_statement_mut(&mut self, stmt: &mut ASTStatement) {
+        match &mut stmt.kind {
+            ASTStatementKind::Expression(expr) => {
+                self.visit_expression_mut(expr);
+            }
+            ASTStatementKind::LetStatement(expr) => {
+                self.visit_let_statement_mut(expr);
+            }
+            ASTStatementKind::For(for_statement) => {
+                self.visit_for_statement_mut(for_statement);
+            }
+            ASTStatementKind::Struct(_) | ASTStatementKind::Enum(_) => {}
+        }
+    }
+    fn visit_let_statement_mut(&mut self, let_statement: &mut ASTLetStatement) {
+        self.visit_expression_mut(&mut let_statement.initializer)
+    }
+    fn visit_for_statement_mut(&mut self, for_statement: &mut ASTForStatement) {
+        self.visit_expression_mut(&mut for_statement.iterable);
+        for statement in &mut for_statement.body {
+            self.visit_statement_mut(statement);
+        }
+    }
+    fn visit_statement_mut(&mut self, stmt: &mut ASTStatement) {
+        self.do_visit_statement_mut(stmt)
+    }
+
+    // Expression
+    fn do_visit_expression_mut(&mut self, expr: &mut ASTExpression) {
+        match &mut expr.kind {
+            ASTExpressionKind::Number(number) => self.visit_number_expression_mut(number),
+            ASTExpressionKind::Binary(expr) => self.visit_binary_expression_mut(expr),
+            ASTExpressionKind::Parenthesized(expr) => self.visit_parenthesized_expression_mut(expr),
+            ASTExpressionKind::Variable(expr) => self.visit_variable_expression_mut(expr),
+            ASTExpressionKind::Assignment(expr) => self.visit_assignment_expression_mut(expr),
+            ASTExpressionKind::String(string) => self.visit_string_expression_mut(string),
+            ASTExpressionKind::List(expr) => self.visit_list_expression_mut(expr),
+            ASTExpressionKind::Map(expr) => self.visit_map_expression_mut(expr),
+            ASTExpressionKind::Index(expr) => self.visit_index_expression_mut(expr),
+            ASTExpressionKind::IndexAssignment(expr) => self.visit_index_assignment_expression_mut(expr),
+            ASTExpressionKind::Slice(expr) => self.visit_slice_expression_mut(expr),
+            ASTExpressionKind::Call(expr) => self.visit_call_expression_mut(expr),
+            ASTExpressionKind::Struct(expr) => self.visit_struct_expression_mut(expr),
+            ASTExpressionKind::Field(expr) => self.visit_field_expression_mut(expr),
+            ASTExpressionKind::FieldAssignment(expr) => self.visit_field_assignment_expression_mut(expr),
+            ASTExpressionKind::Match(expr) => self.visit_match_expression_mut(expr),
+            ASTExpressionKind::Tuple(expr) => self.visit_tuple_expression_mut(expr),
+            ASTExpressionKind::Range(expr) => self.visit_range_expression_mut(expr),
+            ASTExpressionKind::Error(span) => self.visit_error_mut(span),
+        }
+    }
+
+    // Generic Expression, the place to replace `*expr` after walking it
+    fn visit_expression_mut(&mut self, expr: &mut ASTExpression) {
+        self.do_visit_expression_mut(expr)
+    }
+
+    // Binary Case
+    fn visit_binary_expression_mut(&mut self, binary_expr: &mut ASTBinaryExpression) {
+        self.visit_expression_mut(&mut binary_expr.left);
+        self.visit_expression_mut(&mut binary_expr.right)
+    }
+
+    // Parenthesized Expression
+    fn visit_parenthesized_expression_mut(&mut self, expr: &mut ASTParenthesizedExpression) {
+        self.visit_expression_mut(&mut expr.expression)
+    }
+
+    // Assignment Expression
+    fn visit_assignment_expression_mut(&mut self, assignment_expr: &mut ASTAssignmentExpression) {
+        self.visit_expression_mut(&mut assignment_expr.expression)
+    }
+
+    // List Expression
+    fn visit_list_expression_mut(&mut self, list_expr: &mut ASTListExpression) {
+        for element in &mut list_expr.elements {
+            self.visit_expression_mut(element);
+        }
+    }
+
+    // Map Expression
+    fn visit_map_expression_mut(&mut self, map_expr: &mut ASTMapExpression) {
+        for entry in &mut map_expr.entries {
+            self.visit_expression_mut(&mut entry.key);
+            self.visit_expression_mut(&mut entry.value);
+        }
+    }
+
+    // Index Expression
+    fn visit_index_expression_mut(&mut self, index_expr: &mut ASTIndexExpression) {
+        self.visit_expression_mut(&mut index_expr.target);
+        self.visit_expression_mut(&mut index_expr.index)
+    }
+
+    // Index Assignment Expression
+    fn visit_index_assignment_expression_mut(&mut self, assignment_expr: &mut ASTIndexAssignmentExpression) {
+        self.visit_expression_mut(&mut assignment_expr.target);
+        self.visit_expression_mut(&mut assignment_expr.index);
+        self.visit_expression_mut(&mut assignment_expr.expression)
+    }
+
+    // Slice Expression
+    fn visit_slice_expression_mut(&mut self, slice_expr: &mut ASTSliceExpression) {
+        self.visit_expression_mut(&mut slice_expr.target);
+        for bound in [&mut slice_expr.start, &mut slice_expr.end].into_iter().flatten() {
+            self.visit_expression_mut(bound);
+        }
+    }
+
+    // Call Expression
+    fn visit_call_expression_mut(&mut self, call_expr: &mut ASTCallExpression) {
+        for argument in &mut call_expr.arguments {
+            self.visit_expression_mut(argument);
+        }
+    }
+
+    // Struct Expression
+    fn visit_struct_expression_mut(&mut self, struct_expr: &mut ASTStructExpression) {
+        for field in &mut struct_expr.fields {
+            self.visit_expression_mut(&mut field.value);
+        }
+    }
+
+    // Field Expression
+    fn visit_field_expression_mut(&mut self, field_expr: &mut ASTFieldExpression) {
+        self.visit_expression_mut(&mut field_expr.target)
+    }
+
+    // Field Assignment Expression
+    fn visit_field_assignment_expression_mut(&mut self, assignment_expr: &mut ASTFieldAssignmentExpression) {
+        self.visit_expression_mut(&mut assignment_expr.target);
+        self.visit_expression_mut(&mut assignment_expr.expression)
+    }
+
+    // Match Expression
+    fn visit_match_expression_mut(&mut self, match_expr: &mut ASTMatchExpression) {
+        self.visit_expression_mut(&mut match_expr.scrutinee);
+        for arm in &mut match_expr.arms {
+            self.visit_expression_mut(&mut arm.body);
+        }
+    }
+
+    // Tuple Expression
+    fn visit_tuple_expression_mut(&mut self, tuple_expr: &mut ASTTupleExpression) {
+        for element in &mut tuple_expr.elements {
+            self.visit_expression_mut(element);
+        }
+    }
+
+    // Range Expression
+    fn visit_range_expression_mut(&mut self, range_expr: &mut ASTRangeExpression) {
+        self.visit_expression_mut(&mut range_expr.start);
+        self.visit_expression_mut(&mut range_expr.end)
+    }
+
+    // Error
+    fn visit_error_mut(&mut self, _span: &mut TextSpan) {}
+
+    // Number Expression
+    fn visit_number_expression_mut(&mut self, _number: &mut ASTNumberExpression) {}
+
+    // String Expression
+    fn visit_string_expression_mut(&mut self, _string: &mut ASTStringExpression) {}
+
+    // Variable Expression
+    fn visit_variable_expression_mut(&mut self, _variable_expression: &mut ASTVariableExpression) {}
+}
+
+pub struct ASTPrinter<'a> {
     result: String,
+    types: Option<&'a TypeTable>,
+    depth: usize, // Of the loop bodies being printed, each indented once more
 }
 
-impl ASTPrinter {
+impl<'a> ASTPrinter<'a> {
     const NUM_COLOR: color::LightYellow = color::LightYellow;
+    const STRING_COLOR: color::Green = color::Green;
     const KEYWOLD_COLOR: color::Magenta = color::Magenta;
     const TEXT_COLOR: color::LightWhite = color::LightWhite;
     const VAR_COLOR: color::White = color::White;
+    const TYPE_COLOR: color::LightCyan = color::LightCyan;
+    const HINT_COLOR: color::LightBlack = color::LightBlack;
     const RESET_COLOR: color::Reset = color::Reset;
 
-    pub fn new() -> Self {
+    pub fn new(types: Option<&'a TypeTable>) -> Self {
         Self {
-            indent: 0,
             result: String::new(),
+            types,
+            depth: 0,
         }
     }
     
     fn add_whitespace(&mut self) {
-        self.result.push_str(" ");
+        self.result.push(' ');
     }
 
     fn add_newline(&mut self) {
-        self.result.push_str("\n");
+        self.result.push('\n');
     }
 
     fn add_number(&mut self, number: i64) {
@@ -144,14 +458,37 @@ impl ASTPrinter {
         self.add_color(word, Self::KEYWOLD_COLOR.fg_str())
     }
 
+    // Inferred type of `expr`, shown like an editor inlay hint
+    fn add_type_hint(&mut self, expr: &ASTExpression) {
+        if let Some(ty) = self.types.and_then(|types| types.get(expr.id)) {
+            let hint = format!(": {}", ty);
+            self.add_color(&hint, Self::HINT_COLOR.fg_str())
+        }
+    }
 
 }
 
-impl ASTVisitor for ASTPrinter {
+impl ASTVisitor for ASTPrinter<'_> {
+    fn visit_statement(&mut self, stmt: &ASTStatement) {
+        self.result.push_str(&"    ".repeat(self.depth));
+        self.do_visit_statement(stmt);
+        if let ASTStatementKind::Expression(_) = stmt.kind {
+            self.add_newline()
+        }
+    }
+
     fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
         self.add_keyword("let");
         self.add_whitespace();
-        self.add_text(&let_statement.identifier.span.literal);
+        self.add_text(&let_statement.pattern.to_string());
+        match &let_statement.type_annotation {
+            Some(type_annotation) => {
+                self.add_text(":");
+                self.add_whitespace();
+                self.add_color(&type_annotation.to_string(), Self::TYPE_COLOR.fg_str());
+            }
+            None => self.add_type_hint(&let_statement.initializer),
+        }
         self.add_whitespace();
         self.add_text("=");
         self.add_whitespace();
@@ -159,6 +496,76 @@ impl ASTVisitor for ASTPrinter {
         self.add_newline()
     }
 
+    fn visit_struct_statement(&mut self, struct_statement: &ASTStructStatement) {
+        self.add_keyword("struct");
+        self.add_whitespace();
+        self.add_color(&struct_statement.name.span.literal, Self::TYPE_COLOR.fg_str());
+        self.add_whitespace();
+        self.add_text("{");
+        for (index, field) in struct_statement.fields.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+            }
+            self.add_whitespace();
+            self.add_text(&field.name.span.literal);
+            self.add_text(":");
+            self.add_whitespace();
+            self.add_color(&field.type_annotation.to_string(), Self::TYPE_COLOR.fg_str());
+        }
+        if !struct_statement.fields.is_empty() {
+            self.add_whitespace();
+        }
+        self.add_text("}");
+        self.add_newline()
+    }
+
+    fn visit_enum_statement(&mut self, enum_statement: &ASTEnumStatement) {
+        self.add_keyword("enum");
+        self.add_whitespace();
+        self.add_color(&enum_statement.name.span.literal, Self::TYPE_COLOR.fg_str());
+        self.add_whitespace();
+        self.add_text("{");
+        for (index, variant) in enum_statement.variants.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+            }
+            self.add_whitespace();
+            self.add_color(&variant.name.span.literal, Self::TYPE_COLOR.fg_str());
+            if !variant.payload.is_empty() {
+                let payload: Vec<_> = variant.payload.iter().map(|ty| ty.to_string()).collect();
+                self.add_text("(");
+                self.add_color(&payload.join(", "), Self::TYPE_COLOR.fg_str());
+                self.add_text(")");
+            }
+        }
+        if !enum_statement.variants.is_empty() {
+            self.add_whitespace();
+        }
+        self.add_text("}");
+        self.add_newline()
+    }
+
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.add_keyword("for");
+        self.add_whitespace();
+        self.add_color(&for_statement.variable.span.literal, Self::VAR_COLOR.fg_str());
+        self.add_whitespace();
+        self.add_keyword("in");
+        self.add_whitespace();
+        self.visit_expression(&for_statement.iterable);
+        self.add_whitespace();
+        self.add_text("{");
+        self.add_newline();
+        self.depth += 1;
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+        self.depth -= 1;
+        self.result.push_str(&"    ".repeat(self.depth));
+        self.add_text("}");
+        self.add_newline()
+    }
+
     fn visit_number_expression(&mut self, number: &ASTNumberExpression) {
         self.add_number(number.number)
     }
@@ -184,6 +591,172 @@ impl ASTVisitor for ASTPrinter {
     fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression) {
         self.add_color(variable_expression.identifier(), Self::VAR_COLOR.fg_str())
     }
+
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.add_color(&assignment_expr.identifier.span.literal, Self::VAR_COLOR.fg_str());
+        self.add_whitespace();
+        self.add_text("=");
+        self.add_whitespace();
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    fn visit_list_expression(&mut self, list_expr: &ASTListExpression) {
+        self.add_text("[");
+        for (index, element) in list_expr.elements.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+                self.add_whitespace();
+            }
+            self.visit_expression(element);
+        }
+        self.add_text("]")
+    }
+
+    fn visit_tuple_expression(&mut self, tuple_expr: &ASTTupleExpression) {
+        self.add_text("(");
+        for (index, element) in tuple_expr.elements.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+                self.add_whitespace();
+            }
+            self.visit_expression(element);
+        }
+        if tuple_expr.elements.len() == 1 {
+            self.add_text(",");
+        }
+        self.add_text(")")
+    }
+
+    fn visit_range_expression(&mut self, range_expr: &ASTRangeExpression) {
+        self.visit_expression(&range_expr.start);
+        self.add_text(&range_expr.operator.span.literal);
+        self.visit_expression(&range_expr.end)
+    }
+
+    fn visit_string_expression(&mut self, string: &ASTStringExpression) {
+        self.add_color(&format!("{:?}", string.value), Self::STRING_COLOR.fg_str())
+    }
+
+    fn visit_map_expression(&mut self, map_expr: &ASTMapExpression) {
+        self.add_text("{");
+        for (index, entry) in map_expr.entries.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+            }
+            self.add_whitespace();
+            self.visit_expression(&entry.key);
+            self.add_text(":");
+            self.add_whitespace();
+            self.visit_expression(&entry.value);
+        }
+        if !map_expr.entries.is_empty() {
+            self.add_whitespace();
+        }
+        self.add_text("}")
+    }
+
+    fn visit_index_expression(&mut self, index_expr: &ASTIndexExpression) {
+        self.visit_expression(&index_expr.target);
+        self.add_text("[");
+        self.visit_expression(&index_expr.index);
+        self.add_text("]")
+    }
+
+    fn visit_index_assignment_expression(&mut self, assignment_expr: &ASTIndexAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        self.add_text("[");
+        self.visit_expression(&assignment_expr.index);
+        self.add_text("]");
+        self.add_whitespace();
+        self.add_text("=");
+        self.add_whitespace();
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    fn visit_slice_expression(&mut self, slice_expr: &ASTSliceExpression) {
+        self.visit_expression(&slice_expr.target);
+        self.add_text("[");
+        if let Some(start) = &slice_expr.start {
+            self.visit_expression(start);
+        }
+        self.add_text("..");
+        if let Some(end) = &slice_expr.end {
+            self.visit_expression(end);
+        }
+        self.add_text("]")
+    }
+
+    fn visit_struct_expression(&mut self, struct_expr: &ASTStructExpression) {
+        self.add_color(&struct_expr.name.span.literal, Self::TYPE_COLOR.fg_str());
+        self.add_whitespace();
+        self.add_text("{");
+        for (index, field) in struct_expr.fields.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+            }
+            self.add_whitespace();
+            self.add_text(&field.name.span.literal);
+            self.add_text(":");
+            self.add_whitespace();
+            self.visit_expression(&field.value);
+        }
+        if !struct_expr.fields.is_empty() {
+            self.add_whitespace();
+        }
+        self.add_text("}")
+    }
+
+    fn visit_field_expression(&mut self, field_expr: &ASTFieldExpression) {
+        self.visit_expression(&field_expr.target);
+        self.add_text(".");
+        self.add_text(&field_expr.field.span.literal)
+    }
+
+    fn visit_field_assignment_expression(&mut self, assignment_expr: &ASTFieldAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        self.add_text(".");
+        self.add_text(&assignment_expr.field.span.literal);
+        self.add_whitespace();
+        self.add_text("=");
+        self.add_whitespace();
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        self.add_keyword("match");
+        self.add_whitespace();
+        self.visit_expression(&match_expr.scrutinee);
+        self.add_whitespace();
+        self.add_text("{");
+        for (index, arm) in match_expr.arms.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+            }
+            self.add_whitespace();
+            self.add_text(&arm.pattern.to_string());
+            self.add_whitespace();
+            self.add_text("=>");
+            self.add_whitespace();
+            self.visit_expression(&arm.body);
+        }
+        if !match_expr.arms.is_empty() {
+            self.add_whitespace();
+        }
+        self.add_text("}")
+    }
+
+    fn visit_call_expression(&mut self, call_expr: &ASTCallExpression) {
+        self.add_keyword(&call_expr.callee.span.literal);
+        self.add_text("(");
+        for (index, argument) in call_expr.arguments.iter().enumerate() {
+            if index > 0 {
+                self.add_text(",");
+                self.add_whitespace();
+            }
+            self.visit_expression(argument);
+        }
+        self.add_text(")")
+    }
 }
 
 pub enum ASTExpressionKind {
@@ -191,13 +764,27 @@ pub enum ASTExpressionKind {
     Binary(ASTBinaryExpression),
     Parenthesized(ASTParenthesizedExpression),
     Variable(ASTVariableExpression),
+    Assignment(ASTAssignmentExpression),
+    String(ASTStringExpression),
+    List(ASTListExpression),
+    Map(ASTMapExpression),
+    Index(ASTIndexExpression),
+    IndexAssignment(ASTIndexAssignmentExpression),
+    Slice(ASTSliceExpression),
+    Call(ASTCallExpression),
+    Struct(ASTStructExpression),
+    Field(ASTFieldExpression),
+    FieldAssignment(ASTFieldAssignmentExpression),
+    Match(ASTMatchExpression),
+    Tuple(ASTTupleExpression),
+    Range(ASTRangeExpression),
     Error(TextSpan),
 }
 
 // Variable
 
 pub struct ASTVariableExpression {
-    identifier: Token,
+    pub(crate) identifier: Token,
 }
 
 impl ASTVariableExpression {
@@ -206,9 +793,123 @@ impl ASTVariableExpression {
     }
 }
 
+// Assignment
+
+pub struct ASTAssignmentExpression {
+    pub(crate) identifier: Token,
+    pub(crate) expression: Box<ASTExpression>,
+}
+
+// List
+
+pub struct ASTListExpression {
+    pub(crate) elements: Vec<ASTExpression>,
+}
+
+// Map, entries in source order
+
+pub struct ASTMapEntry {
+    pub(crate) key: ASTExpression,
+    pub(crate) value: ASTExpression,
+}
+
+pub struct ASTMapExpression {
+    pub(crate) entries: Vec<ASTMapEntry>,
+}
+
+// Index, of a list or a map
+
+pub struct ASTIndexExpression {
+    pub(crate) target: Box<ASTExpression>,
+    pub(crate) index: Box<ASTExpression>,
+}
+
+// Index Assignment
+
+pub struct ASTIndexAssignmentExpression {
+    pub(crate) target: Box<ASTExpression>,
+    pub(crate) index: Box<ASTExpression>,
+    pub(crate) expression: Box<ASTExpression>,
+}
+
+// Slice, either bound may be left out
+
+pub struct ASTSliceExpression {
+    pub(crate) target: Box<ASTExpression>,
+    pub(crate) start: Option<Box<ASTExpression>>,
+    pub(crate) end: Option<Box<ASTExpression>>,
+    pub(crate) range: TextSpan, // Between the brackets, for runtime errors
+}
+
+// Call, only of built-in functions for now
+
+pub struct ASTCallExpression {
+    pub(crate) callee: Token,
+    pub(crate) arguments: Vec<ASTExpression>,
+}
+
+// Struct, fields in source order
+
+pub struct ASTFieldInitializer {
+    pub(crate) name: Token,
+    pub(crate) value: ASTExpression,
+}
+
+pub struct ASTStructExpression {
+    pub(crate) name: Token,
+    pub(crate) fields: Vec<ASTFieldInitializer>,
+}
+
+// Field
+
+pub struct ASTFieldExpression {
+    pub(crate) target: Box<ASTExpression>,
+    pub(crate) field: Token,
+}
+
+// Field Assignment
+
+pub struct ASTFieldAssignmentExpression {
+    pub(crate) target: Box<ASTExpression>,
+    pub(crate) field: Token,
+    pub(crate) expression: Box<ASTExpression>,
+}
+
+// Match, arms tried in source order
+
+pub struct ASTMatchArm {
+    pub(crate) pattern: ASTPattern,
+    pub(crate) body: ASTExpression,
+}
+
+pub struct ASTMatchExpression {
+    pub(crate) scrutinee: Box<ASTExpression>,
+    pub(crate) arms: Vec<ASTMatchArm>,
+}
+
+// Tuple, of at least one element
+
+pub struct ASTTupleExpression {
+    pub(crate) elements: Vec<ASTExpression>,
+}
+
+// Range, `0..10` or `0..=10` when it includes its end
+
+pub struct ASTRangeExpression {
+    pub(crate) start: Box<ASTExpression>,
+    pub(crate) end: Box<ASTExpression>,
+    pub(crate) operator: Token,
+}
+
+impl ASTRangeExpression {
+    pub fn inclusive(&self) -> bool {
+        self.operator.kind == TokenKind::DotDotEquals
+    }
+}
+
 // Binary
 
-#[derive(Debug)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
 pub enum ASTBinaryOperatorKind {
     Add,
     Subtract,
@@ -218,8 +919,8 @@ pub enum ASTBinaryOperatorKind {
 
 #[derive(Debug)]
 pub struct ASTBinaryOperator {
-    kind: ASTBinaryOperatorKind,
-    token: Token,
+    pub(crate) kind: ASTBinaryOperatorKind,
+    pub(crate) token: Token,
 }
 
 impl ASTBinaryOperator {
@@ -238,92 +939,657 @@ impl ASTBinaryOperator {
 }
 
 pub struct ASTBinaryExpression {
-    left: Box<ASTExpression>,
-    right: Box<ASTExpression>,
-    operator: ASTBinaryOperator,
+    pub(crate) left: Box<ASTExpression>,
+    pub(crate) right: Box<ASTExpression>,
+    pub(crate) operator: ASTBinaryOperator,
 }
 
 // Number
 
 pub struct ASTNumberExpression {
-    number: i64,
+    pub(crate) number: i64,
+}
+
+// String
+
+pub struct ASTStringExpression {
+    pub(crate) value: String,
 }
 
 // Parenthesized
 
 pub struct ASTParenthesizedExpression {
-    expression: Box<ASTExpression>,
+    pub(crate) expression: Box<ASTExpression>,
+}
+
+// Identifies a node for side tables built by later passes (types, ...)
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct ASTNodeId(usize);
+
+impl ASTNodeId {
+    // Ids are unique for the whole process, so nodes built by different
+    // passes never collide
+    pub fn new() -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(0);
+        Self(NEXT_ID.fetch_add(1, Ordering::Relaxed))
+    }
 }
 
 pub struct ASTExpression {
-    kind: ASTExpressionKind,
+    pub(crate) id: ASTNodeId,
+    pub(crate) kind: ASTExpressionKind,
+    pub(crate) span: TextSpan, // From the first to the last token of the expression
 }
 
 impl ASTExpression {
-    pub fn new(kind: ASTExpressionKind) -> Self {
-        Self { kind }
+    pub fn new(kind: ASTExpressionKind, span: TextSpan) -> Self {
+        Self {
+            id: ASTNodeId::new(),
+            kind,
+            span,
+        }
     }
 
-    pub fn number(number: i64) -> Self {
-        Self::new(ASTExpressionKind::Number(ASTNumberExpression { number }))
+    pub fn number(number: i64, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::Number(ASTNumberExpression { number }), span)
     }
 
     pub fn error(span: TextSpan) -> Self {
-        Self::new(ASTExpressionKind::Error(span))
+        Self::new(ASTExpressionKind::Error(span.clone()), span)
     }
 
-    pub fn binary(operator: ASTBinaryOperator, left: ASTExpression, right: ASTExpression) -> Self {
-        Self::new(ASTExpressionKind::Binary(ASTBinaryExpression {
-            left: Box::new(left),
-            right: Box::new(right),
-            operator,
-        }))
+    pub fn binary(operator: ASTBinaryOperator, left: ASTExpression, right: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Binary(ASTBinaryExpression {
+                left: Box::new(left),
+                right: Box::new(right),
+                operator,
+            }),
+            span,
+        )
     }
 
-    pub fn parenthesized(expr: ASTExpression) -> Self {
-        Self::new(ASTExpressionKind::Parenthesized(
-            ASTParenthesizedExpression {
+    pub fn parenthesized(expr: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Parenthesized(ASTParenthesizedExpression {
                 expression: Box::new(expr),
-            },
-        ))
+            }),
+            span,
+        )
     }
 
     pub fn identifier(identifier: Token) -> Self {
-        Self::new(ASTExpressionKind::Variable(
-            ASTVariableExpression {
-                identifier
+        let span = identifier.span.clone();
+        Self::new(
+            ASTExpressionKind::Variable(ASTVariableExpression { identifier }),
+            span,
+        )
+    }
+
+    pub fn assignment(identifier: Token, expression: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Assignment(ASTAssignmentExpression {
+                identifier,
+                expression: Box::new(expression),
+            }),
+            span,
+        )
+    }
+
+    pub fn string(value: String, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::String(ASTStringExpression { value }), span)
+    }
+
+    pub fn map(entries: Vec<ASTMapEntry>, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::Map(ASTMapExpression { entries }), span)
+    }
+
+    pub fn list(elements: Vec<ASTExpression>, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::List(ASTListExpression { elements }), span)
+    }
+
+    pub fn index(target: ASTExpression, index: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Index(ASTIndexExpression {
+                target: Box::new(target),
+                index: Box::new(index),
+            }),
+            span,
+        )
+    }
+
+    pub fn index_assignment(index_expr: ASTIndexExpression, expression: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::IndexAssignment(ASTIndexAssignmentExpression {
+                target: index_expr.target,
+                index: index_expr.index,
+                expression: Box::new(expression),
+            }),
+            span,
+        )
+    }
+
+    pub fn slice(
+        target: ASTExpression,
+        start: Option<ASTExpression>,
+        end: Option<ASTExpression>,
+        range: TextSpan,
+        span: TextSpan,
+    ) -> Self {
+        Self::new(
+            ASTExpressionKind::Slice(ASTSliceExpression {
+                target: Box::new(target),
+                start: start.map(Box::new),
+                end: end.map(Box::new),
+                range,
+            }),
+            span,
+        )
+    }
+
+    pub fn call(callee: Token, arguments: Vec<ASTExpression>, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::Call(ASTCallExpression { callee, arguments }), span)
+    }
+
+    pub fn struct_literal(name: Token, fields: Vec<ASTFieldInitializer>, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::Struct(ASTStructExpression { name, fields }), span)
+    }
+
+    pub fn field(target: ASTExpression, field: Token, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Field(ASTFieldExpression {
+                target: Box::new(target),
+                field,
+            }),
+            span,
+        )
+    }
+
+    pub fn match_expression(scrutinee: ASTExpression, arms: Vec<ASTMatchArm>, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Match(ASTMatchExpression {
+                scrutinee: Box::new(scrutinee),
+                arms,
+            }),
+            span,
+        )
+    }
+
+    pub fn tuple(elements: Vec<ASTExpression>, span: TextSpan) -> Self {
+        Self::new(ASTExpressionKind::Tuple(ASTTupleExpression { elements }), span)
+    }
+
+    pub fn range(start: ASTExpression, operator: Token, end: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::Range(ASTRangeExpression {
+                start: Box::new(start),
+                end: Box::new(end),
+                operator,
+            }),
+            span,
+        )
+    }
+
+    pub fn field_assignment(field_expr: ASTFieldExpression, expression: ASTExpression, span: TextSpan) -> Self {
+        Self::new(
+            ASTExpressionKind::FieldAssignment(ASTFieldAssignmentExpression {
+                target: field_expr.target,
+                field: field_expr.field,
+                expression: Box::new(expression),
+            }),
+            span,
+        )
+    }
+}
+
+// Type
+
+pub enum ASTTypeExpressionKind {
+    Named(Token), // int
+    Array(Box<ASTTypeExpression>), // [int]
+    Map(Box<ASTTypeExpression>, Box<ASTTypeExpression>), // {string: int}
+    Function(Vec<ASTTypeExpression>, Box<ASTTypeExpression>), // fn(int) -> int
+    Optional(Box<ASTTypeExpression>), // int?
+    Tuple(Vec<ASTTypeExpression>), // (int, string)
+    Error(TextSpan),
+}
+
+pub struct ASTTypeExpression {
+    pub(crate) id: ASTNodeId,
+    pub(crate) kind: ASTTypeExpressionKind,
+    pub(crate) span: TextSpan,
+}
+
+impl ASTTypeExpression {
+    pub fn new(kind: ASTTypeExpressionKind, span: TextSpan) -> Self {
+        Self {
+            id: ASTNodeId::new(),
+            kind,
+            span,
+        }
+    }
+}
+
+// Pattern
+
+pub enum ASTPatternKind {
+    Wildcard, // _
+    Identifier(Token), // A binding, unless a variant without payload has this name
+    Number(i64),
+    String(String),
+    Variant(Token, Vec<ASTPattern>), // Circle(r)
+    Tuple(Vec<ASTPattern>), // (a, b)
+    List(Vec<ASTPattern>), // [a, b], only lists of that length
+    Struct(Token, Vec<ASTFieldPattern>, bool), // Point { x, y: 0, .. }, whether `..` skips the other fields
+}
+
+pub struct ASTFieldPattern {
+    pub(crate) name: Token,
+    pub(crate) pattern: ASTPattern, // A binding of the field's name when none is written
+}
+
+pub struct ASTPattern {
+    pub(crate) id: ASTNodeId,
+    pub(crate) kind: ASTPatternKind,
+    pub(crate) span: TextSpan,
+}
+
+impl ASTPattern {
+    pub fn new(kind: ASTPatternKind, span: TextSpan) -> Self {
+        Self {
+            id: ASTNodeId::new(),
+            kind,
+            span,
+        }
+    }
+
+    // Every identifier in the pattern, nested ones included, in source order
+    pub fn identifiers(&self) -> Vec<&Token> {
+        match &self.kind {
+            ASTPatternKind::Identifier(identifier) => vec![identifier],
+            ASTPatternKind::Variant(_, elements) | ASTPatternKind::Tuple(elements) | ASTPatternKind::List(elements) => {
+                elements.iter().flat_map(ASTPattern::identifiers).collect()
+            }
+            ASTPatternKind::Struct(_, fields, _) => fields.iter().flat_map(|field| field.pattern.identifiers()).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    // Whether `identifier` is a field written alone, as `x` in `Point { x }`,
+    // where it names both the field and the binding
+    pub fn is_shorthand(&self, identifier: &Token) -> bool {
+        match &self.kind {
+            ASTPatternKind::Variant(_, elements) | ASTPatternKind::Tuple(elements) | ASTPatternKind::List(elements) => {
+                elements.iter().any(|element| element.is_shorthand(identifier))
+            }
+            ASTPatternKind::Struct(_, fields, _) => fields.iter().any(|field| {
+                field.name.span == identifier.span || field.pattern.is_shorthand(identifier)
+            }),
+            _ => false,
+        }
+    }
+}
+
+/* DISPLAY */
+
+impl Display for ASTPattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ASTPatternKind::Wildcard => write!(f, "_"),
+            ASTPatternKind::Identifier(identifier) => write!(f, "{}", identifier.span.literal),
+            ASTPatternKind::Number(number) => write!(f, "{}", number),
+            ASTPatternKind::String(value) => write!(f, "{:?}", value),
+            ASTPatternKind::Variant(name, payload) => {
+                write!(f, "{}", name.span.literal)?;
+                write_patterns(f, "(", payload, ")")
+            }
+            ASTPatternKind::Tuple(elements) if elements.len() == 1 => write!(f, "({},)", elements[0]),
+            ASTPatternKind::Tuple(elements) => write_patterns(f, "(", elements, ")"),
+            ASTPatternKind::List(elements) => write_patterns(f, "[", elements, "]"),
+            ASTPatternKind::Struct(name, fields, rest) => {
+                write!(f, "{} {{", name.span.literal)?;
+                for (index, field) in fields.iter().enumerate() {
+                    write!(f, "{} {}", if index > 0 { "," } else { "" }, field.name.span.literal)?;
+                    match &field.pattern.kind {
+                        ASTPatternKind::Identifier(identifier) if identifier.span.literal == field.name.span.literal => {}
+                        _ => write!(f, ": {}", field.pattern)?,
+                    }
+                }
+                match (*rest, fields.is_empty()) {
+                    (true, true) => write!(f, " .. }}"),
+                    (true, false) => write!(f, ", .. }}"),
+                    (false, true) => write!(f, "}}"),
+                    (false, false) => write!(f, " }}"),
+                }
             }
-        ))
+        }
     }
 }
 
+// `patterns` separated by commas between `open` and `close`
+fn write_patterns(f: &mut std::fmt::Formatter<'_>, open: &str, patterns: &[ASTPattern], close: &str) -> std::fmt::Result {
+    write!(f, "{}", open)?;
+    for (index, pattern) in patterns.iter().enumerate() {
+        if index > 0 {
+            write!(f, ", ")?;
+        }
+        write!(f, "{}", pattern)?;
+    }
+    write!(f, "{}", close)
+}
+
+impl Display for ASTTypeExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.kind {
+            ASTTypeExpressionKind::Named(name) => write!(f, "{}", name.span.literal),
+            ASTTypeExpressionKind::Array(element) => write!(f, "[{}]", element),
+            ASTTypeExpressionKind::Map(key, value) => write!(f, "{{{}: {}}}", key, value),
+            ASTTypeExpressionKind::Function(parameters, return_type) => {
+                write!(f, "fn(")?;
+                for (index, parameter) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", parameter)?;
+                }
+                write!(f, ") -> {}", return_type)
+            }
+            ASTTypeExpressionKind::Optional(inner) => write!(f, "{}?", inner),
+            ASTTypeExpressionKind::Tuple(elements) => {
+                write!(f, "(")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "{})", if elements.len() == 1 { "," } else { "" })
+            }
+            ASTTypeExpressionKind::Error(span) => write!(f, "{}", span.literal),
+        }
+    }
+}
+
+// `let pattern = initializer`, the pattern matching every value of its type
+// but for the length of the lists it destructures, checked at runtime
+
 pub struct ASTLetStatement {
-    identifier: Token,
-    initializer: ASTExpression,
+    pub(crate) pattern: ASTPattern,
+    pub(crate) type_annotation: Option<ASTTypeExpression>,
+    pub(crate) initializer: ASTExpression,
+}
+
+// `struct Point { x: int, y: int }`, fields in declaration order
+
+pub struct ASTFieldDeclaration {
+    pub(crate) name: Token,
+    pub(crate) type_annotation: ASTTypeExpression,
+}
+
+pub struct ASTStructStatement {
+    pub(crate) name: Token,
+    pub(crate) fields: Vec<ASTFieldDeclaration>,
+}
+
+// `enum Shape { Circle(int), Rect(int, int), Empty }`, variants in
+// declaration order
+
+pub struct ASTVariantDeclaration {
+    pub(crate) name: Token,
+    pub(crate) payload: Vec<ASTTypeExpression>,
+}
+
+pub struct ASTEnumStatement {
+    pub(crate) name: Token,
+    pub(crate) variants: Vec<ASTVariantDeclaration>,
+}
+
+// `for item in iterable { ... }`, over a range, a list, the characters of a
+// string or the keys of a map. The variable and the `let`s of the body are
+// only visible in the body.
+
+pub struct ASTForStatement {
+    pub(crate) variable: Token,
+    pub(crate) iterable: ASTExpression,
+    pub(crate) body: Vec<ASTStatement>,
 }
 
 pub enum ASTStatementKind {
     Expression(ASTExpression),
-    LetStatement(ASTLetStatement),
+    LetStatement(Box<ASTLetStatement>),
+    Struct(ASTStructStatement),
+    Enum(ASTEnumStatement),
+    For(ASTForStatement),
 }
 
 pub struct ASTStatement {
-    kind: ASTStatementKind,
+    pub(crate) id: ASTNodeId,
+    pub(crate) kind: ASTStatementKind,
+    pub(crate) span: TextSpan, // From the first to the last token of the statement
 }
 
 impl ASTStatement {
-    pub fn new(kind: ASTStatementKind) -> Self {
-        Self { kind }
+    pub fn new(kind: ASTStatementKind, span: TextSpan) -> Self {
+        Self {
+            id: ASTNodeId::new(),
+            kind,
+            span,
+        }
     }
 
     pub fn expression(expr: ASTExpression) -> Self {
-        Self::new(ASTStatementKind::Expression(expr))
+        let span = expr.span.clone();
+        Self::new(ASTStatementKind::Expression(expr), span)
+    }
+
+    pub fn let_statement(
+        pattern: ASTPattern,
+        type_annotation: Option<ASTTypeExpression>,
+        initializer: ASTExpression,
+        span: TextSpan,
+    ) -> Self {
+        Self::new(
+            ASTStatementKind::LetStatement(Box::new(ASTLetStatement {
+                pattern,
+                type_annotation,
+                initializer,
+            })),
+            span,
+        )
+    }
+
+    pub fn struct_statement(name: Token, fields: Vec<ASTFieldDeclaration>, span: TextSpan) -> Self {
+        Self::new(ASTStatementKind::Struct(ASTStructStatement { name, fields }), span)
+    }
+
+    pub fn enum_statement(name: Token, variants: Vec<ASTVariantDeclaration>, span: TextSpan) -> Self {
+        Self::new(ASTStatementKind::Enum(ASTEnumStatement { name, variants }), span)
+    }
+
+    pub fn for_statement(variable: Token, iterable: ASTExpression, body: Vec<ASTStatement>, span: TextSpan) -> Self {
+        Self::new(ASTStatementKind::For(ASTForStatement { variable, iterable, body }), span)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compilation_unit::CompilationUnit, lints::LintConfig};
+
+    use super::{
+        lexer::TextSpan, ASTExpression, ASTExpressionKind, ASTNumberExpression, ASTPrinter, ASTStatement,
+        ASTStatementKind, ASTVariableExpression, ASTVisitor, ASTVisitorMut,
+    };
+
+    // Every kind of statement, expression and pattern, except errors
+    const EVERY_CONSTRUCT: &str = "\
+enum Shape { Circle(int), Rect(int, int) }
+struct Point { x: int, y: int }
+let p = Point { x: 1, y: 2 }
+p.x = (p.y + 3) * 2
+let xs = [1, 2, 3]
+xs[0] = len(xs)
+let m = { \"a\": 1 }
+let (a, b) = (xs[1..2], m[\"a\"])
+let s = Circle(a[0])
+let r = match s { Circle(r) => r, Rect(w, _) => w }
+let total = 0
+for i in 0..=r { total = total + i - 1 }
+let Point { x, y: _ } = p
+let [first, _, _] = xs
+total = total + b + x + first
+\"done\"";
+
+    fn compile(input: &str) -> CompilationUnit {
+        CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new())
+    }
+
+    fn strip_colors(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    fn statement_kind(stmt: &ASTStatement) -> &'static str {
+        match stmt.kind {
+            ASTStatementKind::Expression(_) => "expression",
+            ASTStatementKind::LetStatement(_) => "let",
+            ASTStatementKind::Struct(_) => "struct",
+            ASTStatementKind::Enum(_) => "enum",
+            ASTStatementKind::For(_) => "for",
+        }
+    }
+
+    fn expression_kind(expr: &ASTExpression) -> &'static str {
+        match expr.kind {
+            ASTExpressionKind::Number(_) => "number",
+            ASTExpressionKind::Binary(_) => "binary",
+            ASTExpressionKind::Parenthesized(_) => "parenthesized",
+            ASTExpressionKind::Variable(_) => "variable",
+            ASTExpressionKind::Assignment(_) => "assignment",
+            ASTExpressionKind::String(_) => "string",
+            ASTExpressionKind::List(_) => "list",
+            ASTExpressionKind::Map(_) => "map",
+            ASTExpressionKind::Index(_) => "index",
+            ASTExpressionKind::IndexAssignment(_) => "index_assignment",
+            ASTExpressionKind::Slice(_) => "slice",
+            ASTExpressionKind::Call(_) => "call",
+            ASTExpressionKind::Struct(_) => "struct",
+            ASTExpressionKind::Field(_) => "field",
+            ASTExpressionKind::FieldAssignment(_) => "field_assignment",
+            ASTExpressionKind::Match(_) => "match",
+            ASTExpressionKind::Tuple(_) => "tuple",
+            ASTExpressionKind::Range(_) => "range",
+            ASTExpressionKind::Error(_) => "error",
+        }
+    }
+
+    // The kind of every node a visitor reaches, in order
+    #[derive(Default)]
+    struct Recorder {
+        kinds: Vec<&'static str>,
+    }
+
+    impl ASTVisitor for Recorder {
+        fn visit_statement(&mut self, stmt: &ASTStatement) {
+            self.kinds.push(statement_kind(stmt));
+            self.do_visit_statement(stmt)
+        }
+
+        fn visit_expression(&mut self, expr: &ASTExpression) {
+            self.kinds.push(expression_kind(expr));
+            self.do_visit_expression(expr)
+        }
+
+        fn visit_error(&mut self, _span: &TextSpan) {}
+
+        fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {}
+
+        fn visit_variable_expression(&mut self, _variable_expression: &ASTVariableExpression) {}
+    }
+
+    impl ASTVisitorMut for Recorder {
+        fn visit_statement_mut(&mut self, stmt: &mut ASTStatement) {
+            self.kinds.push(statement_kind(stmt));
+            self.do_visit_statement_mut(stmt)
+        }
+
+        fn visit_expression_mut(&mut self, expr: &mut ASTExpression) {
+            self.kinds.push(expression_kind(expr));
+            self.do_visit_expression_mut(expr)
+        }
+    }
+
+    fn record(input: &str) -> (Vec<&'static str>, Vec<&'static str>) {
+        let mut unit = compile(input);
+        let mut recorder = Recorder::default();
+        unit.ast.visit(&mut recorder);
+        let mut recorder_mut = Recorder::default();
+        unit.ast.visit_mut(&mut recorder_mut);
+        (recorder.kinds, recorder_mut.kinds)
+    }
+
+    #[test]
+    fn prints_every_construct_with_its_inferred_type() {
+        let unit = compile(EVERY_CONSTRUCT);
+        assert!(!unit.has_errors());
+        let mut printer = ASTPrinter::new(Some(&unit.types));
+        unit.ast.visit(&mut printer);
+        let expected = "\
+enum Shape { Circle(int), Rect(int, int) }
+struct Point { x: int, y: int }
+let p: Point = Point { x: 1, y: 2 }
+p.x = (p.y + 3) * 2
+let xs: [int] = [1, 2, 3]
+xs[0] = len(xs)
+let m: {string: int} = { \"a\": 1 }
+let (a, b): ([int], int) = (xs[1..2], m[\"a\"])
+let s: Shape = Circle(a[0])
+let r: int = match s { Circle(r) => r, Rect(w, _) => w }
+let total: int = 0
+for i in 0..=r {
+    total = total + i - 1
+}
+let Point { x, y: _ }: Point = p
+let [first, _, _]: [int] = xs
+total = total + b + x + first
+\"done\"
+";
+        assert_eq!(strip_colors(&printer.result), expected);
+    }
+
+    #[test]
+    fn both_visitors_reach_every_node_kind() {
+        let (kinds, kinds_mut) = record(EVERY_CONSTRUCT);
+        assert_eq!(kinds, kinds_mut);
+        let statements = ["expression", "let", "struct", "enum", "for"];
+        let expressions = [
+            "number", "binary", "parenthesized", "variable", "assignment", "string", "list", "map", "index",
+            "index_assignment", "slice", "call", "field", "field_assignment", "match", "tuple", "range",
+        ];
+        for kind in statements.iter().chain(&expressions) {
+            assert!(kinds.contains(kind), "`{}` is never visited", kind);
+        }
+        // Struct literals, whose kind has the same name as struct statements
+        assert_eq!(kinds.iter().filter(|kind| **kind == "struct").count(), 2);
+
+        let (kinds, kinds_mut) = record("let a = 3 +");
+        assert_eq!(kinds, ["let", "binary", "number", "error"]);
+        assert_eq!(kinds, kinds_mut);
     }
 
-    pub fn let_statement(identifier: Token, initializer: ASTExpression) -> Self {
-        Self::new(ASTStatementKind::LetStatement(ASTLetStatement {
-            identifier,
-            initializer,
-        }))
+    #[test]
+    fn types_the_expression_inside_parentheses() {
+        let unit = compile("(1 + 3) * 2");
+        let ASTStatementKind::Expression(expr) = &unit.ast.statements[0].kind else { panic!("expected an expression") };
+        let ASTExpressionKind::Binary(binary) = &expr.kind else { panic!("expected a binary expression") };
+        let ASTExpressionKind::Parenthesized(parenthesized) = &binary.left.kind else { panic!("expected parentheses") };
+        assert_eq!(unit.types.get(parenthesized.expression.id).map(|ty| ty.to_string()), Some("int".to_string()));
     }
 }
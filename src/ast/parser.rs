@@ -3,8 +3,10 @@ use std::cell::Cell;
 use crate::diagnostics::DiagnosticsBagCell;
 
 use super::{
-    lexer::{Token, TokenKind},
-    ASTBinaryOperator, ASTBinaryOperatorKind, ASTExpression, ASTStatement,
+    lexer::{TextSpan, Token, TokenKind},
+    ASTBinaryOperator, ASTBinaryOperatorKind, ASTExpression, ASTExpressionKind, ASTFieldDeclaration, ASTFieldInitializer,
+    ASTFieldPattern, ASTMapEntry, ASTMatchArm, ASTPattern, ASTPatternKind, ASTStatement, ASTTypeExpression, ASTTypeExpressionKind,
+    ASTVariantDeclaration,
 };
 
 pub struct Counter {
@@ -28,19 +30,29 @@ impl Counter {
 
 pub struct Parser {
     tokens: Vec<Token>,
+    source: String, // Rebuilt from every token, used for the literal of node spans
     current: Counter,
+    struct_literals: bool, // Off where `{` opens a block, as after `match`
+    ranges: bool, // Off where `..` slices, as between the brackets of an index
     diagnostics_bag: DiagnosticsBagCell,
 }
 
 impl Parser {
     pub fn new(tokens: Vec<Token>, diagnostics_bag: DiagnosticsBagCell) -> Self {
         Self {
+            source: tokens
+                .iter()
+                .filter(|token| token.kind != TokenKind::Eof)
+                .map(|token| token.span.literal.as_str())
+                .collect(),
             tokens: tokens
                 .iter()
-                .filter(|token| token.kind != TokenKind::Whitespace)
-                .map(|token| token.clone())
+                .filter(|token| !matches!(token.kind, TokenKind::Whitespace | TokenKind::Pragma))
+                .cloned()
                 .collect(),
             current: Counter::new(),
+            struct_literals: true,
+            ranges: true,
             diagnostics_bag,
         } // return Parser
     }
@@ -59,6 +71,9 @@ impl Parser {
     fn parse_statement(&mut self) -> ASTStatement {
         match self.current().kind {
             TokenKind::Let => self.parse_let_stmt(),
+            TokenKind::Struct => self.parse_struct_stmt(),
+            TokenKind::Enum => self.parse_enum_stmt(),
+            TokenKind::For => self.parse_for_stmt(),
             _ => self.parse_expression_stmt()
         }
     }
@@ -69,28 +84,181 @@ impl Parser {
     }
 
     fn parse_let_stmt(&mut self) -> ASTStatement {
-        self.consume_and_check(TokenKind::Let);
-        let identifier = self.consume_and_check(TokenKind::Id).clone();
+        let start = self.consume_and_check(TokenKind::Let).span.start;
+        let pattern = self.parse_pattern();
+        let mut type_annotation = None;
+        if self.current().kind == TokenKind::Colon {
+            self.consume();
+            type_annotation = Some(self.parse_type_expression());
+        }
         self.consume_and_check(TokenKind::Equals);
         let initializer = self.parse_expression();
-        ASTStatement::let_statement(identifier, initializer)
+        let span = self.span_from(start);
+        ASTStatement::let_statement(pattern, type_annotation, initializer, span)
+    }
+
+    // `for item in iterable { statements }`
+    fn parse_for_stmt(&mut self) -> ASTStatement {
+        let start = self.consume_and_check(TokenKind::For).span.start;
+        let variable = self.consume_and_check(TokenKind::Id).clone();
+        self.consume_and_check(TokenKind::In);
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let iterable = self.parse_expression();
+        self.struct_literals = struct_literals;
+        let open = self.consume_and_check(TokenKind::LeftBrace).clone();
+        let mut body = Vec::new();
+        while !matches!(self.current().kind, TokenKind::RightBrace | TokenKind::Eof) {
+            body.push(self.parse_statement());
+        }
+        self.consume_closing(&open, TokenKind::RightBrace);
+        let span = self.span_from(start);
+        ASTStatement::for_statement(variable, iterable, body, span)
+    }
+
+    fn parse_struct_stmt(&mut self) -> ASTStatement {
+        let start = self.consume_and_check(TokenKind::Struct).span.start;
+        let name = self.consume_and_check(TokenKind::Id).clone();
+        let open = self.consume_and_check(TokenKind::LeftBrace).clone();
+        let fields = self.parse_fields(&open, |parser, name| ASTFieldDeclaration {
+            name,
+            type_annotation: parser.parse_type_expression(),
+        });
+        let span = self.span_from(start);
+        ASTStatement::struct_statement(name, fields, span)
+    }
+
+    fn parse_enum_stmt(&mut self) -> ASTStatement {
+        let start = self.consume_and_check(TokenKind::Enum).span.start;
+        let name = self.consume_and_check(TokenKind::Id).clone();
+        let open = self.consume_and_check(TokenKind::LeftBrace).clone();
+        let variants = self.parse_separated(&open, TokenKind::RightBrace, |parser| {
+            let name = parser.consume_and_check(TokenKind::Id).clone();
+            let mut payload = Vec::new();
+            if parser.current().kind == TokenKind::LeftParen {
+                let open = parser.consume().clone();
+                payload = parser.parse_separated(&open, TokenKind::RightParen, Self::parse_type_expression);
+            }
+            ASTVariantDeclaration { name, payload }
+        });
+        let span = self.span_from(start);
+        ASTStatement::enum_statement(name, variants, span)
+    }
+
+    // Type Expression
+
+    fn parse_type_expression(&mut self) -> ASTTypeExpression {
+        let token = self.consume().clone();
+        let kind = match token.kind {
+            TokenKind::Id => ASTTypeExpressionKind::Named(token.clone()),
+            TokenKind::LeftBracket => {
+                let element = self.parse_type_expression();
+                self.consume_closing(&token, TokenKind::RightBracket);
+                ASTTypeExpressionKind::Array(Box::new(element))
+            }
+            TokenKind::LeftBrace => {
+                let key = self.parse_type_expression();
+                self.consume_and_check(TokenKind::Colon);
+                let value = self.parse_type_expression();
+                self.consume_closing(&token, TokenKind::RightBrace);
+                ASTTypeExpressionKind::Map(Box::new(key), Box::new(value))
+            }
+            // `(int, string)` or `(int,)` is a tuple, `(int)` only groups
+            TokenKind::LeftParen => {
+                let (mut elements, comma) =
+                    self.parse_separated_items(&token, TokenKind::RightParen, Self::parse_type_expression);
+                match (elements.len(), comma) {
+                    (1, false) => elements.pop().unwrap().kind,
+                    (0, _) => {
+                        self.diagnostics_bag.borrow_mut().report_expected_type(self.peek(-1));
+                        ASTTypeExpressionKind::Error(self.span_from(token.span.start))
+                    }
+                    _ => ASTTypeExpressionKind::Tuple(elements),
+                }
+            }
+            TokenKind::Fn => {
+                let open = self.consume_and_check(TokenKind::LeftParen).clone();
+                let mut parameters = Vec::new();
+                while !matches!(self.current().kind, TokenKind::RightParen | TokenKind::Eof) {
+                    parameters.push(self.parse_type_expression());
+                    if self.current().kind != TokenKind::Comma {
+                        break;
+                    }
+                    self.consume();
+                }
+                self.consume_closing(&open, TokenKind::RightParen);
+                self.consume_and_check(TokenKind::Arrow);
+                let return_type = self.parse_type_expression();
+                ASTTypeExpressionKind::Function(parameters, Box::new(return_type))
+            }
+            _ => {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_expected_type(&token);
+                ASTTypeExpressionKind::Error(token.span.clone())
+            }
+        };
+        let mut type_expression = ASTTypeExpression::new(kind, self.span_from(token.span.start));
+        while self.current().kind == TokenKind::Question {
+            self.consume();
+            let span = self.span_from(token.span.start);
+            type_expression = ASTTypeExpression::new(ASTTypeExpressionKind::Optional(Box::new(type_expression)), span);
+        }
+        type_expression // return ASTTypeExpression
     }
 
     fn parse_expression(&mut self) -> ASTExpression {
-        self.parse_binary_expression(0) // return ASTExpression
+        if self.current().kind == TokenKind::Id && self.peek(1).kind == TokenKind::Equals {
+            return self.parse_assignment_expression();
+        }
+        let expr = self.parse_binary_expression(0);
+        if self.ranges && matches!(self.current().kind, TokenKind::DotDot | TokenKind::DotDotEquals) {
+            let operator = self.consume().clone();
+            let end = self.parse_binary_expression(0);
+            let span = self.span_from(expr.span.start);
+            return ASTExpression::range(expr, operator, end, span);
+        }
+        // `xs[i] = value` or `p.x = value`; the target is only known once parsed
+        if self.current().kind != TokenKind::Equals {
+            return expr;
+        }
+        let ASTExpression { id, kind, span } = expr;
+        match kind {
+            ASTExpressionKind::Index(index_expr) => {
+                self.consume();
+                let expression = self.parse_expression();
+                let span = self.span_from(span.start);
+                ASTExpression::index_assignment(index_expr, expression, span)
+            }
+            ASTExpressionKind::Field(field_expr) => {
+                self.consume();
+                let expression = self.parse_expression();
+                let span = self.span_from(span.start);
+                ASTExpression::field_assignment(field_expr, expression, span)
+            }
+            kind => ASTExpression { id, kind, span },
+        } // return ASTExpression
+    }
+
+    fn parse_assignment_expression(&mut self) -> ASTExpression {
+        let identifier = self.consume_and_check(TokenKind::Id).clone();
+        self.consume_and_check(TokenKind::Equals);
+        let expression = self.parse_expression();
+        let span = self.span_from(identifier.span.start);
+        ASTExpression::assignment(identifier, expression, span) // return ASTExpression
     }
 
     fn parse_binary_expression(&mut self, precedence: u8) -> ASTExpression {
         let mut left = self.parse_primary_expression();
 
         while let Some(operator) = self.parse_operator() {
-            self.consume();
             let operator_precedence = operator.precedence();
-            if operator_precedence < precedence {
+            if operator_precedence <= precedence {
                 break;
             }
+            self.consume();
             let right = self.parse_binary_expression(operator_precedence);
-            left = ASTExpression::binary(operator, left, right)
+            let span = self.span_from(left.span.start);
+            left = ASTExpression::binary(operator, left, right, span)
         }
 
         left // return ASTExpression
@@ -113,26 +281,267 @@ impl Parser {
     // Generic Expression
 
     fn parse_primary_expression(&mut self) -> ASTExpression {
+        let mut expr = self.parse_atom_expression();
+        loop {
+            expr = match self.current().kind {
+                TokenKind::LeftBracket => self.parse_index_expression(expr),
+                TokenKind::Dot => {
+                    self.consume();
+                    let field = self.consume_and_check(TokenKind::Id).clone();
+                    let span = self.span_from(expr.span.start);
+                    ASTExpression::field(expr, field, span)
+                }
+                _ => return expr,
+            }
+        }
+    }
+
+    // `target[index]` or `target[start..end]`
+    fn parse_index_expression(&mut self, target: ASTExpression) -> ASTExpression {
+        let open = self.consume().clone();
+        let range_start = self.current().span.start;
+        let ranges = std::mem::replace(&mut self.ranges, false);
+        let mut start = None;
+        if self.current().kind != TokenKind::DotDot {
+            start = Some(self.parse_expression());
+        }
+        if self.current().kind != TokenKind::DotDot {
+            self.ranges = ranges;
+            self.consume_closing(&open, TokenKind::RightBracket);
+            let index = start.unwrap();
+            let span = self.span_from(target.span.start);
+            return ASTExpression::index(target, index, span);
+        }
+        self.consume();
+        let mut end = None;
+        if !matches!(self.current().kind, TokenKind::RightBracket | TokenKind::Eof) {
+            end = Some(self.parse_expression());
+        }
+        self.ranges = ranges;
+        let range = self.span_from(range_start);
+        self.consume_closing(&open, TokenKind::RightBracket);
+        let span = self.span_from(target.span.start);
+        ASTExpression::slice(target, start, end, range, span)
+    }
+
+    // Comma-separated items up to `close`, which may follow a trailing comma.
+    // Struct literals and ranges are allowed again inside the delimiters.
+    fn parse_separated<T>(&mut self, open: &Token, close: TokenKind, parse: impl Fn(&mut Self) -> T) -> Vec<T> {
+        self.parse_separated_items(open, close, parse).0
+    }
+
+    // Like `parse_separated`, also telling whether any comma was found, which
+    // makes `(x,)` a tuple where `(x)` only groups
+    fn parse_separated_items<T>(
+        &mut self,
+        open: &Token,
+        close: TokenKind,
+        parse: impl Fn(&mut Self) -> T,
+    ) -> (Vec<T>, bool) {
+        let mut items = Vec::new();
+        let mut comma = false;
+        let struct_literals = std::mem::replace(&mut self.struct_literals, true);
+        let ranges = std::mem::replace(&mut self.ranges, true);
+        while self.current().kind != close && self.current().kind != TokenKind::Eof {
+            items.push(parse(self));
+            if self.current().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+            comma = true;
+        }
+        self.struct_literals = struct_literals;
+        self.ranges = ranges;
+        self.consume_closing(open, close);
+        (items, comma)
+    }
+
+    fn parse_expression_list(&mut self, open: &Token, close: TokenKind) -> Vec<ASTExpression> {
+        self.parse_separated(open, close, Self::parse_expression)
+    }
+
+    // `_`, a binding, a number or string literal, a variant with its payload,
+    // or a tuple, list or struct destructured by more patterns
+    fn parse_pattern(&mut self) -> ASTPattern {
+        let token = self.consume().clone();
+        let kind = match token.kind {
+            TokenKind::Number(number) => ASTPatternKind::Number(number),
+            TokenKind::String(ref value) => ASTPatternKind::String(value.clone()),
+            TokenKind::Id if token.span.literal == "_" => ASTPatternKind::Wildcard,
+            TokenKind::Id if self.current().kind == TokenKind::LeftParen => {
+                let open = self.consume().clone();
+                let payload = self.parse_separated(&open, TokenKind::RightParen, Self::parse_pattern);
+                ASTPatternKind::Variant(token.clone(), payload)
+            }
+            TokenKind::Id if self.current().kind == TokenKind::LeftBrace => self.parse_struct_pattern(token.clone()),
+            TokenKind::Id => ASTPatternKind::Identifier(token.clone()),
+            TokenKind::LeftParen => {
+                let (mut elements, comma) = self.parse_separated_items(&token, TokenKind::RightParen, Self::parse_pattern);
+                match (elements.len(), comma) {
+                    (1, false) => return elements.pop().unwrap(),
+                    (0, _) => {
+                        self.diagnostics_bag.borrow_mut().report_expected_pattern(self.peek(-1));
+                        ASTPatternKind::Wildcard
+                    }
+                    _ => ASTPatternKind::Tuple(elements),
+                }
+            }
+            TokenKind::LeftBracket => {
+                ASTPatternKind::List(self.parse_separated(&token, TokenKind::RightBracket, Self::parse_pattern))
+            }
+            _ => {
+                self.diagnostics_bag.borrow_mut().report_expected_pattern(&token);
+                ASTPatternKind::Wildcard
+            }
+        };
+        ASTPattern::new(kind, self.span_from(token.span.start))
+    }
+
+    // `Name { field: pattern, ... }` up to `}`, where `field` alone binds the
+    // field to its name and a last `..` skips the fields not listed
+    fn parse_struct_pattern(&mut self, name: Token) -> ASTPatternKind {
+        let open = self.consume().clone();
+        let mut fields = Vec::new();
+        let mut rest = false;
+        while !matches!(self.current().kind, TokenKind::RightBrace | TokenKind::Eof) {
+            if self.current().kind == TokenKind::DotDot {
+                self.consume();
+                rest = true;
+                break;
+            }
+            let field = self.consume_and_check(TokenKind::Id).clone();
+            let pattern = match self.current().kind {
+                TokenKind::Colon => {
+                    self.consume();
+                    self.parse_pattern()
+                }
+                _ => ASTPattern::new(ASTPatternKind::Identifier(field.clone()), field.span.clone()),
+            };
+            fields.push(ASTFieldPattern { name: field, pattern });
+            if self.current().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+        }
+        self.consume_closing(&open, TokenKind::RightBrace);
+        ASTPatternKind::Struct(name, fields, rest)
+    }
+
+    // `match value { pattern => expression, ... }`
+    fn parse_match_expression(&mut self, start: usize) -> ASTExpression {
+        let struct_literals = std::mem::replace(&mut self.struct_literals, false);
+        let scrutinee = self.parse_expression();
+        self.struct_literals = struct_literals;
+        let open = self.consume_and_check(TokenKind::LeftBrace).clone();
+        let arms = self.parse_separated(&open, TokenKind::RightBrace, |parser| {
+            let pattern = parser.parse_pattern();
+            parser.consume_and_check(TokenKind::FatArrow);
+            ASTMatchArm { pattern, body: parser.parse_expression() }
+        });
+        ASTExpression::match_expression(scrutinee, arms, self.span_from(start))
+    }
+
+    // `key: value` pairs up to `}`, which may follow a trailing comma
+    fn parse_map_entries(&mut self, open: &Token) -> Vec<ASTMapEntry> {
+        let mut entries = Vec::new();
+        while !matches!(self.current().kind, TokenKind::RightBrace | TokenKind::Eof) {
+            let key = self.parse_expression();
+            self.consume_and_check(TokenKind::Colon);
+            let value = self.parse_expression();
+            entries.push(ASTMapEntry { key, value });
+            if self.current().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+        }
+        self.consume_closing(open, TokenKind::RightBrace);
+        entries
+    }
+
+    // `name: ...` pairs up to `}`, which may follow a trailing comma; `parse`
+    // reads what follows each colon
+    fn parse_fields<T>(&mut self, open: &Token, parse: impl Fn(&mut Self, Token) -> T) -> Vec<T> {
+        let mut fields = Vec::new();
+        while !matches!(self.current().kind, TokenKind::RightBrace | TokenKind::Eof) {
+            let name = self.consume_and_check(TokenKind::Id).clone();
+            self.consume_and_check(TokenKind::Colon);
+            fields.push(parse(self, name));
+            if self.current().kind != TokenKind::Comma {
+                break;
+            }
+            self.consume();
+        }
+        self.consume_closing(open, TokenKind::RightBrace);
+        fields
+    }
+
+    fn parse_atom_expression(&mut self) -> ASTExpression {
         let token = self.consume();
         match token.kind {
-            TokenKind::Number(number) => ASTExpression::number(number),
+            TokenKind::Number(number) => ASTExpression::number(number, token.span.clone()),
+            TokenKind::String(ref value) => ASTExpression::string(value.clone(), token.span.clone()),
+            // `(1, "x")` or `(1,)` is a tuple, `(1)` only groups
             TokenKind::LeftParen => {
-                let expr = self.parse_expression();
-                self.consume_and_check(TokenKind::RightParen);
-                ASTExpression::parenthesized(expr)
+                let open = token.clone();
+                if self.current().kind == TokenKind::RightParen {
+                    self.diagnostics_bag.borrow_mut().report_expected_expression(self.peek(0));
+                }
+                let (mut elements, comma) = self.parse_separated_items(&open, TokenKind::RightParen, Self::parse_expression);
+                let span = self.span_from(open.span.start);
+                match (elements.len(), comma) {
+                    (1, false) => ASTExpression::parenthesized(elements.pop().unwrap(), span),
+                    (0, _) => ASTExpression::error(span),
+                    _ => ASTExpression::tuple(elements, span),
+                }
+            },
+            TokenKind::LeftBracket => {
+                let open = token.clone();
+                let elements = self.parse_expression_list(&open, TokenKind::RightBracket);
+                ASTExpression::list(elements, self.span_from(open.span.start))
+            },
+            // There are no blocks, so a brace in an expression always opens a map
+            TokenKind::LeftBrace => {
+                let open = token.clone();
+                let entries = self.parse_map_entries(&open);
+                ASTExpression::map(entries, self.span_from(open.span.start))
+            },
+            TokenKind::Id if self.peek(0).kind == TokenKind::LeftParen => {
+                let callee = token.clone();
+                let open = self.consume().clone();
+                let arguments = self.parse_expression_list(&open, TokenKind::RightParen);
+                ASTExpression::call(callee.clone(), arguments, self.span_from(callee.span.start))
+            },
+            TokenKind::Id if self.peek(0).kind == TokenKind::LeftBrace && self.struct_literals => {
+                let name = token.clone();
+                let open = self.consume().clone();
+                let fields = self.parse_fields(&open, |parser, name| ASTFieldInitializer {
+                    name,
+                    value: parser.parse_expression(),
+                });
+                ASTExpression::struct_literal(name.clone(), fields, self.span_from(name.span.start))
             },
             TokenKind::Id => {
                 ASTExpression::identifier(token.clone())
             },
+            TokenKind::Match => {
+                let start = token.span.start;
+                self.parse_match_expression(start)
+            },
             _ => {
                 self.diagnostics_bag
                     .borrow_mut()
-                    .report_expected_expression(&token);
+                    .report_expected_expression(token);
                 ASTExpression::error(token.span.clone())
             }
         } // return ASTExpression
     }
 
+    // Span from `start` to the end of the last consumed token
+    fn span_from(&self, start: usize) -> TextSpan {
+        let end = self.peek(-1).span.end.max(start);
+        TextSpan::new(start, end, self.source[start..end].to_string())
+    }
+
     fn peek(&self, offset: isize) -> &Token {
         let mut index = (self.current.get_value() as isize + offset) as usize;
         if index >= self.tokens.len() {
@@ -159,4 +568,65 @@ impl Parser {
         }
         token
     }
+
+    // Unlike `consume_and_check`, a missing closing delimiter does not eat the
+    // unexpected token, so enclosing groups and statements still see it.
+    fn consume_closing(&self, open: &Token, kind: TokenKind) {
+        if self.peek(0).kind == kind {
+            self.consume();
+            return;
+        }
+        let insert_at = self.peek(-1).span.end;
+        self.diagnostics_bag
+            .borrow_mut()
+            .report_unclosed_delimiter(open, &kind, self.peek(0), insert_at);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{ASTExpression, ASTExpressionKind, ASTStatementKind},
+        compilation_unit::CompilationUnit,
+        lints::LintConfig,
+    };
+
+    // Fully parenthesized form of `expr`, showing how its operators group
+    fn grouped(expr: &ASTExpression) -> String {
+        match &expr.kind {
+            ASTExpressionKind::Binary(binary) => format!(
+                "({} {} {})",
+                grouped(&binary.left),
+                binary.operator.token.span.literal,
+                grouped(&binary.right)
+            ),
+            _ => expr.span.literal.clone(),
+        }
+    }
+
+    fn parse(input: &str) -> Vec<String> {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        unit.ast
+            .statements
+            .iter()
+            .map(|stmt| {
+                let ASTStatementKind::Expression(expr) = &stmt.kind else { panic!("expected an expression") };
+                grouped(expr)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn operators_of_the_same_precedence_group_to_the_left() {
+        // Used to group to the right, as `(1 - (2 - 3))` and `(8 / (4 / 2))`
+        assert_eq!(parse("1 - 2 - 3"), ["((1 - 2) - 3)"]);
+        assert_eq!(parse("8 / 4 / 2"), ["((8 / 4) / 2)"]);
+    }
+
+    #[test]
+    fn keeps_an_operator_of_lower_precedence_than_the_one_before() {
+        // Used to drop the `+`, leaving `(2 * 3)` and `4` as two statements
+        assert_eq!(parse("2 * 3 + 4"), ["((2 * 3) + 4)"]);
+        assert_eq!(parse("1 + 2 * 3 - 4"), ["((1 + (2 * 3)) - 4)"]);
+    }
 }
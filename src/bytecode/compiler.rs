@@ -0,0 +1,275 @@
+use crate::{
+    ast::{lexer::TextSpan, ASTBinaryOperatorKind},
+    ir::{BlockId, InstructionKind, Program, Temp, TerminatorKind, Value},
+};
+
+use super::{Chunk, OpCode, StructLayout};
+
+// What the operands that may not fit count, as in "more than 65535 local slots"
+const SLOTS: &str = "local slots";
+const STRINGS: &str = "strings";
+const PAYLOADS: &str = "variant values";
+const TUPLE_ELEMENTS: &str = "tuple elements";
+
+// Translates a lowered `Program` to bytecode. Variables take the first slots
+// of the frame and temporaries the ones after them; each instruction pushes
+// its operands, computes, and stores the result, if any, into its slot,
+// leaving the stack empty in between. Blocks follow each other in layout
+// order, so a jump to the next block is left out. Operands are 16 bits wide;
+// the first one that does not fit is returned as an error, with what it
+// counts and where.
+pub struct BytecodeCompiler {
+    chunk: Chunk,
+    temps_base: usize, // Slot of the first temporary
+    blocks: Vec<usize>, // Offset of every block compiled so far
+    jumps: Vec<(usize, BlockId, TextSpan)>, // Operands to patch with the offset of their target
+    too_large: Option<(&'static str, TextSpan)>,
+}
+
+impl BytecodeCompiler {
+    pub fn new() -> Self {
+        Self {
+            chunk: Chunk::new(),
+            temps_base: 0,
+            blocks: Vec::new(),
+            jumps: Vec::new(),
+            too_large: None,
+        }
+    }
+
+    pub fn compile(mut self, program: &Program) -> Result<Chunk, (&'static str, TextSpan)> {
+        self.temps_base = program.variables.len();
+        // Too many locals is reported where the first slot past the limit is used
+        self.chunk.locals = u16::try_from(self.temps_base + program.temps).unwrap_or(u16::MAX);
+        for (index, block) in program.blocks.iter().enumerate() {
+            self.blocks.push(self.chunk.code.len());
+            let next = BlockId(index + 1);
+            for instruction in &block.instructions {
+                let span = instruction.span.clone();
+                for operand in instruction.operands() {
+                    self.push(operand, &span);
+                }
+                let slot = match instruction.kind {
+                    InstructionKind::Copy { dest, .. } => self.temp_slot(dest.0, &span),
+                    InstructionKind::Binary { dest, operator, .. } => {
+                        let op = match operator {
+                            ASTBinaryOperatorKind::Add => OpCode::Add,
+                            ASTBinaryOperatorKind::Subtract => OpCode::Subtract,
+                            ASTBinaryOperatorKind::Multiply => OpCode::Multiply,
+                            ASTBinaryOperatorKind::Divide => OpCode::Divide,
+                        };
+                        self.emit_with_result(op, dest, &span)
+                    }
+                    InstructionKind::Load { dest, variable } => {
+                        let slot = self.operand(variable.0, SLOTS, &span);
+                        self.chunk.emit_with_operand(OpCode::GetLocal, slot, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Store { variable, .. } => self.operand(variable.0, SLOTS, &span),
+                    InstructionKind::List { dest, ref elements } => {
+                        let count = self.operand(elements.len(), "list elements", &span);
+                        self.chunk.emit_with_operand(OpCode::BuildList, count, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::StringConstant { dest, ref text } => {
+                        let index = self.chunk.add_string(text);
+                        let index = self.fit(index, STRINGS, &span);
+                        self.chunk.emit_with_operand(OpCode::String, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Map { dest, ref entries } => {
+                        let count = self.operand(entries.len(), "map entries", &span);
+                        self.chunk.emit_with_operand(OpCode::BuildMap, count, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Struct { dest, ref name, ref fields } => {
+                        let layout = StructLayout {
+                            name: name.clone(),
+                            fields: fields.iter().map(|(field, _)| field.clone()).collect(),
+                        };
+                        let index = self.chunk.add_struct(layout);
+                        let index = self.fit(index, "struct layouts", &span);
+                        self.chunk.emit_with_operand(OpCode::BuildStruct, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Field { dest, ref field, .. } => {
+                        let index = self.chunk.add_string(field);
+                        let index = self.fit(index, STRINGS, &span);
+                        self.chunk.emit_with_operand(OpCode::GetField, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::SetField { ref field, .. } => {
+                        let index = self.chunk.add_string(field);
+                        let index = self.fit(index, STRINGS, &span);
+                        self.chunk.emit_with_operand(OpCode::SetField, index, span);
+                        continue;
+                    }
+                    InstructionKind::SetIndex { .. } => {
+                        self.chunk.emit(OpCode::SetIndex, span);
+                        continue;
+                    }
+                    InstructionKind::Index { dest, .. } => self.emit_with_result(OpCode::GetIndex, dest, &span),
+                    InstructionKind::Length { dest, .. } => self.emit_with_result(OpCode::Length, dest, &span),
+                    InstructionKind::Push { dest, .. } => self.emit_with_result(OpCode::Append, dest, &span),
+                    InstructionKind::Pop { dest, .. } => self.emit_with_result(OpCode::RemoveLast, dest, &span),
+                    InstructionKind::Slice { dest, .. } => self.emit_with_result(OpCode::Slice, dest, &span),
+                    InstructionKind::Keys { dest, .. } => self.emit_with_result(OpCode::Keys, dest, &span),
+                    InstructionKind::Values { dest, .. } => self.emit_with_result(OpCode::Values, dest, &span),
+                    InstructionKind::Has { dest, .. } => self.emit_with_result(OpCode::Has, dest, &span),
+                    InstructionKind::Variant { dest, ref name, ref payload } => {
+                        let index = self.chunk.add_string(name);
+                        let operands = [self.fit(index, STRINGS, &span), self.operand(payload.len(), PAYLOADS, &span)];
+                        self.chunk.emit_with_operands(OpCode::BuildVariant, &operands, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::IsVariant { dest, ref variant, .. } => {
+                        let index = self.chunk.add_string(variant);
+                        let index = self.fit(index, STRINGS, &span);
+                        self.chunk.emit_with_operand(OpCode::IsVariant, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Payload { dest, index, .. } => {
+                        let index = self.operand(index, PAYLOADS, &span);
+                        self.chunk.emit_with_operand(OpCode::Payload, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Tuple { dest, ref elements } => {
+                        let count = self.operand(elements.len(), TUPLE_ELEMENTS, &span);
+                        self.chunk.emit_with_operand(OpCode::BuildTuple, count, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Element { dest, index, .. } => {
+                        let index = self.operand(index, TUPLE_ELEMENTS, &span);
+                        self.chunk.emit_with_operand(OpCode::Element, index, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::CheckLength { length, .. } => {
+                        let length = self.operand(length, "list elements", &span);
+                        self.chunk.emit_with_operand(OpCode::CheckLength, length, span);
+                        continue;
+                    }
+                    InstructionKind::Equals { dest, .. } => self.emit_with_result(OpCode::Equal, dest, &span),
+                    InstructionKind::Range { dest, inclusive, .. } => {
+                        self.chunk.emit_with_operand(OpCode::BuildRange, inclusive as u16, span.clone());
+                        self.temp_slot(dest.0, &span)
+                    }
+                    InstructionKind::Iterate { dest, .. } => self.emit_with_result(OpCode::Iterate, dest, &span),
+                };
+                self.chunk.emit_with_operand(OpCode::SetLocal, slot, span.clone());
+                self.chunk.emit(OpCode::Pop, span);
+            }
+            let span = &block.terminator.span;
+            match block.terminator.kind {
+                TerminatorKind::Return(value) => {
+                    if let Some(value) = value {
+                        self.push(value, span);
+                    }
+                    self.chunk.emit(OpCode::Return, span.clone());
+                }
+                TerminatorKind::Jump(target) if target == next => {}
+                TerminatorKind::Jump(target) => self.jump(OpCode::Jump, target, span),
+                TerminatorKind::Branch { condition, then_block, else_block } => {
+                    self.push(condition, span);
+                    self.jump(OpCode::JumpIfFalse, else_block, span);
+                    if then_block != next {
+                        self.jump(OpCode::Jump, then_block, span);
+                    }
+                }
+            }
+        }
+        for (operand, target, span) in std::mem::take(&mut self.jumps) {
+            let offset = self.operand(self.blocks[target.0], "bytes of code", &span);
+            self.chunk.patch_operand(operand, offset);
+        }
+        match self.too_large {
+            Some(too_large) => Err(too_large),
+            None => Ok(self.chunk),
+        }
+    }
+
+    // The offset of `target` is patched in once every block is compiled
+    fn jump(&mut self, op: OpCode, target: BlockId, span: &TextSpan) {
+        let offset = self.chunk.emit_with_operand(op, 0, span.clone());
+        self.jumps.push((offset + 1, target, span.clone()));
+    }
+
+    fn push(&mut self, value: Value, span: &TextSpan) {
+        match value {
+            Value::Temp(temp) => {
+                let slot = self.temp_slot(temp.0, span);
+                self.chunk.emit_with_operand(OpCode::GetLocal, slot, span.clone());
+            }
+            Value::Constant(number) => {
+                let index = self.chunk.add_constant(number);
+                let index = self.fit(index, "integer constants", span);
+                self.chunk.emit_with_operand(OpCode::Constant, index, span.clone());
+            }
+        }
+    }
+
+    // Emits `op` and returns the slot of `dest`, where its result goes
+    fn emit_with_result(&mut self, op: OpCode, dest: Temp, span: &TextSpan) -> u16 {
+        self.chunk.emit(op, span.clone());
+        self.temp_slot(dest.0, span)
+    }
+
+    fn temp_slot(&mut self, temp: usize, span: &TextSpan) -> u16 {
+        self.operand(self.temps_base + temp, SLOTS, span)
+    }
+
+    fn operand(&mut self, value: usize, what: &'static str, span: &TextSpan) -> u16 {
+        self.fit(u16::try_from(value).ok(), what, span)
+    }
+
+    // `operand`, or a placeholder once the first operand that does not fit is
+    // recorded
+    fn fit(&mut self, operand: Option<u16>, what: &'static str, span: &TextSpan) -> u16 {
+        operand.unwrap_or_else(|| {
+            self.too_large.get_or_insert_with(|| (what, span.clone()));
+            0
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{cli::Engine, compilation_unit::CompilationUnit, ir::Program, lints::LintConfig};
+
+    use super::BytecodeCompiler;
+
+    fn compile(input: String, file_name: String) -> CompilationUnit {
+        let mut unit = CompilationUnit::compile(input, file_name.clone(), LintConfig::new());
+        unit.optimize();
+        assert!(!unit.has_errors(), "{}", file_name);
+        unit
+    }
+
+    #[test]
+    fn runs_the_examples_like_the_tree_engine() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let unit = compile(fs::read_to_string(&path).unwrap(), path.display().to_string());
+            let tree = unit.execute(&Engine::Tree).map(|value| value.to_string());
+            let vm = unit.execute(&Engine::Vm).map(|value| value.to_string());
+            assert!(!unit.has_errors(), "{}", path.display());
+            assert_eq!(tree, vm, "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn reports_jumps_past_the_reach_of_an_operand() {
+        let body = "    t = t + i\n".repeat(7000);
+        let unit = compile(format!("let t = 0\nfor i in 0..1 {{\n{}}}\nt", body), "test.sn".to_string());
+        let (what, span) = BytecodeCompiler::new().compile(&Program::lower(&unit.ast)).err().unwrap();
+        assert_eq!(what, "bytes of code");
+        assert!(span.literal.starts_with("for i in 0..1"), "{}", span.literal);
+        assert!(unit.execute(&Engine::Vm).is_none());
+        let diagnostics = &unit.diagnostics_bag.borrow().diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.code(), "E0027");
+        assert_eq!(diagnostics[0].message, "Program too large for bytecode: more than 65535 bytes of code");
+    }
+}
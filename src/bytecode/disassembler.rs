@@ -0,0 +1,174 @@
+use crate::text::SourceText;
+
+use super::{Chunk, OpCode};
+
+// Human-readable listing of a chunk: the constant, string and struct pools,
+// then one instruction per line with its offset and source line. The source of
+// a line is shown above its first instruction.
+pub struct Disassembler<'a> {
+    text: &'a SourceText,
+    chunk: &'a Chunk,
+}
+
+impl<'a> Disassembler<'a> {
+    pub fn new(text: &'a SourceText, chunk: &'a Chunk) -> Self {
+        Self { text, chunk }
+    }
+
+    pub fn stringify(&self) -> String {
+        let mut result = format!("== {} ==\n", self.text.file_name());
+        result.push_str(&format!("locals: {}\n", self.chunk.locals));
+        result.push_str("constants:\n");
+        for (index, constant) in self.chunk.constants.iter().enumerate() {
+            result.push_str(&format!("    #{:<4} {}\n", index, constant));
+        }
+        if !self.chunk.strings.is_empty() {
+            result.push_str("strings:\n");
+            for (index, string) in self.chunk.strings.iter().enumerate() {
+                result.push_str(&format!("    #{:<4} {:?}\n", index, string));
+            }
+        }
+        if !self.chunk.structs.is_empty() {
+            result.push_str("structs:\n");
+            for (index, layout) in self.chunk.structs.iter().enumerate() {
+                result.push_str(&format!("    #{:<4} {} {{ {} }}\n", index, layout.name, layout.fields.join(", ")));
+            }
+        }
+        result.push_str("code:\n");
+
+        let mut offset = 0;
+        let mut previous_line = None;
+        while offset < self.chunk.code.len() {
+            let op = OpCode::from_byte(self.chunk.code[offset]).expect("chunk holds a valid opcode");
+            let line = self.chunk.span_at(offset).map(|span| self.text.line_column(span.start).0);
+            let gutter = match line {
+                Some(line) if previous_line != Some(line) => {
+                    result.push_str(&format!("{:>14} {}\n", ";", self.text.get_line(line - 1).trim()));
+                    line.to_string()
+                }
+                _ => "|".to_string(),
+            };
+            previous_line = line;
+            result.push_str(&format!("    {:04} {:>4}  {}\n", offset, gutter, self.instruction(op, offset)));
+            offset += 1 + op.operand_width();
+        }
+        result
+    }
+
+    pub fn print(&self) {
+        print!("{}", self.stringify())
+    }
+
+    fn instruction(&self, op: OpCode, offset: usize) -> String {
+        match op {
+            OpCode::Constant => {
+                let index = self.chunk.read_operand(offset + 1);
+                format!("{:<10} #{} ({})", op, index, self.chunk.constants[index as usize])
+            }
+            OpCode::BuildStruct => {
+                let index = self.chunk.read_operand(offset + 1);
+                format!("{:<10} #{} ({})", op, index, self.chunk.structs[index as usize].name)
+            }
+            OpCode::BuildVariant => {
+                let index = self.chunk.read_operand(offset + 1);
+                let count = self.chunk.read_operand(offset + 3);
+                format!("{:<10} #{} ({}) {}", op, index, self.chunk.strings[index as usize], count)
+            }
+            OpCode::Jump | OpCode::JumpIfFalse => {
+                format!("{:<10} -> {:04}", op, self.chunk.read_operand(offset + 1))
+            }
+            OpCode::GetField | OpCode::SetField | OpCode::IsVariant => {
+                let index = self.chunk.read_operand(offset + 1);
+                format!("{:<10} #{} ({})", op, index, self.chunk.strings[index as usize])
+            }
+            OpCode::String => {
+                let index = self.chunk.read_operand(offset + 1);
+                format!("{:<10} #{} ({:?})", op, index, self.chunk.strings[index as usize])
+            }
+            OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::BuildList
+            | OpCode::BuildMap
+            | OpCode::Payload
+            | OpCode::BuildTuple
+            | OpCode::Element
+            | OpCode::CheckLength
+            | OpCode::BuildRange => {
+                format!("{:<10} {}", op, self.chunk.read_operand(offset + 1))
+            }
+            _ => op.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        bytecode::compiler::BytecodeCompiler, compilation_unit::CompilationUnit, ir::Program, lints::LintConfig,
+    };
+
+    use super::Disassembler;
+
+    fn disassemble(input: &str) -> String {
+        let mut unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        unit.optimize();
+        let chunk = BytecodeCompiler::new().compile(&Program::lower(&unit.ast)).ok().unwrap();
+        Disassembler::new(&unit.text, &chunk).stringify()
+    }
+
+    #[test]
+    fn lists_the_pools_then_each_instruction_under_its_line() {
+        let expected = "\
+== test.sn ==
+locals: 5
+constants:
+    #0    2
+strings:
+    #0    \"hi\"
+code:
+             ; let xs = [\"hi\"]
+    0000    1  STRING     #0 (\"hi\")
+    0003    |  SET_LOCAL  1
+    0006    |  POP
+    0007    |  GET_LOCAL  1
+    0010    |  BUILD_LIST 1
+    0013    |  SET_LOCAL  2
+    0016    |  POP
+    0017    |  GET_LOCAL  2
+    0020    |  SET_LOCAL  0
+    0023    |  POP
+             ; len(xs) + 2
+    0024    2  GET_LOCAL  2
+    0027    |  LENGTH
+    0028    |  SET_LOCAL  3
+    0031    |  POP
+    0032    |  GET_LOCAL  3
+    0035    |  CONSTANT   #0 (2)
+    0038    |  ADD
+    0039    |  SET_LOCAL  4
+    0042    |  POP
+    0043    |  GET_LOCAL  4
+    0046    |  RETURN
+";
+        assert_eq!(disassemble("let xs = [\"hi\"]\nlen(xs) + 2"), expected);
+    }
+
+    #[test]
+    fn names_structs_fields_and_jump_targets() {
+        let listing = disassemble(
+            "struct Point { x: int }\nlet p = Point { x: 1 }\nlet t = 0\nfor _i in 0..3 { t = t + p.x }\nt",
+        );
+        assert!(listing.contains("structs:\n    #0    Point { x }\n"), "{}", listing);
+        assert!(listing.contains("BUILD_STRUCT #0 (Point)\n"), "{}", listing);
+        assert!(listing.contains("GET_FIELD  #0 (x)\n"), "{}", listing);
+        // Every jump names the offset of an instruction, and the loop jumps back
+        let offsets: Vec<&str> = listing.lines().filter_map(|line| line.trim_start().split(' ').next()).collect();
+        let jumps: Vec<(&str, &str)> = listing
+            .lines()
+            .filter_map(|line| Some((line.trim_start().split(' ').next()?, line.split_once("-> ")?.1)))
+            .collect();
+        assert_eq!(jumps.len(), 3, "{}", listing);
+        assert!(jumps.iter().all(|(_, target)| offsets.contains(target)), "{}", listing);
+        assert!(jumps.iter().any(|(offset, target)| target < offset), "{}", listing);
+    }
+}
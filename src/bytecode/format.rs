@@ -0,0 +1,375 @@
+use crate::{ast::lexer::TextSpan, text::SourceText};
+
+use super::{Chunk, OpCode, StructLayout};
+
+// Layout of a `.snc` file, integers in little-endian order:
+//
+//     magic      4 bytes, `SNC\0`
+//     version    u16
+//     checksum   u32, FNV-1a of everything after the header
+//     file name  u32 length + UTF-8
+//     source     u32 length + UTF-8, kept to render runtime diagnostics
+//     locals     u16
+//     constants  u32 count + i64 each
+//     strings    u32 count + (u32 length + UTF-8) each
+//     structs    u32 count + (name, u32 field count + names) each, names as strings
+//     code       u32 length + bytes
+//     spans      u32 count + (u32 offset, u32 start, u32 end) each
+//
+// The version is bumped whenever the layout or the instruction set changes.
+pub const MAGIC: &[u8; 4] = b"SNC\0";
+pub const VERSION: u16 = 7;
+const HEADER_LEN: usize = 10;
+
+pub fn write(text: &SourceText, chunk: &Chunk) -> Vec<u8> {
+    let mut payload = Vec::new();
+    write_bytes(&mut payload, text.file_name().as_bytes());
+    write_bytes(&mut payload, text.text().as_bytes());
+    payload.extend_from_slice(&chunk.locals.to_le_bytes());
+    write_u32(&mut payload, chunk.constants.len());
+    for constant in &chunk.constants {
+        payload.extend_from_slice(&constant.to_le_bytes());
+    }
+    write_u32(&mut payload, chunk.strings.len());
+    for string in &chunk.strings {
+        write_bytes(&mut payload, string.as_bytes());
+    }
+    write_u32(&mut payload, chunk.structs.len());
+    for layout in &chunk.structs {
+        write_bytes(&mut payload, layout.name.as_bytes());
+        write_u32(&mut payload, layout.fields.len());
+        for field in &layout.fields {
+            write_bytes(&mut payload, field.as_bytes());
+        }
+    }
+    write_bytes(&mut payload, &chunk.code);
+    write_u32(&mut payload, chunk.spans.len());
+    for (offset, span) in &chunk.spans {
+        write_u32(&mut payload, *offset);
+        write_u32(&mut payload, span.start);
+        write_u32(&mut payload, span.end);
+    }
+
+    let mut bytes = Vec::with_capacity(HEADER_LEN + payload.len());
+    bytes.extend_from_slice(MAGIC);
+    bytes.extend_from_slice(&VERSION.to_le_bytes());
+    bytes.extend_from_slice(&checksum(&payload).to_le_bytes());
+    bytes.extend_from_slice(&payload);
+    bytes
+}
+
+// Loads a chunk written by `write`. The structure of the code is validated,
+// so the VM never reads past it or its pools; the kinds of values are only
+// known as it runs, so the VM checks those itself. The checksum catches
+// accidental damage only, not edits.
+pub fn read(bytes: &[u8]) -> Result<(SourceText, Chunk), String> {
+    if bytes.len() < HEADER_LEN || &bytes[..4] != MAGIC {
+        return Err("not a sirin bytecode file".to_string());
+    }
+    let version = u16::from_le_bytes([bytes[4], bytes[5]]);
+    if version != VERSION {
+        return Err(format!(
+            "bytecode version {} is not supported, expected version {}; recompile the source",
+            version, VERSION
+        ));
+    }
+    let expected = u32::from_le_bytes([bytes[6], bytes[7], bytes[8], bytes[9]]);
+    let payload = &bytes[HEADER_LEN..];
+    if checksum(payload) != expected {
+        return Err("bytecode file is corrupted (checksum mismatch)".to_string());
+    }
+
+    let mut reader = Reader { bytes: payload, pos: 0 };
+    let file_name = reader.string()?;
+    let source = reader.string()?;
+    let mut chunk = Chunk::new();
+    chunk.locals = u16::from_le_bytes(reader.array()?);
+    for _ in 0..reader.u32()? {
+        chunk.constants.push(i64::from_le_bytes(reader.array()?));
+    }
+    for _ in 0..reader.u32()? {
+        chunk.strings.push(reader.string()?);
+    }
+    for _ in 0..reader.u32()? {
+        let name = reader.string()?;
+        let fields = (0..reader.u32()?).map(|_| reader.string()).collect::<Result<_, _>>()?;
+        chunk.structs.push(StructLayout { name, fields });
+    }
+    let code_len = reader.u32()?;
+    chunk.code = reader.take(code_len)?.to_vec();
+    for _ in 0..reader.u32()? {
+        let offset = reader.u32()?;
+        let (start, end) = (reader.u32()?, reader.u32()?);
+        let literal = source.get(start..end).ok_or("bytecode span is out of bounds")?;
+        chunk.spans.push((offset, TextSpan::new(start, end, literal.to_string())));
+    }
+    if reader.pos != payload.len() {
+        return Err("unexpected data after the end of the bytecode".to_string());
+    }
+    validate(&chunk)?;
+    Ok((SourceText::new(source, file_name), chunk))
+}
+
+// Every instruction must be complete, reference existing constants, strings,
+// structs, slots and instructions, and have a span; the code must end with
+// `RETURN` or `JUMP`, so control never runs past it. Every path reaching an
+// instruction must leave the stack at the same depth, so depths are exact and
+// each instruction finds enough values on the stack.
+fn validate(chunk: &Chunk) -> Result<(), String> {
+    let mut offset = 0;
+    let mut instructions = Vec::new(); // Offset and opcode of each instruction
+    while offset < chunk.code.len() {
+        let op = OpCode::from_byte(chunk.code[offset])
+            .ok_or_else(|| format!("invalid opcode {:#04x} at offset {}", chunk.code[offset], offset))?;
+        if offset + op.operand_width() >= chunk.code.len() {
+            return Err(format!("truncated `{}` instruction at offset {}", op, offset));
+        }
+        let in_range = match op {
+            OpCode::Constant => (chunk.read_operand(offset + 1) as usize) < chunk.constants.len(),
+            OpCode::String | OpCode::GetField | OpCode::SetField | OpCode::BuildVariant | OpCode::IsVariant => {
+                (chunk.read_operand(offset + 1) as usize) < chunk.strings.len()
+            }
+            OpCode::BuildStruct => (chunk.read_operand(offset + 1) as usize) < chunk.structs.len(),
+            OpCode::GetLocal | OpCode::SetLocal => chunk.read_operand(offset + 1) < chunk.locals,
+            OpCode::BuildRange => chunk.read_operand(offset + 1) <= 1,
+            _ => true,
+        };
+        if !in_range {
+            return Err(format!("operand of `{}` at offset {} is out of range", op, offset));
+        }
+        if chunk.span_at(offset).is_none() {
+            return Err(format!("instruction at offset {} has no source span", offset));
+        }
+        instructions.push((offset, op));
+        offset += 1 + op.operand_width();
+    }
+    if !matches!(instructions.last(), Some((_, OpCode::Return | OpCode::Jump))) {
+        return Err("bytecode does not end with `RETURN` or `JUMP`".to_string());
+    }
+    let find = |target: usize| instructions.binary_search_by_key(&target, |(offset, _)| *offset).ok();
+    for (offset, op) in &instructions {
+        if matches!(op, OpCode::Jump | OpCode::JumpIfFalse) && find(chunk.read_operand(offset + 1) as usize).is_none() {
+            return Err(format!("`{}` at offset {} does not target an instruction", op, offset));
+        }
+    }
+
+    // Depth of the stack before each instruction reached from the start
+    let mut depths = vec![None; instructions.len()];
+    let mut pending: Vec<(usize, usize)> = vec![(0, 0)];
+    while let Some((index, depth)) = pending.pop() {
+        let (offset, op) = instructions[index];
+        match depths[index] {
+            Some(known) if known == depth => continue,
+            Some(known) => {
+                return Err(format!(
+                    "stack depth at offset {} is {} along one path and {} along another",
+                    offset, known, depth
+                ))
+            }
+            None => depths[index] = Some(depth),
+        }
+        let (popped, pushed) = match op {
+            OpCode::Constant | OpCode::GetLocal | OpCode::String => (0, 1),
+            OpCode::SetLocal | OpCode::Length | OpCode::RemoveLast | OpCode::Keys | OpCode::Values => (1, 1),
+            OpCode::GetField | OpCode::IsVariant | OpCode::Payload | OpCode::Element | OpCode::Iterate => (1, 1),
+            OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => (2, 1),
+            OpCode::GetIndex | OpCode::Append | OpCode::Has | OpCode::Equal | OpCode::BuildRange => (2, 1),
+            OpCode::Slice => (3, 1),
+            OpCode::SetIndex => (3, 0),
+            OpCode::SetField => (2, 0),
+            OpCode::BuildList | OpCode::BuildTuple => (chunk.read_operand(offset + 1) as usize, 1),
+            OpCode::BuildMap => (2 * chunk.read_operand(offset + 1) as usize, 1),
+            OpCode::BuildStruct => (chunk.structs[chunk.read_operand(offset + 1) as usize].fields.len(), 1),
+            OpCode::BuildVariant => (chunk.read_operand(offset + 3) as usize, 1),
+            OpCode::Pop | OpCode::JumpIfFalse | OpCode::CheckLength => (1, 0),
+            OpCode::Return | OpCode::Jump => (0, 0),
+        };
+        let depth = depth
+            .checked_sub(popped)
+            .ok_or_else(|| format!("`{}` at offset {} underflows the stack", op, offset))?
+            + pushed;
+        let target = || find(chunk.read_operand(offset + 1) as usize).expect("jump targets are checked");
+        match op {
+            OpCode::Return => {}
+            OpCode::Jump => pending.push((target(), depth)),
+            OpCode::JumpIfFalse => pending.extend([(index + 1, depth), (target(), depth)]),
+            _ => pending.push((index + 1, depth)),
+        }
+    }
+    Ok(())
+}
+
+// 32-bit FNV-1a
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5, |hash, byte| (hash ^ *byte as u32).wrapping_mul(0x01000193))
+}
+
+fn write_u32(out: &mut Vec<u8>, value: usize) {
+    out.extend_from_slice(&(value as u32).to_le_bytes());
+}
+
+fn write_bytes(out: &mut Vec<u8>, bytes: &[u8]) {
+    write_u32(out, bytes.len());
+    out.extend_from_slice(bytes);
+}
+
+struct Reader<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], String> {
+        let bytes = self
+            .bytes
+            .get(self.pos..self.pos + len)
+            .ok_or("bytecode file ends unexpectedly")?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn array<const N: usize>(&mut self) -> Result<[u8; N], String> {
+        Ok(self.take(N)?.try_into().unwrap())
+    }
+
+    fn u32(&mut self) -> Result<usize, String> {
+        Ok(u32::from_le_bytes(self.array()?) as usize)
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        let len = self.u32()?;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| "bytecode file holds invalid UTF-8".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, fs, rc::Rc};
+
+    use crate::{
+        ast::lexer::TextSpan,
+        bytecode::{compiler::BytecodeCompiler, vm::VirtualMachine, Chunk, OpCode},
+        cli::Engine,
+        compilation_unit::CompilationUnit,
+        diagnostics::DiagnosticsBag,
+        ir::Program,
+        lints::LintConfig,
+        text::SourceText,
+    };
+
+    use super::{read, write, HEADER_LEN};
+
+    fn compile(input: String, file_name: String) -> (CompilationUnit, Chunk) {
+        let mut unit = CompilationUnit::compile(input, file_name, LintConfig::new());
+        unit.optimize();
+        assert!(!unit.has_errors());
+        let chunk = BytecodeCompiler::new().compile(&Program::lower(&unit.ast)).ok().unwrap();
+        (unit, chunk)
+    }
+
+    #[test]
+    fn reads_back_what_it_writes() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let (unit, chunk) = compile(fs::read_to_string(&path).unwrap(), path.display().to_string());
+            let (text, loaded) = read(&write(&unit.text, &chunk)).unwrap();
+            assert_eq!(text.file_name(), unit.text.file_name());
+            assert_eq!(text.text(), unit.text.text());
+            assert_eq!(loaded.locals, chunk.locals);
+            assert_eq!(loaded.constants, chunk.constants);
+            assert_eq!(loaded.strings, chunk.strings);
+            assert_eq!(loaded.code, chunk.code);
+            assert_eq!(loaded.spans.len(), chunk.spans.len());
+            let result = VirtualMachine::new(&loaded, unit.diagnostics_bag.clone()).run();
+            let expected = unit.execute(&Engine::Tree);
+            assert_eq!(result.map(|value| value.to_string()), expected.map(|value| value.to_string()));
+        }
+    }
+
+    #[test]
+    fn rejects_files_that_are_not_intact_bytecode() {
+        let (unit, chunk) = compile("let xs = [1, 2]\nlen(xs)".to_string(), "test.sn".to_string());
+        let bytes = write(&unit.text, &chunk);
+        let error = |bytes: &[u8]| read(bytes).err().unwrap();
+
+        assert_eq!(error(b"let x = 1"), "not a sirin bytecode file");
+        let mut old = bytes.clone();
+        old[4] = 1;
+        old[5] = 0;
+        assert_eq!(
+            error(&old),
+            format!("bytecode version 1 is not supported, expected version {}; recompile the source", super::VERSION)
+        );
+        let mut corrupted = bytes.clone();
+        corrupted[HEADER_LEN + 5] ^= 1;
+        assert_eq!(error(&corrupted), "bytecode file is corrupted (checksum mismatch)");
+    }
+
+    // Builds a file around hand-written code, which the compiler would never emit
+    fn with_code(emit: impl FnOnce(&mut Chunk, TextSpan)) -> Result<(SourceText, Chunk), String> {
+        let text = SourceText::new("1".to_string(), "test.sn".to_string());
+        let mut chunk = Chunk::new();
+        emit(&mut chunk, TextSpan::new(0, 1, "1".to_string()));
+        read(&write(&text, &chunk))
+    }
+
+    #[test]
+    fn rejects_code_the_vm_could_not_run_safely() {
+        let error = |emit: fn(&mut Chunk, TextSpan)| with_code(emit).err().unwrap();
+        assert_eq!(
+            error(|chunk, span| {
+                chunk.emit(OpCode::Pop, span.clone());
+                chunk.emit(OpCode::Return, span);
+            }),
+            "`POP` at offset 0 underflows the stack"
+        );
+        assert_eq!(
+            error(|chunk, span| {
+                chunk.emit_with_operand(OpCode::Constant, 3, span.clone());
+                chunk.emit(OpCode::Return, span);
+            }),
+            "operand of `CONSTANT` at offset 0 is out of range"
+        );
+        assert_eq!(
+            error(|chunk, span| {
+                let index = chunk.add_constant(1).unwrap();
+                chunk.emit_with_operand(OpCode::Constant, index, span);
+            }),
+            "bytecode does not end with `RETURN` or `JUMP`"
+        );
+        assert_eq!(
+            error(|chunk, span| {
+                chunk.emit_with_operand(OpCode::Jump, 1, span);
+            }),
+            "`JUMP` at offset 0 does not target an instruction"
+        );
+        assert!(with_code(|chunk, span| {
+            let index = chunk.add_constant(1).unwrap();
+            chunk.emit_with_operand(OpCode::Constant, index, span.clone());
+            chunk.emit(OpCode::Return, span);
+        })
+        .is_ok());
+    }
+
+    // Loading checks the structure of the code, running it the kinds of values
+    #[test]
+    fn reports_instructions_applied_to_values_of_the_wrong_kind() {
+        let (text, chunk) = with_code(|chunk, span| {
+            let string = chunk.add_string("x").unwrap();
+            let constant = chunk.add_constant(1).unwrap();
+            chunk.emit_with_operand(OpCode::String, string, span.clone());
+            chunk.emit_with_operand(OpCode::Constant, constant, span.clone());
+            chunk.emit(OpCode::Add, span.clone());
+            chunk.emit(OpCode::Return, span);
+        })
+        .unwrap();
+        let diagnostics_bag = Rc::new(RefCell::new(DiagnosticsBag::new(LintConfig::new())));
+        assert!(VirtualMachine::new(&chunk, diagnostics_bag.clone()).run().is_none());
+        let diagnostics = &diagnostics_bag.borrow().diagnostics;
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].code.code(), "E0028");
+        assert_eq!(diagnostics[0].message, "Invalid bytecode: `ADD` expects an int, found `\"x\"`");
+        assert_eq!(diagnostics[0].span.literal, text.text());
+    }
+}
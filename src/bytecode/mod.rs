@@ -0,0 +1,262 @@
+use std::fmt::Display;
+
+use crate::ast::lexer::TextSpan;
+
+pub mod compiler;
+pub mod disassembler;
+pub mod format;
+pub mod vm;
+
+// One byte per opcode, followed by its operands in little-endian order
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+#[repr(u8)]
+pub enum OpCode {
+    Constant, // u16 index into the constant pool
+    GetLocal, // u16 slot
+    SetLocal, // u16 slot, leaves the value on the stack
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Pop,
+    Return, // Ends the program with the top of the stack, if any, as its result
+    BuildList, // u16 count, pops that many elements, first pushed first
+    GetIndex, // Pops the index, then the list or map
+    SetIndex, // Pops the value, the index, then the list or map
+    Length,
+    Append, // Pops the value, then the list; pushes the new length
+    RemoveLast,
+    Slice, // Pops the end, the start, then the list
+    String, // u16 index into the string pool
+    BuildMap, // u16 count, pops that many key and value pairs, first pushed first
+    Keys,
+    Values,
+    Has, // Pops the key, then the map
+    BuildStruct, // u16 index into the struct pool, pops one value per field, first pushed first
+    GetField, // u16 index into the string pool, pops the struct
+    SetField, // u16 index into the string pool, pops the value, then the struct
+    BuildVariant, // u16 index into the string pool, then u16 count; pops that many values, first pushed first
+    IsVariant, // u16 index into the string pool, pops the value; pushes whether it is that variant
+    Payload, // u16 index, pops the variant
+    BuildTuple, // u16 count, pops that many elements, first pushed first
+    Element, // u16 index, pops the tuple
+    CheckLength, // u16 length, pops the list; fails unless it has that length
+    BuildRange, // u16 1 if inclusive, else 0; pops the end, then the start
+    Iterate, // Pops a range, list, string or map; pushes the items a `for` loop runs over
+    Equal, // Pops two integers or two strings; pushes whether they are equal
+    Jump, // u16 offset of the next instruction to run
+    JumpIfFalse, // u16 offset, pops the condition
+}
+
+impl OpCode {
+    pub const ALL: &'static [OpCode] = &[
+        OpCode::Constant,
+        OpCode::GetLocal,
+        OpCode::SetLocal,
+        OpCode::Add,
+        OpCode::Subtract,
+        OpCode::Multiply,
+        OpCode::Divide,
+        OpCode::Pop,
+        OpCode::Return,
+        OpCode::BuildList,
+        OpCode::GetIndex,
+        OpCode::SetIndex,
+        OpCode::Length,
+        OpCode::Append,
+        OpCode::RemoveLast,
+        OpCode::Slice,
+        OpCode::String,
+        OpCode::BuildMap,
+        OpCode::Keys,
+        OpCode::Values,
+        OpCode::Has,
+        OpCode::BuildStruct,
+        OpCode::GetField,
+        OpCode::SetField,
+        OpCode::BuildVariant,
+        OpCode::IsVariant,
+        OpCode::Payload,
+        OpCode::BuildTuple,
+        OpCode::Element,
+        OpCode::CheckLength,
+        OpCode::BuildRange,
+        OpCode::Iterate,
+        OpCode::Equal,
+        OpCode::Jump,
+        OpCode::JumpIfFalse,
+    ];
+
+    pub fn from_byte(byte: u8) -> Option<Self> {
+        Self::ALL.get(byte as usize).copied()
+    }
+
+    // Number of operand bytes following the opcode
+    pub fn operand_width(&self) -> usize {
+        match self {
+            OpCode::Constant
+            | OpCode::GetLocal
+            | OpCode::SetLocal
+            | OpCode::BuildList
+            | OpCode::String
+            | OpCode::BuildMap
+            | OpCode::BuildStruct
+            | OpCode::GetField
+            | OpCode::SetField
+            | OpCode::IsVariant
+            | OpCode::Payload
+            | OpCode::BuildTuple
+            | OpCode::Element
+            | OpCode::CheckLength
+            | OpCode::BuildRange
+            | OpCode::Jump
+            | OpCode::JumpIfFalse => 2,
+            OpCode::BuildVariant => 4,
+            _ => 0,
+        }
+    }
+}
+
+/* DISPLAY */
+
+impl Display for OpCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            OpCode::Constant => "CONSTANT",
+            OpCode::GetLocal => "GET_LOCAL",
+            OpCode::SetLocal => "SET_LOCAL",
+            OpCode::Add => "ADD",
+            OpCode::Subtract => "SUBTRACT",
+            OpCode::Multiply => "MULTIPLY",
+            OpCode::Divide => "DIVIDE",
+            OpCode::Pop => "POP",
+            OpCode::Return => "RETURN",
+            OpCode::BuildList => "BUILD_LIST",
+            OpCode::GetIndex => "GET_INDEX",
+            OpCode::SetIndex => "SET_INDEX",
+            OpCode::Length => "LENGTH",
+            OpCode::Append => "APPEND",
+            OpCode::RemoveLast => "REMOVE_LAST",
+            OpCode::Slice => "SLICE",
+            OpCode::String => "STRING",
+            OpCode::BuildMap => "BUILD_MAP",
+            OpCode::Keys => "KEYS",
+            OpCode::Values => "VALUES",
+            OpCode::Has => "HAS",
+            OpCode::BuildStruct => "BUILD_STRUCT",
+            OpCode::GetField => "GET_FIELD",
+            OpCode::SetField => "SET_FIELD",
+            OpCode::BuildVariant => "BUILD_VARIANT",
+            OpCode::IsVariant => "IS_VARIANT",
+            OpCode::Payload => "PAYLOAD",
+            OpCode::BuildTuple => "BUILD_TUPLE",
+            OpCode::Element => "ELEMENT",
+            OpCode::CheckLength => "CHECK_LENGTH",
+            OpCode::BuildRange => "BUILD_RANGE",
+            OpCode::Iterate => "ITERATE",
+            OpCode::Equal => "EQUAL",
+            OpCode::Jump => "JUMP",
+            OpCode::JumpIfFalse => "JUMP_IF_FALSE",
+        };
+        f.pad(name)
+    }
+}
+
+// Name and field order of a struct built by `BUILD_STRUCT`
+#[derive(Debug, PartialEq)]
+pub struct StructLayout {
+    pub(crate) name: String,
+    pub(crate) fields: Vec<String>,
+}
+
+// A compiled program
+pub struct Chunk {
+    pub(crate) code: Vec<u8>,
+    pub(crate) constants: Vec<i64>,
+    pub(crate) strings: Vec<String>, // Pool of string literals
+    pub(crate) structs: Vec<StructLayout>, // Pool of struct layouts
+    pub(crate) locals: u16, // Slots reserved on the frame before running
+    pub(crate) spans: Vec<(usize, TextSpan)>, // Source of every instruction, by offset
+}
+
+impl Chunk {
+    pub fn new() -> Self {
+        Self {
+            code: Vec::new(),
+            constants: Vec::new(),
+            strings: Vec::new(),
+            structs: Vec::new(),
+            locals: 0,
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn emit(&mut self, op: OpCode, span: TextSpan) -> usize {
+        self.code.push(op as u8);
+        let offset = self.code.len() - 1;
+        self.spans.push((offset, span));
+        offset
+    }
+
+    pub fn emit_with_operand(&mut self, op: OpCode, operand: u16, span: TextSpan) -> usize {
+        self.emit_with_operands(op, &[operand], span)
+    }
+
+    pub fn emit_with_operands(&mut self, op: OpCode, operands: &[u16], span: TextSpan) -> usize {
+        let offset = self.emit(op, span);
+        for operand in operands {
+            self.code.extend_from_slice(&operand.to_le_bytes());
+        }
+        offset
+    }
+
+    // Overwrites the operand at `offset`, for jumps emitted before their target
+    pub fn patch_operand(&mut self, offset: usize, operand: u16) {
+        self.code[offset..offset + 2].copy_from_slice(&operand.to_le_bytes());
+    }
+
+    // `None` once the pool is too large to be indexed by an operand
+    pub fn add_constant(&mut self, value: i64) -> Option<u16> {
+        let index = match self.constants.iter().position(|constant| *constant == value) {
+            Some(index) => index,
+            None => {
+                self.constants.push(value);
+                self.constants.len() - 1
+            }
+        };
+        u16::try_from(index).ok()
+    }
+
+    pub fn add_string(&mut self, text: &str) -> Option<u16> {
+        let index = match self.strings.iter().position(|string| string == text) {
+            Some(index) => index,
+            None => {
+                self.strings.push(text.to_string());
+                self.strings.len() - 1
+            }
+        };
+        u16::try_from(index).ok()
+    }
+
+    pub fn add_struct(&mut self, layout: StructLayout) -> Option<u16> {
+        let index = match self.structs.iter().position(|existing| *existing == layout) {
+            Some(index) => index,
+            None => {
+                self.structs.push(layout);
+                self.structs.len() - 1
+            }
+        };
+        u16::try_from(index).ok()
+    }
+
+    pub fn read_operand(&self, offset: usize) -> u16 {
+        u16::from_le_bytes([self.code[offset], self.code[offset + 1]])
+    }
+
+    pub fn span_at(&self, offset: usize) -> Option<&TextSpan> {
+        self.spans
+            .binary_search_by_key(&offset, |(instruction, _)| *instruction)
+            .ok()
+            .map(|index| &self.spans[index].1)
+    }
+}
@@ -0,0 +1,308 @@
+use crate::{
+    diagnostics::DiagnosticsBagCell,
+    runtime::{self, AccessError, Map, Value},
+};
+
+use super::{Chunk, OpCode};
+
+// What an instruction expects of an operand, and how to recognize it
+type Kind = (&'static str, fn(&Value) -> bool);
+
+const INT: Kind = ("an int", |value| matches!(value, Value::Int(_)));
+const BOOL: Kind = ("a bool", |value| matches!(value, Value::Bool(_)));
+const KEY: Kind = ("an int or a string", |value| matches!(value, Value::Int(_) | Value::String(_)));
+const LIST: Kind = ("a list", |value| matches!(value, Value::List(_)));
+const MAP: Kind = ("a map", |value| matches!(value, Value::Map(_)));
+const ASSIGNABLE: Kind = ("a list or a map", |value| matches!(value, Value::List(_) | Value::Map(_)));
+const INDEXABLE: Kind = ("a list, a map or a range", |value| {
+    matches!(value, Value::List(_) | Value::Map(_) | Value::Range { .. })
+});
+const ITERABLE: Kind = ("a list, a map, a range or a string", |value| {
+    matches!(value, Value::List(_) | Value::Map(_) | Value::Range { .. } | Value::String(_))
+});
+const STRUCT: Kind = ("a struct", |value| matches!(value, Value::Struct(_)));
+const VARIANT: Kind = ("an enum", |value| matches!(value, Value::Variant(_)));
+const TUPLE: Kind = ("a tuple", |value| matches!(value, Value::Tuple(_)));
+
+// Stack machine running a `Chunk`. Runtime errors are reported to the
+// diagnostics bag and stop the program. The checker guarantees the kind of
+// every value for compiled source, but a `.snc` file may have been edited
+// since it was compiled, so each instruction checks its operands and reports
+// a value of the wrong kind instead of trusting the file.
+pub struct VirtualMachine<'a> {
+    chunk: &'a Chunk,
+    ip: usize,
+    stack: Vec<Value>,
+    locals: Vec<Value>,
+    diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl<'a> VirtualMachine<'a> {
+    pub fn new(chunk: &'a Chunk, diagnostics_bag: DiagnosticsBagCell) -> Self {
+        Self {
+            chunk,
+            ip: 0,
+            stack: Vec::new(),
+            locals: vec![Value::Int(0); chunk.locals as usize],
+            diagnostics_bag,
+        }
+    }
+
+    // The program's result, or `None` if it is empty or failed
+    pub fn run(&mut self) -> Option<Value> {
+        loop {
+            let offset = self.ip;
+            let op = OpCode::from_byte(self.chunk.code[offset]).expect("chunk holds a valid opcode");
+            self.ip += 1;
+            match op {
+                OpCode::Constant => {
+                    let index = self.read_operand();
+                    self.stack.push(Value::Int(self.chunk.constants[index as usize]));
+                }
+                OpCode::GetLocal => {
+                    let slot = self.read_operand();
+                    self.stack.push(self.locals[slot as usize].clone());
+                }
+                OpCode::SetLocal => {
+                    let slot = self.read_operand();
+                    self.locals[slot as usize] = self.stack.last().expect("value to store").clone();
+                }
+                OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => {
+                    let right = self.pop_int(offset)?;
+                    let left = self.pop_int(offset)?;
+                    let result = self.arithmetic(op, left, right, offset)?;
+                    self.stack.push(Value::Int(result));
+                }
+                OpCode::BuildList => {
+                    let count = self.read_operand() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::list(elements));
+                }
+                OpCode::GetIndex => {
+                    let index = self.pop();
+                    let target = self.pop_kind(INDEXABLE, offset)?;
+                    let index = self.check_index(&target, index, offset)?;
+                    let element = self.check_access(runtime::get(&target, &index), offset)?;
+                    self.stack.push(element);
+                }
+                OpCode::SetIndex => {
+                    let value = self.pop();
+                    let index = self.pop();
+                    let target = self.pop_kind(ASSIGNABLE, offset)?;
+                    let index = self.check_index(&target, index, offset)?;
+                    self.check_access(runtime::set(&target, &index, value), offset)?;
+                }
+                OpCode::Length => {
+                    let list = self.pop_kind(INDEXABLE, offset)?;
+                    self.stack.push(Value::Int(runtime::length(&list)));
+                }
+                OpCode::Append => {
+                    let value = self.pop();
+                    let list = self.pop_kind(LIST, offset)?;
+                    self.stack.push(Value::Int(runtime::push(&list, value)));
+                }
+                OpCode::RemoveLast => {
+                    let list = self.pop_kind(LIST, offset)?;
+                    let element = self.check_access(runtime::pop(&list), offset)?;
+                    self.stack.push(element);
+                }
+                OpCode::Slice => {
+                    let end = self.pop_int(offset)?;
+                    let start = self.pop_int(offset)?;
+                    let list = self.pop_kind(LIST, offset)?;
+                    let slice = self.check_access(runtime::slice(&list, start, end), offset)?;
+                    self.stack.push(slice);
+                }
+                OpCode::String => {
+                    let index = self.read_operand();
+                    self.stack.push(Value::string(&self.chunk.strings[index as usize]));
+                }
+                OpCode::BuildMap => {
+                    let count = self.read_operand() as usize;
+                    let entries = self.stack.split_off(self.stack.len() - 2 * count);
+                    let mut map = Map::new();
+                    for entry in entries.chunks(2) {
+                        let key = self.check(entry[0].clone(), KEY, offset)?;
+                        map.insert(&key, entry[1].clone());
+                    }
+                    self.stack.push(Value::map(map));
+                }
+                OpCode::Keys => {
+                    let map = self.pop_kind(MAP, offset)?;
+                    self.stack.push(runtime::keys(&map));
+                }
+                OpCode::Values => {
+                    let map = self.pop_kind(MAP, offset)?;
+                    self.stack.push(runtime::values(&map));
+                }
+                OpCode::Has => {
+                    let key = self.pop_kind(KEY, offset)?;
+                    let map = self.pop_kind(MAP, offset)?;
+                    self.stack.push(runtime::has(&map, &key));
+                }
+                OpCode::BuildStruct => {
+                    let layout = &self.chunk.structs[self.read_operand() as usize];
+                    let values = self.stack.split_off(self.stack.len() - layout.fields.len());
+                    let fields = layout.fields.iter().map(String::as_str).zip(values).collect();
+                    self.stack.push(Value::record(&layout.name, fields));
+                }
+                OpCode::GetField => {
+                    let field = &self.chunk.strings[self.read_operand() as usize];
+                    let target = self.pop_field(field, offset)?;
+                    self.stack.push(runtime::field(&target, field));
+                }
+                OpCode::SetField => {
+                    let field = &self.chunk.strings[self.read_operand() as usize];
+                    let value = self.pop();
+                    let target = self.pop_field(field, offset)?;
+                    runtime::set_field(&target, field, value);
+                }
+                OpCode::BuildVariant => {
+                    let name = &self.chunk.strings[self.read_operand() as usize];
+                    let count = self.read_operand() as usize;
+                    let payload = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::variant(name, payload));
+                }
+                OpCode::IsVariant => {
+                    let variant = &self.chunk.strings[self.read_operand() as usize];
+                    let value = self.pop_kind(VARIANT, offset)?;
+                    self.stack.push(runtime::is_variant(&value, variant));
+                }
+                OpCode::Payload => {
+                    let index = self.read_operand() as usize;
+                    let value = self.pop_kind(VARIANT, offset)?;
+                    if index >= runtime::payload_len(&value) {
+                        return self.fail(&format!("an enum with more than {} values", index), &value, offset);
+                    }
+                    self.stack.push(runtime::payload(&value, index));
+                }
+                OpCode::BuildTuple => {
+                    let count = self.read_operand() as usize;
+                    let elements = self.stack.split_off(self.stack.len() - count);
+                    self.stack.push(Value::tuple(elements));
+                }
+                OpCode::Element => {
+                    let index = self.read_operand() as usize;
+                    let tuple = self.pop_kind(TUPLE, offset)?;
+                    if index >= tuple.as_tuple().len() {
+                        return self.fail(&format!("a tuple of more than {} elements", index), &tuple, offset);
+                    }
+                    self.stack.push(runtime::element(&tuple, index));
+                }
+                OpCode::CheckLength => {
+                    let length = self.read_operand() as usize;
+                    let list = self.pop_kind(LIST, offset)?;
+                    self.check_access(runtime::check_length(&list, length), offset)?;
+                }
+                OpCode::BuildRange => {
+                    let inclusive = self.read_operand() != 0;
+                    let end = self.pop_int(offset)?;
+                    let start = self.pop_int(offset)?;
+                    self.stack.push(Value::Range { start, end, inclusive });
+                }
+                OpCode::Iterate => {
+                    let iterable = self.pop_kind(ITERABLE, offset)?;
+                    self.stack.push(runtime::iterate(&iterable));
+                }
+                OpCode::Equal => {
+                    let right = self.pop_kind(KEY, offset)?;
+                    let left = self.pop_kind(KEY, offset)?;
+                    if std::mem::discriminant(&left) != std::mem::discriminant(&right) {
+                        return self.fail(&format!("a value of the same kind as `{}`", left), &right, offset);
+                    }
+                    self.stack.push(runtime::equals(&left, &right));
+                }
+                OpCode::Jump => self.ip = self.read_operand() as usize,
+                OpCode::JumpIfFalse => {
+                    let target = self.read_operand() as usize;
+                    if !self.pop_kind(BOOL, offset)?.as_bool() {
+                        self.ip = target;
+                    }
+                }
+                OpCode::Pop => {
+                    self.pop();
+                }
+                OpCode::Return => return self.stack.pop(),
+            }
+        }
+    }
+
+    fn read_operand(&mut self) -> u16 {
+        let operand = self.chunk.read_operand(self.ip);
+        self.ip += 2;
+        operand
+    }
+
+    fn pop(&mut self) -> Value {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    fn pop_kind(&mut self, kind: Kind, offset: usize) -> Option<Value> {
+        let value = self.pop();
+        self.check(value, kind, offset)
+    }
+
+    fn pop_int(&mut self, offset: usize) -> Option<i64> {
+        Some(self.pop_kind(INT, offset)?.as_int())
+    }
+
+    fn pop_field(&mut self, field: &str, offset: usize) -> Option<Value> {
+        let target = self.pop_kind(STRUCT, offset)?;
+        match runtime::has_field(&target, field) {
+            true => Some(target),
+            false => self.fail(&format!("a struct with a field `{}`", field), &target, offset),
+        }
+    }
+
+    // Maps are indexed by keys, lists and ranges by ints
+    fn check_index(&mut self, target: &Value, index: Value, offset: usize) -> Option<Value> {
+        match target {
+            Value::Map(_) => self.check(index, KEY, offset),
+            _ => self.check(index, INT, offset),
+        }
+    }
+
+    fn check(&mut self, value: Value, (expected, accepts): Kind, offset: usize) -> Option<Value> {
+        match accepts(&value) {
+            true => Some(value),
+            false => self.fail(expected, &value, offset),
+        }
+    }
+
+    // Reports an operand of the wrong kind for the instruction at `offset`
+    fn fail<T>(&mut self, expected: &str, found: &Value, offset: usize) -> Option<T> {
+        let op = OpCode::from_byte(self.chunk.code[offset]).expect("chunk holds a valid opcode");
+        let span = self.chunk.span_at(offset).cloned().expect("every instruction carries its span");
+        self.diagnostics_bag.borrow_mut().report_invalid_bytecode(&op.to_string(), expected, found, &span);
+        None
+    }
+
+    // Reports a failed list or map operation at the span of the instruction
+    fn check_access<T>(&mut self, result: Result<T, AccessError>, offset: usize) -> Option<T> {
+        result
+            .map_err(|error| {
+                let span = self.chunk.span_at(offset).cloned().expect("every instruction carries its span");
+                self.diagnostics_bag.borrow_mut().report_access_error(&error, &span);
+            })
+            .ok()
+    }
+
+    fn arithmetic(&mut self, op: OpCode, left: i64, right: i64, offset: usize) -> Option<i64> {
+        let (result, operator) = match op {
+            OpCode::Add => (left.checked_add(right), "+"),
+            OpCode::Subtract => (left.checked_sub(right), "-"),
+            OpCode::Multiply => (left.checked_mul(right), "*"),
+            _ => (left.checked_div(right), "/"),
+        };
+        if result.is_none() {
+            let span = self.chunk.span_at(offset).cloned().expect("every instruction carries its span");
+            let mut diagnostics_bag = self.diagnostics_bag.borrow_mut();
+            if op == OpCode::Divide && right == 0 {
+                diagnostics_bag.report_division_by_zero(&span, None);
+            } else {
+                diagnostics_bag.report_arithmetic_overflow(operator, left, right, &span);
+            }
+        }
+        result
+    }
+}
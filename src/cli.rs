@@ -0,0 +1,274 @@
+use std::str::FromStr;
+
+use crate::lints::{LintConfig, LintLevel, LintSource};
+
+pub const USAGE: &str = "\
+Usage: sirin [run] [options] [file]
+       sirin explain <code>
+       sirin fix <file>
+       sirin ast <file>
+       sirin compile [-o <output>] <file>
+       sirin disasm <file>
+       sirin emit-ir [-o <output>] <file>
+       sirin emit-c [-o <output>] <file>
+       sirin emit-wat [-o <output>] <file>
+       sirin emit-asm [-o <output>] <file>
+       sirin emit-llvm [-o <output>] <file>
+       sirin build [-o <output>] <file>
+
+Commands:
+    run        Parse and evaluate a file, or run a compiled `.snc` file (default)
+    explain    Print the long-form description of a diagnostic code
+    fix        Apply every machine-applicable suggestion to a file in place
+    ast        Print the syntax tree as JSON, with node ids, spans and inferred types
+    compile    Compile a file to bytecode, written next to it as `.snc` unless `-o` is given
+    disasm     Print the bytecode of a source or `.snc` file
+    emit-ir    Lower a file to the optimized intermediate representation the backends translate, printed unless `-o` is given
+    emit-c     Translate a file to a self-contained C99 program, printed unless `-o` is given
+    emit-wat   Translate a file to a WebAssembly module in text format, printed unless `-o` is given
+    emit-asm   Translate a file to x86-64 GNU assembly, printed unless `-o` is given
+    emit-llvm  Translate a file to an LLVM IR module in text form, printed unless `-o` is given
+    build      Build a native x86-64 Linux executable with `as` and `ld`, named after the file unless `-o` is given
+
+Options:
+    --format <human|json|sarif>    How diagnostics are reported; json and sarif go to stderr (default: human)
+    --engine <vm|tree>             Run on the bytecode VM or the tree-walking evaluator (default: vm)
+    -A, --allow <lint>             Silence a lint
+    -W, --warn <lint>              Report a lint as a warning
+    -D, --deny <lint>              Report a lint as an error; `-D warnings` denies every warning";
+
+const DEFAULT_FILE: &str = "examples/main.sn";
+
+pub enum DiagnosticsFormat {
+    Human,
+    Json,
+    Sarif,
+}
+
+impl FromStr for DiagnosticsFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "human" => Ok(DiagnosticsFormat::Human),
+            "json" => Ok(DiagnosticsFormat::Json),
+            "sarif" => Ok(DiagnosticsFormat::Sarif),
+            _ => Err(format!("unknown diagnostics format `{}`", s)),
+        }
+    }
+}
+
+pub enum Engine {
+    Vm,
+    Tree,
+}
+
+impl FromStr for Engine {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "vm" => Ok(Engine::Vm),
+            "tree" => Ok(Engine::Tree),
+            _ => Err(format!("unknown engine `{}`", s)),
+        }
+    }
+}
+
+pub enum Command {
+    Run(Options),
+    Explain(String),
+    Fix(String),
+    Ast(String),
+    Compile { file: String, output: Option<String> },
+    Disasm(String),
+    EmitIr { file: String, output: Option<String> },
+    EmitC { file: String, output: Option<String> },
+    EmitWat { file: String, output: Option<String> },
+    EmitAsm { file: String, output: Option<String> },
+    EmitLlvm { file: String, output: Option<String> },
+    Build { file: String, output: Option<String> },
+}
+
+impl Command {
+    pub fn parse(args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut args = args.peekable();
+        match args.peek().map(String::as_str) {
+            Some("run") => {
+                args.next();
+                Options::parse(args).map(Command::Run)
+            }
+            Some("explain") => {
+                args.next();
+                let code = args.next().ok_or("`explain` expects a diagnostic code")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+                Ok(Command::Explain(code))
+            }
+            Some("fix") => {
+                args.next();
+                let file = args.next().ok_or("`fix` expects a file")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+                Ok(Command::Fix(file))
+            }
+            Some("ast") => {
+                args.next();
+                let file = args.next().ok_or("`ast` expects a file")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+                Ok(Command::Ast(file))
+            }
+            Some("compile") => {
+                args.next();
+                let (file, output) = Self::file_and_output("compile", args)?;
+                Ok(Command::Compile { file, output })
+            }
+            Some("disasm") => {
+                args.next();
+                let file = args.next().ok_or("`disasm` expects a file")?;
+                if let Some(arg) = args.next() {
+                    return Err(format!("unexpected argument `{}`", arg));
+                }
+                Ok(Command::Disasm(file))
+            }
+            Some("emit-ir") => {
+                args.next();
+                let (file, output) = Self::file_and_output("emit-ir", args)?;
+                Ok(Command::EmitIr { file, output })
+            }
+            Some("emit-c") => {
+                args.next();
+                let (file, output) = Self::file_and_output("emit-c", args)?;
+                Ok(Command::EmitC { file, output })
+            }
+            Some("emit-wat") => {
+                args.next();
+                let (file, output) = Self::file_and_output("emit-wat", args)?;
+                Ok(Command::EmitWat { file, output })
+            }
+            Some("emit-asm") => {
+                args.next();
+                let (file, output) = Self::file_and_output("emit-asm", args)?;
+                Ok(Command::EmitAsm { file, output })
+            }
+            Some("emit-llvm") => {
+                args.next();
+                let (file, output) = Self::file_and_output("emit-llvm", args)?;
+                Ok(Command::EmitLlvm { file, output })
+            }
+            Some("build") => {
+                args.next();
+                let (file, output) = Self::file_and_output("build", args)?;
+                Ok(Command::Build { file, output })
+            }
+            _ => Options::parse(args).map(Command::Run),
+        }
+    }
+
+    // Arguments of the commands writing a file: `[-o <output>] <file>`
+    fn file_and_output(
+        command: &str,
+        mut args: impl Iterator<Item = String>,
+    ) -> Result<(String, Option<String>), String> {
+        let mut file = None;
+        let mut output = None;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => output = Some(Options::value(&arg, None, &mut args)?),
+                _ if arg.starts_with('-') => return Err(format!("unknown option `{}`", arg)),
+                _ if file.is_none() => file = Some(arg),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+        let file = file.ok_or_else(|| format!("`{}` expects a file", command))?;
+        Ok((file, output))
+    }
+}
+
+pub struct Options {
+    pub file: String,
+    pub format: DiagnosticsFormat,
+    pub engine: Engine,
+    pub lints: LintConfig,
+}
+
+impl Options {
+    pub fn parse(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut file = None;
+        let mut format = DiagnosticsFormat::Human;
+        let mut engine = Engine::Vm;
+        let mut lints = LintConfig::new();
+        while let Some(arg) = args.next() {
+            // Short flags may carry their value directly, as in `-Dwarnings`
+            let short_value = arg.char_indices().nth(2).map(|(index, _)| index);
+            let (flag, inline_value) = match (arg.split_once('='), short_value) {
+                (Some((flag, value)), _) if arg.starts_with("--") => (flag.to_string(), Some(value.to_string())),
+                (_, Some(index)) if !arg.starts_with("--") && arg.starts_with('-') => {
+                    (arg[..index].to_string(), Some(arg[index..].to_string()))
+                }
+                _ => (arg.clone(), None),
+            };
+            match flag.as_str() {
+                "--format" => {
+                    format = Self::value(&flag, inline_value, &mut args)?.parse()?;
+                }
+                "--engine" => {
+                    engine = Self::value(&flag, inline_value, &mut args)?.parse()?;
+                }
+                "-A" | "--allow" | "-W" | "--warn" | "-D" | "--deny" => {
+                    let level = match flag.as_str() {
+                        "-A" | "--allow" => LintLevel::Allow,
+                        "-W" | "--warn" => LintLevel::Warn,
+                        _ => LintLevel::Deny,
+                    };
+                    let name = Self::value(&flag, inline_value, &mut args)?;
+                    lints.set(&name, level, LintSource::CommandLine)?;
+                }
+                _ if flag.starts_with('-') => return Err(format!("unknown option `{}`", flag)),
+                _ if file.is_none() => file = Some(arg),
+                _ => return Err(format!("unexpected argument `{}`", arg)),
+            }
+        }
+        Ok(Self {
+            file: file.unwrap_or_else(|| DEFAULT_FILE.to_string()),
+            format,
+            engine,
+            lints,
+        })
+    }
+
+    fn value(
+        flag: &str,
+        inline_value: Option<String>,
+        args: &mut impl Iterator<Item = String>,
+    ) -> Result<String, String> {
+        inline_value
+            .or_else(|| args.next())
+            .ok_or_else(|| format!("`{}` expects a value", flag))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Options;
+
+    fn parse(args: &[&str]) -> Result<Options, String> {
+        Options::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    #[test]
+    fn short_flags_take_an_inline_value() {
+        assert!(parse(&["-Dwarnings", "main.sn"]).is_ok());
+        assert_eq!(parse(&["-Dnope"]).err().unwrap(), "unknown lint `nope`");
+    }
+
+    #[test]
+    fn rejects_short_flags_with_multi_byte_characters() {
+        assert_eq!(parse(&["-é"]).err().unwrap(), "unknown option `-é`");
+        assert_eq!(parse(&["-éx"]).err().unwrap(), "unknown option `-é`");
+    }
+}
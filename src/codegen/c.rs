@@ -0,0 +1,250 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{lexer::TextSpan, ASTBinaryOperatorKind},
+    ir::{InstructionKind, Program, TerminatorKind, Value},
+    text::SourceText,
+};
+
+const INCLUDES: &str = "\
+#include <inttypes.h>
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+";
+
+const FAIL: &str = r#"
+static void sirin_fail(const char *code, const char *message, const char *at) {
+    fprintf(stderr, "error[%s]: %s\n --> %s\n", code, message, at);
+    exit(1);
+}
+"#;
+
+const OVERFLOW: &str = r#"
+static void sirin_overflow(int64_t a, const char *op, int64_t b, const char *at) {
+    fprintf(stderr,
+            "error[E0011]: This arithmetic operation would overflow\n --> %s\n"
+            "  = note: attempt to compute `%" PRId64 " %s %" PRId64 "`, which would overflow\n",
+            at, a, op, b);
+    exit(1);
+}
+"#;
+
+const ADD: &str = r#"
+static int64_t sirin_add(int64_t a, int64_t b, const char *at) {
+    if ((b > 0 && a > INT64_MAX - b) || (b < 0 && a < INT64_MIN - b)) {
+        sirin_overflow(a, "+", b, at);
+    }
+    return a + b;
+}
+"#;
+
+const SUB: &str = r#"
+static int64_t sirin_sub(int64_t a, int64_t b, const char *at) {
+    if ((b < 0 && a > INT64_MAX + b) || (b > 0 && a < INT64_MIN + b)) {
+        sirin_overflow(a, "-", b, at);
+    }
+    return a - b;
+}
+"#;
+
+const MUL: &str = r#"
+static int64_t sirin_mul(int64_t a, int64_t b, const char *at) {
+    int overflows;
+    if (a > 0) {
+        overflows = b > 0 ? a > INT64_MAX / b : b < INT64_MIN / a;
+    } else {
+        overflows = b > 0 ? a < INT64_MIN / b : a != 0 && b < INT64_MAX / a;
+    }
+    if (overflows) {
+        sirin_overflow(a, "*", b, at);
+    }
+    return a * b;
+}
+"#;
+
+const DIV: &str = r#"
+static int64_t sirin_div(int64_t a, int64_t b, const char *at) {
+    if (b == 0) {
+        sirin_fail("E0010", "This operation would divide by zero", at);
+    }
+    if (a == INT64_MIN && b == -1) {
+        sirin_overflow(a, "/", b, at);
+    }
+    return a / b;
+}
+"#;
+
+// Translates a lowered program into a self-contained C99 translation unit.
+// Variables become `vN` locals and temporaries `tN` locals, so every value
+// is computed in its own statement and Sirin's left-to-right evaluation order
+// is kept where C would leave it unspecified.
+pub struct CGenerator<'a> {
+    text: &'a SourceText,
+    body: String, // Statements of `main`
+    operators: Vec<ASTBinaryOperatorKind>, // Operators whose helper is called
+}
+
+impl<'a> CGenerator<'a> {
+    pub fn new(text: &'a SourceText) -> Self {
+        Self {
+            text,
+            body: String::new(),
+            operators: Vec::new(),
+        }
+    }
+
+    pub fn generate(mut self, program: &Program) -> String {
+        let used = program.used_temps();
+        let mut declared = HashSet::new();
+        let mut result = "    puts(\"None\");\n".to_string();
+        for block in &program.blocks {
+            for instruction in &block.instructions {
+                match instruction.kind {
+                    InstructionKind::Copy { dest, value } => {
+                        self.add_line(&format!("int64_t t{} = {};", dest.0, Self::value(value)))
+                    }
+                    InstructionKind::Binary { dest, operator, left, right } => {
+                        if !self.operators.contains(&operator) {
+                            self.operators.push(operator);
+                        }
+                        let helper = match operator {
+                            ASTBinaryOperatorKind::Add => "sirin_add",
+                            ASTBinaryOperatorKind::Subtract => "sirin_sub",
+                            ASTBinaryOperatorKind::Multiply => "sirin_mul",
+                            ASTBinaryOperatorKind::Divide => "sirin_div",
+                        };
+                        let call = format!(
+                            "{}({}, {}, {})",
+                            helper,
+                            Self::value(left),
+                            Self::value(right),
+                            self.location(&instruction.span)
+                        );
+                        // Kept for its runtime checks even when the result is unused
+                        if used.contains(&dest) {
+                            self.add_line(&format!("int64_t t{} = {};", dest.0, call));
+                        } else {
+                            self.add_line(&format!("(void){};", call));
+                        }
+                    }
+                    InstructionKind::Load { dest, variable } => {
+                        self.add_line(&format!("int64_t t{} = v{};", dest.0, variable.0))
+                    }
+                    InstructionKind::Store { variable, value } => {
+                        if declared.insert(variable) {
+                            let name = program.variables[variable.0].name.replace("*/", "* /");
+                            self.add_line(&format!("int64_t v{} = {}; /* {} */", variable.0, Self::value(value), name));
+                            if !program.is_read(variable) {
+                                self.add_line(&format!("(void)v{};", variable.0));
+                            }
+                        } else {
+                            self.add_line(&format!("v{} = {};", variable.0, Self::value(value)));
+                        }
+                    }
+                    _ => unreachable!("programs using strings, lists, maps, structs or enums are rejected before code generation"),
+                }
+            }
+            match block.terminator.kind {
+                TerminatorKind::Return(Some(value)) => {
+                    result = format!("    printf(\"Some(%\" PRId64 \")\\n\", {});\n", Self::value(value))
+                }
+                TerminatorKind::Return(None) => {}
+                _ => unreachable!("programs with match expressions are rejected before code generation"),
+            }
+        }
+        format!(
+            "/* Generated by sirin from {} */\n{}{}\nint main(void) {{\n{}{}    return 0;\n}}\n",
+            self.text.file_name().replace("*/", "* /"),
+            INCLUDES,
+            self.helpers(),
+            self.body,
+            result
+        )
+    }
+
+    // Only the helpers the program calls, so none is unused
+    fn helpers(&self) -> String {
+        let mut helpers = String::new();
+        if self.operators.contains(&ASTBinaryOperatorKind::Divide) {
+            helpers.push_str(FAIL);
+        }
+        if !self.operators.is_empty() {
+            helpers.push_str(OVERFLOW);
+        }
+        let all = [
+            (ASTBinaryOperatorKind::Add, ADD),
+            (ASTBinaryOperatorKind::Subtract, SUB),
+            (ASTBinaryOperatorKind::Multiply, MUL),
+            (ASTBinaryOperatorKind::Divide, DIV),
+        ];
+        for (operator, helper) in all {
+            if self.operators.contains(&operator) {
+                helpers.push_str(helper);
+            }
+        }
+        helpers
+    }
+
+    fn add_line(&mut self, line: &str) {
+        self.body.push_str("    ");
+        self.body.push_str(line);
+        self.body.push('\n');
+    }
+
+    // `INT64_MIN` has no literal in C, only a negated one that overflows
+    fn value(value: Value) -> String {
+        match value {
+            Value::Temp(temp) => format!("t{}", temp.0),
+            Value::Constant(i64::MIN) => "INT64_MIN".to_string(),
+            Value::Constant(number) => format!("INT64_C({})", number),
+        }
+    }
+
+    // `file:line:column` of `span` as a C string literal
+    fn location(&self, span: &TextSpan) -> String {
+        let (line, column) = self.text.line_column(span.start);
+        let file = self.text.file_name().replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{}:{}:{}\"", file, line, column)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use crate::codegen::testing::{self, has_tool, interpret};
+
+    use super::CGenerator;
+
+    #[test]
+    fn compiled_programs_print_what_the_interpreter_does() {
+        if !has_tool("cc") {
+            eprintln!("skipped: `cc` is not available");
+            return;
+        }
+        let dir = testing::work_dir("c");
+        for (index, test) in testing::programs().iter().enumerate() {
+            let source = dir.join(format!("program{}.c", index));
+            let executable = dir.join(format!("program{}", index));
+            fs::write(&source, CGenerator::new(&test.unit.text).generate(&test.program)).unwrap();
+            let compiled = Command::new("cc")
+                .args(["-std=c99", "-Wall", "-Werror", "-o"])
+                .arg(&executable)
+                .arg(&source)
+                .output()
+                .unwrap();
+            assert!(compiled.status.success(), "{}: {}", test.name, String::from_utf8_lossy(&compiled.stderr));
+            let output = Command::new(&executable).output().unwrap();
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            match interpret(&test.unit) {
+                Some(expected) => {
+                    assert!(output.status.success(), "{}", test.name);
+                    assert_eq!(stdout, expected, "{}", test.name);
+                }
+                None => assert_eq!(output.status.code(), Some(1), "{}", test.name),
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
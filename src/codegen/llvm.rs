@@ -0,0 +1,415 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{lexer::TextSpan, ASTBinaryOperatorKind},
+    ir::{InstructionKind, Program, TerminatorKind, Value},
+    text::SourceText,
+};
+
+// Pointers are opaque (`ptr`): the default since LLVM 15 and the only kind
+// since LLVM 17. LLVM 14 reads them with `-opaque-pointers`.
+const DECLARATIONS: &str = "
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32) noreturn
+";
+
+const SOME: &str = "Some(%lld)\n";
+const NONE: &str = "None\n";
+const OVERFLOW: &str = "error[E0011]: This arithmetic operation would overflow\n --> %s\n  \
+                        = note: attempt to compute `%lld %s %lld`, which would overflow\n";
+const DIVISION_BY_ZERO: &str = "error[E0010]: This operation would divide by zero\n --> %s\n";
+
+// Translates a lowered program to an LLVM IR module in text form, for `lli`
+// or `llc`. Temporaries map to SSA values and variables to `alloca` slots;
+// arithmetic uses the `llvm.s*.with.overflow` intrinsics and branches to an
+// error exit on overflow, as `ASTEvaluator` reports it. Only `main` and the
+// C library's `printf`, `dprintf` and `exit` are needed to run it.
+pub struct LlvmGenerator<'a> {
+    text: &'a SourceText,
+    body: Vec<String>, // Instructions and labels of `main`
+    constants: Vec<String>, // Private globals holding error locations
+    operators: HashSet<ASTBinaryOperatorKind>, // Operators used, selecting intrinsics and helpers
+    labels: usize,
+}
+
+impl<'a> LlvmGenerator<'a> {
+    pub fn new(text: &'a SourceText) -> Self {
+        Self {
+            text,
+            body: Vec::new(),
+            constants: Vec::new(),
+            operators: HashSet::new(),
+            labels: 0,
+        }
+    }
+
+    pub fn generate(mut self, program: &Program) -> String {
+        for variable in 0..program.variables.len() {
+            self.add_instruction(format!("%v{} = alloca i64", variable));
+        }
+        let mut result = None;
+        for block in &program.blocks {
+            for instruction in &block.instructions {
+                match instruction.kind {
+                    // Kept by LLVM as a no-op addition, which it folds away
+                    InstructionKind::Copy { dest, value } => {
+                        self.add_instruction(format!("%t{} = add i64 {}, 0", dest.0, Self::value(value)))
+                    }
+                    InstructionKind::Binary { dest, operator, left, right } => {
+                        self.add_binary(dest.0, operator, left, right, &instruction.span)
+                    }
+                    InstructionKind::Load { dest, variable } => {
+                        self.add_instruction(format!("%t{} = load i64, ptr %v{}", dest.0, variable.0))
+                    }
+                    InstructionKind::Store { variable, value } => {
+                        self.add_instruction(format!("store i64 {}, ptr %v{}", Self::value(value), variable.0))
+                    }
+                    _ => unreachable!("programs using strings, lists, maps, structs or enums are rejected before code generation"),
+                }
+            }
+            match block.terminator.kind {
+                TerminatorKind::Return(value) => result = value,
+                _ => unreachable!("programs with match expressions are rejected before code generation"),
+            }
+        }
+        match result {
+            Some(value) => self.add_instruction(format!(
+                "call i32 (ptr, ...) @printf({}, i64 {})",
+                Self::pointer("@.some"),
+                Self::value(value)
+            )),
+            None => self.add_instruction(format!("call i32 (ptr, ...) @printf({})", Self::pointer("@.none"))),
+        }
+        self.add_instruction("ret i32 0".to_string());
+
+        let mut module = format!("; Generated by sirin from {}\n", self.text.file_name().replace('\n', " "));
+        module.push_str(&format!("source_filename = \"{}\"\n\n", Self::escape(self.text.file_name().as_bytes())));
+        match result {
+            Some(_) => module.push_str(&Self::string_constant("@.some", SOME)),
+            None => module.push_str(&Self::string_constant("@.none", NONE)),
+        }
+        for constant in &self.constants {
+            module.push_str(constant);
+        }
+        module.push_str(DECLARATIONS);
+        module.push_str(&self.helpers());
+        module.push_str("\ndefine i32 @main() {\nentry:\n");
+        for line in &self.body {
+            module.push_str(line);
+            module.push('\n');
+        }
+        module.push_str("}\n");
+        module
+    }
+
+    fn add_binary(&mut self, dest: usize, operator: ASTBinaryOperatorKind, left: Value, right: Value, span: &TextSpan) {
+        self.operators.insert(operator);
+        let location = self.location(span);
+        let (left, right) = (Self::value(left), Self::value(right));
+        let overflow = format!(
+            "call void @sirin_overflow(i64 {}, {}, i64 {}, {})",
+            left,
+            Self::pointer(&format!("@.operator.{}", Self::operator_name(operator))),
+            right,
+            location
+        );
+        let intrinsic = match operator {
+            ASTBinaryOperatorKind::Add => "llvm.sadd.with.overflow.i64",
+            ASTBinaryOperatorKind::Subtract => "llvm.ssub.with.overflow.i64",
+            ASTBinaryOperatorKind::Multiply => "llvm.smul.with.overflow.i64",
+            ASTBinaryOperatorKind::Divide => {
+                // `sdiv` is undefined for a zero divisor and for INT64_MIN / -1
+                let label = self.new_label();
+                self.add_instruction(format!("%zero{} = icmp eq i64 {}, 0", label, right));
+                self.add_instruction(format!(
+                    "br i1 %zero{}, label %division_by_zero{}, label %nonzero{}",
+                    label, label, label
+                ));
+                self.add_label(format!("division_by_zero{}", label));
+                self.add_instruction(format!("call void @sirin_division_by_zero({})", location));
+                self.add_instruction("unreachable".to_string());
+                self.add_label(format!("nonzero{}", label));
+                self.add_instruction(format!("%min{} = icmp eq i64 {}, {}", label, left, i64::MIN));
+                self.add_instruction(format!("%minus_one{} = icmp eq i64 {}, -1", label, right));
+                self.add_instruction(format!("%overflows{} = and i1 %min{}, %minus_one{}", label, label, label));
+                self.add_checked_result(label, &overflow);
+                self.add_instruction(format!("%t{} = sdiv i64 {}, {}", dest, left, right));
+                return;
+            }
+        };
+        let label = self.new_label();
+        self.add_instruction(format!(
+            "%result{} = call {{ i64, i1 }} @{}(i64 {}, i64 {})",
+            label, intrinsic, left, right
+        ));
+        self.add_instruction(format!("%overflows{} = extractvalue {{ i64, i1 }} %result{}, 1", label, label));
+        self.add_checked_result(label, &overflow);
+        self.add_instruction(format!("%t{} = extractvalue {{ i64, i1 }} %result{}, 0", dest, label));
+    }
+
+    // Branches on `%overflows<label>` to a block reporting the overflow
+    fn add_checked_result(&mut self, label: usize, overflow: &str) {
+        self.add_instruction(format!(
+            "br i1 %overflows{}, label %overflow{}, label %ok{}",
+            label, label, label
+        ));
+        self.add_label(format!("overflow{}", label));
+        self.add_instruction(overflow.to_string());
+        self.add_instruction("unreachable".to_string());
+        self.add_label(format!("ok{}", label));
+    }
+
+    // Only the intrinsics and helpers the program calls
+    fn helpers(&self) -> String {
+        let mut helpers = String::new();
+        let all = [
+            (ASTBinaryOperatorKind::Add, "sadd"),
+            (ASTBinaryOperatorKind::Subtract, "ssub"),
+            (ASTBinaryOperatorKind::Multiply, "smul"),
+        ];
+        for (operator, intrinsic) in all {
+            if self.operators.contains(&operator) {
+                helpers.push_str(&format!("declare {{ i64, i1 }} @llvm.{}.with.overflow.i64(i64, i64)\n", intrinsic));
+            }
+        }
+        if !self.operators.is_empty() {
+            helpers.push('\n');
+        }
+        let mut operators: Vec<_> = self.operators.iter().copied().collect();
+        operators.sort_by_key(|operator| Self::operator_name(*operator));
+        for operator in operators {
+            let name = format!("@.operator.{}", Self::operator_name(operator));
+            helpers.push_str(&Self::string_constant(&name, Self::symbol(operator)));
+        }
+        if !self.operators.is_empty() {
+            helpers.push_str(&Self::string_constant("@.overflow", OVERFLOW));
+            helpers.push_str(&format!(
+                "\ndefine private void @sirin_overflow(i64 %a, ptr %op, i64 %b, ptr %at) noreturn {{\n  \
+                 call i32 (i32, ptr, ...) @dprintf(i32 2, {}, ptr %at, i64 %a, ptr %op, i64 %b)\n  \
+                 call void @exit(i32 1)\n  unreachable\n}}\n",
+                Self::pointer("@.overflow")
+            ));
+        }
+        if self.operators.contains(&ASTBinaryOperatorKind::Divide) {
+            helpers.push_str(&Self::string_constant("@.division_by_zero", DIVISION_BY_ZERO));
+            helpers.push_str(&format!(
+                "\ndefine private void @sirin_division_by_zero(ptr %at) noreturn {{\n  \
+                 call i32 (i32, ptr, ...) @dprintf(i32 2, {}, ptr %at)\n  \
+                 call void @exit(i32 1)\n  unreachable\n}}\n",
+                Self::pointer("@.division_by_zero")
+            ));
+        }
+        helpers
+    }
+
+    fn add_instruction(&mut self, instruction: String) {
+        self.body.push(format!("  {}", instruction))
+    }
+
+    fn add_label(&mut self, label: String) {
+        self.body.push(format!("{}:", label))
+    }
+
+    fn new_label(&mut self) -> usize {
+        self.labels += 1;
+        self.labels - 1
+    }
+
+    fn operator_name(operator: ASTBinaryOperatorKind) -> &'static str {
+        match operator {
+            ASTBinaryOperatorKind::Add => "add",
+            ASTBinaryOperatorKind::Subtract => "sub",
+            ASTBinaryOperatorKind::Multiply => "mul",
+            ASTBinaryOperatorKind::Divide => "div",
+        }
+    }
+
+    fn symbol(operator: ASTBinaryOperatorKind) -> &'static str {
+        match operator {
+            ASTBinaryOperatorKind::Add => "+",
+            ASTBinaryOperatorKind::Subtract => "-",
+            ASTBinaryOperatorKind::Multiply => "*",
+            ASTBinaryOperatorKind::Divide => "/",
+        }
+    }
+
+    fn value(value: Value) -> String {
+        match value {
+            Value::Temp(temp) => format!("%t{}", temp.0),
+            Value::Constant(number) => number.to_string(),
+        }
+    }
+
+    // Pointer to a private global holding `file:line:column` of `span`
+    fn location(&mut self, span: &TextSpan) -> String {
+        let (line, column) = self.text.line_column(span.start);
+        let location = format!("{}:{}:{}", self.text.file_name(), line, column);
+        let name = format!("@.location{}", self.constants.len());
+        self.constants.push(Self::string_constant(&name, &location));
+        Self::pointer(&name)
+    }
+
+    // Pointer to the first byte of the string constant `name`
+    fn pointer(name: &str) -> String {
+        format!("ptr {}", name)
+    }
+
+    // A private global holding `text` as a NUL-terminated C string
+    fn string_constant(name: &str, text: &str) -> String {
+        format!(
+            "{} = private unnamed_addr constant [{} x i8] c\"{}\\00\"\n",
+            name,
+            text.len() + 1,
+            Self::escape(text.as_bytes())
+        )
+    }
+
+    // Bytes outside printable ASCII, quotes and backslashes are written as
+    // `\XX` hexadecimal escapes
+    fn escape(bytes: &[u8]) -> String {
+        let mut escaped = String::new();
+        for byte in bytes {
+            match byte {
+                0x20..=0x7e if *byte != b'"' && *byte != b'\\' => escaped.push(*byte as char),
+                _ => escaped.push_str(&format!("\\{:02X}", byte)),
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use crate::{
+        codegen::testing::{self, has_tool, interpret},
+        compilation_unit::CompilationUnit,
+        ir::Program,
+        lints::LintConfig,
+    };
+
+    use super::LlvmGenerator;
+
+    // The module for `input`, left unoptimized so each operator stays
+    fn generate(input: &str) -> String {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        assert!(!unit.has_errors());
+        LlvmGenerator::new(&unit.text).generate(&Program::lower(&unit.ast))
+    }
+
+    // `lli` flags needed to read opaque pointers, or `None` if it cannot
+    fn opaque_pointer_flags() -> Option<&'static [&'static str]> {
+        let output = Command::new("lli").arg("--version").output().ok()?;
+        let version = String::from_utf8_lossy(&output.stdout).into_owned();
+        let major: u32 = version.split("version ").nth(1)?.split('.').next()?.parse().ok()?;
+        match major {
+            ..=13 => None,
+            14 => Some(&["-opaque-pointers"]),
+            _ => Some(&[]),
+        }
+    }
+
+    #[test]
+    fn lli_runs_modules_like_the_interpreter() {
+        let flags = match opaque_pointer_flags() {
+            Some(flags) if has_tool("lli") => flags,
+            _ => {
+                eprintln!("skipped: no `lli` reading opaque pointers is available");
+                return;
+            }
+        };
+        let dir = testing::work_dir("llvm");
+        for (index, test) in testing::programs().iter().enumerate() {
+            let module = dir.join(format!("program{}.ll", index));
+            fs::write(&module, LlvmGenerator::new(&test.unit.text).generate(&test.program)).unwrap();
+            let output = Command::new("lli").args(flags).arg(&module).output().unwrap();
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            match interpret(&test.unit) {
+                Some(expected) => {
+                    assert!(output.status.success(), "{}: {}", test.name, String::from_utf8_lossy(&output.stderr));
+                    assert_eq!(stdout, expected, "{}", test.name);
+                }
+                None => assert_eq!(output.status.code(), Some(1), "{}", test.name),
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+    #[test]
+    fn generates_checked_arithmetic_and_prints_the_result() {
+        let module = generate("let b = 7 / 2\nb * 3 + 1");
+        let expected = r#"; Generated by sirin from test.sn
+source_filename = "test.sn"
+
+@.some = private unnamed_addr constant [12 x i8] c"Some(%lld)\0A\00"
+@.location0 = private unnamed_addr constant [12 x i8] c"test.sn:1:9\00"
+@.location1 = private unnamed_addr constant [12 x i8] c"test.sn:2:1\00"
+@.location2 = private unnamed_addr constant [12 x i8] c"test.sn:2:1\00"
+
+declare i32 @printf(ptr, ...)
+declare i32 @dprintf(i32, ptr, ...)
+declare void @exit(i32) noreturn
+declare { i64, i1 } @llvm.sadd.with.overflow.i64(i64, i64)
+declare { i64, i1 } @llvm.smul.with.overflow.i64(i64, i64)
+
+@.operator.add = private unnamed_addr constant [2 x i8] c"+\00"
+@.operator.div = private unnamed_addr constant [2 x i8] c"/\00"
+@.operator.mul = private unnamed_addr constant [2 x i8] c"*\00"
+@.overflow = private unnamed_addr constant [130 x i8] c"error[E0011]: This arithmetic operation would overflow\0A --> %s\0A  = note: attempt to compute `%lld %s %lld`, which would overflow\0A\00"
+
+define private void @sirin_overflow(i64 %a, ptr %op, i64 %b, ptr %at) noreturn {
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.overflow, ptr %at, i64 %a, ptr %op, i64 %b)
+  call void @exit(i32 1)
+  unreachable
+}
+@.division_by_zero = private unnamed_addr constant [59 x i8] c"error[E0010]: This operation would divide by zero\0A --> %s\0A\00"
+
+define private void @sirin_division_by_zero(ptr %at) noreturn {
+  call i32 (i32, ptr, ...) @dprintf(i32 2, ptr @.division_by_zero, ptr %at)
+  call void @exit(i32 1)
+  unreachable
+}
+
+define i32 @main() {
+entry:
+  %v0 = alloca i64
+  %zero0 = icmp eq i64 2, 0
+  br i1 %zero0, label %division_by_zero0, label %nonzero0
+division_by_zero0:
+  call void @sirin_division_by_zero(ptr @.location0)
+  unreachable
+nonzero0:
+  %min0 = icmp eq i64 7, -9223372036854775808
+  %minus_one0 = icmp eq i64 2, -1
+  %overflows0 = and i1 %min0, %minus_one0
+  br i1 %overflows0, label %overflow0, label %ok0
+overflow0:
+  call void @sirin_overflow(i64 7, ptr @.operator.div, i64 2, ptr @.location0)
+  unreachable
+ok0:
+  %t0 = sdiv i64 7, 2
+  store i64 %t0, ptr %v0
+  %result1 = call { i64, i1 } @llvm.smul.with.overflow.i64(i64 %t0, i64 3)
+  %overflows1 = extractvalue { i64, i1 } %result1, 1
+  br i1 %overflows1, label %overflow1, label %ok1
+overflow1:
+  call void @sirin_overflow(i64 %t0, ptr @.operator.mul, i64 3, ptr @.location1)
+  unreachable
+ok1:
+  %t1 = extractvalue { i64, i1 } %result1, 0
+  %result2 = call { i64, i1 } @llvm.sadd.with.overflow.i64(i64 %t1, i64 1)
+  %overflows2 = extractvalue { i64, i1 } %result2, 1
+  br i1 %overflows2, label %overflow2, label %ok2
+overflow2:
+  call void @sirin_overflow(i64 %t1, ptr @.operator.add, i64 1, ptr @.location2)
+  unreachable
+ok2:
+  %t2 = extractvalue { i64, i1 } %result2, 0
+  call i32 (ptr, ...) @printf(ptr @.some, i64 %t2)
+  ret i32 0
+}
+"#;
+        assert_eq!(module, expected);
+    }
+}
@@ -0,0 +1,28 @@
+use crate::{ast::lexer::TextSpan, ir::Program};
+
+// Backends translating a checked, optimized and lowered `Program` to other
+// languages. Each generated program prints its result like `sirin run` and
+// reports runtime errors with the same codes.
+pub mod c;
+pub mod llvm;
+pub mod wat;
+pub mod x86_64;
+
+#[cfg(test)]
+mod testing;
+
+// The first feature of `program` the backends cannot compile yet, and where
+// it is used. Strings, lists, maps, structs, enums, tuples, ranges, `match`
+// and `for` are only supported by the interpreters.
+pub fn find_unsupported(program: &Program) -> Option<(&'static str, &TextSpan)> {
+    let instructions = program
+        .instructions()
+        .map(|instruction| (instruction.feature(), &instruction.span));
+    let terminators = program
+        .blocks
+        .iter()
+        .map(|block| (block.terminator.feature(), &block.terminator.span));
+    instructions
+        .chain(terminators)
+        .find_map(|(feature, span)| Some((feature?, span)))
+}
@@ -0,0 +1,74 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{self, Command},
+};
+
+use crate::{cli::Engine, codegen, compilation_unit::CompilationUnit, ir::Program, lints::LintConfig};
+
+// Programs every backend compiles, beyond the examples: runtime errors,
+// negative numbers, and programs without a result
+const PROGRAMS: &[&str] = &[
+    "",
+    "let a = 0 - 7\na / 2",
+    "let x = 1\nx = x * 3",
+    "let main = 2\nlet main = main - 1\nmain",
+    "let m = 2\nm = m - 2\n7 / m",
+    "let n = 9223372036854775806\nn = n + 1\nn + 1",
+    "let n = 0 - 9223372036854775807\nn = n - 1\nn / (0 - 1)",
+];
+
+// A checked and optimized program, lowered for the backends
+pub struct TestProgram {
+    pub name: String,
+    pub unit: CompilationUnit,
+    pub program: Program,
+}
+
+// The examples the backends support, then `PROGRAMS`
+pub fn programs() -> Vec<TestProgram> {
+    let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+    let mut sources: Vec<_> = fs::read_dir(examples)
+        .unwrap()
+        .map(|entry| {
+            let path = entry.unwrap().path();
+            (path.display().to_string(), fs::read_to_string(&path).unwrap())
+        })
+        .collect();
+    sources.sort();
+    let inline = PROGRAMS.iter().enumerate().map(|(index, input)| (format!("program{}.sn", index), input.to_string()));
+    sources
+        .into_iter()
+        .chain(inline)
+        .filter_map(|(name, input)| {
+            let mut unit = CompilationUnit::compile(input, name.clone(), LintConfig::new());
+            unit.optimize();
+            assert!(!unit.has_errors(), "{}", name);
+            let program = Program::lower(&unit.ast);
+            codegen::find_unsupported(&program).is_none().then_some(TestProgram { name, unit, program })
+        })
+        .collect()
+}
+
+// What `sirin run` prints on stdout, or `None` when the program fails
+pub fn interpret(unit: &CompilationUnit) -> Option<String> {
+    let result = unit.execute(&Engine::Tree);
+    if unit.has_errors() {
+        return None;
+    }
+    Some(match result {
+        Some(value) => format!("Some({})\n", value),
+        None => "None\n".to_string(),
+    })
+}
+
+pub fn has_tool(name: &str) -> bool {
+    Command::new(name).arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+// A fresh directory for the files of one test
+pub fn work_dir(test: &str) -> PathBuf {
+    let dir = env::temp_dir().join(format!("sirin-{}-{}", test, process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
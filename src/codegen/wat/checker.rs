@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+
+// Structural validation of the modules `WatGenerator` produces, so the
+// backend can be checked without an external toolchain. It understands the
+// subset of the text format the generator uses: `global` and `func` fields
+// over `i64`, with flat instruction bodies, and type-checks every body by
+// simulating its operand stack.
+
+#[derive(Debug, PartialEq)]
+enum SExpr {
+    Atom(String),
+    String(String),
+    List(Vec<SExpr>),
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+enum ValueType {
+    I32,
+    I64,
+}
+
+impl ValueType {
+    fn name(&self) -> &'static str {
+        match self {
+            ValueType::I32 => "i32",
+            ValueType::I64 => "i64",
+        }
+    }
+}
+
+struct Function {
+    params: usize,
+    results: usize,
+}
+
+struct Signature<'a> {
+    params: Vec<String>,
+    results: usize,
+    locals: Vec<String>,
+    body: &'a [SExpr],
+}
+
+struct Frame {
+    height: usize, // Operand stack height when the block was entered
+    unreachable: bool, // After `unreachable`, the stack is polymorphic
+}
+
+pub fn check(source: &str) -> Result<(), String> {
+    let mut tokens = tokenize(source)?.into_iter().peekable();
+    let module = parse(&mut tokens)?;
+    if let Some(token) = tokens.next() {
+        return Err(format!("unexpected `{}` after the module", token));
+    }
+    let fields = match module {
+        SExpr::List(items) if items.first() == Some(&SExpr::Atom("module".to_string())) => items,
+        _ => return Err("expected `(module ...)`".to_string()),
+    };
+
+    let mut globals = HashMap::new(); // name -> mutable
+    let mut functions = HashMap::new();
+    let mut exports = HashSet::new();
+    for field in &fields[1..] {
+        let items = list(field)?;
+        match head(items)? {
+            "global" => {
+                let name = identifier(items.get(1))?;
+                let mut rest = &items[2..];
+                if let Some(export) = export_name(rest.first())? {
+                    check_export(&mut exports, export)?;
+                    rest = &rest[1..];
+                }
+                let mutable = match rest {
+                    [SExpr::List(ty), init] if ty.len() == 2 && head(ty)? == "mut" && atom(&ty[1])? == "i64" => {
+                        check_constant(init)?;
+                        true
+                    }
+                    [SExpr::Atom(ty), init] if ty == "i64" => {
+                        check_constant(init)?;
+                        false
+                    }
+                    _ => return Err(format!("malformed global `{}`", name)),
+                };
+                if globals.insert(name.to_string(), mutable).is_some() {
+                    return Err(format!("duplicate global `{}`", name));
+                }
+            }
+            "func" => {
+                let name = identifier(items.get(1))?;
+                let signature = signature(&items[2..], &mut exports)?;
+                let function = Function { params: signature.params.len(), results: signature.results };
+                if functions.insert(name.to_string(), function).is_some() {
+                    return Err(format!("duplicate function `{}`", name));
+                }
+            }
+            other => return Err(format!("unsupported module field `{}`", other)),
+        }
+    }
+
+    // Bodies are checked once every function is known, so calls can refer
+    // to functions declared later
+    let mut exports = HashSet::new();
+    for field in &fields[1..] {
+        let items = list(field)?;
+        if head(items)? == "func" {
+            let name = identifier(items.get(1))?;
+            let mut signature = signature(&items[2..], &mut exports)?;
+            signature.locals.extend(signature.params);
+            check_body(name, signature.body, &signature.locals, signature.results, &globals, &functions)?;
+        }
+    }
+    Ok(())
+}
+
+// Splits a function after its name into its declarations and body
+fn signature<'a>(items: &'a [SExpr], exports: &mut HashSet<String>) -> Result<Signature<'a>, String> {
+    let mut rest = items;
+    if let Some(export) = export_name(rest.first())? {
+        check_export(exports, export)?;
+        rest = &rest[1..];
+    }
+    let mut params = Vec::new();
+    let mut results = 0;
+    let mut locals = Vec::new();
+    while let Some(SExpr::List(declaration)) = rest.first() {
+        match (head(declaration)?, &declaration[1..]) {
+            ("param", [name, ty]) if results == 0 && locals.is_empty() => {
+                params.push(typed_identifier(name, ty)?)
+            }
+            ("result", [ty]) if results == 0 && locals.is_empty() && atom(ty)? == "i64" => results = 1,
+            ("local", [name, ty]) => locals.push(typed_identifier(name, ty)?),
+            (other, _) => return Err(format!("malformed `{}` declaration", other)),
+        }
+        rest = &rest[1..];
+    }
+    Ok(Signature { params, results, locals, body: rest })
+}
+
+fn check_body(
+    function: &str,
+    body: &[SExpr],
+    locals: &[String],
+    results: usize,
+    globals: &HashMap<String, bool>,
+    functions: &HashMap<String, Function>,
+) -> Result<(), String> {
+    let error = |message: String| format!("in function `{}`: {}", function, message);
+    let mut stack: Vec<ValueType> = Vec::new();
+    let mut frames = vec![Frame { height: 0, unreachable: false }];
+    let mut instructions = body.iter();
+    while let Some(instruction) = instructions.next() {
+        let name = atom(instruction).map_err(error)?;
+        let mut immediate = || -> Result<&str, String> {
+            instructions
+                .next()
+                .ok_or_else(|| error(format!("`{}` expects an immediate", name)))
+                .and_then(|value| atom(value).map_err(error))
+        };
+        let (popped, pushed): (Vec<ValueType>, Vec<ValueType>) = match name {
+            "i64.const" => {
+                let value = immediate()?;
+                value.parse::<i64>().map_err(|_| error(format!("invalid `i64` constant `{}`", value)))?;
+                (vec![], vec![ValueType::I64])
+            }
+            "local.get" | "local.set" | "local.tee" => {
+                let local = immediate()?;
+                if !locals.iter().any(|declared| declared == local) {
+                    return Err(error(format!("unknown local `{}`", local)));
+                }
+                match name {
+                    "local.get" => (vec![], vec![ValueType::I64]),
+                    "local.set" => (vec![ValueType::I64], vec![]),
+                    _ => (vec![ValueType::I64], vec![ValueType::I64]),
+                }
+            }
+            "global.get" | "global.set" => {
+                let global = immediate()?;
+                let mutable = *globals.get(global).ok_or_else(|| error(format!("unknown global `{}`", global)))?;
+                if name == "global.get" {
+                    (vec![], vec![ValueType::I64])
+                } else if mutable {
+                    (vec![ValueType::I64], vec![])
+                } else {
+                    return Err(error(format!("global `{}` is immutable", global)));
+                }
+            }
+            "call" => {
+                let callee = immediate()?;
+                let callee = functions.get(callee).ok_or_else(|| error(format!("unknown function `{}`", callee)))?;
+                (vec![ValueType::I64; callee.params], vec![ValueType::I64; callee.results])
+            }
+            "i64.add" | "i64.sub" | "i64.mul" | "i64.div_s" | "i64.and" | "i64.xor" => {
+                (vec![ValueType::I64, ValueType::I64], vec![ValueType::I64])
+            }
+            "i64.eq" | "i64.ne" | "i64.lt_s" => (vec![ValueType::I64, ValueType::I64], vec![ValueType::I32]),
+            "drop" => {
+                let frame = frames.last().unwrap();
+                if stack.len() == frame.height && !frame.unreachable {
+                    return Err(error("`drop` on an empty stack".to_string()));
+                }
+                stack.pop();
+                continue;
+            }
+            "if" => {
+                pop(&mut stack, &frames, ValueType::I32).map_err(|message| error(format!("`if`: {}", message)))?;
+                frames.push(Frame { height: stack.len(), unreachable: false });
+                continue;
+            }
+            "end" => {
+                if frames.len() == 1 {
+                    return Err(error("`end` without a matching block".to_string()));
+                }
+                let frame = frames.pop().unwrap();
+                if stack.len() != frame.height && !frame.unreachable {
+                    return Err(error("block leaves values on the stack".to_string()));
+                }
+                stack.truncate(frame.height);
+                continue;
+            }
+            "unreachable" => {
+                let frame = frames.last_mut().unwrap();
+                frame.unreachable = true;
+                stack.truncate(frame.height);
+                continue;
+            }
+            other => return Err(error(format!("unsupported instruction `{}`", other))),
+        };
+        for ty in popped.iter().rev() {
+            pop(&mut stack, &frames, *ty).map_err(|message| error(format!("`{}`: {}", name, message)))?;
+        }
+        stack.extend(pushed);
+    }
+    if frames.len() > 1 {
+        return Err(error("block is missing its `end`".to_string()));
+    }
+    let frame = &frames[0];
+    if stack.len() != results && !(frame.unreachable && stack.len() <= results) {
+        return Err(error(format!("expected {} result(s), found {}", results, stack.len())));
+    }
+    if stack.iter().any(|ty| *ty != ValueType::I64) {
+        return Err(error("function returns a non-`i64` value".to_string()));
+    }
+    Ok(())
+}
+
+fn pop(stack: &mut Vec<ValueType>, frames: &[Frame], expected: ValueType) -> Result<(), String> {
+    let frame = frames.last().unwrap();
+    if stack.len() == frame.height {
+        return if frame.unreachable { Ok(()) } else { Err("the stack is empty".to_string()) };
+    }
+    match stack.pop() {
+        Some(found) if found == expected => Ok(()),
+        found => Err(format!("expected `{}`, found `{}`", expected.name(), found.map_or("nothing", |ty| ty.name()))),
+    }
+}
+
+fn check_export(exports: &mut HashSet<String>, name: &str) -> Result<(), String> {
+    if !exports.insert(name.to_string()) {
+        return Err(format!("duplicate export \"{}\"", name));
+    }
+    Ok(())
+}
+
+fn check_constant(init: &SExpr) -> Result<(), String> {
+    match list(init)? {
+        [SExpr::Atom(op), SExpr::Atom(value)] if op == "i64.const" && value.parse::<i64>().is_ok() => Ok(()),
+        _ => Err("a global must be initialized with an `i64.const`".to_string()),
+    }
+}
+
+fn export_name(item: Option<&SExpr>) -> Result<Option<&str>, String> {
+    match item {
+        Some(SExpr::List(items)) if items.first() == Some(&SExpr::Atom("export".to_string())) => match &items[1..] {
+            [SExpr::String(name)] => Ok(Some(name)),
+            _ => Err("malformed export".to_string()),
+        },
+        _ => Ok(None),
+    }
+}
+
+fn typed_identifier(name: &SExpr, ty: &SExpr) -> Result<String, String> {
+    if atom(ty)? != "i64" {
+        return Err(format!("unsupported type `{}`", atom(ty)?));
+    }
+    identifier(Some(name)).map(str::to_string)
+}
+
+fn identifier(item: Option<&SExpr>) -> Result<&str, String> {
+    match item {
+        Some(SExpr::Atom(name)) if name.len() > 1 && name.starts_with('$') => Ok(name),
+        _ => Err("expected an identifier".to_string()),
+    }
+}
+
+fn head(items: &[SExpr]) -> Result<&str, String> {
+    items.first().ok_or_else(|| "empty list".to_string()).and_then(atom)
+}
+
+fn atom(item: &SExpr) -> Result<&str, String> {
+    match item {
+        SExpr::Atom(atom) => Ok(atom),
+        other => Err(format!("expected an instruction or keyword, found {:?}", other)),
+    }
+}
+
+fn list(item: &SExpr) -> Result<&[SExpr], String> {
+    match item {
+        SExpr::List(items) => Ok(items),
+        other => Err(format!("expected a list, found {:?}", other)),
+    }
+}
+
+/* PARSER */
+
+fn tokenize(source: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut chars = source.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            _ if c.is_whitespace() => {}
+            ';' if chars.peek() == Some(&';') => {
+                for c in chars.by_ref() {
+                    if c == '\n' {
+                        break;
+                    }
+                }
+            }
+            '(' | ')' => tokens.push(c.to_string()),
+            '"' => {
+                let mut string = String::from('"');
+                loop {
+                    match chars.next() {
+                        Some('"') => break,
+                        Some('\\') => string.push(chars.next().ok_or("unterminated string")?),
+                        Some(c) => string.push(c),
+                        None => return Err("unterminated string".to_string()),
+                    }
+                }
+                tokens.push(string);
+            }
+            _ => {
+                let mut atom = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_whitespace() || c == '(' || c == ')' || c == ';' || c == '"' {
+                        break;
+                    }
+                    atom.push(c);
+                    chars.next();
+                }
+                tokens.push(atom);
+            }
+        }
+    }
+    Ok(tokens)
+}
+
+fn parse(tokens: &mut std::iter::Peekable<std::vec::IntoIter<String>>) -> Result<SExpr, String> {
+    let token = tokens.next().ok_or("unexpected end of input")?;
+    match token.as_str() {
+        "(" => {
+            let mut items = Vec::new();
+            while tokens.peek().map(String::as_str) != Some(")") {
+                items.push(parse(tokens)?);
+            }
+            tokens.next();
+            Ok(SExpr::List(items))
+        }
+        ")" => Err("unbalanced `)`".to_string()),
+        _ => match token.strip_prefix('"') {
+            Some(string) => Ok(SExpr::String(string.to_string())),
+            None => Ok(SExpr::Atom(token)),
+        },
+    }
+}
@@ -0,0 +1,228 @@
+use crate::{
+    ast::ASTBinaryOperatorKind,
+    ir::{Instruction, InstructionKind, Program, TerminatorKind, Value},
+    text::SourceText,
+};
+
+// Only the tests validate generated modules, as nothing else does offline
+#[cfg(test)]
+mod checker;
+
+// Checked arithmetic; overflow traps with `unreachable`. `i64.div_s` already
+// traps on division by zero and on `INT64_MIN / -1`.
+const HELPERS: &str = "  (func $sirin_add (param $a i64) (param $b i64) (result i64) (local $r i64)
+    local.get $a
+    local.get $b
+    i64.add
+    local.set $r
+    ;; Overflow iff both operands have the same sign and the result does not
+    local.get $a
+    local.get $r
+    i64.xor
+    local.get $b
+    local.get $r
+    i64.xor
+    i64.and
+    i64.const 0
+    i64.lt_s
+    if
+      unreachable
+    end
+    local.get $r)
+  (func $sirin_sub (param $a i64) (param $b i64) (result i64) (local $r i64)
+    local.get $a
+    local.get $b
+    i64.sub
+    local.set $r
+    ;; Overflow iff the operands have different signs and the result has the sign of `b`
+    local.get $a
+    local.get $b
+    i64.xor
+    local.get $a
+    local.get $r
+    i64.xor
+    i64.and
+    i64.const 0
+    i64.lt_s
+    if
+      unreachable
+    end
+    local.get $r)
+  (func $sirin_mul (param $a i64) (param $b i64) (result i64) (local $r i64)
+    local.get $a
+    local.get $b
+    i64.mul
+    local.set $r
+    ;; Overflow iff dividing back does not give `b`; for `-1 * INT64_MIN` the
+    ;; division itself traps
+    local.get $a
+    i64.const 0
+    i64.ne
+    if
+      local.get $r
+      local.get $a
+      i64.div_s
+      local.get $b
+      i64.ne
+      if
+        unreachable
+      end
+    end
+    local.get $r)
+";
+
+// Translates a lowered program to a WebAssembly module in text format.
+// Every variable is a mutable `i64` global, exported under its name, and
+// every temporary a local of `main`; `main` returns the program's result and
+// has no result for an empty program.
+//
+// Export names must be unique, so only the last binding of a name is
+// exported as is. Shadowed bindings, and variables named `main`, get their
+// declaration number appended, as in `x#1`.
+pub struct WatGenerator<'a> {
+    text: &'a SourceText,
+    body: Vec<String>, // Instructions of `main`
+}
+
+impl<'a> WatGenerator<'a> {
+    pub fn new(text: &'a SourceText) -> Self {
+        Self { text, body: Vec::new() }
+    }
+
+    pub fn generate(mut self, program: &Program) -> String {
+        let mut result = "";
+        for block in &program.blocks {
+            for instruction in &block.instructions {
+                self.add_ir_instruction(instruction);
+            }
+            match block.terminator.kind {
+                TerminatorKind::Return(Some(value)) => {
+                    self.push(value);
+                    result = " (result i64)";
+                }
+                TerminatorKind::Return(None) => {}
+                _ => unreachable!("programs with match expressions are rejected before code generation"),
+            }
+        }
+
+        let mut module = format!(";; Generated by sirin from {}\n(module\n", self.text.file_name().replace('\n', " "));
+        for global in 0..program.variables.len() {
+            module.push_str(&format!(
+                "  (global $g{} (export \"{}\") (mut i64) (i64.const 0))\n",
+                global,
+                Self::export_name(program, global)
+            ));
+        }
+        module.push_str(HELPERS);
+        module.push_str(&format!("  (func $main (export \"main\"){}\n", result));
+        if program.temps > 0 {
+            let locals: Vec<_> = (0..program.temps).map(|temp| format!("(local $t{} i64)", temp)).collect();
+            module.push_str(&format!("    {}\n", locals.join(" ")));
+        }
+        for instruction in &self.body {
+            module.push_str(&format!("    {}\n", instruction));
+        }
+        module.push_str("  ))\n");
+        module
+    }
+
+    fn add_ir_instruction(&mut self, instruction: &Instruction) {
+        match instruction.kind {
+            InstructionKind::Copy { dest, value } => {
+                self.push(value);
+                self.add_instruction(format!("local.set $t{}", dest.0));
+            }
+            InstructionKind::Binary { dest, operator, left, right } => {
+                self.push(left);
+                self.push(right);
+                let (line, column) = self.text.line_column(instruction.span.start);
+                let operation = match operator {
+                    ASTBinaryOperatorKind::Add => "call $sirin_add",
+                    ASTBinaryOperatorKind::Subtract => "call $sirin_sub",
+                    ASTBinaryOperatorKind::Multiply => "call $sirin_mul",
+                    ASTBinaryOperatorKind::Divide => "i64.div_s",
+                };
+                self.add_instruction(format!("{} ;; {}:{}", operation, line, column));
+                self.add_instruction(format!("local.set $t{}", dest.0));
+            }
+            InstructionKind::Load { dest, variable } => {
+                self.add_instruction(format!("global.get $g{}", variable.0));
+                self.add_instruction(format!("local.set $t{}", dest.0));
+            }
+            InstructionKind::Store { variable, value } => {
+                self.push(value);
+                self.add_instruction(format!("global.set $g{}", variable.0));
+            }
+            _ => unreachable!("programs using strings, lists, maps, structs or enums are rejected before code generation"),
+        }
+    }
+
+    fn push(&mut self, value: Value) {
+        match value {
+            Value::Temp(temp) => self.add_instruction(format!("local.get $t{}", temp.0)),
+            Value::Constant(number) => self.add_instruction(format!("i64.const {}", number)),
+        }
+    }
+
+    fn export_name(program: &Program, global: usize) -> String {
+        let name = &program.variables[global].name;
+        let escaped = name.replace('\\', "\\\\").replace('"', "\\\"");
+        let is_visible = !program.variables[global + 1..].iter().any(|other| other.name == *name);
+        if is_visible && name != "main" {
+            return escaped;
+        }
+        let declaration = program.variables[..=global].iter().filter(|other| other.name == *name).count();
+        format!("{}#{}", escaped, declaration)
+    }
+
+    fn add_instruction(&mut self, instruction: String) {
+        self.body.push(instruction)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{codegen, compilation_unit::CompilationUnit, ir::Program, lints::LintConfig};
+
+    use super::{checker, WatGenerator};
+
+    fn assert_valid(input: String, file_name: String) {
+        let mut unit = CompilationUnit::compile(input, file_name.clone(), LintConfig::new());
+        unit.optimize();
+        assert!(!unit.has_errors(), "{}", file_name);
+        let program = Program::lower(&unit.ast);
+        if codegen::find_unsupported(&program).is_some() {
+            return;
+        }
+        let wat = WatGenerator::new(&unit.text).generate(&program);
+        if let Err(message) = checker::check(&wat) {
+            panic!("{}: generated an invalid WebAssembly module: {}\n{}", file_name, message, wat)
+        }
+    }
+
+    #[test]
+    fn generates_valid_modules_for_the_examples() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let input = fs::read_to_string(&path).unwrap();
+            assert_valid(input, path.display().to_string());
+        }
+    }
+
+    #[test]
+    fn generates_valid_modules_for_every_supported_operation() {
+        let programs = [
+            "1",
+            "let a = 5\nlet b = 3\na + b - a * b / 2",
+            "let x = 1\nx = x + 1\nx = x * 2\nx",
+            "let main = 2\nlet main = main - 1\nmain",
+            "let n = 7\nlet m = 2\nm = m - 2\nn / m",
+        ];
+        for (index, input) in programs.iter().enumerate() {
+            assert_valid(input.to_string(), format!("program{}.sn", index));
+        }
+    }
+}
@@ -0,0 +1,303 @@
+use crate::{
+    ast::{lexer::TextSpan, ASTBinaryOperatorKind},
+    ir::{Instruction, InstructionKind, Program, Temp, TerminatorKind, Value},
+    text::SourceText,
+};
+
+// Runtime support, called with the value in %rax. Both routines exit: the
+// program's status is the low byte of its result, or 1 after an error.
+const RUNTIME: &str = r#"
+# Prints `Some(<rax>)` and exits with the low byte of %rax
+sirin_print_some:
+    movq %rax, %r12
+    movl $1, %edi
+    leaq .Lsome(%rip), %rsi
+    movl $5, %edx
+    call sirin_write
+    # Digits are produced from the end of a buffer, working on the negated
+    # value so that INT64_MIN needs no special case
+    subq $32, %rsp
+    leaq 32(%rsp), %rsi
+    movq %r12, %rax
+    testq %rax, %rax
+    js 1f
+    negq %rax
+1:  movq $10, %rcx
+2:  cqto
+    idivq %rcx
+    negq %rdx
+    addb $'0', %dl
+    decq %rsi
+    movb %dl, (%rsi)
+    testq %rax, %rax
+    jnz 2b
+    testq %r12, %r12
+    jns 3f
+    decq %rsi
+    movb $'-', (%rsi)
+3:  leaq 32(%rsp), %rdx
+    subq %rsi, %rdx
+    movl $1, %edi
+    call sirin_write
+    movl $1, %edi
+    leaq .Lsome_end(%rip), %rsi
+    movl $2, %edx
+    call sirin_write
+    movq %r12, %rdi
+    andq $255, %rdi
+    jmp sirin_exit
+
+sirin_print_none:
+    movl $1, %edi
+    leaq .Lnone(%rip), %rsi
+    movl $5, %edx
+    call sirin_write
+    xorl %edi, %edi
+    jmp sirin_exit
+
+# Writes the message at %rdi (length %r8) and the location at %rsi (length
+# %rdx) to stderr, then exits with status 1
+sirin_fail:
+    pushq %rsi
+    pushq %rdx
+    movq %rdi, %rsi
+    movq %r8, %rdx
+    movl $2, %edi
+    call sirin_write
+    popq %rdx
+    popq %rsi
+    movl $2, %edi
+    call sirin_write
+    movl $1, %edi
+    jmp sirin_exit
+
+# write(%edi, %rsi, %rdx)
+sirin_write:
+    movl $1, %eax
+    syscall
+    ret
+
+# exit(%rdi)
+sirin_exit:
+    movl $60, %eax
+    syscall
+
+    .section .rodata
+.Lsome:
+    .ascii "Some("
+.Lsome_end:
+    .ascii ")\n"
+.Lnone:
+    .ascii "None\n"
+.Loverflow:
+    .ascii "error[E0011]: This arithmetic operation would overflow\n"
+    .set .Loverflow_len, . - .Loverflow
+.Ldivision_by_zero:
+    .ascii "error[E0010]: This operation would divide by zero\n"
+    .set .Ldivision_by_zero_len, . - .Ldivision_by_zero
+"#;
+
+// Translates a lowered program to GNU assembler x86-64 (AT&T syntax) for a
+// freestanding Linux executable: `_start` runs the program and talks to the
+// kernel directly, so linking needs no C library. Variables, then
+// temporaries, live in frame slots below %rbp; each instruction works through
+// %rax and %rcx.
+pub struct X86Generator<'a> {
+    text: &'a SourceText,
+    body: Vec<String>, // Instructions of `_start`
+    temps_base: usize, // Slot of the first temporary
+    errors: Vec<(String, &'static str, TextSpan)>, // Label, message and location of each error exit
+}
+
+impl<'a> X86Generator<'a> {
+    pub fn new(text: &'a SourceText) -> Self {
+        Self {
+            text,
+            body: Vec::new(),
+            temps_base: 0,
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn generate(mut self, program: &Program) -> String {
+        self.temps_base = program.variables.len();
+        let mut finish = "jmp sirin_print_none";
+        for block in &program.blocks {
+            for instruction in &block.instructions {
+                self.add_ir_instruction(instruction);
+            }
+            match block.terminator.kind {
+                TerminatorKind::Return(Some(value)) => {
+                    self.load(value, "%rax");
+                    finish = "jmp sirin_print_some";
+                }
+                TerminatorKind::Return(None) => {}
+                _ => unreachable!("programs with match expressions are rejected before code generation"),
+            }
+        }
+
+        let mut assembly = format!("# Generated by sirin from {}\n", self.text.file_name().replace('\n', " "));
+        assembly.push_str("    .text\n    .globl _start\n_start:\n");
+        assembly.push_str("    movq %rsp, %rbp\n");
+        assembly.push_str(&format!("    subq ${}, %rsp\n", 8 * (self.temps_base + program.temps)));
+        for instruction in &self.body {
+            assembly.push_str(&format!("    {}\n", instruction));
+        }
+        assembly.push_str(&format!("    {}\n", finish));
+        for (index, (label, message, _)) in self.errors.iter().enumerate() {
+            assembly.push_str(&format!("{}:\n", label));
+            assembly.push_str(&format!("    leaq {}(%rip), %rdi\n    movq ${}_len, %r8\n", message, message));
+            assembly.push_str(&format!("    leaq .Llocation{}(%rip), %rsi\n    movq $.Llocation{}_len, %rdx\n", index, index));
+            assembly.push_str("    jmp sirin_fail\n");
+        }
+        assembly.push_str(RUNTIME);
+        for (index, (_, _, span)) in self.errors.iter().enumerate() {
+            let (line, column) = self.text.line_column(span.start);
+            let location = format!(" --> {}:{}:{}\n", self.text.file_name(), line, column);
+            assembly.push_str(&format!(
+                ".Llocation{}:\n    .ascii \"{}\"\n    .set .Llocation{}_len, . - .Llocation{}\n",
+                index,
+                Self::escape(&location),
+                index,
+                index
+            ));
+        }
+        assembly
+    }
+
+    fn add_ir_instruction(&mut self, instruction: &Instruction) {
+        match instruction.kind {
+            InstructionKind::Copy { dest, value } => {
+                self.load(value, "%rax");
+                self.store_temp(dest);
+            }
+            InstructionKind::Load { dest, variable } => {
+                self.add_instruction(&format!("movq {}, %rax", Self::slot_operand(variable.0)));
+                self.store_temp(dest);
+            }
+            InstructionKind::Store { variable, value } => {
+                self.load(value, "%rax");
+                self.add_instruction(&format!("movq %rax, {}", Self::slot_operand(variable.0)));
+            }
+            InstructionKind::Binary { dest, operator, left, right } => {
+                self.load(left, "%rax");
+                self.load(right, "%rcx");
+                let overflow = self.error_exit(".Loverflow", &instruction.span);
+                match operator {
+                    ASTBinaryOperatorKind::Add => self.add_instruction("addq %rcx, %rax"),
+                    ASTBinaryOperatorKind::Subtract => self.add_instruction("subq %rcx, %rax"),
+                    ASTBinaryOperatorKind::Multiply => self.add_instruction("imulq %rcx, %rax"),
+                    ASTBinaryOperatorKind::Divide => {
+                        // `idivq` faults on a zero divisor and on INT64_MIN / -1,
+                        // which is a negation that overflows
+                        let division_by_zero = self.error_exit(".Ldivision_by_zero", &instruction.span);
+                        self.add_instruction("testq %rcx, %rcx");
+                        self.add_instruction(&format!("jz {}", division_by_zero));
+                        self.add_instruction("cmpq $-1, %rcx");
+                        self.add_instruction("jne 1f");
+                        self.add_instruction("negq %rax");
+                        self.add_instruction(&format!("jo {}", overflow));
+                        self.add_instruction("jmp 2f");
+                        self.add_instruction("1: cqto");
+                        self.add_instruction("idivq %rcx");
+                        self.add_instruction("2:");
+                        return self.store_temp(dest);
+                    }
+                }
+                self.add_instruction(&format!("jo {}", overflow));
+                self.store_temp(dest);
+            }
+            _ => unreachable!("programs using strings, lists, maps, structs or enums are rejected before code generation"),
+        }
+    }
+
+    fn load(&mut self, value: Value, register: &str) {
+        match value {
+            Value::Temp(temp) => {
+                let operand = Self::slot_operand(self.temps_base + temp.0);
+                self.add_instruction(&format!("movq {}, {}", operand, register))
+            }
+            Value::Constant(number) => self.add_instruction(&format!("movabsq ${}, {}", number, register)),
+        }
+    }
+
+    fn store_temp(&mut self, temp: Temp) {
+        let operand = Self::slot_operand(self.temps_base + temp.0);
+        self.add_instruction(&format!("movq %rax, {}", operand));
+    }
+
+    fn add_instruction(&mut self, instruction: &str) {
+        self.body.push(instruction.to_string())
+    }
+
+    fn slot_operand(slot: usize) -> String {
+        format!("-{}(%rbp)", 8 * (slot + 1))
+    }
+
+    // Label of a new error exit reporting `message` at `span`
+    fn error_exit(&mut self, message: &'static str, span: &TextSpan) -> String {
+        let label = format!(".Lerror{}", self.errors.len());
+        self.errors.push((label.clone(), message, span.clone()));
+        label
+    }
+
+    // Bytes outside printable ASCII are written as octal escapes
+    fn escape(string: &str) -> String {
+        let mut escaped = String::new();
+        for byte in string.bytes() {
+            match byte {
+                b'"' | b'\\' => {
+                    escaped.push('\\');
+                    escaped.push(byte as char);
+                }
+                0x20..=0x7e => escaped.push(byte as char),
+                _ => escaped.push_str(&format!("\\{:03o}", byte)),
+            }
+        }
+        escaped
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, process::Command};
+
+    use crate::codegen::testing::{self, has_tool, interpret};
+
+    use super::X86Generator;
+
+    #[test]
+    fn executables_print_and_exit_like_the_interpreter() {
+        if !has_tool("as") || !has_tool("ld") {
+            eprintln!("skipped: `as` or `ld` is not available");
+            return;
+        }
+        let dir = testing::work_dir("x86_64");
+        for (index, test) in testing::programs().iter().enumerate() {
+            let source = dir.join(format!("program{}.s", index));
+            let object = dir.join(format!("program{}.o", index));
+            let executable = dir.join(format!("program{}", index));
+            fs::write(&source, X86Generator::new(&test.unit.text).generate(&test.program)).unwrap();
+            for (tool, output, input) in [("as", &object, &source), ("ld", &executable, &object)] {
+                let built = Command::new(tool).arg("-o").arg(output).arg(input).output().unwrap();
+                assert!(built.status.success(), "{}: {}", test.name, String::from_utf8_lossy(&built.stderr));
+            }
+            let output = Command::new(&executable).output().unwrap();
+            let stdout = String::from_utf8(output.stdout).unwrap();
+            match interpret(&test.unit) {
+                Some(expected) => {
+                    // The status is the low byte of the result
+                    let result = expected.strip_prefix("Some(").and_then(|rest| rest.strip_suffix(")\n"));
+                    let status = result.map_or(0, |value| value.parse::<i64>().unwrap() & 255);
+                    assert_eq!(stdout, expected, "{}", test.name);
+                    assert_eq!(output.status.code(), Some(status as i32), "{}", test.name);
+                }
+                None => {
+                    assert_eq!(output.status.code(), Some(1), "{}", test.name);
+                    assert!(String::from_utf8_lossy(&output.stderr).starts_with("error[E00"), "{}", test.name);
+                }
+            }
+        }
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,99 @@
+use std::{cell::RefCell, rc::Rc};
+
+use crate::{
+    ast::{
+        evaluator::ASTEvaluator,
+        lexer::{Lexer, TokenKind},
+        parser::Parser,
+        Ast,
+    },
+    bytecode::{compiler::BytecodeCompiler, vm::VirtualMachine},
+    cli::Engine,
+    diagnostics::{DiagnosticsBag, DiagnosticsBagCell},
+    ir::Program,
+    lints::{self, LintConfig},
+    optimizer,
+    runtime::Value,
+    text::SourceText,
+    typeck::{TypeChecker, TypeTable},
+};
+
+// A parsed source file together with everything reported while processing it
+pub struct CompilationUnit {
+    pub text: SourceText,
+    pub ast: Ast,
+    pub types: TypeTable,
+    pub diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl CompilationUnit {
+    pub fn compile(input: String, file_name: String, lints: LintConfig) -> Self {
+        let mut lexer = Lexer::new(&input);
+        let mut tokens = Vec::new();
+        while let Some(token) = lexer.next_token() {
+            tokens.push(token)
+        }
+        // println!("{:?}", tokens);
+        let diagnostics_bag: DiagnosticsBagCell = Rc::new(RefCell::new(DiagnosticsBag::new(lints)));
+        let pragmas: Vec<_> = tokens
+            .iter()
+            .filter(|token| token.kind == TokenKind::Pragma)
+            .cloned()
+            .collect();
+        let mut lints = diagnostics_bag.borrow().lints.clone();
+        lints.apply_pragmas(&pragmas, &diagnostics_bag);
+        diagnostics_bag.borrow_mut().lints = lints;
+
+        let mut ast = Ast::new();
+        let mut parser = Parser::new(tokens, Rc::clone(&diagnostics_bag));
+        while let Some(stmt) = parser.next_statement() {
+            ast.add_statement(stmt);
+        }
+        // Later passes only make sense on a syntactically valid program
+        let mut types = TypeTable::new();
+        if !diagnostics_bag.borrow().has_errors() {
+            let mut type_checker = TypeChecker::new(Rc::clone(&diagnostics_bag));
+            ast.visit(&mut type_checker);
+            types = type_checker.finish();
+            lints::run_lints(&ast, &diagnostics_bag);
+        }
+        Self {
+            text: SourceText::new(input, file_name),
+            ast,
+            types,
+            diagnostics_bag,
+        }
+    }
+
+    // Skipped when compilation failed, as the optimizer assumes a well-typed tree
+    pub fn optimize(&mut self) {
+        if !self.has_errors() {
+            optimizer::optimize(&mut self.ast, &self.diagnostics_bag);
+        }
+    }
+
+    // Runs a unit without errors on `engine`; runtime errors are reported to
+    // its diagnostics bag
+    pub fn execute(&self, engine: &Engine) -> Option<Value> {
+        match engine {
+            Engine::Tree => {
+                let mut eval = ASTEvaluator::new(self.diagnostics_bag.clone());
+                self.ast.visit(&mut eval);
+                eval.last_value
+            }
+            Engine::Vm => {
+                match BytecodeCompiler::new().compile(&Program::lower(&self.ast)) {
+                    Ok(chunk) => VirtualMachine::new(&chunk, self.diagnostics_bag.clone()).run(),
+                    Err((what, span)) => {
+                        self.diagnostics_bag.borrow_mut().report_program_too_large(what, &span);
+                        None
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics_bag.borrow().has_errors()
+    }
+}
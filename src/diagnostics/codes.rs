@@ -0,0 +1,732 @@
+use std::{fmt::Display, str::FromStr};
+
+// Stable identifiers of every diagnostic the compiler can emit. Codes are
+// never reused once published, so tooling can rely on them across versions.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum DiagnosticsCode {
+    UnexpectedToken,
+    ExpectedExpression,
+    UnclosedDelimiter,
+    MalformedPragma,
+    UndeclaredVariable,
+    MismatchedTypes,
+    InvalidOperands,
+    ExpectedType,
+    UnknownType,
+    DivisionByZero,
+    ArithmeticOverflow,
+    UnknownFunction,
+    ArgumentCountMismatch,
+    IndexOutOfBounds,
+    UnsupportedByBackend,
+    InvalidMapKey,
+    KeyNotFound,
+    UnknownField,
+    MissingFields,
+    DuplicateName,
+    ExpectedPattern,
+    UnknownVariant,
+    PayloadCountMismatch,
+    NonExhaustiveMatch,
+    RefutablePattern,
+    NotIterable,
+    ProgramTooLarge,
+    InvalidBytecode,
+    Shadowing,
+    UnknownLint,
+    UnusedVariable,
+    DeadStore,
+    UnreachablePattern,
+}
+
+impl DiagnosticsCode {
+    pub const ALL: &'static [DiagnosticsCode] = &[
+        DiagnosticsCode::UnexpectedToken,
+        DiagnosticsCode::ExpectedExpression,
+        DiagnosticsCode::UnclosedDelimiter,
+        DiagnosticsCode::MalformedPragma,
+        DiagnosticsCode::UndeclaredVariable,
+        DiagnosticsCode::MismatchedTypes,
+        DiagnosticsCode::InvalidOperands,
+        DiagnosticsCode::ExpectedType,
+        DiagnosticsCode::UnknownType,
+        DiagnosticsCode::DivisionByZero,
+        DiagnosticsCode::ArithmeticOverflow,
+        DiagnosticsCode::UnknownFunction,
+        DiagnosticsCode::ArgumentCountMismatch,
+        DiagnosticsCode::IndexOutOfBounds,
+        DiagnosticsCode::UnsupportedByBackend,
+        DiagnosticsCode::InvalidMapKey,
+        DiagnosticsCode::KeyNotFound,
+        DiagnosticsCode::UnknownField,
+        DiagnosticsCode::MissingFields,
+        DiagnosticsCode::DuplicateName,
+        DiagnosticsCode::ExpectedPattern,
+        DiagnosticsCode::UnknownVariant,
+        DiagnosticsCode::PayloadCountMismatch,
+        DiagnosticsCode::NonExhaustiveMatch,
+        DiagnosticsCode::RefutablePattern,
+        DiagnosticsCode::NotIterable,
+        DiagnosticsCode::ProgramTooLarge,
+        DiagnosticsCode::InvalidBytecode,
+        DiagnosticsCode::Shadowing,
+        DiagnosticsCode::UnknownLint,
+        DiagnosticsCode::UnusedVariable,
+        DiagnosticsCode::DeadStore,
+        DiagnosticsCode::UnreachablePattern,
+    ];
+
+    pub fn code(&self) -> &'static str {
+        match self {
+            DiagnosticsCode::UnexpectedToken => "E0001",
+            DiagnosticsCode::ExpectedExpression => "E0002",
+            DiagnosticsCode::UnclosedDelimiter => "E0003",
+            DiagnosticsCode::MalformedPragma => "E0004",
+            DiagnosticsCode::UndeclaredVariable => "E0005",
+            DiagnosticsCode::MismatchedTypes => "E0006",
+            DiagnosticsCode::InvalidOperands => "E0007",
+            DiagnosticsCode::ExpectedType => "E0008",
+            DiagnosticsCode::UnknownType => "E0009",
+            DiagnosticsCode::DivisionByZero => "E0010",
+            DiagnosticsCode::ArithmeticOverflow => "E0011",
+            DiagnosticsCode::UnknownFunction => "E0012",
+            DiagnosticsCode::ArgumentCountMismatch => "E0013",
+            DiagnosticsCode::IndexOutOfBounds => "E0014",
+            DiagnosticsCode::UnsupportedByBackend => "E0015",
+            DiagnosticsCode::InvalidMapKey => "E0016",
+            DiagnosticsCode::KeyNotFound => "E0017",
+            DiagnosticsCode::UnknownField => "E0018",
+            DiagnosticsCode::MissingFields => "E0019",
+            DiagnosticsCode::DuplicateName => "E0020",
+            DiagnosticsCode::ExpectedPattern => "E0021",
+            DiagnosticsCode::UnknownVariant => "E0022",
+            DiagnosticsCode::PayloadCountMismatch => "E0023",
+            DiagnosticsCode::NonExhaustiveMatch => "E0024",
+            DiagnosticsCode::RefutablePattern => "E0025",
+            DiagnosticsCode::NotIterable => "E0026",
+            DiagnosticsCode::ProgramTooLarge => "E0027",
+            DiagnosticsCode::InvalidBytecode => "E0028",
+            DiagnosticsCode::Shadowing => "W0001",
+            DiagnosticsCode::UnknownLint => "W0002",
+            DiagnosticsCode::UnusedVariable => "W0003",
+            DiagnosticsCode::DeadStore => "W0004",
+            DiagnosticsCode::UnreachablePattern => "W0005",
+        }
+    }
+
+    pub fn summary(&self) -> &'static str {
+        match self {
+            DiagnosticsCode::UnexpectedToken => "a different token was expected",
+            DiagnosticsCode::ExpectedExpression => "an expression was expected",
+            DiagnosticsCode::UnclosedDelimiter => "a delimiter was never closed",
+            DiagnosticsCode::MalformedPragma => "a pragma could not be parsed",
+            DiagnosticsCode::UndeclaredVariable => "a variable is used before being declared",
+            DiagnosticsCode::MismatchedTypes => "a value does not have the expected type",
+            DiagnosticsCode::InvalidOperands => "an operator is applied to values it does not support",
+            DiagnosticsCode::ExpectedType => "a type was expected",
+            DiagnosticsCode::UnknownType => "a type annotation names a type that does not exist",
+            DiagnosticsCode::DivisionByZero => "an integer is divided by zero",
+            DiagnosticsCode::ArithmeticOverflow => "an arithmetic operation overflows `int`",
+            DiagnosticsCode::UnknownFunction => "a call names a function that does not exist",
+            DiagnosticsCode::ArgumentCountMismatch => "a function is called with the wrong number of arguments",
+            DiagnosticsCode::IndexOutOfBounds => "a list is accessed outside of its bounds or has the wrong length",
+            DiagnosticsCode::UnsupportedByBackend => "a program uses a feature the chosen backend cannot compile",
+            DiagnosticsCode::InvalidMapKey => "a map is keyed by a type other than `int` or `string`",
+            DiagnosticsCode::KeyNotFound => "a map is read at a key it does not contain",
+            DiagnosticsCode::UnknownField => "a field is accessed that the value's type does not declare",
+            DiagnosticsCode::MissingFields => "a struct is constructed or matched without every field",
+            DiagnosticsCode::DuplicateName => "a type, variant, field or binding name is used twice where it must be unique",
+            DiagnosticsCode::ExpectedPattern => "a pattern was expected",
+            DiagnosticsCode::UnknownVariant => "a pattern names a variant that no enum declares",
+            DiagnosticsCode::PayloadCountMismatch => "a variant is built or matched with the wrong number of values",
+            DiagnosticsCode::NonExhaustiveMatch => "a `match` does not handle every possible value",
+            DiagnosticsCode::RefutablePattern => "the pattern of a `let` does not match every possible value",
+            DiagnosticsCode::NotIterable => "a `for` loop iterates over a value that has no items",
+            DiagnosticsCode::ProgramTooLarge => "a program is too large to compile to bytecode",
+            DiagnosticsCode::InvalidBytecode => "a bytecode file applies an instruction to a value of the wrong kind",
+            DiagnosticsCode::Shadowing => "a variable shadows an earlier binding",
+            DiagnosticsCode::UnknownLint => "a pragma names a lint that does not exist",
+            DiagnosticsCode::UnusedVariable => "a variable is never read",
+            DiagnosticsCode::DeadStore => "a value is overwritten before being read",
+            DiagnosticsCode::UnreachablePattern => "a `match` arm can never be reached",
+        }
+    }
+
+    pub fn explanation(&self) -> &'static str {
+        match self {
+            DiagnosticsCode::UnexpectedToken => E0001,
+            DiagnosticsCode::ExpectedExpression => E0002,
+            DiagnosticsCode::UnclosedDelimiter => E0003,
+            DiagnosticsCode::MalformedPragma => E0004,
+            DiagnosticsCode::UndeclaredVariable => E0005,
+            DiagnosticsCode::MismatchedTypes => E0006,
+            DiagnosticsCode::InvalidOperands => E0007,
+            DiagnosticsCode::ExpectedType => E0008,
+            DiagnosticsCode::UnknownType => E0009,
+            DiagnosticsCode::DivisionByZero => E0010,
+            DiagnosticsCode::ArithmeticOverflow => E0011,
+            DiagnosticsCode::UnknownFunction => E0012,
+            DiagnosticsCode::ArgumentCountMismatch => E0013,
+            DiagnosticsCode::IndexOutOfBounds => E0014,
+            DiagnosticsCode::UnsupportedByBackend => E0015,
+            DiagnosticsCode::InvalidMapKey => E0016,
+            DiagnosticsCode::KeyNotFound => E0017,
+            DiagnosticsCode::UnknownField => E0018,
+            DiagnosticsCode::MissingFields => E0019,
+            DiagnosticsCode::DuplicateName => E0020,
+            DiagnosticsCode::ExpectedPattern => E0021,
+            DiagnosticsCode::UnknownVariant => E0022,
+            DiagnosticsCode::PayloadCountMismatch => E0023,
+            DiagnosticsCode::NonExhaustiveMatch => E0024,
+            DiagnosticsCode::RefutablePattern => E0025,
+            DiagnosticsCode::NotIterable => E0026,
+            DiagnosticsCode::ProgramTooLarge => E0027,
+            DiagnosticsCode::InvalidBytecode => E0028,
+            DiagnosticsCode::Shadowing => W0001,
+            DiagnosticsCode::UnknownLint => W0002,
+            DiagnosticsCode::UnusedVariable => W0003,
+            DiagnosticsCode::DeadStore => W0004,
+            DiagnosticsCode::UnreachablePattern => W0005,
+        }
+    }
+}
+
+impl FromStr for DiagnosticsCode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|code| code.code().eq_ignore_ascii_case(s))
+            .copied()
+            .ok_or_else(|| format!("`{}` is not a valid diagnostic code", s))
+    }
+}
+
+/* DISPLAY */
+
+impl Display for DiagnosticsCode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.code())
+    }
+}
+
+/* EXPLANATIONS */
+
+const E0001: &str = "\
+The parser found a token other than the one the grammar requires at this
+position.
+
+Erroneous code example:
+
+    let 5 = 3
+
+A `let` statement must name the variable it declares, so an identifier is
+expected right after `let`. Fix the statement so that every token appears in
+the expected order:
+
+    let five = 3";
+
+const E0002: &str = "\
+An expression was expected, but the parser found a token that cannot start
+one.
+
+Erroneous code example:
+
+    let a = 3 +
+
+Every operator needs an operand on both sides, and the initializer of a `let`
+cannot be empty. An expression is a number, a variable, a list such as
+`[1, 2]`, a call such as `len(xs)`, or any expression wrapped in parentheses.
+Complete the expression:
+
+    let a = 3 + 4";
+
+const E0003: &str = "\
+A parenthesized expression was opened but never closed.
+
+Erroneous code example:
+
+    let d = (10 * c / 5
+
+Every `(` must be matched by a `)` once the inner expression ends. Close the
+group where it was meant to end:
+
+    let d = (10 * c) / 5";
+
+const E0004: &str = "\
+A line starting with `#` is a pragma, but it does not follow the
+`#level(lint, ...)` form.
+
+Erroneous code example:
+
+    #allow shadowing
+
+A pragma names a lint level (`allow`, `warn` or `deny`) followed by a
+parenthesized, comma-separated list of lints. `warnings` stands for every lint
+at the `warn` level:
+
+    #allow(shadowing)
+    #deny(warnings)";
+
+const E0005: &str = "\
+A variable is read or assigned, but no `let` statement declares it before
+this point.
+
+Erroneous code example:
+
+    let width = 5
+    widht * 2
+
+Check the spelling of the name, or declare the variable before using it:
+
+    let width = 5
+    width * 2";
+
+const E0006: &str = "\
+A value does not have the type the context requires. Every variable keeps the
+type of the value it was declared with.
+
+Erroneous code example:
+
+    let a: float = 5
+
+`5` is an `int`, but the binding was annotated as `float`. Make the value and
+the expected type agree:
+
+    let a: int = 5";
+
+const E0007: &str = "\
+An arithmetic operator is applied to a value of a type it does not support.
+`+`, `-`, `*` and `/` are only defined between integers, and so are the
+bounds of the ranges `..` and `..=` build.
+
+Erroneous code example:
+
+    let limit: int? = 5
+    limit + 1
+
+`limit` may hold no value at all, so it cannot be added to. Declare it with a
+type the operator supports:
+
+    let limit: int = 5
+    limit + 1";
+
+const E0008: &str = "\
+A type annotation was expected after `:`, but the parser found a token that
+cannot start a type.
+
+Erroneous code example:
+
+    let a: = 5
+
+A type is a name such as `int`, `float`, `bool`, `string` or a struct, an
+array `[int]`, a map `{string: int}`, a function `fn(int, int) -> int`, or any
+of them followed by `?` to make it optional:
+
+    let a: int = 5";
+
+const E0009: &str = "\
+A type annotation names a type that does not exist.
+
+Erroneous code example:
+
+    let a: integer = 5
+
+The primitive types are `int`, `float`, `bool` and `string`; other names must
+be declared with `struct` or `enum` before they are used:
+
+    let a: int = 5";
+
+const E0010: &str = "\
+The divisor of a division is zero. The error is reported before running the
+program when the divisor is known and the division is not inside a `match` arm
+or a loop, and stops the program otherwise.
+
+Erroneous code example:
+
+    let zero = 0
+    10 / zero
+
+Constants are propagated from `let` bindings that are never reassigned, so the
+divisor does not have to be a literal `0` for the error to be reported. Make
+sure the divisor cannot be zero:
+
+    let divisor = 2
+    10 / divisor";
+
+const E0011: &str = "\
+The result of an arithmetic operation does not fit in an `int`, a signed
+64-bit integer. The error is reported before running the program when the
+operands are known and the operation is not inside a `match` arm or a loop,
+and stops the program otherwise.
+
+Erroneous code example:
+
+    let big = 9223372036854775807
+    big + 1
+
+Integers do not wrap around on overflow. Keep intermediate results within
+-9223372036854775808 and 9223372036854775807:
+
+    let big = 9223372036854775806
+    big + 1";
+
+const E0012: &str = "\
+A call names a function that does not exist. Only the built-in functions
+`len`, `push`, `pop`, `keys`, `values` and `has` can be called.
+
+Erroneous code example:
+
+    let xs = [1, 2, 3]
+    length(xs)
+
+Check the spelling of the function:
+
+    let xs = [1, 2, 3]
+    len(xs)";
+
+const E0013: &str = "\
+A function is called with more or fewer arguments than it takes.
+
+Erroneous code example:
+
+    let xs = [1, 2, 3]
+    push(xs)
+
+`len(xs)` and `pop(xs)` take a list, `push(xs, x)` a list and the element to
+append to it. `keys(m)` and `values(m)` take a map, `has(m, k)` a map and a
+key:
+
+    let xs = [1, 2, 3]
+    push(xs, 4)";
+
+const E0014: &str = "\
+A list is indexed or sliced outside of its bounds, `pop` is called on an empty
+list, or a `let` destructures a list whose length is not the one its pattern
+matches. The error stops the program.
+
+Erroneous code example:
+
+    let xs = [1, 2, 3]
+    xs[3]
+
+Indexes start at 0, so the valid indexes of a list go up to its length minus
+one. A slice `xs[start..end]` needs `start <= end <= len(xs)`. Check the index
+against the length of the list:
+
+    let xs = [1, 2, 3]
+    xs[len(xs) - 1]";
+
+const E0015: &str = "\
+The program uses a feature that the backend it is compiled with does not
+support yet. The C, WebAssembly, x86-64 and LLVM backends only handle
+integers, so programs using strings, lists, maps, structs, tuples, ranges or
+`for` loops must be run with `sirin run`.
+
+Erroneous code example:
+
+    $ sirin emit-c lists.sn
+
+where `lists.sn` contains
+
+    let xs = [1, 2, 3]
+    len(xs)
+
+Run the program with the interpreter or the VM instead:
+
+    $ sirin run lists.sn
+    $ sirin run --engine vm lists.sn";
+
+const E0016: &str = "\
+A map is keyed by a type other than `int` or `string`. Keys are looked up by
+their content, which only integers and strings support.
+
+Erroneous code example:
+
+    let seen = { [1]: 1 }
+
+Use an integer or a string as the key:
+
+    let seen = { \"1\": 1 }";
+
+const E0017: &str = "\
+A map is read at a key it does not contain. The error stops the program.
+
+Erroneous code example:
+
+    let ages = { \"ada\": 36 }
+    ages[\"alan\"]
+
+Assigning to a missing key inserts it, but reading one is an error. Check for
+the key with `has` first:
+
+    let ages = { \"ada\": 36 }
+    has(ages, \"alan\")";
+
+const E0018: &str = "\
+A field is read or assigned that the type of the value does not declare, or a
+struct is constructed with a field its declaration does not list. Only structs
+have fields.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int }
+    let p = Point { x: 1, y: 2 }
+    p.z
+
+Use one of the fields declared by the struct:
+
+    struct Point { x: int, y: int }
+    let p = Point { x: 1, y: 2 }
+    p.y";
+
+const E0019: &str = "\
+A struct is constructed without a value for one of its fields, or a struct
+pattern leaves out fields without ending with `..`. Fields have no default
+values.
+
+Erroneous code example:
+
+    struct Point { x: int, y: int }
+    Point { x: 1 }
+
+Give every field a value:
+
+    struct Point { x: int, y: int }
+    Point { x: 1, y: 0 }";
+
+const E0020: &str = "\
+A struct or enum is declared twice, two enums declare a variant with the same
+name, a struct declares the same field twice, a struct literal gives the same
+field two values, or a pattern binds the same variable or matches the same
+field twice.
+
+Erroneous code example:
+
+    struct Point { x: int, x: int }
+
+Give each type, variant, field and binding a distinct name:
+
+    struct Point { x: int, y: int }";
+
+const E0021: &str = "\
+A pattern was expected, but the parser found a token that cannot start one.
+
+Erroneous code example:
+
+    let n = 3
+    match n { n + 1 => 0, _ => n }
+
+A pattern is `_`, a variable to bind, a number, a string, a variant such as
+`Circle(r)`, a tuple `(a, b)`, a list `[a, b]` or a struct `Point { x, y: 0 }`,
+whose parts are matched by more patterns:
+
+    let n = 3
+    match n { 4 => 0, _ => n }";
+
+const E0022: &str = "\
+A pattern names a variant that no enum declares.
+
+Erroneous code example:
+
+    enum Shape { Circle(int), Square(int) }
+    let s = Circle(2)
+    match s { Circle(r) => r, Rectangle(w) => w }
+
+Check the spelling of the variant, or declare it in the enum:
+
+    enum Shape { Circle(int), Square(int) }
+    let s = Circle(2)
+    match s { Circle(r) => r, Square(w) => w }";
+
+const E0023: &str = "\
+A variant is constructed with more or fewer values than it declares, or a
+pattern matches more or fewer values than the variant holds.
+
+Erroneous code example:
+
+    enum Shape { Circle(int), Rect(int, int) }
+    let s = Rect(2)
+
+Give the variant one value per declared type, and match each of them in
+patterns, with `_` for the ones that do not matter:
+
+    enum Shape { Circle(int), Rect(int, int) }
+    let s = Rect(2, 3)
+    match s { Circle(r) => r, Rect(w, _) => w }";
+
+const E0024: &str = "\
+A `match` expression does not handle every value of the matched expression,
+so the program would have no arm to run for the missing ones.
+
+Erroneous code example:
+
+    enum Shape { Circle(int), Rect(int, int), Empty }
+    let s = Circle(2)
+    match s { Circle(r) => r, Rect(w, h) => w * h }
+
+Add an arm for each missing pattern the diagnostic lists, or a wildcard `_`
+arm matching every remaining value:
+
+    enum Shape { Circle(int), Rect(int, int), Empty }
+    let s = Circle(2)
+    match s { Circle(r) => r, Rect(w, h) => w * h, Empty => 0 }";
+
+const E0025: &str = "\
+The pattern of a `let` statement does not match every value of its type, so
+the program would have nothing to bind for the missing ones. Only the length
+of a list pattern is left to a check at runtime.
+
+Erroneous code example:
+
+    enum Shape { Circle(int), Rect(int, int) }
+    let s = Circle(2)
+    let Circle(r) = s
+
+Use a `match` to handle the values the pattern does not match:
+
+    enum Shape { Circle(int), Rect(int, int) }
+    let s = Circle(2)
+    let r = match s { Circle(r) => r, Rect(w, _) => w }";
+
+const E0026: &str = "\
+A `for` loop iterates over a value that has no items. Loops run over ranges
+of integers, the elements of a list, the characters of a string or the keys
+of a map.
+
+Erroneous code example:
+
+    let count = 3
+    for i in count { i }
+
+Iterate over a range ending at the number instead:
+
+    let count = 3
+    for i in 0..count { i }";
+
+const E0027: &str = "\
+The program does not fit in the bytecode the VM runs. Its operands are 16 bits
+wide, so a program can have at most 65535 local slots, integer constants,
+strings, list elements in a literal, and bytes of code to jump over.
+
+Erroneous code example:
+
+    $ sirin run --engine vm generated.sn
+
+where `generated.sn` declares 70000 variables. Split the program, or run it
+with the tree-walking interpreter, which has no such limits:
+
+    $ sirin run --engine tree generated.sn";
+
+const E0028: &str = "\
+A compiled `.snc` file applies an instruction to a value it does not work on,
+such as adding a string to an integer. The compiler never emits such code, so
+the file was edited or written by another tool. Loading a file checks its
+structure, but the kinds of values are only known as the program runs.
+
+Erroneous code example:
+
+    $ sirin run edited.snc
+
+where `edited.snc` pushes a string where `ADD` expects an integer. Compile
+the source again:
+
+    $ sirin compile program.sn";
+
+const W0001: &str = "\
+A `let` statement declares a variable whose name is already bound. The earlier
+value becomes unreachable, which is often a mistake. This is the `shadowing`
+lint.
+
+Example:
+
+    let total = 5
+    let total = 10
+
+Give the second binding a distinct name, or silence the lint for the file with
+`#allow(shadowing)` if the shadowing is intentional.";
+
+const W0002: &str = "\
+A pragma or a command line flag names a lint that does not exist, so it has no
+effect. This is the `unknown_lint` lint.
+
+Example:
+
+    #allow(shadowign)
+
+Check the spelling of the lint name:
+
+    #allow(shadowing)";
+
+const W0003: &str = "\
+A variable is declared but its value is never read. This is the
+`unused_variable` lint.
+
+Example:
+
+    let width = 5
+    let height = 3
+    height * 2
+
+If the variable is needed for its side effects only, or kept on purpose,
+prefix its name with an underscore to silence the lint:
+
+    let _width = 5";
+
+const W0004: &str = "\
+A value is stored into a variable and then overwritten, or shadowed by a new
+`let`, before anything reads it. The first computation is wasted. This is the
+`dead_store` lint.
+
+Example:
+
+    let total = 1
+    total = 2
+    total
+
+Remove the first store, or read the value before replacing it:
+
+    let total = 2
+    total";
+
+const W0005: &str = "\
+A `match` arm can never run, because the arms before it already match every
+value its pattern matches. Arms are tried in order. This is the
+`unreachable_pattern` lint.
+
+Example:
+
+    let n = 3
+    match n { _ => 0, 1 => 1 }
+
+Remove the arm, or move it before the arms that cover it:
+
+    let n = 3
+    match n { 1 => 1, _ => 0 }";
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::DiagnosticsCode;
+
+    #[test]
+    fn every_code_parses_back_to_itself() {
+        for code in DiagnosticsCode::ALL {
+            assert_eq!(code.code().parse::<DiagnosticsCode>(), Ok(*code));
+            assert_eq!(code.code().to_lowercase().parse::<DiagnosticsCode>(), Ok(*code));
+        }
+        assert!("E9999".parse::<DiagnosticsCode>().is_err());
+    }
+
+    #[test]
+    fn every_code_is_unique_and_documented() {
+        let mut seen = HashSet::new();
+        for code in DiagnosticsCode::ALL {
+            assert!(seen.insert(code.code()), "{} is listed twice", code);
+            assert!(!code.summary().is_empty(), "{} has no summary", code);
+            assert!(!code.explanation().trim().is_empty(), "{} has no explanation", code);
+        }
+    }
+}
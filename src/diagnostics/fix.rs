@@ -0,0 +1,74 @@
+use super::{Applicability, Diagnostics, Suggestion};
+
+// Applies every machine-applicable suggestion to `source`. Suggestions are
+// applied front to back and any one overlapping an already applied edit is
+// skipped. Returns the rewritten source and how many suggestions were applied.
+pub fn apply_suggestions(source: &str, diagnostics: &[Diagnostics]) -> (String, usize) {
+    let mut suggestions: Vec<&Suggestion> = diagnostics
+        .iter()
+        .flat_map(|diagnostic| &diagnostic.suggestions)
+        .filter(|suggestion| suggestion.applicability == Applicability::MachineApplicable)
+        .collect();
+    suggestions.sort_by_key(|suggestion| (suggestion.span.start, suggestion.span.end));
+
+    let mut result = String::with_capacity(source.len());
+    let mut cursor = 0;
+    let mut applied = 0;
+    for suggestion in suggestions {
+        if suggestion.span.start < cursor || suggestion.span.end > source.len() {
+            continue;
+        }
+        result.push_str(&source[cursor..suggestion.span.start]);
+        result.push_str(&suggestion.replacement);
+        cursor = suggestion.span.end;
+        applied += 1;
+    }
+    result.push_str(&source[cursor..]);
+    (result, applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::lexer::TextSpan,
+        diagnostics::{codes::DiagnosticsCode, Applicability, Diagnostics, DiagnosticsKind, Suggestion},
+    };
+
+    use super::apply_suggestions;
+
+    // A diagnostic offering one suggestion to replace `start..end`
+    fn suggesting(start: usize, end: usize, replacement: &str, applicability: Applicability) -> Diagnostics {
+        let span = TextSpan::new(start, end, String::new());
+        Diagnostics::new(DiagnosticsCode::UnusedVariable, String::new(), span.clone(), DiagnosticsKind::Warning)
+            .with_suggestion(Suggestion::new(String::new(), span, replacement.to_string(), applicability))
+    }
+
+    #[test]
+    fn applies_suggestions_front_to_back_whatever_their_order() {
+        let diagnostics = [
+            suggesting(8, 9, "y", Applicability::MachineApplicable),
+            suggesting(4, 4, "_", Applicability::MachineApplicable),
+            suggesting(0, 3, "var", Applicability::MachineApplicable),
+        ];
+        assert_eq!(apply_suggestions("let x = z", &diagnostics), ("var _x = y".to_string(), 3));
+    }
+
+    #[test]
+    fn skips_suggestions_overlapping_an_applied_edit() {
+        let diagnostics = [
+            suggesting(4, 9, "y + 1", Applicability::MachineApplicable),
+            suggesting(0, 5, "let z", Applicability::MachineApplicable),
+            suggesting(8, 9, "2", Applicability::MachineApplicable),
+        ];
+        assert_eq!(apply_suggestions("let x = 1", &diagnostics), ("let z = 2".to_string(), 2));
+    }
+
+    #[test]
+    fn skips_suggestions_needing_review_or_outside_the_source() {
+        let diagnostics = [
+            suggesting(4, 5, "_x", Applicability::MaybeIncorrect),
+            suggesting(8, 12, "2", Applicability::MachineApplicable),
+        ];
+        assert_eq!(apply_suggestions("let x = 1", &diagnostics), ("let x = 1".to_string(), 0));
+    }
+}
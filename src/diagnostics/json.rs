@@ -0,0 +1,87 @@
+use crate::{ast::lexer::TextSpan, json::JsonValue, text::SourceText};
+
+use super::{Diagnostics, Suggestion};
+
+// Emits one JSON object per diagnostic, one per line
+pub struct JsonDiagnosticsPrinter<'a> {
+    text: &'a SourceText,
+    diagnostics: &'a [Diagnostics],
+}
+
+impl<'a> JsonDiagnosticsPrinter<'a> {
+    pub fn new(text: &'a SourceText, diagnostics: &'a [Diagnostics]) -> Self {
+        Self { text, diagnostics }
+    }
+
+    pub fn stringify_diagnostic(&self, diagnostic: &Diagnostics) -> String {
+        let mut labels = vec![self.label(&diagnostic.span, diagnostic.label.as_deref(), true)];
+        for label in &diagnostic.secondary_labels {
+            labels.push(self.label(&label.span, Some(&label.message), false));
+        }
+        let strings = |values: &[String]| {
+            JsonValue::Array(values.iter().map(|value| JsonValue::string(value)).collect())
+        };
+
+        JsonValue::object(vec![
+            ("severity", JsonValue::string(&diagnostic.kind.to_string())),
+            ("code", JsonValue::string(diagnostic.code.code())),
+            ("message", JsonValue::string(&diagnostic.message)),
+            ("file", JsonValue::string(self.text.file_name())),
+            ("span", Self::byte_range(&diagnostic.span)),
+            ("range", self.line_range(&diagnostic.span)),
+            ("labels", JsonValue::Array(labels)),
+            ("notes", strings(&diagnostic.notes)),
+            ("help", strings(&diagnostic.help)),
+            (
+                "suggestions",
+                JsonValue::Array(diagnostic.suggestions.iter().map(|s| self.suggestion(s)).collect()),
+            ),
+        ])
+        .to_string()
+    }
+
+    fn label(&self, span: &TextSpan, message: Option<&str>, primary: bool) -> JsonValue {
+        JsonValue::object(vec![
+            ("primary", JsonValue::Bool(primary)),
+            ("message", message.map_or(JsonValue::Null, JsonValue::string)),
+            ("span", Self::byte_range(span)),
+            ("range", self.line_range(span)),
+        ])
+    }
+
+    fn suggestion(&self, suggestion: &Suggestion) -> JsonValue {
+        JsonValue::object(vec![
+            ("message", JsonValue::string(&suggestion.message)),
+            ("span", Self::byte_range(&suggestion.span)),
+            ("range", self.line_range(&suggestion.span)),
+            ("replacement", JsonValue::string(&suggestion.replacement)),
+            ("applicability", JsonValue::string(&suggestion.applicability.to_string())),
+        ])
+    }
+
+    fn byte_range(span: &TextSpan) -> JsonValue {
+        JsonValue::object(vec![
+            ("start", JsonValue::Number(span.start as i64)),
+            ("end", JsonValue::Number(span.end as i64)),
+        ])
+    }
+
+    // 1-based line/column range, the end being exclusive
+    fn line_range(&self, span: &TextSpan) -> JsonValue {
+        let position = |pos: usize| {
+            let (line, column) = self.text.line_column(pos);
+            JsonValue::object(vec![
+                ("line", JsonValue::Number(line as i64)),
+                ("column", JsonValue::Number(column as i64)),
+            ])
+        };
+        JsonValue::object(vec![("start", position(span.start)), ("end", position(span.end))])
+    }
+
+    // On stderr, so stdout keeps the result of the program
+    pub fn eprint(&self) {
+        for diagnostic in self.diagnostics {
+            eprintln!("{}", self.stringify_diagnostic(diagnostic))
+        }
+    }
+}
@@ -1,64 +1,726 @@
-use std::{cell::RefCell, rc::Rc};
+use std::{cell::RefCell, fmt::Display, rc::Rc};
 
-use crate::ast::lexer::{TextSpan, Token, TokenKind};
+use crate::{
+    ast::lexer::{TextSpan, Token, TokenKind},
+    lints::{Lint, LintConfig, LintLevel, LintSource},
+    runtime::{AccessError, Builtin, Value},
+    typeck::types::Type,
+};
 
+use self::codes::DiagnosticsCode;
+
+pub mod codes;
+pub mod fix;
+pub mod json;
 pub mod printer;
+pub mod sarif;
 
 pub enum DiagnosticsKind {
     Error,
     Warning,
 }
 
+impl Display for DiagnosticsKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DiagnosticsKind::Error => write!(f, "error"),
+            DiagnosticsKind::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+pub struct DiagnosticsLabel {
+    pub span: TextSpan,
+    pub message: String,
+}
+
+impl DiagnosticsLabel {
+    pub fn new(span: TextSpan, message: String) -> Self {
+        Self { span, message }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Applicability {
+    MachineApplicable, // Safe to apply without review, used by `sirin fix`
+    MaybeIncorrect,
+}
+
+impl Display for Applicability {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Applicability::MachineApplicable => write!(f, "machine-applicable"),
+            Applicability::MaybeIncorrect => write!(f, "maybe-incorrect"),
+        }
+    }
+}
+
+// Replace the text covered by `span` with `replacement`; an empty span is an insertion
+pub struct Suggestion {
+    pub message: String,
+    pub span: TextSpan,
+    pub replacement: String,
+    pub applicability: Applicability,
+}
+
+impl Suggestion {
+    pub fn new(message: String, span: TextSpan, replacement: String, applicability: Applicability) -> Self {
+        Self {
+            message,
+            span,
+            replacement,
+            applicability,
+        }
+    }
+}
+
 pub struct Diagnostics {
+    pub code: DiagnosticsCode,
     pub message: String,
     pub span: TextSpan,
     pub kind: DiagnosticsKind,
+    pub label: Option<String>, // Message shown under the primary span
+    pub secondary_labels: Vec<DiagnosticsLabel>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+    pub suggestions: Vec<Suggestion>,
 }
 
 impl Diagnostics {
-    pub fn new(message: String, span: TextSpan, kind: DiagnosticsKind) -> Self {
+    pub fn new(code: DiagnosticsCode, message: String, span: TextSpan, kind: DiagnosticsKind) -> Self {
         Self {
+            code,
             message,
             span,
             kind,
+            label: None,
+            secondary_labels: Vec::new(),
+            notes: Vec::new(),
+            help: Vec::new(),
+            suggestions: Vec::new(),
         }
     }
+
+    pub fn with_label(mut self, message: String) -> Self {
+        self.label = Some(message);
+        self
+    }
+
+    pub fn with_secondary_label(mut self, span: TextSpan, message: String) -> Self {
+        self.secondary_labels.push(DiagnosticsLabel::new(span, message));
+        self
+    }
+
+    pub fn with_note(mut self, note: String) -> Self {
+        self.notes.push(note);
+        self
+    }
+
+    pub fn with_help(mut self, help: String) -> Self {
+        self.help.push(help);
+        self
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Suggestion) -> Self {
+        self.suggestions.push(suggestion);
+        self
+    }
 }
 
 pub type DiagnosticsBagCell = Rc<RefCell<DiagnosticsBag>>;
 
 pub struct DiagnosticsBag {
     pub diagnostics: Vec<Diagnostics>,
+    pub lints: LintConfig,
 }
 
 impl DiagnosticsBag {
-    pub fn new() -> Self {
+    pub fn new(lints: LintConfig) -> Self {
         Self {
             diagnostics: vec![],
+            lints,
         }
     }
 
-    pub fn report_error(&mut self, message: String, span: TextSpan) {
-        let error = Diagnostics::new(message, span, DiagnosticsKind::Error);
+    pub fn has_errors(&self) -> bool {
+        self.diagnostics
+            .iter()
+            .any(|diagnostic| matches!(diagnostic.kind, DiagnosticsKind::Error))
+    }
+
+    pub fn report(&mut self, diagnostic: Diagnostics) {
+        self.diagnostics.push(diagnostic)
+    }
+
+    pub fn report_error(&mut self, code: DiagnosticsCode, message: String, span: TextSpan) {
+        let error = Diagnostics::new(code, message, span, DiagnosticsKind::Error);
         self.diagnostics.push(error)
     }
 
-    pub fn report_warning(&mut self, message: String, span: TextSpan) {
-        let warn = Diagnostics::new(message, span, DiagnosticsKind::Warning);
-        self.diagnostics.push(warn)
+    // Reports `diagnostic` at the level configured for `lint`, or drops it
+    // when the lint is allowed
+    pub fn report_lint(&mut self, lint: Lint, mut diagnostic: Diagnostics) {
+        let (level, source) = self.lints.level(lint);
+        diagnostic.kind = match level {
+            LintLevel::Allow => return,
+            LintLevel::Warn => DiagnosticsKind::Warning,
+            LintLevel::Deny => DiagnosticsKind::Error,
+        };
+        match source {
+            LintSource::Default => {
+                diagnostic = diagnostic.with_note(format!("`#{}({})` on by default", level.name(), lint))
+            }
+            LintSource::DenyWarnings => {
+                diagnostic = diagnostic.with_note(format!("`#deny({})` implied by `#deny(warnings)`", lint))
+            }
+            LintSource::CommandLine | LintSource::Pragma => {}
+        }
+        self.report(diagnostic)
+    }
+
+    pub fn report_malformed_pragma(&mut self, pragma: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::MalformedPragma,
+                format!("Malformed pragma `{}`", pragma.span.literal.trim_end()),
+                pragma.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_help("pragmas are written `#allow(lint)`, `#warn(lint)` or `#deny(lint)`".to_string()),
+        )
+    }
+
+    pub fn report_unknown_lint(&mut self, name: &str, pragma: &Token) {
+        self.report_lint(
+            Lint::UnknownLint,
+            Diagnostics::new(
+                Lint::UnknownLint.code(),
+                format!("Unknown lint `{}`", name),
+                pragma.span.clone(),
+                DiagnosticsKind::Warning,
+            ),
+        )
+    }
+
+    pub fn report_expected_type(&mut self, token: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::ExpectedType,
+                format!("Expected -> <Type> | Found -> <{}>", token.kind),
+                token.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("expected a type here".to_string()),
+        )
+    }
+
+    pub fn report_unknown_type(&mut self, name: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnknownType,
+                format!("Cannot find type `{}`", name.span.literal),
+                name.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("not a known type".to_string())
+            .with_note("the primitive types are `int`, `float`, `bool` and `string`, other types are declared with `struct`".to_string()),
+        )
+    }
+
+    pub fn report_undeclared_variable(&mut self, name: &str, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UndeclaredVariable,
+                format!("Cannot find variable `{}` in this scope", name),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("not declared before this point".to_string()),
+        )
+    }
+
+    // `expected_due_to` points at what imposed the expected type, if anything
+    pub fn report_mismatched_types(
+        &mut self,
+        expected: &Type,
+        found: &Type,
+        span: &TextSpan,
+        expected_due_to: Option<&TextSpan>,
+    ) {
+        let mut diagnostic = Diagnostics::new(
+            DiagnosticsCode::MismatchedTypes,
+            format!("Mismatched types: Expected -> <{}> | Found -> <{}>", expected, found),
+            span.clone(),
+            DiagnosticsKind::Error,
+        )
+        .with_label(format!("expected `{}`, found `{}`", expected, found));
+        if let Some(expected_due_to) = expected_due_to {
+            diagnostic = diagnostic.with_secondary_label(expected_due_to.clone(), "expected due to this".to_string());
+        }
+        self.report(diagnostic)
+    }
+
+    // `offending` is the operand whose type the operator does not accept
+    pub fn report_invalid_operands(
+        &mut self,
+        operator: &Token,
+        left: &Type,
+        right: &Type,
+        offending: &TextSpan,
+        offending_type: &Type,
+    ) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::InvalidOperands,
+                format!("Cannot apply `{}` to <{}> and <{}>", operator.kind, left, right),
+                offending.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("this is `{}`", offending_type))
+            .with_secondary_label(operator.span.clone(), format!("`{}` expects `int` operands", operator.kind)),
+        )
+    }
+
+    // `divisor` is known when the error is found before running the program
+    pub fn report_division_by_zero(&mut self, span: &TextSpan, divisor: Option<&TextSpan>) {
+        let mut diagnostic = Diagnostics::new(
+            DiagnosticsCode::DivisionByZero,
+            "This operation would divide by zero".to_string(),
+            span.clone(),
+            DiagnosticsKind::Error,
+        )
+        .with_label("attempt to divide by zero".to_string());
+        if let Some(divisor) = divisor {
+            diagnostic = diagnostic.with_secondary_label(divisor.clone(), "this is always `0`".to_string());
+        }
+        self.report(diagnostic)
+    }
+
+    pub fn report_arithmetic_overflow(&mut self, operator: &str, left: i64, right: i64, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::ArithmeticOverflow,
+                "This arithmetic operation would overflow".to_string(),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("attempt to compute `{} {} {}`, which would overflow", left, operator, right)),
+        )
+    }
+
+    pub fn report_unknown_function(&mut self, callee: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnknownFunction,
+                format!("Cannot find function `{}`", callee.span.literal),
+                callee.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("not a known function".to_string())
+            .with_note("the built-in functions are `len`, `push`, `pop`, `keys`, `values` and `has`".to_string()),
+        )
+    }
+
+    pub fn report_argument_count_mismatch(&mut self, builtin: Builtin, found: usize, span: &TextSpan) {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let expected = builtin.arity();
+        let verb = if found == 1 { "was" } else { "were" };
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::ArgumentCountMismatch,
+                format!(
+                    "Function `{}` takes {} argument{} but {} {} supplied",
+                    builtin,
+                    expected,
+                    plural(expected),
+                    found,
+                    verb
+                ),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("expected {} argument{}, found {}", expected, plural(expected), found)),
+        )
+    }
+
+    // `span` is the index or key, the slice range or the list `pop` is
+    // called on
+    pub fn report_access_error(&mut self, error: &AccessError, span: &TextSpan) {
+        let (code, message, label) = match error {
+            AccessError::Index { index, length } => (
+                DiagnosticsCode::IndexOutOfBounds,
+                "Index out of bounds".to_string(),
+                format!("the length is {} but the index is {}", length, index),
+            ),
+            AccessError::Slice { start, end, length } => (
+                DiagnosticsCode::IndexOutOfBounds,
+                "Slice out of bounds".to_string(),
+                format!("the length is {} but the range is {}..{}", length, start, end),
+            ),
+            AccessError::Empty => (
+                DiagnosticsCode::IndexOutOfBounds,
+                "Cannot pop from an empty list".to_string(),
+                "this list is empty".to_string(),
+            ),
+            AccessError::Length { expected, length } => (
+                DiagnosticsCode::IndexOutOfBounds,
+                format!("Expected a list of length {}", expected),
+                format!("this pattern needs {} elements but the list has {}", expected, length),
+            ),
+            AccessError::MissingKey(key) => (
+                DiagnosticsCode::KeyNotFound,
+                format!("Key {} not found", key),
+                "the map has no entry for this key".to_string(),
+            ),
+        };
+        let mut diagnostic = Diagnostics::new(code, message, span.clone(), DiagnosticsKind::Error).with_label(label);
+        if let AccessError::MissingKey(_) = error {
+            diagnostic = diagnostic.with_help("check for the key with `has` first".to_string());
+        }
+        self.report(diagnostic)
+    }
+
+    // `fields` are those declared by `ty`, when it is a struct
+    pub fn report_unknown_field(&mut self, ty: &Type, field: &Token, fields: &[String]) {
+        let note = match ty {
+            Type::Struct(name) if fields.is_empty() => format!("`{}` has no fields", name),
+            Type::Struct(name) => format!("the fields of `{}` are {}", name, Self::list(fields)),
+            Type::Var(_) => "the type of this value is not known here; add a type annotation".to_string(),
+            _ => "only structs have fields".to_string(),
+        };
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnknownField,
+                format!("No field `{}` on type <{}>", field.span.literal, ty),
+                field.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("unknown field".to_string())
+            .with_note(note),
+        )
+    }
+
+    // `pattern` tells whether the fields are missing from a pattern rather
+    // than a struct literal
+    pub fn report_missing_fields(&mut self, name: &Token, missing: &[String], pattern: bool) {
+        let plural = if missing.len() == 1 { "" } else { "s" };
+        let what = if pattern { "pattern" } else { "initializer" };
+        let mut diagnostic = Diagnostics::new(
+            DiagnosticsCode::MissingFields,
+            format!("Missing field{} {} in {} of `{}`", plural, Self::list(missing), what, name.span.literal),
+            name.span.clone(),
+            DiagnosticsKind::Error,
+        )
+        .with_label(format!("missing {}", Self::list(missing)));
+        if pattern {
+            diagnostic = diagnostic.with_help("end the pattern with `..` to skip the fields it does not match".to_string());
+        }
+        self.report(diagnostic)
+    }
+
+    // `what` describes the name, as in "field", and `how` what was done to it
+    // twice, as in "declared"
+    pub fn report_duplicate_name(&mut self, what: &str, how: &str, name: &Token, previous: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::DuplicateName,
+                format!("The {} `{}` is {} more than once", what, name.span.literal, how),
+                name.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("{} again here", how))
+            .with_secondary_label(previous.clone(), format!("first {} here", how)),
+        )
+    }
+
+    pub fn report_unknown_variant(&mut self, name: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnknownVariant,
+                format!("Cannot find variant `{}`", name.span.literal),
+                name.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("not a variant of any enum".to_string()),
+        )
+    }
+
+    // `pattern` tells whether the variant is matched rather than constructed
+    pub fn report_payload_count_mismatch(&mut self, variant: &Token, expected: usize, found: usize, pattern: bool) {
+        let plural = |count: usize| if count == 1 { "" } else { "s" };
+        let message = if pattern {
+            format!(
+                "Variant `{}` holds {} value{} but the pattern matches {}",
+                variant.span.literal,
+                expected,
+                plural(expected),
+                found
+            )
+        } else {
+            format!(
+                "Variant `{}` takes {} value{} but {} {} supplied",
+                variant.span.literal,
+                expected,
+                plural(expected),
+                found,
+                if found == 1 { "was" } else { "were" }
+            )
+        };
+        self.report(
+            Diagnostics::new(DiagnosticsCode::PayloadCountMismatch, message, variant.span.clone(), DiagnosticsKind::Error)
+                .with_label(format!("expected {} value{}, found {}", expected, plural(expected), found)),
+        )
+    }
+
+    // `missing` are patterns for values no arm matches, at most three shown
+    pub fn report_non_exhaustive_match(&mut self, ty: &Type, missing: &[String], scrutinee: &TextSpan) {
+        let patterns = Self::patterns(missing);
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::NonExhaustiveMatch,
+                format!("Non-exhaustive patterns: {} not covered", patterns),
+                scrutinee.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("{} not covered", patterns))
+            .with_note(format!("the matched value is of type <{}>", ty))
+            .with_help("add an arm for each missing pattern, or a wildcard `_` arm".to_string()),
+        )
+    }
+
+    // `missing` are patterns for values the pattern of a `let` does not
+    // match, at most three shown
+    pub fn report_refutable_pattern(&mut self, ty: &Type, missing: &[String], pattern: &TextSpan) {
+        let patterns = Self::patterns(missing);
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::RefutablePattern,
+                format!("Refutable pattern in `let`: {} not covered", patterns),
+                pattern.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("{} not covered", patterns))
+            .with_note(format!("the bound value is of type <{}>", ty))
+            .with_help("use a `match` to handle the values this pattern does not match".to_string()),
+        )
+    }
+
+    pub fn report_not_iterable(&mut self, ty: &Type, iterable: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::NotIterable,
+                format!("Cannot iterate over a value of type <{}>", ty),
+                iterable.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("not iterable".to_string())
+            .with_help("a `for` loop iterates over ranges, lists, strings and maps".to_string()),
+        )
+    }
+
+    // `pattern `a``, `patterns `a` and `b``, or the first three and how many more
+    fn patterns(missing: &[String]) -> String {
+        let shown: Vec<_> = missing.iter().take(3).map(|pattern| format!("`{}`", pattern)).collect();
+        match (shown.split_last(), missing.len() - shown.len()) {
+            (Some((last, [])), 0) => format!("pattern {}", last),
+            (Some((last, rest)), 0) => format!("patterns {} and {}", rest.join(", "), last),
+            (_, more) => format!("patterns {} and {} more", shown.join(", "), more),
+        }
+    }
+
+    // `a`, `a` and `b`, `a`, `b` and `c`
+    fn list(names: &[String]) -> String {
+        let names: Vec<_> = names.iter().map(|name| format!("`{}`", name)).collect();
+        match names.split_last() {
+            Some((last, [])) => last.clone(),
+            Some((last, rest)) => format!("{} and {}", rest.join(", "), last),
+            None => String::new(),
+        }
+    }
+
+    pub fn report_invalid_map_key(&mut self, key: &Type, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::InvalidMapKey,
+                format!("Type <{}> cannot be used as a map key", key),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("this key".to_string())
+            .with_note("map keys must be of type `int` or `string`".to_string()),
+        )
+    }
+
+    // `feature` names what the backend lacks, as in "lists"
+    pub fn report_unsupported_by_backend(&mut self, backend: &str, feature: &str, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnsupportedByBackend,
+                format!("The {} backend does not support {}", backend, feature),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("this needs {}", feature))
+            .with_help("run the program with `sirin run` instead".to_string()),
+        )
+    }
+
+    // `what` names what there are too many of, as in "local slots"
+    pub fn report_program_too_large(&mut self, what: &str, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::ProgramTooLarge,
+                format!("Program too large for bytecode: more than {} {}", u16::MAX, what),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("this needs more than {} {}", u16::MAX, what))
+            .with_help("run the program with `--engine tree` instead".to_string()),
+        )
+    }
+
+    // `instruction` met a value other than the `expected` kind, which only a
+    // hand-edited bytecode file can cause
+    pub fn report_invalid_bytecode(&mut self, instruction: &str, expected: &str, found: &Value, span: &TextSpan) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::InvalidBytecode,
+                format!("Invalid bytecode: `{}` expects {}, found `{}`", instruction, expected, found),
+                span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("`{}` was compiled from this", instruction))
+            .with_help("compile the source again with `sirin compile`".to_string()),
+        )
+    }
+
+    // Renaming only the declaration is safe as long as nothing assigns to it
+    // and the new name is not `taken` by another binding or use
+    // A `shorthand` binding keeps its field name, as in `Point { x: _x }`
+    pub fn report_unused_variable(&mut self, identifier: &Token, assigned: bool, shorthand: bool, taken: bool) {
+        let name = &identifier.span.literal;
+        let message = match assigned {
+            true => format!("Variable `{}` is assigned to, but never read", name),
+            false => format!("Unused variable `{}`", name),
+        };
+        let applicability = match assigned || taken {
+            true => Applicability::MaybeIncorrect,
+            false => Applicability::MachineApplicable,
+        };
+        self.report_lint(
+            Lint::UnusedVariable,
+            Diagnostics::new(
+                Lint::UnusedVariable.code(),
+                message,
+                identifier.span.clone(),
+                DiagnosticsKind::Warning,
+            )
+            .with_suggestion(Suggestion::new(
+                "if this is intentional, prefix it with an underscore".to_string(),
+                identifier.span.clone(),
+                match shorthand {
+                    true => format!("{}: _{}", name, name),
+                    false => format!("_{}", name),
+                },
+                applicability,
+            )),
+        )
+    }
+
+    pub fn report_dead_store(&mut self, identifier: &Token, store: &TextSpan, overwritten_by: &TextSpan) {
+        self.report_lint(
+            Lint::DeadStore,
+            Diagnostics::new(
+                Lint::DeadStore.code(),
+                format!("Value assigned to `{}` is never read", identifier.span.literal),
+                store.clone(),
+                DiagnosticsKind::Warning,
+            )
+            .with_secondary_label(overwritten_by.clone(), "overwritten here before being read".to_string()),
+        )
+    }
+
+    pub fn report_shadowed_variable(&mut self, identifier: &Token, previous: &TextSpan) {
+        self.report_lint(
+            Lint::Shadowing,
+            Diagnostics::new(
+                Lint::Shadowing.code(),
+                format!("Variable `{}` shadows an earlier binding", identifier.span.literal),
+                identifier.span.clone(),
+                DiagnosticsKind::Warning,
+            )
+            .with_label(format!("`{}` is declared again here", identifier.span.literal))
+            .with_secondary_label(previous.clone(), "previous binding here".to_string()),
+        )
+    }
+
+    pub fn report_unreachable_pattern(&mut self, pattern: &TextSpan) {
+        self.report_lint(
+            Lint::UnreachablePattern,
+            Diagnostics::new(
+                Lint::UnreachablePattern.code(),
+                "Unreachable pattern".to_string(),
+                pattern.clone(),
+                DiagnosticsKind::Warning,
+            )
+            .with_label("the arms above already match every value this pattern matches".to_string()),
+        )
     }
 
     pub fn report_unexpected_token(&mut self, expected: &TokenKind, token: &Token) {
         self.report_error(
+            DiagnosticsCode::UnexpectedToken,
             format!("Expected -> <{}> | Found -> <{}>", expected, token.kind),
             token.span.clone(),
         )
     }
 
     pub fn report_expected_expression(&mut self, token: &Token) {
-        self.report_error(
+        let mut diagnostic = Diagnostics::new(
+            DiagnosticsCode::ExpectedExpression,
             format!("Expected -> <Expression> | Found -> <{}>", token.kind),
             token.span.clone(),
+            DiagnosticsKind::Error,
+        )
+        .with_label("expected an expression here".to_string())
+        .with_note("an expression is a number, a variable, a list, a call or a parenthesized expression".to_string());
+        if token.kind == TokenKind::Eof {
+            diagnostic = diagnostic.with_help("the file ended before the expression was complete".to_string());
+        }
+        self.report(diagnostic)
+    }
+
+    pub fn report_expected_pattern(&mut self, token: &Token) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::ExpectedPattern,
+                format!("Expected -> <Pattern> | Found -> <{}>", token.kind),
+                token.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label("expected a pattern here".to_string())
+            .with_note("a pattern is `_`, a variable, a number, a string or a variant such as `Some(x)`".to_string()),
+        )
+    }
+
+    // `insert_at` is where the closing delimiter belongs, right after the
+    // last token of the enclosed expression
+    pub fn report_unclosed_delimiter(&mut self, open: &Token, close: &TokenKind, found: &Token, insert_at: usize) {
+        self.report(
+            Diagnostics::new(
+                DiagnosticsCode::UnclosedDelimiter,
+                format!("Expected -> <{}> | Found -> <{}>", close, found.kind),
+                found.span.clone(),
+                DiagnosticsKind::Error,
+            )
+            .with_label(format!("expected `{}` here", close))
+            .with_secondary_label(open.span.clone(), format!("unclosed `{}` opened here", open.kind))
+            .with_suggestion(Suggestion::new(
+                format!("close the `{}` with a matching `{}`", open.kind, close),
+                TextSpan::new(insert_at, insert_at, String::new()),
+                close.to_string(),
+                Applicability::MachineApplicable,
+            )),
         )
     }
 }
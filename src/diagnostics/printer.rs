@@ -1,12 +1,29 @@
-use std::cmp;
+use std::collections::BTreeSet;
 
-use termion::color::{Fg, Red, Reset};
+use termion::{
+    color::{self, Fg},
+    style,
+};
 
-use crate::text::SourceText;
+use crate::{ast::lexer::TextSpan, text::SourceText};
 
-use super::Diagnostics;
+use super::{codes::DiagnosticsCode, Diagnostics, DiagnosticsKind, Suggestion};
 
-const PREFIX_LENGTH: usize = 8;
+// Lines of a multi-line span kept on each side before the middle is elided
+const CONTEXT_LINES: usize = 2;
+
+struct Annotation<'a> {
+    start: (usize, usize), // (line index, column) of the first character
+    end: (usize, usize),   // (line index, column) of the last character
+    message: Option<&'a str>,
+    primary: bool,
+}
+
+impl Annotation<'_> {
+    fn is_multiline(&self) -> bool {
+        self.start.0 != self.end.0
+    }
+}
 
 pub struct DiagnosticsPrinter<'a> {
     text: &'a SourceText,
@@ -14,47 +31,487 @@ pub struct DiagnosticsPrinter<'a> {
 }
 
 impl <'a> DiagnosticsPrinter<'a> {
+    const GUTTER_COLOR: color::LightBlue = color::LightBlue;
+
     pub fn new(text: &'a SourceText, diagnostics: &'a [Diagnostics]) -> Self {
         Self { text, diagnostics }
     }
 
+    /// Renders a diagnostic the way rustc does:
+    ///
+    /// ```text
+    /// error[E0003]: Expected -> <)> | Found -> <Eof>
+    ///  --> examples/main.sn:4:20
+    ///   |
+    /// 4 | let d = (10 * c / 5
+    ///   |         - unclosed `(` opened here
+    ///   |                    ^ expected `)` here
+    /// help: close the `(` with a matching `)`
+    ///   |
+    /// 4 | let d = (10 * c / 5)
+    ///   |                    +
+    /// ```
     pub fn stringify_diagnostic(&self, diagnostic: &Diagnostics) -> String {
-        let line_index = self.text.line_index(diagnostic.span.start);
-        let line = self.text.get_line(line_index);
-        let line_start = self.text.line_start(line_index);
+        let annotations = self.annotations(diagnostic);
+        let lines = Self::lines_to_display(&annotations);
+        let last_line = lines.iter().next_back().copied().unwrap_or(0);
+        let gutter_width = (last_line + 1).to_string().len();
+        let (line, column) = self.text.line_column(diagnostic.span.start);
 
-        let col = diagnostic.span.start - line_start;
-        let (prefix, span, suffix) = self.get_text_span(diagnostic, line, col);
+        let mut result = String::new();
+        result.push_str(&Self::format_header(diagnostic));
+        result.push_str(&format!(
+            "{:width$}{}-->{} {}:{}:{}\n",
+            "",
+            Fg(Self::GUTTER_COLOR),
+            Fg(color::Reset),
+            self.text.file_name(),
+            line,
+            column,
+            width = gutter_width
+        ));
+        result.push_str(&Self::format_gutter("", gutter_width));
+        result.push('\n');
+        result.push_str(&self.format_snippet(diagnostic, &annotations, &lines, gutter_width));
+
+        if !diagnostic.notes.is_empty() || !diagnostic.help.is_empty() {
+            result.push_str(&Self::format_gutter("", gutter_width));
+            result.push('\n');
+        }
+        for note in &diagnostic.notes {
+            result.push_str(&Self::format_footer("note", note, gutter_width));
+        }
+        for help in &diagnostic.help {
+            result.push_str(&Self::format_footer("help", help, gutter_width));
+        }
+        for suggestion in &diagnostic.suggestions {
+            result.push_str(&self.format_suggestion(suggestion, gutter_width));
+        }
+        result.truncate(result.trim_end().len());
+        result
+    }
+
+    fn annotations<'d>(&self, diagnostic: &'d Diagnostics) -> Vec<Annotation<'d>> {
+        let mut annotations = vec![self.annotation(
+            &diagnostic.span,
+            diagnostic.label.as_deref(),
+            true,
+        )];
+        for label in &diagnostic.secondary_labels {
+            annotations.push(self.annotation(&label.span, Some(&label.message), false));
+        }
+        annotations
+    }
+
+    fn annotation<'d>(&self, span: &TextSpan, message: Option<&'d str>, primary: bool) -> Annotation<'d> {
+        // Start of the last character, which may take several bytes
+        let mut last = if span.length() > 0 { span.end - 1 } else { span.start };
+        while !self.text.text().is_char_boundary(last) {
+            last -= 1;
+        }
+        Annotation {
+            start: self.position(span.start),
+            end: self.position(last),
+            message,
+            primary,
+        }
+    }
 
-        let indent = cmp::min(PREFIX_LENGTH, col);
-        let (arrow_pointers, arrow_line) = Self::format_arrow(diagnostic, indent);
-        let error_message = format!("{:indent$}+-- {}", "", diagnostic.message, indent = indent);
-        format!("{}{}{}{}{}\n{}\n{}\n{}", prefix, Fg(Red), span, Fg(Reset), suffix, arrow_pointers, arrow_line, error_message)
+    fn position(&self, pos: usize) -> (usize, usize) {
+        let (line, column) = self.text.line_column(pos);
+        (line - 1, column - 1)
     }
 
-    fn format_arrow(diagnostic: &Diagnostics, indent: usize) -> (String, String) {
-        let arrow_pointers = format!("{:indent$}{}", "", std::iter::repeat('^').take(diagnostic.span.length()).collect::<String>(), indent = indent);
-        let arrow_line = format!("{:indent$}|", "", indent = indent);
-        (arrow_pointers, arrow_line)
+    fn lines_to_display(annotations: &[Annotation]) -> BTreeSet<usize> {
+        let mut lines = BTreeSet::new();
+        for annotation in annotations {
+            let (start, end) = (annotation.start.0, annotation.end.0);
+            if end - start <= CONTEXT_LINES * 2 + 1 {
+                lines.extend(start..=end);
+            } else {
+                lines.extend(start..start + CONTEXT_LINES);
+                lines.extend(end + 1 - CONTEXT_LINES..=end);
+            }
+        }
+        lines
     }
 
-    fn get_text_span(&'a self, diagnostic: &Diagnostics, line: &'a str, column: usize) -> (&'a str, &'a str, &'a str) {
-        let prefix_start = cmp::max(0, column as isize - PREFIX_LENGTH as isize) as usize;
-        let prefix_end = column;
-        let prefix = &line[prefix_start..prefix_end];
+    fn format_snippet(
+        &self,
+        diagnostic: &Diagnostics,
+        annotations: &[Annotation],
+        lines: &BTreeSet<usize>,
+        gutter_width: usize,
+    ) -> String {
+        // Every multi-line annotation owns one column of the left margin, where
+        // its vertical connector bar is drawn while the span is open.
+        let multiline: Vec<&Annotation> = annotations.iter().filter(|a| a.is_multiline()).collect();
+        let mut open = vec![false; multiline.len()];
+
+        let mut result = String::new();
+        let mut previous: Option<usize> = None;
+        for &line_index in lines {
+            if previous.is_some_and(|previous| line_index > previous + 1) {
+                result.push_str(&format!(
+                    "{}{:>width$}{} {}\n",
+                    Fg(Self::GUTTER_COLOR),
+                    "...",
+                    Fg(color::Reset),
+                    self.format_margin(diagnostic, &multiline, &open, None),
+                    width = gutter_width + 2
+                ));
+            }
+            previous = Some(line_index);
+
+            result.push_str(&Self::format_gutter(&(line_index + 1).to_string(), gutter_width));
+            result.push_str(&format!(
+                " {}{}\n",
+                self.format_margin(diagnostic, &multiline, &open, None),
+                self.text.get_line(line_index)
+            ));
 
-        let suffix_start = cmp::min(column + diagnostic.span.length(), line.len());
-        let suffix_end = cmp::min(suffix_start + PREFIX_LENGTH, line.len());
-        let suffix = &line[suffix_start..suffix_end];
+            for (slot, annotation) in multiline.iter().enumerate() {
+                if annotation.start.0 != line_index {
+                    continue;
+                }
+                let color = Self::annotation_color(diagnostic, annotation);
+                result.push_str(&Self::format_gutter("", gutter_width));
+                result.push_str(&format!(
+                    " {}{}{}{}{}\n",
+                    self.format_margin(diagnostic, &multiline, &open, Some((slot, ' '))),
+                    color,
+                    "_".repeat(annotation.start.1),
+                    Self::marker(annotation),
+                    Fg(color::Reset)
+                ));
+                open[slot] = true;
+            }
 
-        let span = &line[prefix_end..suffix_start];
+            let mut single_line: Vec<&Annotation> = annotations
+                .iter()
+                .filter(|a| !a.is_multiline() && a.start.0 == line_index)
+                .collect();
+            single_line.sort_by_key(|a| a.start.1);
+            for annotation in single_line {
+                let length = annotation.end.1 - annotation.start.1 + 1;
+                result.push_str(&Self::format_gutter("", gutter_width));
+                result.push_str(&format!(
+                    " {}{:indent$}{}{}{}{}\n",
+                    self.format_margin(diagnostic, &multiline, &open, None),
+                    "",
+                    Self::annotation_color(diagnostic, annotation),
+                    Self::marker(annotation).repeat(length),
+                    Self::format_label(annotation),
+                    Fg(color::Reset),
+                    indent = annotation.start.1
+                ));
+            }
 
-        (prefix, span, suffix)
+            for (slot, annotation) in multiline.iter().enumerate() {
+                if annotation.end.0 != line_index {
+                    continue;
+                }
+                let color = Self::annotation_color(diagnostic, annotation);
+                result.push_str(&Self::format_gutter("", gutter_width));
+                result.push_str(&format!(
+                    " {}{}{}{}{}{}\n",
+                    self.format_margin(diagnostic, &multiline, &open, Some((slot, '|'))),
+                    color,
+                    "_".repeat(annotation.end.1),
+                    Self::marker(annotation),
+                    Self::format_label(annotation),
+                    Fg(color::Reset)
+                ));
+                open[slot] = false;
+            }
+        }
+        result
+    }
+
+    // Draws the connector bars of the open multi-line annotations. When a
+    // connector for `slot` is being drawn, its own column shows `first` and
+    // every column to its right becomes part of the horizontal `_` line.
+    fn format_margin(
+        &self,
+        diagnostic: &Diagnostics,
+        multiline: &[&Annotation],
+        open: &[bool],
+        connector: Option<(usize, char)>,
+    ) -> String {
+        if multiline.is_empty() {
+            return String::new();
+        }
+        let mut margin = String::new();
+        for (slot, annotation) in multiline.iter().enumerate() {
+            let color = Self::annotation_color(diagnostic, annotation);
+            let c = match connector {
+                Some((connector_slot, first)) if connector_slot == slot => first,
+                Some((connector_slot, _)) if slot > connector_slot => '_',
+                _ if open[slot] => '|',
+                _ => ' ',
+            };
+            margin.push_str(&format!("{}{}{}", color, c, Fg(color::Reset)));
+        }
+        match connector {
+            Some((slot, _)) => {
+                let color = Self::annotation_color(diagnostic, multiline[slot]);
+                margin.push_str(&format!("{}_{}", color, Fg(color::Reset)))
+            }
+            None => margin.push(' '),
+        }
+        margin
+    }
+
+    fn marker(annotation: &Annotation) -> &'static str {
+        if annotation.primary {
+            "^"
+        } else {
+            "-"
+        }
+    }
+
+    fn format_label(annotation: &Annotation) -> String {
+        match annotation.message {
+            Some(message) => format!(" {}", message),
+            None => String::new(),
+        }
+    }
+
+    fn annotation_color(diagnostic: &Diagnostics, annotation: &Annotation) -> String {
+        if annotation.primary {
+            Self::severity_color(&diagnostic.kind)
+        } else {
+            Fg(Self::GUTTER_COLOR).to_string()
+        }
+    }
+
+    fn severity_color(kind: &DiagnosticsKind) -> String {
+        match kind {
+            DiagnosticsKind::Error => Fg(color::LightRed).to_string(),
+            DiagnosticsKind::Warning => Fg(color::LightYellow).to_string(),
+        }
+    }
+
+    fn format_header(diagnostic: &Diagnostics) -> String {
+        format!(
+            "{}{}{}[{}]:{} {}{}\n",
+            style::Bold,
+            Self::severity_color(&diagnostic.kind),
+            diagnostic.kind,
+            diagnostic.code,
+            Fg(color::Reset),
+            diagnostic.message,
+            style::Reset
+        )
+    }
+
+    fn format_gutter(line_number: &str, width: usize) -> String {
+        format!(
+            "{}{:>width$} |{}",
+            Fg(Self::GUTTER_COLOR),
+            line_number,
+            Fg(color::Reset),
+            width = width
+        )
+    }
+
+    fn format_footer(title: &str, message: &str, width: usize) -> String {
+        format!(
+            "{:width$} {}={} {}{}:{} {}\n",
+            "",
+            Fg(Self::GUTTER_COLOR),
+            Fg(color::Reset),
+            style::Bold,
+            title,
+            style::Reset,
+            message,
+            width = width
+        )
+    }
+
+    // Previews the line as it would read once the suggestion is applied.
+    // Suggestions spanning several lines only show their message.
+    fn format_suggestion(&self, suggestion: &Suggestion, gutter_width: usize) -> String {
+        let mut result = format!(
+            "{}{}help:{} {}{}\n",
+            style::Bold,
+            Fg(color::LightCyan),
+            Fg(color::Reset),
+            suggestion.message,
+            style::Reset
+        );
+        let line_index = self.text.line_index(suggestion.span.start);
+        let line = self.text.get_line(line_index);
+        let line_start = self.text.line_start(line_index);
+        let start = suggestion.span.start - line_start;
+        let end = suggestion.span.end - line_start;
+        if end > line.len() || suggestion.replacement.contains('\n') {
+            return result;
+        }
+
+        let line_number = (line_index + 1).to_string();
+        let gutter_width = gutter_width.max(line_number.len());
+        let marker = if suggestion.span.length() == 0 {
+            '+'
+        } else if suggestion.replacement.is_empty() {
+            '-'
+        } else {
+            '~'
+        };
+        // Markers and their indent are counted in characters, as displayed
+        let marked = if suggestion.replacement.is_empty() {
+            line[start..end].chars().count()
+        } else {
+            suggestion.replacement.chars().count()
+        };
+        let patched = format!("{}{}{}", &line[..start], suggestion.replacement, &line[end..]);
+        let shown = if suggestion.replacement.is_empty() { line } else { patched.as_str() };
+
+        result.push_str(&Self::format_gutter("", gutter_width));
+        result.push('\n');
+        result.push_str(&Self::format_gutter(&line_number, gutter_width));
+        result.push_str(&format!(" {}\n", shown));
+        result.push_str(&Self::format_gutter("", gutter_width));
+        result.push_str(&format!(
+            " {:indent$}{}{}{}\n",
+            "",
+            Fg(color::LightGreen),
+            marker.to_string().repeat(marked),
+            Fg(color::Reset),
+            indent = line[..start].chars().count()
+        ));
+        result
     }
 
     pub fn print(&self) {
+        let mut codes: Vec<DiagnosticsCode> = Vec::new();
         for diagnostic in self.diagnostics {
-            println!("{}", self.stringify_diagnostic(diagnostic))
+            println!("{}\n", self.stringify_diagnostic(diagnostic));
+            if matches!(diagnostic.kind, DiagnosticsKind::Error) && !codes.contains(&diagnostic.code) {
+                codes.push(diagnostic.code);
+            }
         }
+        match codes.as_slice() {
+            [] => {}
+            [code] => println!(
+                "{}For more information about this error, try `sirin explain {}`.{}",
+                style::Bold,
+                code,
+                style::Reset
+            ),
+            codes => println!(
+                "{}Some errors have detailed explanations: {}.\nFor more information about an error, try `sirin explain {}`.{}",
+                style::Bold,
+                codes.iter().map(|code| code.code()).collect::<Vec<_>>().join(", "),
+                codes[0],
+                style::Reset
+            ),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compilation_unit::CompilationUnit, lints::LintConfig};
+
+    use super::DiagnosticsPrinter;
+
+    // Every diagnostic reported for `input`, without colours
+    fn render(input: &str) -> String {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        let diagnostics = &unit.diagnostics_bag.borrow().diagnostics;
+        let printer = DiagnosticsPrinter::new(&unit.text, diagnostics);
+        let rendered: Vec<_> = diagnostics.iter().map(|d| strip_colors(&printer.stringify_diagnostic(d))).collect();
+        rendered.join("\n\n")
+    }
+
+    fn strip_colors(text: &str) -> String {
+        let mut plain = String::new();
+        let mut chars = text.chars();
+        while let Some(c) = chars.next() {
+            if c == '\x1b' {
+                chars.by_ref().find(|c| *c == 'm');
+            } else {
+                plain.push(c);
+            }
+        }
+        plain
+    }
+
+    #[test]
+    fn underlines_spans_ending_in_a_multi_byte_character() {
+        let unit = CompilationUnit::compile("# é\n1".to_string(), "test.sn".to_string(), LintConfig::new());
+        let diagnostics = &unit.diagnostics_bag.borrow().diagnostics;
+        let printer = DiagnosticsPrinter::new(&unit.text, diagnostics);
+        let rendered = printer.stringify_diagnostic(&diagnostics[0]);
+        assert!(rendered.contains("Malformed pragma `# é`"), "{}", rendered);
+        assert!(rendered.contains("test.sn:1:1"), "{}", rendered);
+    }
+
+    #[test]
+    fn measures_columns_and_underlines_in_characters() {
+        let expected = "\
+error[E0007]: Cannot apply `+` to <string> and <int>
+ --> test.sn:1:9
+  |
+1 | let s = \"ééé\" + 1
+  |         ^^^^^ this is `string`
+  |               - `+` expects `int` operands";
+        assert_eq!(render("let s = \"ééé\" + 1"), expected);
+    }
+
+    #[test]
+    fn places_carets_on_lines_after_crlf_line_endings() {
+        let expected = "\
+error[E0007]: Cannot apply `+` to <int> and <string>
+ --> test.sn:4:5
+  |
+4 | d + \"x\"
+  |   - `+` expects `int` operands
+  |     ^^^ this is `string`";
+        assert_eq!(render("let a = 1\r\nlet b = 2\r\nlet d = a + b\r\nd + \"x\"\r\n"), expected);
+    }
+
+    #[test]
+    fn points_from_an_unclosed_parenthesis_to_the_end_of_the_file() {
+        let expected = "\
+error[E0002]: Expected -> <Expression> | Found -> <Eof>
+ --> test.sn:2:6
+  |
+2 |   2 *
+  |      ^ expected an expression here
+  |
+  = note: an expression is a number, a variable, a list, a call or a parenthesized expression
+  = help: the file ended before the expression was complete
+
+error[E0003]: Expected -> <)> | Found -> <Eof>
+ --> test.sn:2:6
+  |
+1 | let a = (1 +
+  |         - unclosed `(` opened here
+2 |   2 *
+  |      ^ expected `)` here
+help: close the `(` with a matching `)`
+  |
+3 | )
+  | +";
+        assert_eq!(render("let a = (1 +\n  2 *\n"), expected);
+    }
+
+    #[test]
+    fn elides_the_middle_of_long_multi_line_spans() {
+        let expected = "\
+error[E0007]: Cannot apply `+` to <[int]> and <int>
+ --> test.sn:1:10
+  |
+1 |   let xs = [
+  |  __________^
+2 | |   1,
+... |\x20
+7 | |   6
+8 | | ] + 1
+  | |   - `+` expects `int` operands
+  | |_^ this is `[int]`";
+        assert_eq!(render("let xs = [\n  1,\n  2,\n  3,\n  4,\n  5,\n  6\n] + 1"), expected);
     }
 }
\ No newline at end of file
@@ -0,0 +1,153 @@
+use crate::{ast::lexer::TextSpan, json::JsonValue, text::SourceText};
+
+use super::{codes::DiagnosticsCode, Diagnostics, DiagnosticsKind, Suggestion};
+
+const SARIF_VERSION: &str = "2.1.0";
+const SARIF_SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+// Emits a single SARIF 2.1.0 log with one run holding every diagnostic
+pub struct SarifDiagnosticsPrinter<'a> {
+    text: &'a SourceText,
+    diagnostics: &'a [Diagnostics],
+}
+
+impl<'a> SarifDiagnosticsPrinter<'a> {
+    pub fn new(text: &'a SourceText, diagnostics: &'a [Diagnostics]) -> Self {
+        Self { text, diagnostics }
+    }
+
+    pub fn stringify(&self) -> String {
+        let rules = DiagnosticsCode::ALL
+            .iter()
+            .map(|code| {
+                JsonValue::object(vec![
+                    ("id", JsonValue::string(code.code())),
+                    ("shortDescription", Self::message(code.summary())),
+                    ("fullDescription", Self::message(code.explanation())),
+                ])
+            })
+            .collect();
+        let driver = JsonValue::object(vec![
+            ("name", JsonValue::string(env!("CARGO_PKG_NAME"))),
+            ("version", JsonValue::string(env!("CARGO_PKG_VERSION"))),
+            ("rules", JsonValue::Array(rules)),
+        ]);
+        let run = JsonValue::object(vec![
+            ("tool", JsonValue::object(vec![("driver", driver)])),
+            // Columns come from `SourceText::line_column`, which counts characters
+            ("columnKind", JsonValue::string("unicodeCodePoints")),
+            (
+                "results",
+                JsonValue::Array(self.diagnostics.iter().map(|d| self.result(d)).collect()),
+            ),
+        ]);
+        JsonValue::object(vec![
+            ("$schema", JsonValue::string(SARIF_SCHEMA)),
+            ("version", JsonValue::string(SARIF_VERSION)),
+            ("runs", JsonValue::Array(vec![run])),
+        ])
+        .to_string()
+    }
+
+    fn result(&self, diagnostic: &Diagnostics) -> JsonValue {
+        let level = match diagnostic.kind {
+            DiagnosticsKind::Error => "error",
+            DiagnosticsKind::Warning => "warning",
+        };
+        let mut text = diagnostic.message.clone();
+        for note in &diagnostic.notes {
+            text.push_str(&format!("\nnote: {}", note));
+        }
+        for help in &diagnostic.help {
+            text.push_str(&format!("\nhelp: {}", help));
+        }
+        let related = diagnostic
+            .secondary_labels
+            .iter()
+            .enumerate()
+            .map(|(index, label)| {
+                let mut location = self.location(&label.span, Some(&label.message));
+                if let JsonValue::Object(fields) = &mut location {
+                    fields.insert(0, ("id".to_string(), JsonValue::Number(index as i64)));
+                }
+                location
+            })
+            .collect();
+
+        JsonValue::object(vec![
+            ("ruleId", JsonValue::string(diagnostic.code.code())),
+            ("level", JsonValue::string(level)),
+            ("message", Self::message(&text)),
+            (
+                "locations",
+                JsonValue::Array(vec![self.location(&diagnostic.span, diagnostic.label.as_deref())]),
+            ),
+            ("relatedLocations", JsonValue::Array(related)),
+            (
+                "fixes",
+                JsonValue::Array(diagnostic.suggestions.iter().map(|s| self.fix(s)).collect()),
+            ),
+        ])
+    }
+
+    fn fix(&self, suggestion: &Suggestion) -> JsonValue {
+        let replacement = JsonValue::object(vec![
+            (
+                "deletedRegion",
+                JsonValue::object(vec![
+                    ("byteOffset", JsonValue::Number(suggestion.span.start as i64)),
+                    ("byteLength", JsonValue::Number(suggestion.span.length() as i64)),
+                ]),
+            ),
+            (
+                "insertedContent",
+                JsonValue::object(vec![("text", JsonValue::string(&suggestion.replacement))]),
+            ),
+        ]);
+        let change = JsonValue::object(vec![
+            (
+                "artifactLocation",
+                JsonValue::object(vec![("uri", JsonValue::string(self.text.file_name()))]),
+            ),
+            ("replacements", JsonValue::Array(vec![replacement])),
+        ]);
+        JsonValue::object(vec![
+            ("description", Self::message(&suggestion.message)),
+            ("artifactChanges", JsonValue::Array(vec![change])),
+        ])
+    }
+
+    fn location(&self, span: &TextSpan, message: Option<&str>) -> JsonValue {
+        let (start_line, start_column) = self.text.line_column(span.start);
+        let (end_line, end_column) = self.text.line_column(span.end);
+        let region = JsonValue::object(vec![
+            ("startLine", JsonValue::Number(start_line as i64)),
+            ("startColumn", JsonValue::Number(start_column as i64)),
+            ("endLine", JsonValue::Number(end_line as i64)),
+            ("endColumn", JsonValue::Number(end_column as i64)),
+            ("byteOffset", JsonValue::Number(span.start as i64)),
+            ("byteLength", JsonValue::Number(span.length() as i64)),
+        ]);
+        let physical_location = JsonValue::object(vec![
+            (
+                "artifactLocation",
+                JsonValue::object(vec![("uri", JsonValue::string(self.text.file_name()))]),
+            ),
+            ("region", region),
+        ]);
+        let mut fields = vec![("physicalLocation", physical_location)];
+        if let Some(message) = message {
+            fields.push(("message", Self::message(message)));
+        }
+        JsonValue::object(fields)
+    }
+
+    fn message(text: &str) -> JsonValue {
+        JsonValue::object(vec![("text", JsonValue::string(text))])
+    }
+
+    // On stderr, so stdout keeps the result of the program
+    pub fn eprint(&self) {
+        eprintln!("{}", self.stringify())
+    }
+}
@@ -0,0 +1,504 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        lexer::TextSpan, ASTAssignmentExpression, ASTBinaryExpression, ASTBinaryOperatorKind, ASTCallExpression,
+        ASTEnumStatement, ASTExpression, ASTFieldAssignmentExpression, ASTFieldExpression, ASTForStatement,
+        ASTIndexAssignmentExpression, ASTIndexExpression, ASTLetStatement, ASTListExpression, ASTMapExpression,
+        ASTMatchArm, ASTMatchExpression, ASTNumberExpression, ASTPattern, ASTPatternKind, ASTRangeExpression,
+        ASTSliceExpression, ASTStatement, ASTStringExpression, ASTStructExpression, ASTStructStatement,
+        ASTTupleExpression, ASTVariableExpression, ASTVisitor, Ast,
+    },
+    runtime::Builtin,
+};
+
+use super::{
+    BasicBlock, BlockId, Instruction, InstructionKind, Program, Temp, Terminator, TerminatorKind, Value, Variable,
+    VariableId,
+};
+
+// Lowers a type-checked `Ast` to IR. Every expression leaves its result in
+// `last_value`; the program returns the value of its last statement, as with
+// `ASTEvaluator`. Blocks are laid out in the order they are started, which
+// follows the source. Until the program is built, jumps target labels, which
+// are only given a block when it starts.
+pub struct IRBuilder {
+    variables: Vec<Variable>,
+    blocks: Vec<BasicBlock>, // Finished blocks
+    instructions: Vec<Instruction>, // Of the current block
+    labels: Vec<Option<usize>>, // Block started at each label
+    scope: HashMap<String, VariableId>, // Variable visible under each name
+    structs: HashMap<String, Vec<String>>, // Field names of each struct, in declaration order
+    variants: HashSet<String>,
+    temps: usize,
+    last_value: Option<Value>,
+    spans: Vec<TextSpan>, // Nodes being lowered, innermost last
+}
+
+impl IRBuilder {
+    pub fn new() -> Self {
+        Self {
+            variables: Vec::new(),
+            blocks: Vec::new(),
+            instructions: Vec::new(),
+            labels: Vec::new(),
+            scope: HashMap::new(),
+            structs: HashMap::new(),
+            variants: HashSet::new(),
+            temps: 0,
+            last_value: None,
+            spans: Vec::new(),
+        }
+    }
+
+    pub fn build(mut self, ast: &Ast) -> Program {
+        for stmt in &ast.statements {
+            self.visit_statement(stmt);
+        }
+        let end = ast.statements.last().map_or(0, |stmt| stmt.span.end);
+        let terminator = Terminator::new(TerminatorKind::Return(self.last_value), TextSpan::new(end, end, String::new()));
+        self.blocks.push(BasicBlock {
+            instructions: self.instructions,
+            terminator,
+        });
+        for block in &mut self.blocks {
+            for target in block.terminator.targets_mut() {
+                *target = BlockId(self.labels[target.0].expect("every label starts a block"));
+            }
+        }
+        Program {
+            variables: self.variables,
+            blocks: self.blocks,
+            temps: self.temps,
+        }
+    }
+
+    fn new_label(&mut self) -> BlockId {
+        self.labels.push(None);
+        BlockId(self.labels.len() - 1)
+    }
+
+    // Ends the current block with `terminator` and starts the one at `label`
+    fn start_block(&mut self, terminator: TerminatorKind, label: BlockId) {
+        let span = self.spans.last().cloned().expect("blocks are started while visiting a node");
+        self.blocks.push(BasicBlock {
+            instructions: std::mem::take(&mut self.instructions),
+            terminator: Terminator::new(terminator, span),
+        });
+        self.labels[label.0] = Some(self.blocks.len());
+    }
+
+    fn add_instruction(&mut self, kind: InstructionKind) {
+        let span = self.spans.last().cloned().expect("instructions are added while visiting a node");
+        self.instructions.push(Instruction::new(kind, span));
+    }
+
+    // For instructions whose runtime errors point at a part of the node, such
+    // as the index of `xs[i]`
+    fn add_instruction_at(&mut self, kind: InstructionKind, span: &TextSpan) {
+        self.instructions.push(Instruction::new(kind, span.clone()));
+    }
+
+    // Lowers `expr` and returns its value
+    fn lower(&mut self, expr: &ASTExpression) -> Value {
+        self.visit_expression(expr);
+        self.value()
+    }
+
+    fn new_temp(&mut self) -> Temp {
+        self.temps += 1;
+        Temp(self.temps - 1)
+    }
+
+    fn new_variable(&mut self, name: &str) -> VariableId {
+        self.variables.push(Variable { name: name.to_string() });
+        VariableId(self.variables.len() - 1)
+    }
+
+    // Adds the test `test` builds for its destination, then continues in a
+    // new block when it holds and at `otherwise` when it does not. Nothing is
+    // tested without `otherwise`.
+    fn lower_test(&mut self, test: impl FnOnce(Temp) -> InstructionKind, otherwise: Option<BlockId>) {
+        let Some(else_block) = otherwise else {
+            return;
+        };
+        let condition = self.new_temp();
+        self.add_instruction(test(condition));
+        let then_block = self.new_label();
+        let branch = TerminatorKind::Branch { condition: Value::Temp(condition), then_block, else_block };
+        self.start_block(branch, then_block);
+    }
+
+    // Tests `value` against `pattern`, continuing at `otherwise` when it does
+    // not match, and binds the variables the pattern names. Without
+    // `otherwise` the pattern is known to match, but for the length of lists,
+    // which traps as in `let`. The bindings replace the variables in `hidden`
+    // until the end of the arm.
+    fn lower_pattern(
+        &mut self,
+        pattern: &ASTPattern,
+        value: Value,
+        otherwise: Option<BlockId>,
+        hidden: &mut Vec<(String, Option<VariableId>)>,
+    ) {
+        self.spans.push(pattern.span.clone());
+        match &pattern.kind {
+            ASTPatternKind::Wildcard => {}
+            ASTPatternKind::Identifier(identifier) if self.variants.contains(&identifier.span.literal) => {
+                let variant = identifier.span.literal.clone();
+                self.lower_test(|dest| InstructionKind::IsVariant { dest, value, variant }, otherwise)
+            }
+            ASTPatternKind::Identifier(identifier) => {
+                let name = identifier.span.literal.clone();
+                let variable = self.new_variable(&name);
+                self.add_instruction(InstructionKind::Store { variable, value });
+                hidden.push((name.clone(), self.scope.insert(name, variable)));
+            }
+            ASTPatternKind::Number(number) => {
+                let right = Value::Constant(*number);
+                self.lower_test(|dest| InstructionKind::Equals { dest, left: value, right }, otherwise)
+            }
+            ASTPatternKind::String(string) if otherwise.is_some() => {
+                let text = self.new_temp();
+                self.add_instruction(InstructionKind::StringConstant { dest: text, text: string.clone() });
+                let right = Value::Temp(text);
+                self.lower_test(|dest| InstructionKind::Equals { dest, left: value, right }, otherwise)
+            }
+            ASTPatternKind::String(_) => {}
+            ASTPatternKind::Variant(name, payload) => {
+                let variant = name.span.literal.clone();
+                self.lower_test(|dest| InstructionKind::IsVariant { dest, value, variant }, otherwise);
+                for (index, field) in payload.iter().enumerate() {
+                    let dest = self.new_temp();
+                    self.add_instruction(InstructionKind::Payload { dest, value, index });
+                    self.lower_pattern(field, Value::Temp(dest), otherwise, hidden);
+                }
+            }
+            ASTPatternKind::Tuple(elements) => {
+                for (index, element) in elements.iter().enumerate() {
+                    let dest = self.new_temp();
+                    self.add_instruction(InstructionKind::Element { dest, tuple: value, index });
+                    self.lower_pattern(element, Value::Temp(dest), otherwise, hidden);
+                }
+            }
+            ASTPatternKind::List(elements) => {
+                if otherwise.is_some() {
+                    let length = self.new_temp();
+                    self.add_instruction(InstructionKind::Length { dest: length, list: value });
+                    let (left, right) = (Value::Temp(length), Value::Constant(elements.len() as i64));
+                    self.lower_test(|dest| InstructionKind::Equals { dest, left, right }, otherwise)
+                } else {
+                    self.add_instruction(InstructionKind::CheckLength { list: value, length: elements.len() });
+                }
+                for (index, element) in elements.iter().enumerate() {
+                    let dest = self.new_temp();
+                    let index = Value::Constant(index as i64);
+                    self.add_instruction(InstructionKind::Index { dest, target: value, index });
+                    self.lower_pattern(element, Value::Temp(dest), otherwise, hidden);
+                }
+            }
+            ASTPatternKind::Struct(_, fields, _) => {
+                for field in fields {
+                    let dest = self.new_temp();
+                    let name = field.name.span.literal.clone();
+                    self.add_instruction(InstructionKind::Field { dest, target: value, field: name });
+                    self.lower_pattern(&field.pattern, Value::Temp(dest), otherwise, hidden);
+                }
+            }
+        }
+        self.spans.pop();
+    }
+
+    // Stores the value of the arm into `result` when its pattern matches
+    fn lower_arm(&mut self, arm: &ASTMatchArm, scrutinee: Value, result: VariableId, otherwise: Option<BlockId>) {
+        let mut hidden = Vec::new();
+        self.lower_pattern(&arm.pattern, scrutinee, otherwise, &mut hidden);
+        let value = self.lower(&arm.body);
+        self.add_instruction(InstructionKind::Store { variable: result, value });
+        for (name, previous) in hidden {
+            match previous {
+                Some(variable) => self.scope.insert(name, variable),
+                None => self.scope.remove(&name),
+            };
+        }
+    }
+
+    fn value(&self) -> Value {
+        self.last_value.expect("every expression has a value")
+    }
+
+    fn variable(&self, name: &str) -> VariableId {
+        *self.scope.get(name).expect("variables are resolved by the type checker")
+    }
+}
+
+impl ASTVisitor for IRBuilder {
+    fn visit_statement(&mut self, stmt: &ASTStatement) {
+        self.spans.push(stmt.span.clone());
+        self.do_visit_statement(stmt);
+        self.spans.pop();
+    }
+
+    // The bindings stay in scope for the rest of the program
+    fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
+        let value = self.lower(&let_statement.initializer);
+        self.lower_pattern(&let_statement.pattern, value, None, &mut Vec::new());
+    }
+
+    fn visit_struct_statement(&mut self, struct_statement: &ASTStructStatement) {
+        let fields = struct_statement.fields.iter().map(|field| field.name.span.literal.clone()).collect();
+        self.structs.insert(struct_statement.name.span.literal.clone(), fields);
+    }
+
+    fn visit_enum_statement(&mut self, enum_statement: &ASTEnumStatement) {
+        for variant in &enum_statement.variants {
+            self.variants.insert(variant.name.span.literal.clone());
+        }
+    }
+
+    fn visit_expression(&mut self, expr: &ASTExpression) {
+        self.spans.push(expr.span.clone());
+        self.do_visit_expression(expr);
+        self.spans.pop();
+    }
+
+    fn visit_binary_expression(&mut self, binary_expr: &ASTBinaryExpression) {
+        self.visit_expression(&binary_expr.left);
+        let left = self.value();
+        self.visit_expression(&binary_expr.right);
+        let right = self.value();
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::Binary {
+            dest,
+            operator: binary_expr.operator.kind,
+            left,
+            right,
+        });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    // The value stays the one assigned, even if the variable changes later
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.visit_expression(&assignment_expr.expression);
+        let variable = self.variable(&assignment_expr.identifier.span.literal);
+        let value = self.value();
+        self.add_instruction(InstructionKind::Store { variable, value });
+    }
+
+    fn visit_list_expression(&mut self, list_expr: &ASTListExpression) {
+        let elements = list_expr.elements.iter().map(|element| self.lower(element)).collect();
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::List { dest, elements });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_map_expression(&mut self, map_expr: &ASTMapExpression) {
+        let entries = map_expr.entries.iter().map(|entry| (self.lower(&entry.key), self.lower(&entry.value))).collect();
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::Map { dest, entries });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    // The items are counted through a hidden variable, tested in a header
+    // block the body jumps back to. The loop variable and the variables the
+    // body declares go out of scope after it; the value of the program is
+    // left as it was.
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        let last_value = self.last_value;
+        let iterable = self.lower(&for_statement.iterable);
+        let items = self.new_temp();
+        self.add_instruction(InstructionKind::Iterate { dest: items, iterable });
+        let count = self.new_temp();
+        self.add_instruction(InstructionKind::Length { dest: count, list: Value::Temp(items) });
+        let counter = self.new_variable("for");
+        self.add_instruction(InstructionKind::Store { variable: counter, value: Value::Constant(0) });
+        let (header, body, exit) = (self.new_label(), self.new_label(), self.new_label());
+        self.start_block(TerminatorKind::Jump(header), header);
+        let current = self.new_temp();
+        self.add_instruction(InstructionKind::Load { dest: current, variable: counter });
+        let done = self.new_temp();
+        let (left, right) = (Value::Temp(current), Value::Temp(count));
+        self.add_instruction(InstructionKind::Equals { dest: done, left, right });
+        let branch = TerminatorKind::Branch { condition: Value::Temp(done), then_block: exit, else_block: body };
+        self.start_block(branch, body);
+        let item = self.new_temp();
+        let index = Value::Temp(current);
+        self.add_instruction(InstructionKind::Index { dest: item, target: Value::Temp(items), index });
+        let scope = self.scope.clone();
+        let name = for_statement.variable.span.literal.clone();
+        let variable = self.new_variable(&name);
+        self.add_instruction(InstructionKind::Store { variable, value: Value::Temp(item) });
+        self.scope.insert(name, variable);
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+        self.scope = scope;
+        let next = self.new_temp();
+        self.add_instruction(InstructionKind::Binary {
+            dest: next,
+            operator: ASTBinaryOperatorKind::Add,
+            left: Value::Temp(current),
+            right: Value::Constant(1),
+        });
+        self.add_instruction(InstructionKind::Store { variable: counter, value: Value::Temp(next) });
+        self.start_block(TerminatorKind::Jump(header), exit);
+        self.last_value = last_value;
+    }
+
+    fn visit_range_expression(&mut self, range_expr: &ASTRangeExpression) {
+        let start = self.lower(&range_expr.start);
+        let end = self.lower(&range_expr.end);
+        let dest = self.new_temp();
+        let inclusive = range_expr.inclusive();
+        self.add_instruction(InstructionKind::Range { dest, start, end, inclusive });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_tuple_expression(&mut self, tuple_expr: &ASTTupleExpression) {
+        let elements = tuple_expr.elements.iter().map(|element| self.lower(element)).collect();
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::Tuple { dest, elements });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_string_expression(&mut self, string: &ASTStringExpression) {
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::StringConstant { dest, text: string.value.clone() });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_index_expression(&mut self, index_expr: &ASTIndexExpression) {
+        let target = self.lower(&index_expr.target);
+        let index = self.lower(&index_expr.index);
+        let dest = self.new_temp();
+        self.add_instruction_at(InstructionKind::Index { dest, target, index }, &index_expr.index.span);
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_index_assignment_expression(&mut self, assignment_expr: &ASTIndexAssignmentExpression) {
+        let target = self.lower(&assignment_expr.target);
+        let index = self.lower(&assignment_expr.index);
+        let value = self.lower(&assignment_expr.expression);
+        self.add_instruction_at(InstructionKind::SetIndex { target, index, value }, &assignment_expr.index.span);
+        self.last_value = Some(value);
+    }
+
+    // Missing bounds are the start and the length of the list
+    fn visit_slice_expression(&mut self, slice_expr: &ASTSliceExpression) {
+        let list = self.lower(&slice_expr.target);
+        let start = match &slice_expr.start {
+            Some(start) => self.lower(start),
+            None => Value::Constant(0),
+        };
+        let end = match &slice_expr.end {
+            Some(end) => self.lower(end),
+            None => {
+                let length = self.new_temp();
+                self.add_instruction_at(InstructionKind::Length { dest: length, list }, &slice_expr.range);
+                Value::Temp(length)
+            }
+        };
+        let dest = self.new_temp();
+        self.add_instruction_at(InstructionKind::Slice { dest, list, start, end }, &slice_expr.range);
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    // Fields are lowered in source order and stored in declaration order
+    fn visit_struct_expression(&mut self, struct_expr: &ASTStructExpression) {
+        let values: HashMap<_, _> = struct_expr
+            .fields
+            .iter()
+            .map(|field| (field.name.span.literal.as_str(), self.lower(&field.value)))
+            .collect();
+        let name = struct_expr.name.span.literal.clone();
+        let fields = self.structs[&name].iter().map(|field| (field.clone(), values[field.as_str()])).collect();
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::Struct { dest, name, fields });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_field_expression(&mut self, field_expr: &ASTFieldExpression) {
+        let target = self.lower(&field_expr.target);
+        let dest = self.new_temp();
+        let field = field_expr.field.span.literal.clone();
+        self.add_instruction(InstructionKind::Field { dest, target, field });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_field_assignment_expression(&mut self, assignment_expr: &ASTFieldAssignmentExpression) {
+        let target = self.lower(&assignment_expr.target);
+        let value = self.lower(&assignment_expr.expression);
+        let field = assignment_expr.field.span.literal.clone();
+        self.add_instruction(InstructionKind::SetField { target, field, value });
+        self.last_value = Some(value);
+    }
+
+    fn visit_call_expression(&mut self, call_expr: &ASTCallExpression) {
+        let arguments: Vec<_> = call_expr.arguments.iter().map(|argument| self.lower(argument)).collect();
+        let callee = &call_expr.callee.span.literal;
+        if self.variants.contains(callee) {
+            let dest = self.new_temp();
+            self.add_instruction(InstructionKind::Variant { dest, name: callee.clone(), payload: arguments });
+            self.last_value = Some(Value::Temp(dest));
+            return;
+        }
+        let builtin = Builtin::from_name(&call_expr.callee.span.literal).expect("calls are resolved by the type checker");
+        let dest = self.new_temp();
+        match builtin {
+            Builtin::Len => self.add_instruction(InstructionKind::Length { dest, list: arguments[0] }),
+            Builtin::Push => self.add_instruction(InstructionKind::Push { dest, list: arguments[0], value: arguments[1] }),
+            Builtin::Pop => {
+                let kind = InstructionKind::Pop { dest, list: arguments[0] };
+                self.add_instruction_at(kind, &call_expr.arguments[0].span)
+            }
+            Builtin::Keys => self.add_instruction(InstructionKind::Keys { dest, map: arguments[0] }),
+            Builtin::Values => self.add_instruction(InstructionKind::Values { dest, map: arguments[0] }),
+            Builtin::Has => self.add_instruction(InstructionKind::Has { dest, map: arguments[0], key: arguments[1] }),
+        }
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_number_expression(&mut self, number: &ASTNumberExpression) {
+        self.last_value = Some(Value::Constant(number.number));
+    }
+
+    // Variables hide unit variants of the same name
+    fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression) {
+        let name = variable_expression.identifier();
+        let dest = self.new_temp();
+        match self.scope.get(name) {
+            Some(&variable) => self.add_instruction(InstructionKind::Load { dest, variable }),
+            None => self.add_instruction(InstructionKind::Variant { dest, name: name.to_string(), payload: Vec::new() }),
+        }
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    // Arms are tried in order, each falling through to the next when its
+    // pattern does not match. The result reaches the block after the arms
+    // through a variable, as temporaries are assigned once.
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        let scrutinee = self.lower(&match_expr.scrutinee);
+        let Some((last, arms)) = match_expr.arms.split_last() else {
+            // No value has the type of an enum without variants
+            self.last_value = Some(Value::Constant(0));
+            return;
+        };
+        let result = self.new_variable("match");
+        let join = self.new_label();
+        for arm in arms {
+            let next = self.new_label();
+            self.lower_arm(arm, scrutinee, result, Some(next));
+            self.start_block(TerminatorKind::Jump(join), next);
+        }
+        // The checker made sure the last arm matches whatever is left
+        self.lower_arm(last, scrutinee, result, None);
+        self.start_block(TerminatorKind::Jump(join), join);
+        let dest = self.new_temp();
+        self.add_instruction(InstructionKind::Load { dest, variable: result });
+        self.last_value = Some(Value::Temp(dest));
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {
+        unreachable!("programs with errors are never lowered")
+    }
+}
@@ -0,0 +1,516 @@
+use std::{collections::HashSet, fmt::Display};
+
+use crate::ast::{lexer::TextSpan, ASTBinaryOperatorKind, Ast};
+
+use self::builder::IRBuilder;
+
+pub mod builder;
+pub mod passes;
+pub mod verifier;
+
+// Three-address intermediate representation between the `Ast` and the
+// backends. Temporaries are assigned exactly once; variables are memory,
+// read and written with `load` and `store`. Every basic block ends with a
+// terminator, which may jump to another block.
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct Temp(pub(crate) usize);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct VariableId(pub(crate) usize);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub struct BlockId(pub(crate) usize);
+
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Value {
+    Temp(Temp),
+    Constant(i64),
+}
+
+pub enum InstructionKind {
+    Copy { dest: Temp, value: Value },
+    Binary { dest: Temp, operator: ASTBinaryOperatorKind, left: Value, right: Value }, // Traps on overflow and division by zero
+    Load { dest: Temp, variable: VariableId },
+    Store { variable: VariableId, value: Value },
+    StringConstant { dest: Temp, text: String },
+    // Lists and maps are references; the instructions below trap when out of
+    // bounds or, for maps, when reading a missing key
+    List { dest: Temp, elements: Vec<Value> },
+    Map { dest: Temp, entries: Vec<(Value, Value)> }, // Keys and values
+    Index { dest: Temp, target: Value, index: Value },
+    SetIndex { target: Value, index: Value, value: Value }, // Inserts missing map keys
+    Length { dest: Temp, list: Value },
+    Push { dest: Temp, list: Value, value: Value }, // The new length
+    Pop { dest: Temp, list: Value },
+    Slice { dest: Temp, list: Value, start: Value, end: Value },
+    Keys { dest: Temp, map: Value },
+    Values { dest: Temp, map: Value },
+    Has { dest: Temp, map: Value, key: Value },
+    // Structs are references too, fields in declaration order
+    Struct { dest: Temp, name: String, fields: Vec<(String, Value)> },
+    Field { dest: Temp, target: Value, field: String },
+    SetField { target: Value, field: String, value: Value },
+    // Enum values are immutable; the tests below yield booleans for branches
+    Variant { dest: Temp, name: String, payload: Vec<Value> },
+    IsVariant { dest: Temp, value: Value, variant: String },
+    Payload { dest: Temp, value: Value, index: usize }, // Of a value known to be the right variant
+    Equals { dest: Temp, left: Value, right: Value },   // Of two integers or two strings
+    // Tuples are immutable too
+    Tuple { dest: Temp, elements: Vec<Value> },
+    Element { dest: Temp, tuple: Value, index: usize },
+    CheckLength { list: Value, length: usize }, // Traps unless the list has that length, for `let`
+    // Ranges are immutable and read like lists of their integers
+    Range { dest: Temp, start: Value, end: Value, inclusive: bool },
+    Iterate { dest: Temp, iterable: Value }, // The items a `for` loop runs over, indexed from 0
+}
+
+pub struct Instruction {
+    pub(crate) kind: InstructionKind,
+    pub(crate) span: TextSpan, // Source of the instruction, for runtime errors and debug info
+}
+
+impl Instruction {
+    pub fn new(kind: InstructionKind, span: TextSpan) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn dest(&self) -> Option<Temp> {
+        match self.kind {
+            InstructionKind::Copy { dest, .. }
+            | InstructionKind::Binary { dest, .. }
+            | InstructionKind::Load { dest, .. }
+            | InstructionKind::StringConstant { dest, .. }
+            | InstructionKind::List { dest, .. }
+            | InstructionKind::Map { dest, .. }
+            | InstructionKind::Index { dest, .. }
+            | InstructionKind::Length { dest, .. }
+            | InstructionKind::Push { dest, .. }
+            | InstructionKind::Pop { dest, .. }
+            | InstructionKind::Slice { dest, .. }
+            | InstructionKind::Keys { dest, .. }
+            | InstructionKind::Values { dest, .. }
+            | InstructionKind::Has { dest, .. }
+            | InstructionKind::Struct { dest, .. }
+            | InstructionKind::Field { dest, .. }
+            | InstructionKind::Variant { dest, .. }
+            | InstructionKind::IsVariant { dest, .. }
+            | InstructionKind::Payload { dest, .. }
+            | InstructionKind::Equals { dest, .. }
+            | InstructionKind::Tuple { dest, .. }
+            | InstructionKind::Element { dest, .. }
+            | InstructionKind::Range { dest, .. }
+            | InstructionKind::Iterate { dest, .. } => Some(dest),
+            InstructionKind::Store { .. }
+            | InstructionKind::SetIndex { .. }
+            | InstructionKind::SetField { .. }
+            | InstructionKind::CheckLength { .. } => None,
+        }
+    }
+
+    pub fn dest_mut(&mut self) -> Option<&mut Temp> {
+        match &mut self.kind {
+            InstructionKind::Copy { dest, .. }
+            | InstructionKind::Binary { dest, .. }
+            | InstructionKind::Load { dest, .. }
+            | InstructionKind::StringConstant { dest, .. }
+            | InstructionKind::List { dest, .. }
+            | InstructionKind::Map { dest, .. }
+            | InstructionKind::Index { dest, .. }
+            | InstructionKind::Length { dest, .. }
+            | InstructionKind::Push { dest, .. }
+            | InstructionKind::Pop { dest, .. }
+            | InstructionKind::Slice { dest, .. }
+            | InstructionKind::Keys { dest, .. }
+            | InstructionKind::Values { dest, .. }
+            | InstructionKind::Has { dest, .. }
+            | InstructionKind::Struct { dest, .. }
+            | InstructionKind::Field { dest, .. }
+            | InstructionKind::Variant { dest, .. }
+            | InstructionKind::IsVariant { dest, .. }
+            | InstructionKind::Payload { dest, .. }
+            | InstructionKind::Equals { dest, .. }
+            | InstructionKind::Tuple { dest, .. }
+            | InstructionKind::Element { dest, .. }
+            | InstructionKind::Range { dest, .. }
+            | InstructionKind::Iterate { dest, .. } => Some(dest),
+            InstructionKind::Store { .. }
+            | InstructionKind::SetIndex { .. }
+            | InstructionKind::SetField { .. }
+            | InstructionKind::CheckLength { .. } => None,
+        }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match &self.kind {
+            InstructionKind::Copy { value, .. } | InstructionKind::Store { value, .. } => vec![*value],
+            InstructionKind::Binary { left, right, .. } => vec![*left, *right],
+            InstructionKind::Load { .. } | InstructionKind::StringConstant { .. } => vec![],
+            InstructionKind::List { elements, .. } => elements.clone(),
+            InstructionKind::Map { entries, .. } => entries.iter().flat_map(|(key, value)| [*key, *value]).collect(),
+            InstructionKind::Index { target, index, .. } => vec![*target, *index],
+            InstructionKind::SetIndex { target, index, value } => vec![*target, *index, *value],
+            InstructionKind::Length { list, .. } | InstructionKind::Pop { list, .. } => vec![*list],
+            InstructionKind::Push { list, value, .. } => vec![*list, *value],
+            InstructionKind::Slice { list, start, end, .. } => vec![*list, *start, *end],
+            InstructionKind::Keys { map, .. } | InstructionKind::Values { map, .. } => vec![*map],
+            InstructionKind::Has { map, key, .. } => vec![*map, *key],
+            InstructionKind::Struct { fields, .. } => fields.iter().map(|(_, value)| *value).collect(),
+            InstructionKind::Field { target, .. } => vec![*target],
+            InstructionKind::SetField { target, value, .. } => vec![*target, *value],
+            InstructionKind::Variant { payload, .. } => payload.clone(),
+            InstructionKind::IsVariant { value, .. } | InstructionKind::Payload { value, .. } => vec![*value],
+            InstructionKind::Equals { left, right, .. } => vec![*left, *right],
+            InstructionKind::Tuple { elements, .. } => elements.clone(),
+            InstructionKind::Element { tuple, .. } => vec![*tuple],
+            InstructionKind::CheckLength { list, .. } => vec![*list],
+            InstructionKind::Range { start, end, .. } => vec![*start, *end],
+            InstructionKind::Iterate { iterable, .. } => vec![*iterable],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match &mut self.kind {
+            InstructionKind::Copy { value, .. } | InstructionKind::Store { value, .. } => vec![value],
+            InstructionKind::Binary { left, right, .. } => vec![left, right],
+            InstructionKind::Load { .. } | InstructionKind::StringConstant { .. } => vec![],
+            InstructionKind::List { elements, .. } => elements.iter_mut().collect(),
+            InstructionKind::Map { entries, .. } => entries.iter_mut().flat_map(|(key, value)| [key, value]).collect(),
+            InstructionKind::Index { target, index, .. } => vec![target, index],
+            InstructionKind::SetIndex { target, index, value } => vec![target, index, value],
+            InstructionKind::Length { list, .. } | InstructionKind::Pop { list, .. } => vec![list],
+            InstructionKind::Push { list, value, .. } => vec![list, value],
+            InstructionKind::Slice { list, start, end, .. } => vec![list, start, end],
+            InstructionKind::Keys { map, .. } | InstructionKind::Values { map, .. } => vec![map],
+            InstructionKind::Has { map, key, .. } => vec![map, key],
+            InstructionKind::Struct { fields, .. } => fields.iter_mut().map(|(_, value)| value).collect(),
+            InstructionKind::Field { target, .. } => vec![target],
+            InstructionKind::SetField { target, value, .. } => vec![target, value],
+            InstructionKind::Variant { payload, .. } => payload.iter_mut().collect(),
+            InstructionKind::IsVariant { value, .. } | InstructionKind::Payload { value, .. } => vec![value],
+            InstructionKind::Equals { left, right, .. } => vec![left, right],
+            InstructionKind::Tuple { elements, .. } => elements.iter_mut().collect(),
+            InstructionKind::Element { tuple, .. } => vec![tuple],
+            InstructionKind::CheckLength { list, .. } => vec![list],
+            InstructionKind::Range { start, end, .. } => vec![start, end],
+            InstructionKind::Iterate { iterable, .. } => vec![iterable],
+        }
+    }
+
+    // What the instruction works on beyond integers, which only the
+    // interpreters support, named for diagnostics
+    pub fn feature(&self) -> Option<&'static str> {
+        match self.kind {
+            InstructionKind::Copy { .. }
+            | InstructionKind::Binary { .. }
+            | InstructionKind::Load { .. }
+            | InstructionKind::Store { .. } => None,
+            InstructionKind::StringConstant { .. } => Some("strings"),
+            InstructionKind::Map { .. }
+            | InstructionKind::Keys { .. }
+            | InstructionKind::Values { .. }
+            | InstructionKind::Has { .. } => Some("maps"),
+            InstructionKind::Struct { .. } | InstructionKind::Field { .. } | InstructionKind::SetField { .. } => {
+                Some("structs")
+            }
+            InstructionKind::Variant { .. } | InstructionKind::IsVariant { .. } | InstructionKind::Payload { .. } => {
+                Some("enums")
+            }
+            InstructionKind::Equals { .. } => Some("match expressions"),
+            InstructionKind::Tuple { .. } | InstructionKind::Element { .. } => Some("tuples"),
+            InstructionKind::Range { .. } => Some("ranges"),
+            InstructionKind::Iterate { .. } => Some("for loops"),
+            _ => Some("lists"),
+        }
+    }
+}
+
+pub enum TerminatorKind {
+    Return(Option<Value>), // Ends the program with its result
+    Jump(BlockId),
+    Branch { condition: Value, then_block: BlockId, else_block: BlockId }, // On a boolean
+}
+
+pub struct Terminator {
+    pub(crate) kind: TerminatorKind,
+    pub(crate) span: TextSpan,
+}
+
+impl Terminator {
+    pub fn new(kind: TerminatorKind, span: TextSpan) -> Self {
+        Self { kind, span }
+    }
+
+    pub fn operands(&self) -> Vec<Value> {
+        match &self.kind {
+            TerminatorKind::Return(value) => value.iter().copied().collect(),
+            TerminatorKind::Jump(_) => vec![],
+            TerminatorKind::Branch { condition, .. } => vec![*condition],
+        }
+    }
+
+    pub fn operands_mut(&mut self) -> Vec<&mut Value> {
+        match &mut self.kind {
+            TerminatorKind::Return(value) => value.iter_mut().collect(),
+            TerminatorKind::Jump(_) => vec![],
+            TerminatorKind::Branch { condition, .. } => vec![condition],
+        }
+    }
+
+    // Blocks control may continue to
+    pub fn targets(&self) -> Vec<BlockId> {
+        match self.kind {
+            TerminatorKind::Return(_) => vec![],
+            TerminatorKind::Jump(target) => vec![target],
+            TerminatorKind::Branch { then_block, else_block, .. } => vec![then_block, else_block],
+        }
+    }
+
+    pub fn targets_mut(&mut self) -> Vec<&mut BlockId> {
+        match &mut self.kind {
+            TerminatorKind::Return(_) => vec![],
+            TerminatorKind::Jump(target) => vec![target],
+            TerminatorKind::Branch { then_block, else_block, .. } => vec![then_block, else_block],
+        }
+    }
+
+    // Control flow only the interpreters support, named for diagnostics
+    pub fn feature(&self) -> Option<&'static str> {
+        match self.kind {
+            TerminatorKind::Return(_) => None,
+            TerminatorKind::Jump(_) | TerminatorKind::Branch { .. } => Some("match expressions"),
+        }
+    }
+}
+
+pub struct BasicBlock {
+    pub(crate) instructions: Vec<Instruction>,
+    pub(crate) terminator: Terminator,
+}
+
+// A `let` binding; shadowing declares a new variable under the same name
+pub struct Variable {
+    pub(crate) name: String,
+}
+
+pub struct Program {
+    pub(crate) variables: Vec<Variable>,
+    pub(crate) blocks: Vec<BasicBlock>, // The first one is the entry
+    pub(crate) temps: usize,
+}
+
+impl Program {
+    // Lowers a type-checked `Ast`, then optimizes it
+    pub fn lower(ast: &Ast) -> Self {
+        let mut program = IRBuilder::new().build(ast);
+        Self::verify(&program, "lowering");
+        passes::optimize(&mut program);
+        program
+    }
+
+    // A program failing verification is a compiler bug
+    pub fn verify(program: &Program, after: &str) {
+        if let Err(message) = verifier::verify(program) {
+            panic!("invalid IR after {}: {}\n{}", after, message, program)
+        }
+    }
+
+    // Whether `variable` is ever loaded, as opposed to only stored
+    pub fn is_read(&self, variable: VariableId) -> bool {
+        self.instructions()
+            .any(|instruction| matches!(instruction.kind, InstructionKind::Load { variable: loaded, .. } if loaded == variable))
+    }
+
+    // Temporaries whose value is used by an instruction or terminator
+    pub fn used_temps(&self) -> HashSet<Temp> {
+        let terminators = self.blocks.iter().flat_map(|block| block.terminator.operands());
+        self.instructions()
+            .flat_map(|instruction| instruction.operands())
+            .chain(terminators)
+            .filter_map(|value| match value {
+                Value::Temp(temp) => Some(temp),
+                Value::Constant(_) => None,
+            })
+            .collect()
+    }
+
+    pub fn instructions(&self) -> impl Iterator<Item = &Instruction> {
+        self.blocks.iter().flat_map(|block| block.instructions.iter())
+    }
+}
+
+/* DISPLAY */
+
+impl Display for Temp {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "%{}", self.0)
+    }
+}
+
+impl Display for BlockId {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "bb{}", self.0)
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Temp(temp) => write!(f, "{}", temp),
+            Value::Constant(value) => write!(f, "{}", value),
+        }
+    }
+}
+
+impl Display for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let variable = |id: VariableId| format!("@{}({})", id.0, self.variables[id.0].name);
+        for (index, block) in self.blocks.iter().enumerate() {
+            writeln!(f, "{}:", BlockId(index))?;
+            for instruction in &block.instructions {
+                match &instruction.kind {
+                    InstructionKind::Copy { dest, value } => writeln!(f, "    {} = {}", dest, value)?,
+                    InstructionKind::Binary { dest, operator, left, right } => {
+                        let operator = match operator {
+                            ASTBinaryOperatorKind::Add => "add",
+                            ASTBinaryOperatorKind::Subtract => "sub",
+                            ASTBinaryOperatorKind::Multiply => "mul",
+                            ASTBinaryOperatorKind::Divide => "div",
+                        };
+                        writeln!(f, "    {} = {} {}, {}", dest, operator, left, right)?
+                    }
+                    InstructionKind::Load { dest, variable: id } => writeln!(f, "    {} = load {}", dest, variable(*id))?,
+                    InstructionKind::Store { variable: id, value } => writeln!(f, "    store {}, {}", variable(*id), value)?,
+                    InstructionKind::List { dest, elements } => {
+                        let elements: Vec<_> = elements.iter().map(|element| element.to_string()).collect();
+                        writeln!(f, "    {} = list [{}]", dest, elements.join(", "))?
+                    }
+                    InstructionKind::StringConstant { dest, text } => writeln!(f, "    {} = string {:?}", dest, text)?,
+                    InstructionKind::Map { dest, entries } => {
+                        let entries: Vec<_> = entries.iter().map(|(key, value)| format!("{}: {}", key, value)).collect();
+                        writeln!(f, "    {} = map {{{}}}", dest, entries.join(", "))?
+                    }
+                    InstructionKind::Index { dest, target, index } => {
+                        writeln!(f, "    {} = index {}, {}", dest, target, index)?
+                    }
+                    InstructionKind::SetIndex { target, index, value } => {
+                        writeln!(f, "    setindex {}, {}, {}", target, index, value)?
+                    }
+                    InstructionKind::Length { dest, list } => writeln!(f, "    {} = len {}", dest, list)?,
+                    InstructionKind::Push { dest, list, value } => writeln!(f, "    {} = push {}, {}", dest, list, value)?,
+                    InstructionKind::Pop { dest, list } => writeln!(f, "    {} = pop {}", dest, list)?,
+                    InstructionKind::Slice { dest, list, start, end } => {
+                        writeln!(f, "    {} = slice {}, {}, {}", dest, list, start, end)?
+                    }
+                    InstructionKind::Keys { dest, map } => writeln!(f, "    {} = keys {}", dest, map)?,
+                    InstructionKind::Values { dest, map } => writeln!(f, "    {} = values {}", dest, map)?,
+                    InstructionKind::Has { dest, map, key } => writeln!(f, "    {} = has {}, {}", dest, map, key)?,
+                    InstructionKind::Struct { dest, name, fields } => {
+                        let fields: Vec<_> = fields.iter().map(|(field, value)| format!("{}: {}", field, value)).collect();
+                        writeln!(f, "    {} = struct {} {{{}}}", dest, name, fields.join(", "))?
+                    }
+                    InstructionKind::Field { dest, target, field } => {
+                        writeln!(f, "    {} = field {}, {}", dest, target, field)?
+                    }
+                    InstructionKind::SetField { target, field, value } => {
+                        writeln!(f, "    setfield {}, {}, {}", target, field, value)?
+                    }
+                    InstructionKind::Variant { dest, name, payload } => {
+                        let payload: Vec<_> = payload.iter().map(|value| value.to_string()).collect();
+                        match payload.is_empty() {
+                            true => writeln!(f, "    {} = variant {}", dest, name)?,
+                            false => writeln!(f, "    {} = variant {}({})", dest, name, payload.join(", "))?,
+                        }
+                    }
+                    InstructionKind::IsVariant { dest, value, variant } => {
+                        writeln!(f, "    {} = isvariant {}, {}", dest, value, variant)?
+                    }
+                    InstructionKind::Payload { dest, value, index } => {
+                        writeln!(f, "    {} = payload {}, {}", dest, value, index)?
+                    }
+                    InstructionKind::Equals { dest, left, right } => writeln!(f, "    {} = eq {}, {}", dest, left, right)?,
+                    InstructionKind::Tuple { dest, elements } => {
+                        let elements: Vec<_> = elements.iter().map(|element| element.to_string()).collect();
+                        writeln!(f, "    {} = tuple ({})", dest, elements.join(", "))?
+                    }
+                    InstructionKind::Element { dest, tuple, index } => {
+                        writeln!(f, "    {} = element {}, {}", dest, tuple, index)?
+                    }
+                    InstructionKind::CheckLength { list, length } => writeln!(f, "    checklen {}, {}", list, length)?,
+                    InstructionKind::Range { dest, start, end, inclusive } => {
+                        let operator = if *inclusive { "..=" } else { ".." };
+                        writeln!(f, "    {} = range {}{}{}", dest, start, operator, end)?
+                    }
+                    InstructionKind::Iterate { dest, iterable } => writeln!(f, "    {} = iterate {}", dest, iterable)?,
+                }
+            }
+            match &block.terminator.kind {
+                TerminatorKind::Return(Some(value)) => writeln!(f, "    return {}", value)?,
+                TerminatorKind::Return(None) => writeln!(f, "    return")?,
+                TerminatorKind::Jump(target) => writeln!(f, "    jump {}", target)?,
+                TerminatorKind::Branch { condition, then_block, else_block } => {
+                    writeln!(f, "    branch {}, {}, {}", condition, then_block, else_block)?
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use crate::{compilation_unit::CompilationUnit, lints::LintConfig};
+
+    use super::Program;
+
+    fn lower(input: String, file_name: String) -> Program {
+        let mut unit = CompilationUnit::compile(input, file_name.clone(), LintConfig::new());
+        unit.optimize();
+        assert!(!unit.has_errors(), "{}", file_name);
+        Program::lower(&unit.ast)
+    }
+
+    // Lowering verifies the program after every step, so this only has to finish
+    #[test]
+    fn lowers_every_example_to_valid_ir() {
+        let examples = concat!(env!("CARGO_MANIFEST_DIR"), "/examples");
+        for entry in fs::read_dir(examples).unwrap() {
+            let path = entry.unwrap().path();
+            let program = lower(fs::read_to_string(&path).unwrap(), path.display().to_string());
+            assert!(!program.blocks.is_empty(), "{}", path.display());
+        }
+    }
+
+    #[test]
+    fn lowers_a_loop_to_blocks_that_jump_back_to_its_condition() {
+        let expected = "\
+bb0:
+    store @0(t), 0
+    %0 = range 0..3
+    %1 = iterate %0
+    %2 = len %1
+    store @1(for), 0
+    jump bb1
+bb1:
+    %3 = load @1(for)
+    %4 = eq %3, %2
+    branch %4, bb3, bb2
+bb2:
+    %5 = index %1, %3
+    store @2(i), %5
+    %6 = load @0(t)
+    %7 = add %6, %5
+    store @0(t), %7
+    %8 = add %3, 1
+    store @1(for), %8
+    jump bb1
+bb3:
+    %9 = load @0(t)
+    return %9
+";
+        let program = lower("let t = 0\nfor i in 0..3 { t = t + i }\nt".to_string(), "test.sn".to_string());
+        assert_eq!(program.to_string(), expected);
+    }
+}
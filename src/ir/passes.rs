@@ -0,0 +1,225 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{InstructionKind, Program, Temp, Value};
+
+// A pass reports whether it changed the program
+type Pass = fn(&mut Program) -> bool;
+
+// Runs the passes until none of them changes the program, then numbers the
+// remaining temporaries densely. The program is verified after every pass.
+pub fn optimize(program: &mut Program) {
+    let passes: [(&str, Pass); 3] = [
+        ("common subexpression elimination", eliminate_common_subexpressions),
+        ("copy propagation", propagate_copies),
+        ("dead code elimination", eliminate_dead_code),
+    ];
+    loop {
+        let mut changed = false;
+        for (name, pass) in passes {
+            changed |= pass(program);
+            Program::verify(program, name);
+        }
+        if !changed {
+            break;
+        }
+    }
+    renumber_temps(program);
+    Program::verify(program, "renumbering");
+}
+
+// Replaces a computation already made in the same block by a copy of its
+// result. A load is known after a store to or a load from the same variable,
+// so it becomes a copy of that value. Arithmetic can trap, but the first
+// occurrence traps before any duplicate would run.
+fn eliminate_common_subexpressions(program: &mut Program) -> bool {
+    let mut changed = false;
+    for block in &mut program.blocks {
+        let mut computed = HashMap::new();
+        let mut known = HashMap::new();
+        for instruction in &mut block.instructions {
+            match instruction.kind {
+                InstructionKind::Binary { dest, operator, left, right } => match computed.get(&(operator, left, right)) {
+                    Some(previous) => {
+                        instruction.kind = InstructionKind::Copy { dest, value: Value::Temp(*previous) };
+                        changed = true;
+                    }
+                    None => {
+                        computed.insert((operator, left, right), dest);
+                    }
+                },
+                InstructionKind::Load { dest, variable } => match known.get(&variable) {
+                    Some(value) => {
+                        instruction.kind = InstructionKind::Copy { dest, value: *value };
+                        changed = true;
+                    }
+                    None => {
+                        known.insert(variable, Value::Temp(dest));
+                    }
+                },
+                InstructionKind::Store { variable, value } => {
+                    known.insert(variable, value);
+                }
+                _ => {}
+            }
+        }
+    }
+    changed
+}
+
+// Uses the source of every copy in place of its destination. Temporaries are
+// assigned once, so the substitution holds wherever the copy is visible.
+fn propagate_copies(program: &mut Program) -> bool {
+    let mut copies: HashMap<Temp, Value> = HashMap::new();
+    let mut changed = false;
+    let mut substitute = |copies: &HashMap<Temp, Value>, operand: &mut Value| {
+        if let Value::Temp(temp) = operand {
+            if let Some(value) = copies.get(temp) {
+                *operand = *value;
+                changed = true;
+            }
+        }
+    };
+    for block in &mut program.blocks {
+        for instruction in &mut block.instructions {
+            for operand in instruction.operands_mut() {
+                substitute(&copies, operand);
+            }
+            if let InstructionKind::Copy { dest, value } = instruction.kind {
+                copies.insert(dest, value);
+            }
+        }
+        for operand in block.terminator.operands_mut() {
+            substitute(&copies, operand);
+        }
+    }
+    changed
+}
+
+// Removes copies, loads, new strings, lists, maps, structs, variants, tuples
+// and ranges, and the queries and tests that cannot fail, whose result is
+// unused, and stores overwritten in the same block before the variable is
+// loaded again. Arithmetic and list and map accesses are kept even when
+// unused, as they may report an error at runtime.
+fn eliminate_dead_code(program: &mut Program) -> bool {
+    let used = program.used_temps();
+    let mut changed = false;
+    for block in &mut program.blocks {
+        let mut overwritten = HashSet::new();
+        let mut live = vec![true; block.instructions.len()];
+        for (index, instruction) in block.instructions.iter().enumerate().rev() {
+            live[index] = match instruction.kind {
+                InstructionKind::Copy { dest, .. }
+                | InstructionKind::StringConstant { dest, .. }
+                | InstructionKind::List { dest, .. }
+                | InstructionKind::Map { dest, .. }
+                | InstructionKind::Length { dest, .. }
+                | InstructionKind::Keys { dest, .. }
+                | InstructionKind::Values { dest, .. }
+                | InstructionKind::Has { dest, .. }
+                | InstructionKind::Struct { dest, .. }
+                | InstructionKind::Field { dest, .. }
+                | InstructionKind::Variant { dest, .. }
+                | InstructionKind::IsVariant { dest, .. }
+                | InstructionKind::Payload { dest, .. }
+                | InstructionKind::Equals { dest, .. }
+                | InstructionKind::Tuple { dest, .. }
+                | InstructionKind::Element { dest, .. }
+                | InstructionKind::Range { dest, .. }
+                | InstructionKind::Iterate { dest, .. } => used.contains(&dest),
+                InstructionKind::Load { dest, variable } => {
+                    overwritten.remove(&variable);
+                    used.contains(&dest)
+                }
+                InstructionKind::Store { variable, .. } => overwritten.insert(variable),
+                _ => true,
+            };
+        }
+        let before = block.instructions.len();
+        let mut live = live.into_iter();
+        block.instructions.retain(|_| live.next().unwrap());
+        changed |= block.instructions.len() != before;
+    }
+    changed
+}
+
+// Numbers temporaries in order of assignment, closing the gaps left by the
+// instructions removed
+fn renumber_temps(program: &mut Program) {
+    let mut numbers = HashMap::new();
+    let rename = |numbers: &HashMap<Temp, Temp>, operand: &mut Value| {
+        if let Value::Temp(temp) = operand {
+            *temp = numbers[temp];
+        }
+    };
+    for block in &mut program.blocks {
+        for instruction in &mut block.instructions {
+            for operand in instruction.operands_mut() {
+                rename(&numbers, operand);
+            }
+            let Some(dest) = instruction.dest_mut() else {
+                continue;
+            };
+            let number = Temp(numbers.len());
+            numbers.insert(*dest, number);
+            *dest = number;
+        }
+        for operand in block.terminator.operands_mut() {
+            rename(&numbers, operand);
+        }
+    }
+    program.temps = numbers.len();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compilation_unit::CompilationUnit, ir::builder::IRBuilder, lints::LintConfig};
+
+    use super::optimize;
+
+    // The listing of `input` lowered from the tree as written, then optimized
+    fn optimized(input: &str) -> String {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        assert!(!unit.has_errors());
+        let mut program = IRBuilder::new().build(&unit.ast);
+        optimize(&mut program);
+        program.to_string()
+    }
+
+    #[test]
+    fn reuses_loads_and_computations_made_earlier_in_the_block() {
+        let expected = "\
+bb0:
+    store @0(a), 2
+    %0 = add 3, 1
+    store @1(b), %0
+    %1 = mul 2, %0
+    %2 = add %1, %1
+    return %2
+";
+        assert_eq!(optimized("let a = 2\nlet b = 3\nb = b + 1\n(a * b) + (a * b)"), expected);
+    }
+
+    #[test]
+    fn removes_unused_values_but_keeps_arithmetic_that_may_trap() {
+        let expected = "\
+bb0:
+    store @0(a), 4
+    %0 = list [4]
+    store @1(xs), %0
+    %1 = div 4, 2
+    return 1
+";
+        assert_eq!(optimized("let a = 4\nlet xs = [a]\nlen(xs)\na / 2\n1"), expected);
+    }
+
+    #[test]
+    fn removes_stores_overwritten_before_a_load() {
+        let expected = "\
+bb0:
+    %0 = add 5, 1
+    store @0(a), %0
+    return %0
+";
+        assert_eq!(optimized("let a = 4\na = 5\na = a + 1\na"), expected);
+    }
+}
@@ -0,0 +1,122 @@
+use std::collections::HashSet;
+
+use super::{InstructionKind, Program, Value};
+
+// Checks the invariants the passes and backends rely on: there is an entry
+// block, jumps target blocks of the program, every temporary is assigned
+// exactly once before it is used, and every variable is stored before it is
+// loaded. Blocks are checked in layout order; the builder lays out a block
+// after every block whose temporaries it uses, and whose stores it loads.
+pub fn verify(program: &Program) -> Result<(), String> {
+    if program.blocks.is_empty() {
+        return Err("the program has no entry block".to_string());
+    }
+    let mut defined = HashSet::new();
+    let mut stored = HashSet::new();
+    let check_operand = |defined: &HashSet<_>, value: &Value, block: usize| match value {
+        Value::Temp(temp) if !defined.contains(temp) => Err(format!("{} is used before it is assigned in bb{}", temp, block)),
+        _ => Ok(()),
+    };
+
+    for (index, block) in program.blocks.iter().enumerate() {
+        for instruction in &block.instructions {
+            for operand in instruction.operands() {
+                check_operand(&defined, &operand, index)?;
+            }
+            match instruction.kind {
+                InstructionKind::Load { variable, .. } if !stored.contains(&variable) => {
+                    return Err(format!("@{} is loaded before it is stored in bb{}", variable.0, index));
+                }
+                InstructionKind::Store { variable, .. } if variable.0 >= program.variables.len() => {
+                    return Err(format!("@{} is not a variable of the program", variable.0));
+                }
+                InstructionKind::Store { variable, .. } => {
+                    stored.insert(variable);
+                }
+                _ => {}
+            }
+            if let Some(dest) = instruction.dest() {
+                if dest.0 >= program.temps {
+                    return Err(format!("{} is out of range, the program has {} temporaries", dest, program.temps));
+                }
+                if !defined.insert(dest) {
+                    return Err(format!("{} is assigned more than once", dest));
+                }
+            }
+        }
+        for operand in block.terminator.operands() {
+            check_operand(&defined, &operand, index)?;
+        }
+        if let Some(target) = block.terminator.targets().into_iter().find(|target| target.0 >= program.blocks.len()) {
+            return Err(format!("bb{} jumps to {}, which does not exist", index, target));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{lexer::TextSpan, ASTBinaryOperatorKind},
+        ir::{
+            BasicBlock, BlockId, Instruction, InstructionKind, Program, Temp, Terminator, TerminatorKind, Value,
+            Variable, VariableId,
+        },
+    };
+
+    use super::verify;
+
+    // A one-variable program of a single block
+    fn program(instructions: Vec<InstructionKind>, terminator: TerminatorKind) -> Program {
+        let span = || TextSpan::new(0, 0, String::new());
+        Program {
+            variables: vec![Variable { name: "a".to_string() }],
+            blocks: vec![BasicBlock {
+                instructions: instructions.into_iter().map(|kind| Instruction::new(kind, span())).collect(),
+                terminator: Terminator::new(terminator, span()),
+            }],
+            temps: 2,
+        }
+    }
+
+    fn add(dest: usize, left: Value) -> InstructionKind {
+        let operator = ASTBinaryOperatorKind::Add;
+        InstructionKind::Binary { dest: Temp(dest), operator, left, right: Value::Constant(1) }
+    }
+
+    #[test]
+    fn accepts_temporaries_and_variables_defined_before_use() {
+        let store = InstructionKind::Store { variable: VariableId(0), value: Value::Constant(1) };
+        let load = InstructionKind::Load { dest: Temp(0), variable: VariableId(0) };
+        let result = TerminatorKind::Return(Some(Value::Temp(Temp(1))));
+        let program = program(vec![store, load, add(1, Value::Temp(Temp(0)))], result);
+        assert_eq!(verify(&program), Ok(()));
+    }
+
+    #[test]
+    fn rejects_broken_invariants() {
+        let mut empty = program(vec![], TerminatorKind::Return(None));
+        empty.blocks.clear();
+        assert_eq!(verify(&empty).unwrap_err(), "the program has no entry block");
+
+        let unassigned = program(vec![add(1, Value::Temp(Temp(0)))], TerminatorKind::Return(None));
+        assert_eq!(verify(&unassigned).unwrap_err(), "%0 is used before it is assigned in bb0");
+
+        let twice = program(vec![add(0, Value::Constant(1)), add(0, Value::Constant(2))], TerminatorKind::Return(None));
+        assert_eq!(verify(&twice).unwrap_err(), "%0 is assigned more than once");
+
+        let out_of_range = program(vec![add(2, Value::Constant(1))], TerminatorKind::Return(None));
+        assert_eq!(verify(&out_of_range).unwrap_err(), "%2 is out of range, the program has 2 temporaries");
+
+        let load = InstructionKind::Load { dest: Temp(0), variable: VariableId(0) };
+        let unstored = program(vec![load], TerminatorKind::Return(None));
+        assert_eq!(verify(&unstored).unwrap_err(), "@0 is loaded before it is stored in bb0");
+
+        let store = InstructionKind::Store { variable: VariableId(1), value: Value::Constant(1) };
+        let undeclared = program(vec![store], TerminatorKind::Return(None));
+        assert_eq!(verify(&undeclared).unwrap_err(), "@1 is not a variable of the program");
+
+        let dangling = program(vec![], TerminatorKind::Jump(BlockId(1)));
+        assert_eq!(verify(&dangling).unwrap_err(), "bb0 jumps to bb1, which does not exist");
+    }
+}
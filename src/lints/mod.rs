@@ -0,0 +1,209 @@
+use std::{collections::HashMap, fmt::Display, str::FromStr};
+
+use crate::{
+    ast::{lexer::Token, Ast},
+    diagnostics::{codes::DiagnosticsCode, DiagnosticsBagCell},
+};
+
+use self::{shadowing::ShadowingLint, unused::UnusedLint};
+
+pub mod shadowing;
+pub mod unused;
+
+// Name of the group covering every lint currently at the `warn` level
+const WARNINGS_GROUP: &str = "warnings";
+
+// Variants are named after their lint, `unknown_lint` included
+#[allow(clippy::enum_variant_names)]
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+pub enum Lint {
+    Shadowing,
+    UnknownLint,
+    UnusedVariable,
+    DeadStore,
+    UnreachablePattern,
+}
+
+impl Lint {
+    pub const ALL: &'static [Lint] = &[
+        Lint::Shadowing,
+        Lint::UnknownLint,
+        Lint::UnusedVariable,
+        Lint::DeadStore,
+        Lint::UnreachablePattern,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Lint::Shadowing => "shadowing",
+            Lint::UnknownLint => "unknown_lint",
+            Lint::UnusedVariable => "unused_variable",
+            Lint::DeadStore => "dead_store",
+            Lint::UnreachablePattern => "unreachable_pattern",
+        }
+    }
+
+    pub fn code(&self) -> DiagnosticsCode {
+        match self {
+            Lint::Shadowing => DiagnosticsCode::Shadowing,
+            Lint::UnknownLint => DiagnosticsCode::UnknownLint,
+            Lint::UnusedVariable => DiagnosticsCode::UnusedVariable,
+            Lint::DeadStore => DiagnosticsCode::DeadStore,
+            Lint::UnreachablePattern => DiagnosticsCode::UnreachablePattern,
+        }
+    }
+
+    pub fn default_level(&self) -> LintLevel {
+        match self {
+            Lint::Shadowing => LintLevel::Warn,
+            Lint::UnknownLint => LintLevel::Warn,
+            Lint::UnusedVariable => LintLevel::Warn,
+            Lint::DeadStore => LintLevel::Warn,
+            Lint::UnreachablePattern => LintLevel::Warn,
+        }
+    }
+}
+
+impl FromStr for Lint {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Self::ALL
+            .iter()
+            .find(|lint| lint.name() == s)
+            .copied()
+            .ok_or_else(|| format!("unknown lint `{}`", s))
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LintLevel {
+    Allow,
+    Warn,
+    Deny,
+}
+
+impl LintLevel {
+    // Name used both by the pragmas and the long command line flags
+    pub fn name(&self) -> &'static str {
+        match self {
+            LintLevel::Allow => "allow",
+            LintLevel::Warn => "warn",
+            LintLevel::Deny => "deny",
+        }
+    }
+}
+
+impl FromStr for LintLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => Err(format!("unknown lint level `{}`", s)),
+        }
+    }
+}
+
+/* DISPLAY */
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.name())
+    }
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum LintSource {
+    Default,
+    CommandLine,
+    Pragma,
+    DenyWarnings, // A `warn` lint promoted by `deny(warnings)`
+}
+
+// Level of every lint for one compilation. Command line flags are applied
+// first and pragmas in the file override them; `warnings` denies every lint
+// that ends up at the `warn` level.
+#[derive(Clone)]
+pub struct LintConfig {
+    levels: HashMap<Lint, (LintLevel, LintSource)>,
+    deny_warnings: bool,
+}
+
+impl LintConfig {
+    pub fn new() -> Self {
+        Self {
+            levels: Lint::ALL
+                .iter()
+                .map(|lint| (*lint, (lint.default_level(), LintSource::Default)))
+                .collect(),
+            deny_warnings: false,
+        }
+    }
+
+    pub fn set(&mut self, name: &str, level: LintLevel, source: LintSource) -> Result<(), String> {
+        if name == WARNINGS_GROUP {
+            match level {
+                LintLevel::Deny => self.deny_warnings = true,
+                LintLevel::Warn => self.deny_warnings = false,
+                LintLevel::Allow => {
+                    self.deny_warnings = false;
+                    for entry in self.levels.values_mut() {
+                        if entry.0 == LintLevel::Warn {
+                            *entry = (LintLevel::Allow, source);
+                        }
+                    }
+                }
+            }
+            return Ok(());
+        }
+        let lint: Lint = name.parse()?;
+        self.levels.insert(lint, (level, source));
+        Ok(())
+    }
+
+    pub fn level(&self, lint: Lint) -> (LintLevel, LintSource) {
+        let (level, source) = self.levels[&lint];
+        if level == LintLevel::Warn && self.deny_warnings {
+            return (LintLevel::Deny, LintSource::DenyWarnings);
+        }
+        (level, source)
+    }
+
+    // Applies the `#allow(...)`, `#warn(...)` and `#deny(...)` pragmas of a file
+    pub fn apply_pragmas(&mut self, pragmas: &[Token], diagnostics_bag: &DiagnosticsBagCell) {
+        for pragma in pragmas {
+            let Some((level, names)) = Self::parse_pragma(&pragma.span.literal) else {
+                diagnostics_bag.borrow_mut().report_malformed_pragma(pragma);
+                continue;
+            };
+            for name in names {
+                if self.set(name, level, LintSource::Pragma).is_err() {
+                    diagnostics_bag.borrow_mut().report_unknown_lint(name, pragma);
+                }
+            }
+        }
+    }
+
+    fn parse_pragma(literal: &str) -> Option<(LintLevel, Vec<&str>)> {
+        let body = literal.strip_prefix('#')?.trim();
+        let (level, rest) = body.split_once('(')?;
+        let names = rest.trim_end().strip_suffix(')')?;
+        let level = level.trim().parse().ok()?;
+        let names: Vec<&str> = names.split(',').map(str::trim).collect();
+        if names.iter().any(|name| name.is_empty()) {
+            return None;
+        }
+        Some((level, names))
+    }
+}
+
+pub fn run_lints(ast: &Ast, diagnostics_bag: &DiagnosticsBagCell) {
+    ast.visit(&mut ShadowingLint::new(DiagnosticsBagCell::clone(diagnostics_bag)));
+
+    let mut unused = UnusedLint::new(ast, DiagnosticsBagCell::clone(diagnostics_bag));
+    ast.visit(&mut unused);
+    unused.finish(ast);
+}
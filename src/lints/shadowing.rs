@@ -0,0 +1,69 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    ast::{
+        lexer::TextSpan, ASTEnumStatement, ASTForStatement, ASTLetStatement, ASTNumberExpression,
+        ASTVariableExpression, ASTVisitor,
+    },
+    diagnostics::DiagnosticsBagCell,
+};
+
+// Warns when a `let` declares a name that is already bound
+pub struct ShadowingLint {
+    bindings: HashMap<String, TextSpan>,
+    variants: HashSet<String>, // Unit variants in patterns bind nothing
+    diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl ShadowingLint {
+    pub fn new(diagnostics_bag: DiagnosticsBagCell) -> Self {
+        Self {
+            bindings: HashMap::new(),
+            variants: HashSet::new(),
+            diagnostics_bag,
+        }
+    }
+}
+
+impl ASTVisitor for ShadowingLint {
+    fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
+        self.visit_expression(&let_statement.initializer);
+        for identifier in let_statement.pattern.identifiers() {
+            if self.variants.contains(&identifier.span.literal) {
+                continue;
+            }
+            if let Some(previous) = self.bindings.get(&identifier.span.literal) {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_shadowed_variable(identifier, previous);
+            }
+            self.bindings
+                .insert(identifier.span.literal.clone(), identifier.span.clone());
+        }
+    }
+
+    // Like the bindings of a match arm, the loop variable may reuse a name; the
+    // bindings of the body are only visible there
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.visit_expression(&for_statement.iterable);
+        let bindings = self.bindings.clone();
+        let variable = &for_statement.variable;
+        self.bindings.insert(variable.span.literal.clone(), variable.span.clone());
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+        self.bindings = bindings;
+    }
+
+    fn visit_enum_statement(&mut self, enum_statement: &ASTEnumStatement) {
+        for variant in &enum_statement.variants {
+            self.variants.insert(variant.name.span.literal.clone());
+        }
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {}
+
+    fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {}
+
+    fn visit_variable_expression(&mut self, _variable_expression: &ASTVariableExpression) {}
+}
@@ -0,0 +1,300 @@
+use std::collections::HashSet;
+
+use crate::{
+    ast::{
+        lexer::{TextSpan, Token},
+        ASTAssignmentExpression, ASTEnumStatement, ASTForStatement, ASTLetStatement, ASTMatchExpression,
+        ASTNumberExpression, ASTStatementKind, ASTVariableExpression, ASTVisitor, Ast,
+    },
+    diagnostics::DiagnosticsBagCell,
+};
+
+struct Binding {
+    identifier: Token,
+    shorthand: bool, // Also names a field, as in `Point { x }`
+    read: bool,
+    assigned: bool,
+    // Last write not followed by a read, and whether it was the `let` itself
+    pending_store: Option<(TextSpan, bool)>,
+    // Writes overwritten before being read: (store, overwritten by)
+    dead_stores: Vec<(TextSpan, TextSpan)>,
+}
+
+impl Binding {
+    fn new(identifier: Token, shorthand: bool) -> Self {
+        let span = identifier.span.clone();
+        Self {
+            identifier,
+            shorthand,
+            read: false,
+            assigned: false,
+            pending_store: Some((span, true)),
+            dead_stores: Vec::new(),
+        }
+    }
+}
+
+// Warns about `let`, pattern and loop bindings that are never read and about
+// values that are overwritten before anything reads them. Names starting with
+// `_` are exempt.
+pub struct UnusedLint {
+    names: HashSet<String>, // Every name bound or used in the program, which a rename must not take
+    // Live bindings, a later `let` of the same name retires the earlier one
+    // unless it was declared outside the loop being visited
+    bindings: Vec<Binding>,
+    scope: usize, // Index of the first binding declared in the innermost loop body
+    variants: HashSet<String>, // Unit variants in patterns bind nothing
+    diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl UnusedLint {
+    pub fn new(ast: &Ast, diagnostics_bag: DiagnosticsBagCell) -> Self {
+        let mut names = NameCollector::new();
+        ast.visit(&mut names);
+        Self {
+            names: names.names,
+            bindings: Vec::new(),
+            scope: 0,
+            variants: HashSet::new(),
+            diagnostics_bag,
+        }
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut Binding> {
+        self.bindings
+            .iter_mut()
+            .rev()
+            .find(|binding| binding.identifier.span.literal == name)
+    }
+
+    // Called when `name` is declared again by `shadowed_by`
+    fn retire(&mut self, name: &str, shadowed_by: &TextSpan) {
+        if let Some(index) = self.bindings[self.scope..]
+            .iter()
+            .position(|binding| binding.identifier.span.literal == name)
+        {
+            let binding = self.bindings.remove(self.scope + index);
+            self.report(binding, Some(shadowed_by));
+        }
+    }
+
+    // Values written before a loop may be read after it when it runs no
+    // iteration, and values written in its body by the next one, so neither
+    // is known to be overwritten
+    fn forget_stores(&mut self) {
+        for binding in &mut self.bindings {
+            binding.pending_store = None;
+        }
+    }
+
+    // Reports everything collected for the remaining bindings. A value
+    // written last is kept, as it may be the result of the program, and so
+    // are the bindings of a final `let`, whose value is that result.
+    pub fn finish(mut self, ast: &Ast) {
+        if let Some(ASTStatementKind::LetStatement(let_statement)) = ast.statements.last().map(|stmt| &stmt.kind) {
+            for identifier in let_statement.pattern.identifiers() {
+                if let Some(binding) = self.lookup(&identifier.span.literal) {
+                    binding.read = true;
+                }
+            }
+        }
+        for binding in std::mem::take(&mut self.bindings) {
+            self.report(binding, None);
+        }
+    }
+
+    fn report(&self, mut binding: Binding, shadowed_by: Option<&TextSpan>) {
+        if binding.identifier.span.literal.starts_with('_') {
+            return;
+        }
+        let mut diagnostics_bag = self.diagnostics_bag.borrow_mut();
+        if !binding.read {
+            let taken = self.names.contains(&format!("_{}", binding.identifier.span.literal));
+            diagnostics_bag.report_unused_variable(&binding.identifier, binding.assigned, binding.shorthand, taken);
+            return;
+        }
+        if let (Some((store, false)), Some(shadowed_by)) = (binding.pending_store.take(), shadowed_by) {
+            binding.dead_stores.push((store, shadowed_by.clone()));
+        }
+        for (store, overwritten_by) in &binding.dead_stores {
+            diagnostics_bag.report_dead_store(&binding.identifier, store, overwritten_by);
+        }
+    }
+}
+
+impl ASTVisitor for UnusedLint {
+    fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
+        self.visit_expression(&let_statement.initializer);
+        for identifier in let_statement.pattern.identifiers() {
+            if self.variants.contains(&identifier.span.literal) {
+                continue;
+            }
+            self.retire(&identifier.span.literal, &identifier.span);
+            let shorthand = let_statement.pattern.is_shorthand(identifier);
+            self.bindings.push(Binding::new(identifier.clone(), shorthand));
+        }
+    }
+
+    fn visit_enum_statement(&mut self, enum_statement: &ASTEnumStatement) {
+        for variant in &enum_statement.variants {
+            self.variants.insert(variant.name.span.literal.clone());
+        }
+    }
+
+    // Bindings of a pattern go out of scope at the end of their arm
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        self.visit_expression(&match_expr.scrutinee);
+        for arm in &match_expr.arms {
+            let depth = self.bindings.len();
+            for identifier in arm.pattern.identifiers() {
+                let literal = &identifier.span.literal;
+                let bound = self.bindings[depth..].iter().any(|binding| binding.identifier.span.literal == *literal);
+                if !bound && !self.variants.contains(literal) {
+                    let shorthand = arm.pattern.is_shorthand(identifier);
+                    self.bindings.push(Binding::new(identifier.clone(), shorthand));
+                }
+            }
+            self.visit_expression(&arm.body);
+            for binding in self.bindings.split_off(depth) {
+                self.report(binding, None);
+            }
+        }
+    }
+
+    // The loop variable and the bindings of the body go out of scope at the
+    // end of the body
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.visit_expression(&for_statement.iterable);
+        self.forget_stores();
+        let depth = self.bindings.len();
+        let scope = std::mem::replace(&mut self.scope, depth);
+        self.bindings.push(Binding::new(for_statement.variable.clone(), false));
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+        for binding in self.bindings.split_off(depth) {
+            self.report(binding, None);
+        }
+        self.scope = scope;
+        self.forget_stores();
+    }
+
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.visit_expression(&assignment_expr.expression);
+        let identifier = &assignment_expr.identifier;
+        if let Some(binding) = self.lookup(&identifier.span.literal) {
+            if let Some((store, _)) = binding.pending_store.take() {
+                binding.dead_stores.push((store, identifier.span.clone()));
+            }
+            binding.assigned = true;
+            binding.pending_store = Some((identifier.span.clone(), false));
+        }
+    }
+
+    fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression) {
+        if let Some(binding) = self.lookup(variable_expression.identifier()) {
+            binding.read = true;
+            binding.pending_store = None;
+        }
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {}
+
+    fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {}
+}
+
+// Names bound by patterns and loops, and read or assigned by expressions,
+// anywhere in the program
+struct NameCollector {
+    names: HashSet<String>,
+}
+
+impl NameCollector {
+    fn new() -> Self {
+        Self { names: HashSet::new() }
+    }
+}
+
+impl ASTVisitor for NameCollector {
+    fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
+        self.names.extend(let_statement.pattern.identifiers().iter().map(|identifier| identifier.span.literal.clone()));
+        self.visit_expression(&let_statement.initializer);
+    }
+
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.names.insert(for_statement.variable.span.literal.clone());
+        self.visit_expression(&for_statement.iterable);
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+    }
+
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        self.visit_expression(&match_expr.scrutinee);
+        for arm in &match_expr.arms {
+            self.names.extend(arm.pattern.identifiers().iter().map(|identifier| identifier.span.literal.clone()));
+            self.visit_expression(&arm.body);
+        }
+    }
+
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.names.insert(assignment_expr.identifier.span.literal.clone());
+        self.visit_expression(&assignment_expr.expression);
+    }
+
+    fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression) {
+        self.names.insert(variable_expression.identifier().to_string());
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {}
+
+    fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compilation_unit::CompilationUnit, diagnostics::fix::apply_suggestions, lints::LintConfig};
+
+    fn warnings(input: &str) -> Vec<String> {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        let bag = unit.diagnostics_bag.borrow();
+        bag.diagnostics.iter().map(|diagnostic| diagnostic.message.clone()).collect()
+    }
+
+    #[test]
+    fn reports_bindings_that_are_never_read() {
+        assert_eq!(warnings("let a = 1\nlet b = 2\nb"), ["Unused variable `a`"]);
+        assert_eq!(warnings("let _a = 1\n2"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn the_final_let_is_the_result_of_the_program() {
+        assert_eq!(warnings("let a = 5\nlet d = a * 2"), Vec::<String>::new());
+        assert_eq!(warnings("let (x, y) = (1, 2)"), Vec::<String>::new());
+        assert_eq!(warnings("let d = 1\nlet e = 2"), ["Unused variable `d`"]);
+    }
+
+    #[test]
+    fn only_the_final_top_level_let_is_exempt() {
+        assert_eq!(warnings("for i in 0..3 { let d = i }"), ["Unused variable `d`"]);
+    }
+
+    // `input` after `sirin fix`
+    fn fixed(input: &str) -> String {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        let bag = unit.diagnostics_bag.borrow();
+        apply_suggestions(input, &bag.diagnostics).0
+    }
+
+    #[test]
+    fn prefixes_unused_bindings_with_an_underscore() {
+        assert_eq!(fixed("let x = 1\n2"), "let _x = 1\n2");
+    }
+
+    #[test]
+    fn leaves_the_rename_to_review_when_the_new_name_is_taken() {
+        assert_eq!(fixed("let _x = 1\nlet x = 2\n_x"), "let _x = 1\nlet x = 2\n_x");
+        assert_eq!(fixed("let (x, _x) = (1, 2)\n_x"), "let (x, _x) = (1, 2)\n_x");
+        assert_eq!(fixed("let x = 1\nfor _x in 0..2 { _x }\n3"), "let x = 1\nfor _x in 0..2 { _x }\n3");
+    }
+}
@@ -1,40 +1,301 @@
-use std::{cell::RefCell, fs, path::Path, rc::Rc};
+use std::{cell::RefCell, env, fs, path::Path, process::{self, Command as Tool}, rc::Rc};
 
 use crate::{
-    ast::{evaluator::ASTEvaluator, lexer::Lexer, parser::Parser, Ast},
-    diagnostics::DiagnosticsBagCell,
+    ast::dump::ASTJsonDumper,
+    bytecode::{compiler::BytecodeCompiler, disassembler::Disassembler, format, vm::VirtualMachine, Chunk},
+    cli::{Command, DiagnosticsFormat, Engine, Options},
+    codegen::{c::CGenerator, llvm::LlvmGenerator, wat::WatGenerator, x86_64::X86Generator},
+    compilation_unit::CompilationUnit,
+    diagnostics::{
+        codes::DiagnosticsCode, fix::apply_suggestions, json::JsonDiagnosticsPrinter,
+        printer::DiagnosticsPrinter, sarif::SarifDiagnosticsPrinter, Diagnostics, DiagnosticsBag,
+    },
+    ir::Program,
+    lints::LintConfig,
+    runtime::Value,
+    text::SourceText,
 };
 
 mod ast;
+mod bytecode;
+mod cli;
+mod codegen;
+mod compilation_unit;
 mod diagnostics;
+mod ir;
+mod json;
+mod lints;
+mod optimizer;
+mod runtime;
 mod text;
+mod typeck;
 
 fn main() {
-    let input = fs::read_to_string(Path::new("examples/main.sn")).expect("Should have been able to read the file");
-    let text = &text::SourceText::new(input.clone());
-
-    let mut lexer = Lexer::new(&input);
-    let mut tokens = Vec::new();
-    while let Some(token) = lexer.next_token() {
-        tokens.push(token)
-    }
-    // println!("{:?}", tokens);
-    let diagnostics_bag: DiagnosticsBagCell =
-        Rc::new(RefCell::new(diagnostics::DiagnosticsBag::new()));
-
-    let mut ast = Ast::new();
-    let mut parser = Parser::new(tokens, Rc::clone(&diagnostics_bag));
-    while let Some(stmt) = parser.next_statement() {
-        ast.add_statement(stmt);
-    }
-    ast.visualize();
-    let diagnostics_binding = diagnostics_bag.borrow();
-    if diagnostics_binding.diagnostics.len() > 0 {
-        let diagnostics_printer =
-            diagnostics::printer::DiagnosticsPrinter::new(text, &diagnostics_binding.diagnostics);
-        return diagnostics_printer.print();
-    }
-    let mut eval = ASTEvaluator::new();
-    ast.visit(&mut eval);
-    println!("{:?}", eval.last_value);
+    let command = Command::parse(env::args().skip(1)).unwrap_or_else(|message| {
+        eprintln!("error: {}\n\n{}", message, cli::USAGE);
+        process::exit(2)
+    });
+    match command {
+        Command::Run(options) => run(options),
+        Command::Explain(code) => explain(&code),
+        Command::Fix(file) => fix(file),
+        Command::Ast(file) => dump_ast(file),
+        Command::Compile { file, output } => compile(file, output),
+        Command::Disasm(file) => disasm(file),
+        Command::EmitIr { file, output } => emit(&file, output, None, |_, program| program.to_string()),
+        Command::EmitC { file, output } => {
+            emit(&file, output, Some("C"), |text, program| CGenerator::new(text).generate(program))
+        }
+        Command::EmitWat { file, output } => {
+            emit(&file, output, Some("WebAssembly"), |text, program| WatGenerator::new(text).generate(program))
+        }
+        Command::EmitAsm { file, output } => {
+            emit(&file, output, Some("x86-64"), |text, program| X86Generator::new(text).generate(program))
+        }
+        Command::EmitLlvm { file, output } => {
+            emit(&file, output, Some("LLVM"), |text, program| LlvmGenerator::new(text).generate(program))
+        }
+        Command::Build { file, output } => build(file, output),
+    }
+}
+
+fn read_source(file: &str) -> String {
+    fs::read_to_string(file).unwrap_or_else(|error| {
+        eprintln!("error: could not read `{}`: {}", file, error);
+        process::exit(2)
+    })
+}
+
+// Extension of compiled bytecode files, which `run` and `disasm` load directly
+const BYTECODE_EXTENSION: &str = "snc";
+
+fn is_bytecode(file: &str) -> bool {
+    Path::new(file).extension().is_some_and(|extension| extension == BYTECODE_EXTENSION)
+}
+
+fn load_bytecode(file: &str) -> (SourceText, Chunk) {
+    let bytes = fs::read(file).unwrap_or_else(|error| {
+        eprintln!("error: could not read `{}`: {}", file, error);
+        process::exit(2)
+    });
+    format::read(&bytes).unwrap_or_else(|message| {
+        eprintln!("error: could not load `{}`: {}", file, message);
+        process::exit(2)
+    })
+}
+
+// Checks and optimizes a source file for a backend, printing its diagnostics
+// and exiting if it has errors
+fn check_source(file: &str) -> CompilationUnit {
+    let mut unit = CompilationUnit::compile(read_source(file), file.to_string(), LintConfig::new());
+    unit.optimize();
+    eprint_diagnostics(&unit.text, &unit.diagnostics_bag.borrow().diagnostics);
+    if unit.has_errors() {
+        process::exit(1);
+    }
+    unit
+}
+
+// Lowers a checked unit for `backend`, if it is a native one, reporting what
+// it cannot compile and exiting
+fn lower(unit: &CompilationUnit, backend: Option<&str>) -> Program {
+    let program = Program::lower(&unit.ast);
+    let unsupported = backend.zip(codegen::find_unsupported(&program));
+    if let Some((backend, (feature, span))) = unsupported {
+        let reported = unit.diagnostics_bag.borrow().diagnostics.len();
+        unit.diagnostics_bag.borrow_mut().report_unsupported_by_backend(backend, feature, span);
+        eprint_diagnostics(&unit.text, &unit.diagnostics_bag.borrow().diagnostics[reported..]);
+        process::exit(1);
+    }
+    program
+}
+
+// Writes what `generate` produces from the lowered `file` to `output`, or to
+// stdout. `backend` names a native backend, which cannot compile everything.
+fn emit(
+    file: &str,
+    output: Option<String>,
+    backend: Option<&str>,
+    generate: impl FnOnce(&SourceText, &Program) -> String,
+) {
+    let unit = check_source(file);
+    let code = generate(&unit.text, &lower(&unit, backend));
+    match output {
+        Some(output) => fs::write(&output, code).unwrap_or_else(|error| {
+            eprintln!("error: could not write `{}`: {}", output, error);
+            process::exit(2)
+        }),
+        None => print!("{}", code),
+    }
+}
+
+// Assembles and links in a temporary directory, so only the executable is
+// left next to the source
+fn build(file: String, output: Option<String>) {
+    let unit = check_source(&file);
+    let assembly = X86Generator::new(&unit.text).generate(&lower(&unit, Some("x86-64")));
+    let output = output.unwrap_or_else(|| Path::new(&file).with_extension("").to_string_lossy().into_owned());
+    let work_dir = env::temp_dir().join(format!("sirin-build-{}", process::id()));
+    let assembly_file = work_dir.join("main.s");
+    let object_file = work_dir.join("main.o");
+    let result = fs::create_dir_all(&work_dir)
+        .and_then(|_| fs::write(&assembly_file, assembly))
+        .map_err(|error| format!("could not write `{}`: {}", assembly_file.display(), error))
+        .and_then(|_| run_tool(Tool::new("as").arg("-o").arg(&object_file).arg(&assembly_file)))
+        .and_then(|_| run_tool(Tool::new("ld").arg("-o").arg(&output).arg(&object_file)));
+    let _ = fs::remove_dir_all(&work_dir);
+    if let Err(message) = result {
+        eprintln!("error: {}", message);
+        process::exit(2)
+    }
+    println!("Built {} to {}", file, output);
+}
+
+fn run_tool(tool: &mut Tool) -> Result<(), String> {
+    let name = tool.get_program().to_string_lossy().into_owned();
+    let status = tool.status().map_err(|error| format!("could not run `{}`: {}", name, error))?;
+    if !status.success() {
+        return Err(format!("`{}` failed with {}", name, status));
+    }
+    Ok(())
+}
+
+// Compiles a checked unit to bytecode, reporting a program too large for it
+// and exiting
+fn compile_bytecode(unit: &CompilationUnit) -> Chunk {
+    BytecodeCompiler::new().compile(&Program::lower(&unit.ast)).unwrap_or_else(|(what, span)| {
+        let reported = unit.diagnostics_bag.borrow().diagnostics.len();
+        unit.diagnostics_bag.borrow_mut().report_program_too_large(what, &span);
+        eprint_diagnostics(&unit.text, &unit.diagnostics_bag.borrow().diagnostics[reported..]);
+        process::exit(1)
+    })
+}
+
+fn compile(file: String, output: Option<String>) {
+    let unit = check_source(&file);
+    let chunk = compile_bytecode(&unit);
+    let output = output.unwrap_or_else(|| {
+        Path::new(&file).with_extension(BYTECODE_EXTENSION).to_string_lossy().into_owned()
+    });
+    fs::write(&output, format::write(&unit.text, &chunk)).unwrap_or_else(|error| {
+        eprintln!("error: could not write `{}`: {}", output, error);
+        process::exit(2)
+    });
+    println!("Compiled {} to {}", file, output);
+}
+
+fn disasm(file: String) {
+    if is_bytecode(&file) {
+        let (text, chunk) = load_bytecode(&file);
+        Disassembler::new(&text, &chunk).print();
+    } else {
+        let unit = check_source(&file);
+        let chunk = compile_bytecode(&unit);
+        Disassembler::new(&unit.text, &chunk).print();
+    }
+}
+
+fn explain(code: &str) {
+    match code.parse::<DiagnosticsCode>() {
+        Ok(code) => println!("{}: {}\n\n{}", code, code.summary(), code.explanation()),
+        Err(message) => {
+            eprintln!("error: {}", message);
+            process::exit(2)
+        }
+    }
+}
+
+fn fix(file: String) {
+    let input = read_source(&file);
+    let unit = CompilationUnit::compile(input, file.clone(), LintConfig::new());
+    let (fixed, applied) =
+        apply_suggestions(unit.text.text(), &unit.diagnostics_bag.borrow().diagnostics);
+    if applied == 0 {
+        println!("Nothing to fix in {}", file);
+        return;
+    }
+    fs::write(&file, fixed).unwrap_or_else(|error| {
+        eprintln!("error: could not write `{}`: {}", file, error);
+        process::exit(2)
+    });
+    println!("Applied {} fix(es) to {}", applied, file);
+}
+
+// Diagnostics go to stderr so stdout stays a single JSON document
+fn dump_ast(file: String) {
+    let input = read_source(&file);
+    let unit = CompilationUnit::compile(input, file, LintConfig::new());
+    eprint_diagnostics(&unit.text, &unit.diagnostics_bag.borrow().diagnostics);
+    println!("{}", ASTJsonDumper::new(&unit.text, &unit.types).dump(&unit.ast));
+}
+
+fn run(options: Options) {
+    if is_bytecode(&options.file) {
+        return run_bytecode(options);
+    }
+    let input = read_source(&options.file);
+    let mut unit = CompilationUnit::compile(input, options.file.clone(), options.lints);
+
+    let human = matches!(options.format, DiagnosticsFormat::Human);
+    if human {
+        unit.ast.visualize(&unit.types);
+    }
+    unit.optimize();
+    // Human diagnostics are printed as they are found, runtime errors after
+    // everything found before running. Structured ones are emitted once the
+    // program ran, so a SARIF log is a single document.
+    let mut reported = 0;
+    if human {
+        reported = unit.diagnostics_bag.borrow().diagnostics.len();
+        print_diagnostics(&options.format, &unit.text, &unit.diagnostics_bag.borrow().diagnostics);
+    }
+    let result = match unit.has_errors() {
+        true => None,
+        false => unit.execute(&options.engine),
+    };
+    print_diagnostics(&options.format, &unit.text, &unit.diagnostics_bag.borrow().diagnostics[reported..]);
+    if unit.has_errors() {
+        process::exit(1);
+    }
+    print_result(result);
+}
+
+// Compiled files carry their source, so runtime errors render as usual
+fn run_bytecode(options: Options) {
+    if let Engine::Tree = options.engine {
+        eprintln!("error: `{}` is compiled bytecode and only runs on the vm engine", options.file);
+        process::exit(2)
+    }
+    let (text, chunk) = load_bytecode(&options.file);
+    let diagnostics_bag = Rc::new(RefCell::new(DiagnosticsBag::new(options.lints)));
+    let result = VirtualMachine::new(&chunk, Rc::clone(&diagnostics_bag)).run();
+    print_diagnostics(&options.format, &text, &diagnostics_bag.borrow().diagnostics);
+    if diagnostics_bag.borrow().has_errors() {
+        process::exit(1);
+    }
+    print_result(result);
+}
+
+// Printed like an `Option`, as `Some(<value>)` or `None`
+fn print_result(result: Option<Value>) {
+    match result {
+        Some(value) => println!("Some({})", value),
+        None => println!("None"),
+    }
+}
+
+// For commands whose stdout is machine-readable output
+fn eprint_diagnostics(text: &SourceText, diagnostics: &[Diagnostics]) {
+    let printer = DiagnosticsPrinter::new(text, diagnostics);
+    for diagnostic in diagnostics {
+        eprintln!("{}\n", printer.stringify_diagnostic(diagnostic));
+    }
+}
+
+fn print_diagnostics(format: &DiagnosticsFormat, text: &SourceText, diagnostics: &[Diagnostics]) {
+    match format {
+        DiagnosticsFormat::Human => DiagnosticsPrinter::new(text, diagnostics).print(),
+        DiagnosticsFormat::Json => JsonDiagnosticsPrinter::new(text, diagnostics).eprint(),
+        DiagnosticsFormat::Sarif => SarifDiagnosticsPrinter::new(text, diagnostics).eprint(),
+    }
 }
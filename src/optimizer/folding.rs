@@ -0,0 +1,296 @@
+use std::{collections::{HashMap, HashSet}, mem};
+
+use crate::{
+    ast::{
+        lexer::TextSpan, ASTAssignmentExpression, ASTBinaryExpression, ASTBinaryOperatorKind, ASTExpression,
+        ASTExpressionKind, ASTForStatement, ASTLetStatement, ASTMatchExpression, ASTNumberExpression, ASTPatternKind,
+        ASTVariableExpression, ASTVisitor, ASTVisitorMut, Ast,
+    },
+    diagnostics::DiagnosticsBagCell,
+};
+
+// Folds arithmetic on constants, drops parentheses, propagates the value of
+// `let` bindings that are never reassigned and simplifies `x * 1`, `x + 0`
+// and friends. A rewritten expression keeps its id and span, so the type
+// table and diagnostics still refer to it.
+pub struct ConstantFolder {
+    reassigned: HashSet<String>,
+    constants: HashMap<String, i64>,
+    // Inside a match arm or a loop body, code that may never run
+    conditional: bool,
+    diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl ConstantFolder {
+    pub fn new(ast: &Ast, diagnostics_bag: DiagnosticsBagCell) -> Self {
+        let mut assignments = AssignmentCollector::new();
+        ast.visit(&mut assignments);
+        Self {
+            reassigned: assignments.names,
+            constants: HashMap::new(),
+            conditional: false,
+            diagnostics_bag,
+        }
+    }
+
+    fn number(expr: &ASTExpression) -> Option<i64> {
+        match expr.kind {
+            ASTExpressionKind::Number(ASTNumberExpression { number }) => Some(number),
+            _ => None,
+        }
+    }
+
+    // `None` leaves the expression as written; errors are reported here, unless
+    // the code is conditional, where they are left to trap at runtime
+    fn fold_binary(&mut self, binary: &ASTBinaryExpression, span: &TextSpan) -> Option<ASTExpressionKind> {
+        let left = Self::number(&binary.left);
+        let right = Self::number(&binary.right);
+        if let (Some(left), Some(right)) = (left, right) {
+            let result = match binary.operator.kind {
+                ASTBinaryOperatorKind::Add => left.checked_add(right),
+                ASTBinaryOperatorKind::Subtract => left.checked_sub(right),
+                ASTBinaryOperatorKind::Multiply => left.checked_mul(right),
+                ASTBinaryOperatorKind::Divide if right == 0 => {
+                    if !self.conditional {
+                        self.diagnostics_bag.borrow_mut().report_division_by_zero(span, Some(&binary.right.span));
+                    }
+                    return None;
+                }
+                ASTBinaryOperatorKind::Divide => left.checked_div(right),
+            };
+            return match result {
+                Some(number) => Some(ASTExpressionKind::Number(ASTNumberExpression { number })),
+                None if self.conditional => None,
+                None => {
+                    self.diagnostics_bag
+                        .borrow_mut()
+                        .report_arithmetic_overflow(&binary.operator.token.span.literal, left, right, span);
+                    None
+                }
+            };
+        }
+        if let (ASTBinaryOperatorKind::Divide, Some(0), false) = (&binary.operator.kind, right, self.conditional) {
+            self.diagnostics_bag.borrow_mut().report_division_by_zero(span, Some(&binary.right.span));
+        }
+        None
+    }
+
+    // Which operand an identity reduces the expression to, if any
+    fn simplify_identity(binary: &ASTBinaryExpression) -> Option<bool> {
+        let left = Self::number(&binary.left);
+        let right = Self::number(&binary.right);
+        match (&binary.operator.kind, left, right) {
+            (ASTBinaryOperatorKind::Add, _, Some(0))
+            | (ASTBinaryOperatorKind::Subtract, _, Some(0))
+            | (ASTBinaryOperatorKind::Multiply, _, Some(1))
+            | (ASTBinaryOperatorKind::Divide, _, Some(1)) => Some(true),
+            (ASTBinaryOperatorKind::Add, Some(0), _) | (ASTBinaryOperatorKind::Multiply, Some(1), _) => {
+                Some(false)
+            }
+            _ => None,
+        }
+    }
+}
+
+impl ASTVisitorMut for ConstantFolder {
+    fn visit_let_statement_mut(&mut self, let_statement: &mut ASTLetStatement) {
+        self.visit_expression_mut(&mut let_statement.initializer);
+        // Variables bound by destructuring are never constants
+        let ASTPatternKind::Identifier(identifier) = &let_statement.pattern.kind else {
+            for identifier in let_statement.pattern.identifiers() {
+                self.constants.remove(&identifier.span.literal);
+            }
+            return;
+        };
+        let name = &identifier.span.literal;
+        match Self::number(&let_statement.initializer) {
+            Some(number) if !self.reassigned.contains(name) => {
+                self.constants.insert(name.clone(), number);
+            }
+            _ => {
+                self.constants.remove(name);
+            }
+        }
+    }
+
+    // The loop variable hides a constant of the same name in the body, and the
+    // constants the body declares are not visible after it
+    fn visit_for_statement_mut(&mut self, for_statement: &mut ASTForStatement) {
+        self.visit_expression_mut(&mut for_statement.iterable);
+        let constants = self.constants.clone();
+        let conditional = mem::replace(&mut self.conditional, true);
+        self.constants.remove(&for_statement.variable.span.literal);
+        for statement in &mut for_statement.body {
+            self.visit_statement_mut(statement);
+        }
+        self.conditional = conditional;
+        self.constants = constants;
+    }
+
+    // Pattern bindings hide constants of the same name in their arm
+    fn visit_match_expression_mut(&mut self, match_expr: &mut ASTMatchExpression) {
+        self.visit_expression_mut(&mut match_expr.scrutinee);
+        let conditional = mem::replace(&mut self.conditional, true);
+        for arm in &mut match_expr.arms {
+            let hidden: Vec<_> = arm
+                .pattern
+                .identifiers()
+                .iter()
+                .filter_map(|identifier| self.constants.remove_entry(&identifier.span.literal))
+                .collect();
+            self.visit_expression_mut(&mut arm.body);
+            self.constants.extend(hidden);
+        }
+        self.conditional = conditional;
+    }
+
+    fn visit_expression_mut(&mut self, expr: &mut ASTExpression) {
+        self.do_visit_expression_mut(expr);
+        let kind = mem::replace(&mut expr.kind, ASTExpressionKind::Error(expr.span.clone()));
+        expr.kind = match kind {
+            ASTExpressionKind::Parenthesized(parenthesized) => parenthesized.expression.kind,
+            ASTExpressionKind::Variable(variable) => match self.constants.get(variable.identifier()) {
+                Some(&number) => ASTExpressionKind::Number(ASTNumberExpression { number }),
+                None => ASTExpressionKind::Variable(variable),
+            },
+            ASTExpressionKind::Binary(binary) => match self.fold_binary(&binary, &expr.span) {
+                Some(folded) => folded,
+                None => match Self::simplify_identity(&binary) {
+                    Some(true) => binary.left.kind,
+                    Some(false) => binary.right.kind,
+                    None => ASTExpressionKind::Binary(binary),
+                },
+            },
+            kind => kind,
+        };
+    }
+}
+
+// Names that are the target of an assignment anywhere in the program
+struct AssignmentCollector {
+    names: HashSet<String>,
+}
+
+impl AssignmentCollector {
+    fn new() -> Self {
+        Self { names: HashSet::new() }
+    }
+}
+
+impl ASTVisitor for AssignmentCollector {
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.names.insert(assignment_expr.identifier.span.literal.clone());
+        self.visit_expression(&assignment_expr.expression)
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {}
+
+    fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {}
+
+    fn visit_variable_expression(&mut self, _variable_expression: &ASTVariableExpression) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        ast::{ASTExpressionKind, ASTNumberExpression, ASTStatementKind},
+        cli::Engine,
+        compilation_unit::CompilationUnit,
+        diagnostics::DiagnosticsKind,
+        lints::LintConfig,
+    };
+
+    // Compiles and optimizes `input`, then runs it on both engines
+    fn run(input: &str) -> Vec<Option<String>> {
+        [Engine::Tree, Engine::Vm]
+            .iter()
+            .map(|engine| {
+                let mut unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+                unit.optimize();
+                assert!(!unit.has_errors());
+                unit.execute(engine).map(|value| value.to_string())
+            })
+            .collect()
+    }
+
+    fn errors(input: &str) -> Vec<&'static str> {
+        let mut unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        unit.optimize();
+        let bag = unit.diagnostics_bag.borrow();
+        bag.diagnostics
+            .iter()
+            .filter(|diagnostic| matches!(diagnostic.kind, DiagnosticsKind::Error))
+            .map(|diagnostic| diagnostic.code.code())
+            .collect()
+    }
+
+    // The last statement of `input` after optimizing, which must be an expression
+    fn optimized(input: &str) -> ASTExpressionKind {
+        let mut unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        unit.optimize();
+        assert!(!unit.has_errors());
+        let ASTStatementKind::Expression(expr) = unit.ast.statements.pop().unwrap().kind else {
+            panic!("expected an expression")
+        };
+        expr.kind
+    }
+
+    fn number(kind: &ASTExpressionKind) -> Option<i64> {
+        match kind {
+            ASTExpressionKind::Number(ASTNumberExpression { number }) => Some(*number),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn folds_arithmetic_on_constants() {
+        assert_eq!(number(&optimized("1 + 2 * 3")), Some(7));
+        assert_eq!(number(&optimized("(10 - 4) / (1 + 2)")), Some(2));
+    }
+
+    #[test]
+    fn propagates_lets_that_are_never_reassigned() {
+        assert_eq!(number(&optimized("let a = 4\nlet b = a * 2\nb + 1")), Some(9));
+        assert!(matches!(optimized("let a = 4\na = 5\na + 1"), ASTExpressionKind::Binary(_)));
+    }
+
+    #[test]
+    fn simplifies_identities_to_the_other_operand() {
+        for input in ["let a = 4\na = 5\na * 1", "let a = 4\na = 5\n0 + a", "let a = 4\na = 5\n(a - 0) / 1"] {
+            assert!(matches!(optimized(input), ASTExpressionKind::Variable(_)), "{}", input);
+        }
+    }
+
+    #[test]
+    fn reports_division_by_zero_in_code_that_always_runs() {
+        assert_eq!(errors("let z = 0\n10 / z"), ["E0010"]);
+        assert_eq!(errors("9223372036854775807 + 1"), ["E0011"]);
+    }
+
+    #[test]
+    fn leaves_undefined_operations_in_match_arms_to_the_runtime() {
+        let results = run("let z = 0\nlet k = 5\nmatch k { 0 => 10 / z, _ => 1 }");
+        assert_eq!(results, [Some("1".to_string()), Some("1".to_string())]);
+        let results = run("let k = 5\nmatch k { 0 => 9223372036854775807 + 1, _ => 2 }");
+        assert_eq!(results, [Some("2".to_string()), Some("2".to_string())]);
+    }
+
+    #[test]
+    fn leaves_undefined_operations_in_loop_bodies_to_the_runtime() {
+        let results = run("for i in 0..0 { 1 / 0 }\n3");
+        assert_eq!(results, [Some("3".to_string()), Some("3".to_string())]);
+    }
+
+    #[test]
+    fn still_folds_defined_operations_in_conditional_code() {
+        let mut unit = CompilationUnit::compile(
+            "let k = 5\nmatch k { 0 => 2 * 3, _ => 1 }".to_string(),
+            "test.sn".to_string(),
+            LintConfig::new(),
+        );
+        unit.optimize();
+        let ASTStatementKind::Expression(expr) = &unit.ast.statements[1].kind else { panic!("expected a match") };
+        let ASTExpressionKind::Match(match_expr) = &expr.kind else { panic!("expected a match") };
+        assert!(matches!(match_expr.arms[0].body.kind, ASTExpressionKind::Number(ASTNumberExpression { number: 6 })));
+    }
+}
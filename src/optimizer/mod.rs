@@ -0,0 +1,12 @@
+use crate::{ast::Ast, diagnostics::DiagnosticsBagCell};
+
+use self::folding::ConstantFolder;
+
+pub mod folding;
+
+// Rewrites `ast` into a simpler, equivalent program. Only meaningful on a
+// program that type checks.
+pub fn optimize(ast: &mut Ast, diagnostics_bag: &DiagnosticsBagCell) {
+    let mut folder = ConstantFolder::new(ast, diagnostics_bag.clone());
+    ast.visit_mut(&mut folder);
+}
@@ -0,0 +1,620 @@
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc};
+
+// Values the interpreters work on. Lists, maps and structs are shared:
+// copying one copies the reference, so `push` through one binding is seen by
+// all.
+#[derive(Clone)]
+pub enum Value {
+    Int(i64),
+    Bool(bool),
+    String(Rc<str>),
+    List(Rc<RefCell<Vec<Value>>>),
+    Map(Rc<RefCell<Map>>),
+    Struct(Rc<RefCell<Record>>),
+    Variant(Rc<Variant>), // Never modified, so sharing it is not observable
+    Tuple(Rc<[Value]>),   // Never modified either
+    Range { start: i64, end: i64, inclusive: bool },
+}
+
+impl Value {
+    pub fn list(elements: Vec<Value>) -> Self {
+        Value::List(Rc::new(RefCell::new(elements)))
+    }
+
+    pub fn string(text: &str) -> Self {
+        Value::String(Rc::from(text))
+    }
+
+    pub fn map(map: Map) -> Self {
+        Value::Map(Rc::new(RefCell::new(map)))
+    }
+
+    // `fields` are in declaration order
+    pub fn record(name: &str, fields: Vec<(&str, Value)>) -> Self {
+        let fields = fields.into_iter().map(|(field, value)| (Rc::from(field), value)).collect();
+        Value::Struct(Rc::new(RefCell::new(Record { name: Rc::from(name), fields })))
+    }
+
+    pub fn variant(name: &str, payload: Vec<Value>) -> Self {
+        Value::Variant(Rc::new(Variant { name: Rc::from(name), payload }))
+    }
+
+    pub fn tuple(elements: Vec<Value>) -> Self {
+        Value::Tuple(Rc::from(elements))
+    }
+
+    // The checker guarantees the type, so a mismatch is an interpreter bug
+    pub fn as_int(&self) -> i64 {
+        match self {
+            Value::Int(number) => *number,
+            _ => panic!("expected an int, found {}", self),
+        }
+    }
+
+    pub fn as_bool(&self) -> bool {
+        match self {
+            Value::Bool(value) => *value,
+            _ => panic!("expected a bool, found {}", self),
+        }
+    }
+
+    pub fn as_list(&self) -> &Rc<RefCell<Vec<Value>>> {
+        match self {
+            Value::List(elements) => elements,
+            _ => panic!("expected a list, found {}", self),
+        }
+    }
+
+    pub fn as_map(&self) -> &Rc<RefCell<Map>> {
+        match self {
+            Value::Map(map) => map,
+            _ => panic!("expected a map, found {}", self),
+        }
+    }
+
+    pub fn as_record(&self) -> &Rc<RefCell<Record>> {
+        match self {
+            Value::Struct(record) => record,
+            _ => panic!("expected a struct, found {}", self),
+        }
+    }
+
+    pub fn as_variant(&self) -> &Variant {
+        match self {
+            Value::Variant(variant) => variant,
+            _ => panic!("expected an enum, found {}", self),
+        }
+    }
+
+    pub fn as_tuple(&self) -> &[Value] {
+        match self {
+            Value::Tuple(elements) => elements,
+            _ => panic!("expected a tuple, found {}", self),
+        }
+    }
+
+    // Only ints and strings are accepted as map keys by the checker
+    fn as_key(&self) -> MapKey {
+        match self {
+            Value::Int(number) => MapKey::Int(*number),
+            Value::String(text) => MapKey::String(text.clone()),
+            _ => panic!("expected a map key, found {}", self),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash, Clone)]
+enum MapKey {
+    Int(i64),
+    String(Rc<str>),
+}
+
+impl MapKey {
+    fn value(&self) -> Value {
+        match self {
+            MapKey::Int(number) => Value::Int(*number),
+            MapKey::String(text) => Value::String(text.clone()),
+        }
+    }
+}
+
+// Entries in insertion order, with a hash index for lookups. Assigning to an
+// existing key keeps its position.
+#[derive(Default)]
+pub struct Map {
+    entries: Vec<(MapKey, Value)>,
+    positions: HashMap<MapKey, usize>,
+}
+
+impl Map {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, key: &Value, value: Value) {
+        let key = key.as_key();
+        match self.positions.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                self.positions.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    fn get(&self, key: &Value) -> Option<&Value> {
+        self.positions.get(&key.as_key()).map(|&position| &self.entries[position].1)
+    }
+}
+
+// The value of a struct, fields in declaration order
+pub struct Record {
+    name: Rc<str>,
+    fields: Vec<(Rc<str>, Value)>,
+}
+
+impl Record {
+    // The checker guarantees the field exists
+    fn position(&self, name: &str) -> usize {
+        self.fields
+            .iter()
+            .position(|(field, _)| &**field == name)
+            .unwrap_or_else(|| panic!("struct `{}` has no field `{}`", self.name, name))
+    }
+}
+
+// The value of an enum: which variant it is and its payload
+pub struct Variant {
+    name: Rc<str>,
+    payload: Vec<Value>,
+}
+
+// Functions callable without being declared
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Builtin {
+    Len,  // len(xs) -> int
+    Push, // push(xs, x) -> int, the new length
+    Pop,  // pop(xs) -> x, the removed last element
+    Keys,   // keys(m) -> [k], in insertion order
+    Values, // values(m) -> [v], in insertion order
+    Has,    // has(m, k) -> bool
+}
+
+impl Builtin {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "len" => Some(Builtin::Len),
+            "push" => Some(Builtin::Push),
+            "pop" => Some(Builtin::Pop),
+            "keys" => Some(Builtin::Keys),
+            "values" => Some(Builtin::Values),
+            "has" => Some(Builtin::Has),
+            _ => None,
+        }
+    }
+
+    pub fn arity(&self) -> usize {
+        match self {
+            Builtin::Len | Builtin::Pop | Builtin::Keys | Builtin::Values => 1,
+            Builtin::Push | Builtin::Has => 2,
+        }
+    }
+}
+
+// Why a list or map operation failed. A missing key is reported as
+// `KeyNotFound`, the others as `IndexOutOfBounds`.
+pub enum AccessError {
+    Index { index: i64, length: usize },
+    Slice { start: i64, end: i64, length: usize },
+    Empty,
+    MissingKey(Value),
+    Length { expected: usize, length: usize }, // Of a list destructured by `let`
+}
+
+// List and map operations shared by the tree interpreter and the VM. List
+// indexes count from 0; negative ones are out of bounds. Ranges are read like
+// the lists of their integers.
+
+pub fn get(target: &Value, index: &Value) -> Result<Value, AccessError> {
+    match target {
+        Value::Map(map) => {
+            return map.borrow().get(index).cloned().ok_or_else(|| AccessError::MissingKey(index.clone()));
+        }
+        Value::Range { start, .. } => {
+            let (index, length) = (index.as_int(), length(target));
+            return match (0..length).contains(&index) {
+                true => Ok(Value::Int(start + index)),
+                false => Err(AccessError::Index { index, length: length as usize }),
+            };
+        }
+        _ => {}
+    }
+    let elements = target.as_list().borrow();
+    let (index, length) = (index.as_int(), elements.len());
+    usize::try_from(index)
+        .ok()
+        .and_then(|index| elements.get(index).cloned())
+        .ok_or(AccessError::Index { index, length })
+}
+
+// Assigning to a missing map key inserts it
+pub fn set(target: &Value, index: &Value, value: Value) -> Result<(), AccessError> {
+    if let Value::Map(map) = target {
+        map.borrow_mut().insert(index, value);
+        return Ok(());
+    }
+    let mut elements = target.as_list().borrow_mut();
+    let (index, length) = (index.as_int(), elements.len());
+    let element = usize::try_from(index)
+        .ok()
+        .and_then(|index| elements.get_mut(index))
+        .ok_or(AccessError::Index { index, length })?;
+    *element = value;
+    Ok(())
+}
+
+// A new list holding the elements from `start` up to, not including, `end`
+pub fn slice(list: &Value, start: i64, end: i64) -> Result<Value, AccessError> {
+    let elements = list.as_list().borrow();
+    let length = elements.len();
+    match (usize::try_from(start), usize::try_from(end)) {
+        (Ok(from), Ok(to)) if from <= to && to <= length => Ok(Value::list(elements[from..to].to_vec())),
+        _ => Err(AccessError::Slice { start, end, length }),
+    }
+}
+
+pub fn length(target: &Value) -> i64 {
+    match target {
+        Value::Map(map) => map.borrow().entries.len() as i64,
+        Value::Range { start, end, inclusive } => {
+            let length = *end as i128 + *inclusive as i128 - *start as i128;
+            length.clamp(0, i64::MAX as i128) as i64
+        }
+        _ => target.as_list().borrow().len() as i64,
+    }
+}
+
+// The items a `for` loop runs over: the integers of a range, the elements of a
+// list, the characters of a string or the keys of a map. Lists are copied, so
+// changing one in the loop does not change what it iterates over.
+pub fn iterate(iterable: &Value) -> Value {
+    match iterable {
+        Value::Range { .. } => iterable.clone(),
+        Value::String(text) => {
+            Value::list(text.chars().map(|char| Value::string(char.encode_utf8(&mut [0; 4]))).collect())
+        }
+        Value::Map(_) => keys(iterable),
+        _ => Value::list(iterable.as_list().borrow().clone()),
+    }
+}
+
+// Lists destructured by `let` must have as many elements as the pattern
+pub fn check_length(list: &Value, expected: usize) -> Result<(), AccessError> {
+    let length = list.as_list().borrow().len();
+    match length == expected {
+        true => Ok(()),
+        false => Err(AccessError::Length { expected, length }),
+    }
+}
+
+pub fn push(list: &Value, value: Value) -> i64 {
+    let mut elements = list.as_list().borrow_mut();
+    elements.push(value);
+    elements.len() as i64
+}
+
+pub fn pop(list: &Value) -> Result<Value, AccessError> {
+    list.as_list().borrow_mut().pop().ok_or(AccessError::Empty)
+}
+
+// Struct fields cannot be missing at runtime, so unlike list and map accesses
+// these do not fail
+
+pub fn field(target: &Value, name: &str) -> Value {
+    let record = target.as_record().borrow();
+    record.fields[record.position(name)].1.clone()
+}
+
+pub fn has_field(target: &Value, name: &str) -> bool {
+    target.as_record().borrow().fields.iter().any(|(field, _)| &**field == name)
+}
+
+pub fn set_field(target: &Value, name: &str, value: Value) {
+    let mut record = target.as_record().borrow_mut();
+    let position = record.position(name);
+    record.fields[position].1 = value;
+}
+
+// The tests and accesses `match` is made of. Variant names are unique across
+// enums, and a payload is only read once its variant is known.
+
+pub fn is_variant(value: &Value, name: &str) -> Value {
+    Value::Bool(&*value.as_variant().name == name)
+}
+
+pub fn payload(value: &Value, index: usize) -> Value {
+    value.as_variant().payload[index].clone()
+}
+
+pub fn payload_len(value: &Value) -> usize {
+    value.as_variant().payload.len()
+}
+
+pub fn element(tuple: &Value, index: usize) -> Value {
+    tuple.as_tuple()[index].clone()
+}
+
+// Only ints and strings are compared, as literal patterns are one or the other
+pub fn equals(left: &Value, right: &Value) -> Value {
+    match (left, right) {
+        (Value::Int(left), Value::Int(right)) => Value::Bool(left == right),
+        (Value::String(left), Value::String(right)) => Value::Bool(left == right),
+        _ => panic!("cannot compare {} and {}", left, right),
+    }
+}
+
+pub fn keys(map: &Value) -> Value {
+    Value::list(map.as_map().borrow().entries.iter().map(|(key, _)| key.value()).collect())
+}
+
+pub fn values(map: &Value) -> Value {
+    Value::list(map.as_map().borrow().entries.iter().map(|(_, value)| value.clone()).collect())
+}
+
+pub fn has(map: &Value, key: &Value) -> Value {
+    Value::Bool(map.as_map().borrow().get(key).is_some())
+}
+
+/* DISPLAY */
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(number) => write!(f, "{}", number),
+            Value::Bool(value) => write!(f, "{}", value),
+            Value::String(text) => write!(f, "{:?}", text),
+            Value::List(elements) => {
+                write!(f, "[")?;
+                for (index, element) in elements.borrow().iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(map) => {
+                write!(f, "{{")?;
+                for (index, (key, value)) in map.borrow().entries.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key.value(), value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::Struct(record) => {
+                let record = record.borrow();
+                write!(f, "{} {{", record.name)?;
+                for (index, (field, value)) in record.fields.iter().enumerate() {
+                    write!(f, "{} {}: {}", if index > 0 { "," } else { "" }, field, value)?;
+                }
+                if !record.fields.is_empty() {
+                    write!(f, " ")?;
+                }
+                write!(f, "}}")
+            }
+            Value::Variant(variant) => {
+                write!(f, "{}", variant.name)?;
+                if variant.payload.is_empty() {
+                    return Ok(());
+                }
+                write!(f, "(")?;
+                for (index, value) in variant.payload.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", value)?;
+                }
+                write!(f, ")")
+            }
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "{})", if elements.len() == 1 { "," } else { "" })
+            }
+            Value::Range { start, end, inclusive } => {
+                write!(f, "{}{}{}", start, if *inclusive { "..=" } else { ".." }, end)
+            }
+        }
+    }
+}
+
+impl Display for Builtin {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Builtin::Len => write!(f, "len"),
+            Builtin::Push => write!(f, "push"),
+            Builtin::Pop => write!(f, "pop"),
+            Builtin::Keys => write!(f, "keys"),
+            Builtin::Values => write!(f, "values"),
+            Builtin::Has => write!(f, "has"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{cli::Engine, compilation_unit::CompilationUnit, diagnostics::DiagnosticsKind, lints::LintConfig};
+
+    // Runs `input` on both engines, which must agree: the value of the
+    // program, or the code and message of the error that stopped it
+    fn run(input: &str) -> String {
+        let results: Vec<_> = [Engine::Tree, Engine::Vm]
+            .iter()
+            .map(|engine| {
+                let mut unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+                unit.optimize();
+                assert!(!unit.has_errors(), "{}", input);
+                let value = unit.execute(engine);
+                let bag = unit.diagnostics_bag.borrow();
+                match bag.diagnostics.iter().find(|diagnostic| matches!(diagnostic.kind, DiagnosticsKind::Error)) {
+                    Some(error) => format!("{}: {}", error.code.code(), error.message),
+                    None => value.map_or("None".to_string(), |value| value.to_string()),
+                }
+            })
+            .collect();
+        assert_eq!(results[0], results[1], "{}", input);
+        results[0].clone()
+    }
+
+    // Codes of the errors reported while compiling `input`
+    fn errors(input: &str) -> Vec<&'static str> {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        let bag = unit.diagnostics_bag.borrow();
+        bag.diagnostics
+            .iter()
+            .filter(|diagnostic| matches!(diagnostic.kind, DiagnosticsKind::Error))
+            .map(|diagnostic| diagnostic.code.code())
+            .collect()
+    }
+
+    #[test]
+    fn indexes_assigns_and_slices_lists() {
+        assert_eq!(run("let xs = [1, 2, 3]\nxs[1..3]"), "[2, 3]");
+        assert_eq!(run("let xs = [1, 2, 3]\nxs[0] = xs[2] * 2\nxs"), "[6, 2, 3]");
+        assert_eq!(run("let xs = [1, 2]\nlen(xs[2..2])"), "0");
+    }
+
+    #[test]
+    fn pushes_and_pops_through_every_binding_of_a_list() {
+        assert_eq!(run("let xs = []\npush(xs, 4)\npush(xs, 5)\npop(xs) * 10 + len(xs)"), "51");
+        assert_eq!(run("let xs = [1]\nlet ys = xs\npush(ys, 2)\nxs"), "[1, 2]");
+    }
+
+    #[test]
+    fn stops_at_list_accesses_out_of_bounds() {
+        assert_eq!(run("let xs = [1]\nxs[1]"), "E0014: Index out of bounds");
+        assert_eq!(run("let xs = [1]\nxs[0 - 1] = 2"), "E0014: Index out of bounds");
+        assert_eq!(run("let xs = [1]\nxs[1..0]"), "E0014: Slice out of bounds");
+        assert_eq!(run("let xs = [1]\npop(xs)\npop(xs)"), "E0014: Cannot pop from an empty list");
+    }
+
+    #[test]
+    fn rejects_ill_typed_list_operations() {
+        assert_eq!(errors("[1, \"a\"]"), ["E0006"]);
+        assert_eq!(errors("len(1)"), ["E0006"]);
+        assert_eq!(errors("let xs = [1]\npush(xs, \"a\")"), ["E0006"]);
+        assert_eq!(errors("push([1])"), ["E0013"]);
+    }
+
+    #[test]
+    fn reads_and_writes_maps_by_key_in_insertion_order() {
+        let updated = run("let m = {\"a\": 1, \"b\": 2}\nm[\"b\"] = 5\nm[\"c\"] = 7\nm");
+        assert_eq!(updated, "{\"a\": 1, \"b\": 5, \"c\": 7}");
+        assert_eq!(run("let m = {\"b\": 1, \"a\": 2}\n(keys(m), values(m))"), "([\"b\", \"a\"], [1, 2])");
+        assert_eq!(run("let m = {1: 2, 1: 3}\nm"), "{1: 3}");
+        assert_eq!(run("let m = {\"a\": 1}\nlet n = m\nn[\"b\"] = 2\n(has(m, \"b\"), has(m, \"z\"))"), "(true, false)");
+    }
+
+    #[test]
+    fn stops_at_a_missing_key() {
+        assert_eq!(run("let m = {\"a\": 1}\nm[\"z\"]"), "E0017: Key \"z\" not found");
+    }
+
+    #[test]
+    fn rejects_ill_typed_map_operations() {
+        assert_eq!(errors("{[1]: 2}"), ["E0016"]);
+        assert_eq!(errors("let m = {\"a\": 1}\nm[\"a\"] = \"x\""), ["E0006"]);
+        assert_eq!(errors("let m = {\"a\": 1}\nm[1]"), ["E0006"]);
+        assert_eq!(errors("has([1], 1)"), ["E0006"]);
+    }
+
+    #[test]
+    fn reads_and_assigns_struct_fields_through_every_binding() {
+        let point = "struct P { x: int, y: int }\nlet p = P { y: 2, x: 1 }\n";
+        assert_eq!(run(&format!("{}p.x = p.x + 10\np", point)), "P { x: 11, y: 2 }");
+        assert_eq!(run(&format!("{}let q = p\nq.y = 5\np.y", point)), "5");
+    }
+
+    #[test]
+    fn rejects_ill_formed_struct_declarations_and_uses() {
+        let point = "struct P { x: int, y: int }\n";
+        assert_eq!(errors(&format!("{}let p = P {{ x: 1, y: 2 }}\np.z", point)), ["E0018"]);
+        assert_eq!(errors(&format!("{}P {{ x: 1, y: 2, z: 3 }}", point)), ["E0018"]);
+        assert_eq!(errors(&format!("{}P {{ x: 1 }}", point)), ["E0019"]);
+        assert_eq!(errors(&format!("{}P {{ x: 1, y: \"a\" }}", point)), ["E0006"]);
+        assert_eq!(errors(&format!("{}let p = P {{ x: 1, y: 2 }}\np.x = \"a\"", point)), ["E0006"]);
+        assert_eq!(errors("struct P { x: int, x: int }"), ["E0020"]);
+        assert_eq!(errors("struct P { x: int }\nstruct P { y: int }"), ["E0020"]);
+        assert_eq!(errors("let a = 1\na.x"), ["E0018"]);
+    }
+
+    #[test]
+    fn matches_variants_and_binds_their_payloads() {
+        let shape = "enum Shape { Circle(int), Rect(int, int), Empty }\n";
+        let area = "match s { Circle(r) => 3 * r * r, Rect(w, h) => w * h, Empty => 0 }";
+        let total = format!("{}let t = 0\nfor s in [Circle(2), Rect(3, 4), Empty] {{ t = t + {} }}\nt", shape, area);
+        assert_eq!(run(&total), "24");
+        assert_eq!(run(&format!("{}match Rect(1, 5) {{ Rect(1, h) => h, _ => 0 }}", shape)), "5");
+        assert_eq!(run(&format!("{}Rect(1, 5)", shape)), "Rect(1, 5)");
+        assert_eq!(run("match \"b\" { \"a\" => 1, other => len([other]) + 1 }"), "2");
+    }
+
+    #[test]
+    fn rejects_ill_formed_enums_and_patterns() {
+        let shape = "enum Shape { Circle(int), Empty }\n";
+        assert_eq!(errors(&format!("{}Circle(1, 2)", shape)), ["E0023"]);
+        assert_eq!(errors(&format!("{}match Empty {{ Circle(_, _) => 1, _ => 0 }}", shape)), ["E0023"]);
+        assert_eq!(errors(&format!("{}match Empty {{ Square(_) => 1, _ => 0 }}", shape)), ["E0022"]);
+        assert_eq!(errors(&format!("{}enum Other {{ Empty }}", shape)), ["E0020"]);
+        assert_eq!(errors(&format!("{}match Empty {{ Circle(r) => r, Empty => \"none\" }}", shape)), ["E0006"]);
+    }
+
+    #[test]
+    fn destructures_tuples_lists_and_structs_in_let() {
+        assert_eq!(run("let t = (1, \"a\")\nt"), "(1, \"a\")");
+        assert_eq!(run("let (a, (b, c)) = (1, (2, 3))\na * 100 + b * 10 + c"), "123");
+        assert_eq!(run("let [a, _b] = [1, 2]\na"), "1");
+        assert_eq!(run("struct P { x: int, y: int }\nlet P { x, y: z } = P { x: 1, y: 2 }\nx * 10 + z"), "12");
+    }
+
+    #[test]
+    fn stops_at_a_list_of_another_length_than_its_pattern() {
+        assert_eq!(run("let [a, _b] = [1, 2, 3]\na"), "E0014: Expected a list of length 2");
+    }
+
+    #[test]
+    fn rejects_ill_formed_destructuring() {
+        assert_eq!(errors("let (a, _b) = (1, 2, 3)\na"), ["E0006"]);
+        assert_eq!(errors("let (a, _) = 5\na"), ["E0006"]);
+        assert_eq!(errors("let (a, a) = (1, 2)\na"), ["E0020"]);
+        assert_eq!(errors("struct P { x: int, y: int }\nlet P { x } = P { x: 1, y: 2 }\nx"), ["E0019"]);
+    }
+
+    #[test]
+    fn loops_over_ranges_lists_strings_and_map_keys() {
+        assert_eq!(run("let t = 0\nfor i in 1..=4 { t = t * 10 + i }\nt"), "1234");
+        assert_eq!(run("let t = 0\nfor i in 3..1 { t = t + i }\nt"), "0");
+        assert_eq!(run("let cs = []\nfor c in \"hé\" { push(cs, c) }\ncs"), "[\"h\", \"é\"]");
+        assert_eq!(run("let m = {\"a\": 1, \"b\": 2}\nlet ks = []\nfor k in m { push(ks, k) }\nks"), "[\"a\", \"b\"]");
+        assert_eq!(run("for i in 0..3 { i }"), "None");
+    }
+
+    #[test]
+    fn iterates_over_a_copy_of_the_list_in_a_scope_of_its_own() {
+        assert_eq!(run("let xs = [1, 2]\nfor x in xs { push(xs, x) }\nxs"), "[1, 2, 1, 2]");
+        assert_eq!(run("let i = 10\nfor i in 0..2 { i }\ni"), "10");
+    }
+
+    #[test]
+    fn rejects_loops_over_values_that_are_not_iterable() {
+        assert_eq!(errors("for i in 5 { i }"), ["E0026"]);
+        assert_eq!(errors("for i in (1, 2) { i }"), ["E0026"]);
+    }
+}
@@ -1,21 +1,77 @@
 pub struct SourceText {
-    text: String
+    text: String,
+    file_name: String,
 }
 
 impl SourceText {
-    pub fn new(text: String) -> Self {
-        Self { text }
+    pub fn new(text: String, file_name: String) -> Self {
+        Self { text, file_name }
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn file_name(&self) -> &str {
+        &self.file_name
     }
 
     pub fn line_index(&self, pos: usize) -> usize {
-        self.text[..pos].lines().count() - 1
+        let pos = pos.min(self.text.len());
+        self.text[..pos].matches('\n').count()
     }
 
     pub fn get_line(&self, index: usize) -> &str {
-        self.text.lines().nth(index).unwrap()
+        self.text.lines().nth(index).unwrap_or("")
     }
 
+    // Byte position of the first character of a line, found from the `\n`
+    // before it, as `str::lines` drops the `\r` of a CRLF line ending
     pub fn line_start(&self, index: usize) -> usize {
-        self.text.lines().take(index).map(|line| line.len() + 1).sum()
+        match index {
+            0 => 0,
+            _ => self.text.match_indices('\n').nth(index - 1).map_or(self.text.len(), |(pos, _)| pos + 1),
+        }
     }
-}
\ No newline at end of file
+
+    // 1-based (line, column) of a byte position, as shown to the user, with
+    // columns counted in characters. The end of a file with a trailing line
+    // break is reported at the end of its last line.
+    pub fn line_column(&self, pos: usize) -> (usize, usize) {
+        let mut pos = pos.min(self.text.len());
+        if pos == self.text.len() {
+            let break_len = match self.text.ends_with("\r\n") {
+                true => 2,
+                false => self.text.ends_with('\n') as usize,
+            };
+            pos -= break_len;
+        }
+        let line_index = self.line_index(pos);
+        let column = self.text[self.line_start(line_index)..pos].chars().count();
+        (line_index + 1, column + 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SourceText;
+
+    fn text(source: &str) -> SourceText {
+        SourceText::new(source.to_string(), "test.sn".to_string())
+    }
+
+    #[test]
+    fn counts_columns_in_characters() {
+        let source = text("let s = \"ééé\" + 1");
+        assert_eq!(source.line_column(source.text().find('+').unwrap()), (1, 15));
+    }
+
+    #[test]
+    fn finds_lines_after_crlf_line_endings() {
+        let source = text("let a = 1\r\nlet b = 2\r\nlet d = a\r\n");
+        assert_eq!(source.line_start(2), 22);
+        assert_eq!(source.line_column(source.text().find('d').unwrap()), (3, 5));
+        assert_eq!(source.get_line(2), "let d = a");
+        assert_eq!(source.line_column(source.text().len()), (3, 10));
+    }
+}
@@ -0,0 +1,315 @@
+use std::{collections::HashMap, fmt::Display, iter, slice};
+
+use super::{types::Type, EnumType, StructType};
+
+// A pattern reduced to what decides the values it matches: bindings are
+// wildcards, and variants, numbers, strings, tuples, lists and structs are
+// constructors.
+#[derive(Clone)]
+pub enum Pattern {
+    Wildcard,
+    Constructor(Constructor, Vec<Pattern>), // One pattern per field of the constructor
+}
+
+#[derive(PartialEq, Clone)]
+pub enum Constructor {
+    Variant(String),
+    Int(i64),
+    String(String),
+    Tuple,
+    List(usize),                 // Of lists of that length
+    Struct(String, Vec<String>), // Fields in declaration order
+}
+
+// Decides which values a list of patterns leaves unmatched, after Maranget's
+// "Warnings for pattern matching" (2007). Patterns are rows of a matrix with
+// one column per value being matched; a vector of patterns is useful against
+// the matrix when it matches a value no row does. Enums, tuples and structs
+// have a finite set of constructors; a match on anything else needs a
+// wildcard.
+pub struct Usefulness<'a> {
+    enums: &'a HashMap<String, EnumType>,
+    structs: &'a HashMap<String, StructType>,
+    lengths_checked: bool, // List patterns match every length, which is checked at runtime, as in `let`
+}
+
+impl<'a> Usefulness<'a> {
+    pub fn new(
+        enums: &'a HashMap<String, EnumType>,
+        structs: &'a HashMap<String, StructType>,
+        lengths_checked: bool,
+    ) -> Self {
+        Self { enums, structs, lengths_checked }
+    }
+
+    // Whether `pattern` matches a value of type `ty` that none of `rows` does
+    pub fn is_useful(&self, rows: &[Pattern], pattern: &Pattern, ty: &Type) -> bool {
+        let rows: Vec<_> = rows.iter().map(|row| vec![row.clone()]).collect();
+        !self.witnesses(&rows, slice::from_ref(pattern), slice::from_ref(ty)).is_empty()
+    }
+
+    // Patterns for the values of type `ty` that none of `rows` matches
+    pub fn missing(&self, rows: &[Pattern], ty: &Type) -> Vec<Pattern> {
+        let rows: Vec<_> = rows.iter().map(|row| vec![row.clone()]).collect();
+        self.witnesses(&rows, &[Pattern::Wildcard], slice::from_ref(ty))
+            .into_iter()
+            .map(|mut witness| witness.remove(0))
+            .collect()
+    }
+
+    // Vectors of patterns for the values `vector` matches but none of `rows`
+    // does; `types` are those of the columns
+    fn witnesses(&self, rows: &[Vec<Pattern>], vector: &[Pattern], types: &[Type]) -> Vec<Vec<Pattern>> {
+        let Some((head, tail)) = vector.split_first() else {
+            return if rows.is_empty() { vec![Vec::new()] } else { Vec::new() };
+        };
+        if let Pattern::Constructor(constructor, fields) = head {
+            return self.specialized_witnesses(rows, constructor, fields, tail, types);
+        }
+        let used: Vec<&Constructor> = rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                Pattern::Constructor(constructor, _) => Some(constructor),
+                Pattern::Wildcard => None,
+            })
+            .collect();
+        let all = self.constructors(&types[0], &used);
+        // Rows covering every constructor: each is tried in turn
+        if let Some(all) = all.as_ref().filter(|all| all.iter().all(|(constructor, _)| used.contains(&constructor))) {
+            return all
+                .iter()
+                .flat_map(|(constructor, arity)| {
+                    let fields = vec![Pattern::Wildcard; *arity];
+                    self.specialized_witnesses(rows, constructor, &fields, tail, types)
+                })
+                .collect();
+        }
+        // Values built with a constructor no row starts with are only matched
+        // by rows starting with a wildcard
+        let default: Vec<Vec<Pattern>> = rows
+            .iter()
+            .filter(|row| matches!(row[0], Pattern::Wildcard))
+            .map(|row| row[1..].to_vec())
+            .collect();
+        let heads: Vec<Pattern> = match all {
+            Some(all) => all
+                .into_iter()
+                .filter(|(constructor, _)| !used.contains(&constructor))
+                .map(|(constructor, arity)| Pattern::Constructor(constructor, vec![Pattern::Wildcard; arity]))
+                .collect(),
+            None => vec![Pattern::Wildcard],
+        };
+        self.witnesses(&default, tail, &types[1..])
+            .into_iter()
+            .flat_map(|witness| {
+                heads
+                    .iter()
+                    .map(move |head| iter::once(head.clone()).chain(witness.clone()).collect())
+            })
+            .collect()
+    }
+
+    // Witnesses among the values built with `constructor` whose fields match
+    // `fields`
+    fn specialized_witnesses(
+        &self,
+        rows: &[Vec<Pattern>],
+        constructor: &Constructor,
+        fields: &[Pattern],
+        tail: &[Pattern],
+        types: &[Type],
+    ) -> Vec<Vec<Pattern>> {
+        let arity = fields.len();
+        let specialized: Vec<Vec<Pattern>> = rows
+            .iter()
+            .filter_map(|row| match &row[0] {
+                Pattern::Constructor(other, row_fields) if other == constructor => {
+                    Some(row_fields.iter().chain(&row[1..]).cloned().collect())
+                }
+                Pattern::Constructor(..) => None,
+                Pattern::Wildcard => Some(
+                    iter::repeat_n(Pattern::Wildcard, arity)
+                        .chain(row[1..].iter().cloned())
+                        .collect(),
+                ),
+            })
+            .collect();
+        let vector: Vec<Pattern> = fields.iter().chain(tail).cloned().collect();
+        let types: Vec<Type> = self
+            .field_types(constructor, &types[0], arity)
+            .into_iter()
+            .chain(types[1..].iter().cloned())
+            .collect();
+        self.witnesses(&specialized, &vector, &types)
+            .into_iter()
+            .map(|mut witness| {
+                let rest = witness.split_off(arity);
+                iter::once(Pattern::Constructor(constructor.clone(), witness))
+                    .chain(rest)
+                    .collect()
+            })
+            .collect()
+    }
+
+    // Every constructor of `ty` with its number of fields, when there are
+    // finitely many. When lengths are checked, the lengths of lists `used` by
+    // the rows are all there is.
+    fn constructors(&self, ty: &Type, used: &[&Constructor]) -> Option<Vec<(Constructor, usize)>> {
+        match ty {
+            Type::Enum(name) => {
+                let variants = self.enums[name].variants.iter();
+                Some(
+                    variants
+                        .map(|variant| (Constructor::Variant(variant.name.span.literal.clone()), variant.payload.len()))
+                        .collect(),
+                )
+            }
+            Type::Tuple(elements) => Some(vec![(Constructor::Tuple, elements.len())]),
+            Type::Struct(name) => {
+                let fields = self.structs[name].field_names();
+                let arity = fields.len();
+                Some(vec![(Constructor::Struct(name.clone(), fields), arity)])
+            }
+            Type::Array(_) if self.lengths_checked => {
+                let lengths: Vec<_> = used
+                    .iter()
+                    .filter_map(|constructor| match constructor {
+                        Constructor::List(length) => Some((Constructor::List(*length), *length)),
+                        _ => None,
+                    })
+                    .collect();
+                (!lengths.is_empty()).then_some(lengths)
+            }
+            _ => None,
+        }
+    }
+
+    // Types of the fields of values of type `ty` built with `constructor`
+    fn field_types(&self, constructor: &Constructor, ty: &Type, arity: usize) -> Vec<Type> {
+        let types = match (constructor, ty) {
+            (Constructor::Variant(name), _) => self
+                .enums
+                .values()
+                .flat_map(|enum_type| &enum_type.variants)
+                .find(|variant| variant.name.span.literal == *name)
+                .map(|variant| variant.payload.clone()),
+            (Constructor::Tuple, Type::Tuple(elements)) => Some(elements.clone()),
+            (Constructor::List(length), Type::Array(element)) => Some(vec![(**element).clone(); *length]),
+            (Constructor::Struct(name, _), _) => self
+                .structs
+                .get(name)
+                .map(|struct_type| struct_type.fields.iter().map(|(_, ty)| ty.clone()).collect()),
+            _ => None,
+        };
+        types.unwrap_or_else(|| vec![Type::Error; arity])
+    }
+}
+
+/* DISPLAY */
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Pattern::Wildcard => write!(f, "_"),
+            Pattern::Constructor(Constructor::Int(number), _) => write!(f, "{}", number),
+            Pattern::Constructor(Constructor::String(string), _) => write!(f, "{:?}", string),
+            Pattern::Constructor(Constructor::Variant(name), fields) if fields.is_empty() => write!(f, "{}", name),
+            Pattern::Constructor(Constructor::Variant(name), fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "{}({})", name, fields.join(", "))
+            }
+            Pattern::Constructor(Constructor::Tuple, fields) if fields.len() == 1 => write!(f, "({},)", fields[0]),
+            Pattern::Constructor(Constructor::Tuple, fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "({})", fields.join(", "))
+            }
+            Pattern::Constructor(Constructor::List(_), fields) => {
+                let fields: Vec<_> = fields.iter().map(|field| field.to_string()).collect();
+                write!(f, "[{}]", fields.join(", "))
+            }
+            // Fields matching anything are left to `..`
+            Pattern::Constructor(Constructor::Struct(name, names), fields) => {
+                let mut shown: Vec<_> = names
+                    .iter()
+                    .zip(fields)
+                    .filter(|(_, field)| !matches!(field, Pattern::Wildcard))
+                    .map(|(name, field)| format!("{}: {}", name, field))
+                    .collect();
+                if shown.len() < fields.len() {
+                    shown.push("..".to_string());
+                }
+                match shown.is_empty() {
+                    true => write!(f, "{} {{}}", name),
+                    false => write!(f, "{} {{ {} }}", name, shown.join(", ")),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{compilation_unit::CompilationUnit, lints::LintConfig};
+
+    const SHAPE: &str = "enum Shape { Circle(int), Rect(int, int), Empty }\n";
+
+    // The code and message of every diagnostic reported for `input`
+    fn reported(input: &str) -> Vec<String> {
+        let unit = CompilationUnit::compile(input.to_string(), "test.sn".to_string(), LintConfig::new());
+        let bag = unit.diagnostics_bag.borrow();
+        bag.diagnostics.iter().map(|diagnostic| format!("{}: {}", diagnostic.code.code(), diagnostic.message)).collect()
+    }
+
+    #[test]
+    fn accepts_matches_covering_every_value() {
+        let arms = "Circle(r) => r, Rect(w, h) => w * h, Empty => 0";
+        assert!(reported(&format!("{}match Empty {{ {} }}", SHAPE, arms)).is_empty());
+        assert!(reported(&format!("{}match Empty {{ Circle(0) => 0, _ => 1 }}", SHAPE)).is_empty());
+        assert!(reported("match \"a\" { \"a\" => 1, _ => 0 }").is_empty());
+    }
+
+    #[test]
+    fn names_a_value_the_arms_do_not_cover() {
+        assert_eq!(
+            reported(&format!("{}match Empty {{ Circle(r) => r, Empty => 0 }}", SHAPE)),
+            ["E0024: Non-exhaustive patterns: pattern `Rect(_, _)` not covered"]
+        );
+        assert_eq!(
+            reported(&format!("{}match Empty {{ Circle(0) => 0, Rect(_, _) => 1, Empty => 2 }}", SHAPE)),
+            ["E0024: Non-exhaustive patterns: pattern `Circle(_)` not covered"]
+        );
+        assert_eq!(
+            reported("match 3 { 1 => 1, 2 => 2 }"),
+            ["E0024: Non-exhaustive patterns: pattern `_` not covered"]
+        );
+        assert_eq!(
+            reported("match (1, 2) { (1, _) => 1 }"),
+            ["E0024: Non-exhaustive patterns: pattern `(_, _)` not covered"]
+        );
+    }
+
+    #[test]
+    fn warns_about_arms_that_earlier_ones_cover() {
+        assert_eq!(
+            reported(&format!("{}match Empty {{ Circle(_) => 1, _ => 2, Empty => 3 }}", SHAPE)),
+            ["W0005: Unreachable pattern"]
+        );
+        assert_eq!(
+            reported(&format!("{}match Empty {{ Circle(x) => x, Circle(1) => 2, _ => 3 }}", SHAPE)),
+            ["W0005: Unreachable pattern"]
+        );
+    }
+
+    #[test]
+    fn rejects_let_patterns_that_may_not_match() {
+        assert!(reported("let (a, [b, c]) = (1, [2, 3])\na + b + c").is_empty());
+        assert_eq!(
+            reported(&format!("{}let Circle(r) = Circle(1)\nr", SHAPE)),
+            ["E0025: Refutable pattern in `let`: patterns `Rect(_, _)` and `Empty` not covered"]
+        );
+        assert_eq!(
+            reported("let (1, b) = (1, 2)\nb"),
+            ["E0025: Refutable pattern in `let`: pattern `(_, _)` not covered"]
+        );
+    }
+}
@@ -0,0 +1,987 @@
+use std::{collections::HashMap, slice};
+
+use crate::{
+    ast::{
+        lexer::{TextSpan, Token},
+        ASTAssignmentExpression, ASTBinaryExpression, ASTCallExpression, ASTEnumStatement, ASTExpression,
+        ASTFieldAssignmentExpression, ASTFieldExpression, ASTFieldPattern, ASTForStatement, ASTIndexAssignmentExpression,
+        ASTIndexExpression, ASTLetStatement, ASTListExpression, ASTMapExpression, ASTMatchExpression, ASTNodeId,
+        ASTNumberExpression, ASTPattern, ASTPatternKind, ASTRangeExpression, ASTSliceExpression, ASTStringExpression,
+        ASTStructExpression, ASTStructStatement, ASTTupleExpression, ASTTypeExpression, ASTTypeExpressionKind,
+        ASTVariableExpression, ASTVisitor,
+    },
+    diagnostics::DiagnosticsBagCell,
+    runtime::Builtin,
+};
+
+use self::{
+    exhaustiveness::{Constructor, Pattern, Usefulness},
+    types::Type,
+};
+
+pub mod exhaustiveness;
+pub mod types;
+
+// Type of every expression, keyed by node id
+pub struct TypeTable {
+    types: HashMap<ASTNodeId, Type>,
+}
+
+impl TypeTable {
+    pub fn new() -> Self {
+        Self {
+            types: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, id: ASTNodeId) -> Option<&Type> {
+        self.types.get(&id)
+    }
+}
+
+// A struct declared so far
+pub struct StructType {
+    name: Token,
+    fields: Vec<(Token, Type)>, // In declaration order
+}
+
+impl StructType {
+    fn field(&self, name: &str) -> Option<&Type> {
+        self.fields.iter().find(|(field, _)| field.span.literal == name).map(|(_, ty)| ty)
+    }
+
+    fn field_names(&self) -> Vec<String> {
+        self.fields.iter().map(|(field, _)| field.span.literal.clone()).collect()
+    }
+}
+
+// An enum declared so far
+pub struct EnumType {
+    name: Token,
+    variants: Vec<VariantType>, // In declaration order
+}
+
+pub struct VariantType {
+    name: Token,
+    payload: Vec<Type>,
+}
+
+// Infers a type for every expression by unification, in the style of
+// Hindley-Milner: each expression starts as a fresh type variable that is
+// refined by the constraints its operators and bindings place on it.
+pub struct TypeChecker {
+    scopes: Vec<HashMap<String, Type>>, // Innermost last; match arms and loop bodies push one
+    structs: HashMap<String, StructType>,
+    enums: HashMap<String, EnumType>,
+    substitution: Vec<Option<Type>>,
+    types: HashMap<ASTNodeId, Type>,
+    last_type: Type,
+    diagnostics_bag: DiagnosticsBagCell,
+}
+
+impl TypeChecker {
+    pub fn new(diagnostics_bag: DiagnosticsBagCell) -> Self {
+        Self {
+            scopes: vec![HashMap::new()],
+            structs: HashMap::new(),
+            enums: HashMap::new(),
+            substitution: Vec::new(),
+            types: HashMap::new(),
+            last_type: Type::Error,
+            diagnostics_bag,
+        }
+    }
+
+    // Resolves what is left of the inference variables
+    pub fn finish(self) -> TypeTable {
+        let types = self
+            .types
+            .iter()
+            .map(|(id, ty)| (*id, self.resolve(ty)))
+            .collect();
+        TypeTable { types }
+    }
+
+    fn fresh(&mut self) -> Type {
+        self.substitution.push(None);
+        Type::Var(self.substitution.len() - 1)
+    }
+
+    // Substitutes every bound inference variable, recursively
+    fn resolve(&self, ty: &Type) -> Type {
+        match ty {
+            Type::Var(var) => match &self.substitution[*var] {
+                Some(ty) => self.resolve(ty),
+                None => ty.clone(),
+            },
+            Type::Array(element) => Type::Array(Box::new(self.resolve(element))),
+            Type::Map(key, value) => Type::Map(Box::new(self.resolve(key)), Box::new(self.resolve(value))),
+            Type::Function(parameters, return_type) => Type::Function(
+                parameters.iter().map(|parameter| self.resolve(parameter)).collect(),
+                Box::new(self.resolve(return_type)),
+            ),
+            Type::Optional(inner) => Type::Optional(Box::new(self.resolve(inner))),
+            Type::Tuple(elements) => Type::Tuple(elements.iter().map(|element| self.resolve(element)).collect()),
+            _ => ty.clone(),
+        }
+    }
+
+    fn occurs(&self, var: usize, ty: &Type) -> bool {
+        match self.resolve(ty) {
+            Type::Var(other) => other == var,
+            Type::Array(element) | Type::Optional(element) => self.occurs(var, &element),
+            Type::Map(key, value) => self.occurs(var, &key) || self.occurs(var, &value),
+            Type::Function(parameters, return_type) => {
+                parameters.iter().any(|parameter| self.occurs(var, parameter))
+                    || self.occurs(var, &return_type)
+            }
+            Type::Tuple(elements) => elements.iter().any(|element| self.occurs(var, element)),
+            _ => false,
+        }
+    }
+
+    // On failure returns both types, resolved, for the diagnostic
+    fn unify(&mut self, expected: &Type, found: &Type) -> Result<(), (Type, Type)> {
+        if self.unify_inner(expected, found) {
+            return Ok(());
+        }
+        Err((self.resolve(expected), self.resolve(found)))
+    }
+
+    fn unify_inner(&mut self, expected: &Type, found: &Type) -> bool {
+        let expected = self.resolve(expected);
+        let found = self.resolve(found);
+        match (&expected, &found) {
+            (Type::Var(a), Type::Var(b)) if a == b => true,
+            (Type::Var(var), ty) | (ty, Type::Var(var)) => {
+                if self.occurs(*var, ty) {
+                    return false;
+                }
+                self.substitution[*var] = Some(ty.clone());
+                true
+            }
+            // Errors were already reported, don't cascade
+            (Type::Error, _) | (_, Type::Error) => true,
+            (Type::Array(a), Type::Array(b)) | (Type::Optional(a), Type::Optional(b)) => {
+                self.unify_inner(a, b)
+            }
+            (Type::Map(a_key, a_value), Type::Map(b_key, b_value)) => {
+                self.unify_inner(a_key, b_key) && self.unify_inner(a_value, b_value)
+            }
+            (Type::Function(a_parameters, a_return), Type::Function(b_parameters, b_return)) => {
+                a_parameters.len() == b_parameters.len()
+                    && a_parameters
+                        .iter()
+                        .zip(b_parameters)
+                        .all(|(a, b)| self.unify_inner(a, b))
+                    && self.unify_inner(a_return, b_return)
+            }
+            (Type::Tuple(a), Type::Tuple(b)) => {
+                a.len() == b.len() && a.iter().zip(b).all(|(a, b)| self.unify_inner(a, b))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    // A value may be stored where its own type or an optional of it is expected
+    fn check_assignable(
+        &mut self,
+        expected: &Type,
+        found: &Type,
+        span: &TextSpan,
+        expected_due_to: Option<&TextSpan>,
+    ) {
+        if let Type::Optional(inner) = self.resolve(expected) {
+            if self.unify(&inner, found).is_ok() {
+                return;
+            }
+        }
+        if let Err((expected, found)) = self.unify(expected, found) {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_mismatched_types(&expected, &found, span, expected_due_to);
+        }
+    }
+
+    fn resolve_type_expression(&mut self, type_expression: &ASTTypeExpression) -> Type {
+        match &type_expression.kind {
+            ASTTypeExpressionKind::Named(name) => match Type::from_name(&name.span.literal) {
+                Some(ty) => ty,
+                None if self.structs.contains_key(&name.span.literal) => Type::Struct(name.span.literal.clone()),
+                None if self.enums.contains_key(&name.span.literal) => Type::Enum(name.span.literal.clone()),
+                None => {
+                    self.diagnostics_bag.borrow_mut().report_unknown_type(name);
+                    Type::Error
+                }
+            },
+            ASTTypeExpressionKind::Array(element) => {
+                Type::Array(Box::new(self.resolve_type_expression(element)))
+            }
+            ASTTypeExpressionKind::Map(key, value) => {
+                let key_type = self.resolve_type_expression(key);
+                self.check_key(&key_type, &key.span);
+                Type::Map(Box::new(key_type), Box::new(self.resolve_type_expression(value)))
+            }
+            ASTTypeExpressionKind::Function(parameters, return_type) => Type::Function(
+                parameters
+                    .iter()
+                    .map(|parameter| self.resolve_type_expression(parameter))
+                    .collect(),
+                Box::new(self.resolve_type_expression(return_type)),
+            ),
+            ASTTypeExpressionKind::Optional(inner) => {
+                Type::Optional(Box::new(self.resolve_type_expression(inner)))
+            }
+            ASTTypeExpressionKind::Tuple(elements) => Type::Tuple(
+                elements
+                    .iter()
+                    .map(|element| self.resolve_type_expression(element))
+                    .collect(),
+            ),
+            ASTTypeExpressionKind::Error(_) => Type::Error,
+        }
+    }
+
+    // Checks the expression just visited against `expected`
+    fn expect(&mut self, expected: &Type, expr: &ASTExpression) {
+        let found = self.types[&expr.id].clone();
+        if let Err((expected, found)) = self.unify(expected, &found) {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_mismatched_types(&expected, &found, &expr.span, None);
+        }
+    }
+
+    // Visits an element of a list or map literal, which must have the type of
+    // the `first` one
+    fn check_element(&mut self, expected: &Type, expr: &ASTExpression, first: Option<&ASTExpression>) {
+        self.visit_expression(expr);
+        let found = self.last_type.clone();
+        let expected_due_to = first.filter(|first| first.id != expr.id).map(|first| &first.span);
+        if let Err((expected, found)) = self.unify(expected, &found) {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_mismatched_types(&expected, &found, &expr.span, expected_due_to);
+        }
+    }
+
+    // Maps are keyed by values compared by content, which only `int` and
+    // `string` are. A key type still unknown is left to a later use.
+    fn check_key(&mut self, key: &Type, span: &TextSpan) {
+        let key = self.resolve(key);
+        if !matches!(key, Type::Int | Type::String | Type::Var(_) | Type::Error) {
+            self.diagnostics_bag.borrow_mut().report_invalid_map_key(&key, span);
+        }
+    }
+
+    // Visits `target[index]` and returns the element type. The target is a
+    // list unless it is already known to be a map.
+    fn check_index(&mut self, target: &ASTExpression, index: &ASTExpression) -> Type {
+        self.visit_expression(target);
+        if let Type::Map(key, value) = self.resolve(&self.types[&target.id]) {
+            self.visit_expression(index);
+            self.expect(&key, index);
+            self.check_key(&self.types[&index.id].clone(), &index.span);
+            return *value;
+        }
+        let element = self.fresh();
+        self.expect(&Type::Array(Box::new(element.clone())), target);
+        self.visit_expression(index);
+        self.expect(&Type::Int, index);
+        element
+    }
+
+    // Type of the field `field` of `target`, which was just visited
+    fn field_type(&mut self, target: &ASTExpression, field: &Token) -> Type {
+        let ty = self.resolve(&self.types[&target.id]);
+        let fields = match &ty {
+            Type::Error => return Type::Error,
+            Type::Struct(name) => {
+                let struct_type = &self.structs[name];
+                if let Some(field_type) = struct_type.field(&field.span.literal) {
+                    return field_type.clone();
+                }
+                struct_type.field_names()
+            }
+            _ => Vec::new(),
+        };
+        self.diagnostics_bag.borrow_mut().report_unknown_field(&ty, field, &fields);
+        Type::Error
+    }
+
+    // Parameter and return types of a built-in, over a fresh element type
+    fn signature(&mut self, builtin: Builtin) -> (Vec<Type>, Type) {
+        let element = self.fresh();
+        let list = Type::Array(Box::new(element.clone()));
+        match builtin {
+            Builtin::Len => (vec![list], Type::Int),
+            Builtin::Push => (vec![list, element], Type::Int),
+            Builtin::Pop => (vec![list], element),
+            Builtin::Keys | Builtin::Values | Builtin::Has => {
+                let value = self.fresh();
+                let map = Type::Map(Box::new(element.clone()), Box::new(value.clone()));
+                match builtin {
+                    Builtin::Keys => (vec![map], Type::Array(Box::new(element))),
+                    Builtin::Values => (vec![map], Type::Array(Box::new(value))),
+                    _ => (vec![map, element], Type::Bool),
+                }
+            }
+        }
+    }
+
+    // Where the struct or enum `name` is declared, if it is
+    fn declared_type(&self, name: &str) -> Option<TextSpan> {
+        let struct_type = self.structs.get(name).map(|struct_type| &struct_type.name);
+        let enum_type = self.enums.get(name).map(|enum_type| &enum_type.name);
+        struct_type.or(enum_type).map(|name| name.span.clone())
+    }
+
+    // The enum declaring the variant `name`, and the variant. Variant names
+    // are unique across enums.
+    fn find_variant(&self, name: &str) -> Option<(&EnumType, &VariantType)> {
+        self.enums.values().find_map(|enum_type| {
+            let variant = enum_type.variants.iter().find(|variant| variant.name.span.literal == name)?;
+            Some((enum_type, variant))
+        })
+    }
+
+    // The type of the enum declaring the variant `name` and its payload types
+    fn variant_signature(&self, name: &str) -> Option<(Type, Vec<Type>)> {
+        let (enum_type, variant) = self.find_variant(name)?;
+        Some((Type::Enum(enum_type.name.span.literal.clone()), variant.payload.clone()))
+    }
+
+    // Unifies the type of the value a pattern matches with the type the
+    // pattern itself matches
+    fn check_pattern_type(&mut self, expected: &Type, found: &Type, pattern: &ASTPattern) -> bool {
+        if let Err((expected, found)) = self.unify(expected, found) {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_mismatched_types(&expected, &found, &pattern.span, None);
+            return false;
+        }
+        true
+    }
+
+    // Checks `pattern` against the type of the value it matches and collects
+    // the variables it binds. Returns the pattern as the exhaustiveness
+    // checker sees it, or `None` if it is erroneous.
+    fn check_pattern(
+        &mut self,
+        pattern: &ASTPattern,
+        expected: &Type,
+        bindings: &mut Vec<(Token, Type)>,
+    ) -> Option<Pattern> {
+        self.types.insert(pattern.id, expected.clone());
+        match &pattern.kind {
+            ASTPatternKind::Wildcard => Some(Pattern::Wildcard),
+            // A name is a unit variant when one is declared, a binding otherwise
+            ASTPatternKind::Identifier(identifier) => match self.variant_signature(&identifier.span.literal) {
+                Some((_, payload)) if !payload.is_empty() => {
+                    self.diagnostics_bag
+                        .borrow_mut()
+                        .report_payload_count_mismatch(identifier, payload.len(), 0, true);
+                    None
+                }
+                Some((ty, _)) => {
+                    let constructor = Constructor::Variant(identifier.span.literal.clone());
+                    self.check_pattern_type(expected, &ty, pattern)
+                        .then(|| Pattern::Constructor(constructor, Vec::new()))
+                }
+                None => {
+                    let literal = &identifier.span.literal;
+                    if let Some((previous, _)) = bindings.iter().find(|(previous, _)| previous.span.literal == *literal) {
+                        self.diagnostics_bag
+                            .borrow_mut()
+                            .report_duplicate_name("variable", "bound", identifier, &previous.span);
+                        return None;
+                    }
+                    bindings.push((identifier.clone(), expected.clone()));
+                    Some(Pattern::Wildcard)
+                }
+            },
+            ASTPatternKind::Number(number) => self
+                .check_pattern_type(expected, &Type::Int, pattern)
+                .then(|| Pattern::Constructor(Constructor::Int(*number), Vec::new())),
+            ASTPatternKind::String(string) => self
+                .check_pattern_type(expected, &Type::String, pattern)
+                .then(|| Pattern::Constructor(Constructor::String(string.clone()), Vec::new())),
+            ASTPatternKind::Variant(name, payload) => {
+                let Some((ty, payload_types)) = self.variant_signature(&name.span.literal) else {
+                    self.diagnostics_bag.borrow_mut().report_unknown_variant(name);
+                    self.bind_erroneous(pattern, bindings);
+                    return None;
+                };
+                if payload.len() != payload_types.len() {
+                    self.diagnostics_bag.borrow_mut().report_payload_count_mismatch(
+                        name,
+                        payload_types.len(),
+                        payload.len(),
+                        true,
+                    );
+                    self.bind_erroneous(pattern, bindings);
+                    return None;
+                }
+                if !self.check_pattern_type(expected, &ty, pattern) {
+                    return None;
+                }
+                // Every field is checked, even after an erroneous one
+                let fields: Vec<_> = payload
+                    .iter()
+                    .zip(&payload_types)
+                    .map(|(field, ty)| self.check_pattern(field, ty, bindings))
+                    .collect();
+                let constructor = Constructor::Variant(name.span.literal.clone());
+                Some(Pattern::Constructor(constructor, fields.into_iter().collect::<Option<_>>()?))
+            }
+            ASTPatternKind::Tuple(elements) => {
+                let types: Vec<_> = elements.iter().map(|_| self.fresh()).collect();
+                if !self.check_pattern_type(expected, &Type::Tuple(types.clone()), pattern) {
+                    self.bind_erroneous(pattern, bindings);
+                    return None;
+                }
+                let fields: Vec<_> = elements
+                    .iter()
+                    .zip(&types)
+                    .map(|(element, ty)| self.check_pattern(element, ty, bindings))
+                    .collect();
+                Some(Pattern::Constructor(Constructor::Tuple, fields.into_iter().collect::<Option<_>>()?))
+            }
+            ASTPatternKind::List(elements) => {
+                let element_type = self.fresh();
+                if !self.check_pattern_type(expected, &Type::Array(Box::new(element_type.clone())), pattern) {
+                    self.bind_erroneous(pattern, bindings);
+                    return None;
+                }
+                let fields: Vec<_> = elements
+                    .iter()
+                    .map(|element| self.check_pattern(element, &element_type, bindings))
+                    .collect();
+                let constructor = Constructor::List(elements.len());
+                Some(Pattern::Constructor(constructor, fields.into_iter().collect::<Option<_>>()?))
+            }
+            ASTPatternKind::Struct(name, fields, rest) => self.check_struct_pattern(pattern, name, fields, *rest, expected, bindings),
+        }
+    }
+
+    // Fields are matched in source order and given to the exhaustiveness
+    // checker in declaration order, `_` standing for those not listed
+    fn check_struct_pattern(
+        &mut self,
+        pattern: &ASTPattern,
+        name: &Token,
+        fields: &[ASTFieldPattern],
+        rest: bool,
+        expected: &Type,
+        bindings: &mut Vec<(Token, Type)>,
+    ) -> Option<Pattern> {
+        let Some(struct_type) = self.structs.get(&name.span.literal) else {
+            self.diagnostics_bag.borrow_mut().report_unknown_type(name);
+            self.bind_erroneous(pattern, bindings);
+            return None;
+        };
+        let (declared, names) = (struct_type.fields.clone(), struct_type.field_names());
+        let ty = Type::Struct(name.span.literal.clone());
+        if !self.check_pattern_type(expected, &ty, pattern) {
+            self.bind_erroneous(pattern, bindings);
+            return None;
+        }
+        let mut matched: Vec<(&Token, Option<Pattern>)> = Vec::new();
+        let mut erroneous = false;
+        for field in fields {
+            let literal = &field.name.span.literal;
+            if let Some((previous, _)) = matched.iter().find(|(previous, _)| previous.span.literal == *literal) {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_duplicate_name("field", "matched", &field.name, &previous.span);
+                self.bind_erroneous(&field.pattern, bindings);
+                erroneous = true;
+                continue;
+            }
+            match declared.iter().find(|(declaration, _)| declaration.span.literal == *literal) {
+                Some((_, field_type)) => {
+                    let checked = self.check_pattern(&field.pattern, field_type, bindings);
+                    matched.push((&field.name, checked));
+                }
+                None => {
+                    self.diagnostics_bag.borrow_mut().report_unknown_field(&ty, &field.name, &names);
+                    self.bind_erroneous(&field.pattern, bindings);
+                    erroneous = true;
+                }
+            }
+        }
+        let missing: Vec<_> = names
+            .iter()
+            .filter(|field| !matched.iter().any(|(matched, _)| matched.span.literal == **field))
+            .cloned()
+            .collect();
+        if !rest && !missing.is_empty() {
+            self.diagnostics_bag.borrow_mut().report_missing_fields(name, &missing, true);
+            erroneous = true;
+        }
+        let mut patterns = Vec::new();
+        for field in &names {
+            match matched.iter_mut().find(|(matched, _)| matched.span.literal == *field) {
+                Some((_, checked)) => patterns.push(checked.take()),
+                None => patterns.push(Some(Pattern::Wildcard)),
+            }
+        }
+        let patterns = patterns.into_iter().collect::<Option<_>>()?;
+        (!erroneous).then(|| Pattern::Constructor(Constructor::Struct(name.span.literal.clone(), names), patterns))
+    }
+
+    // Binds the variables of a pattern that failed to check, so their uses in
+    // the arm are not reported again
+    fn bind_erroneous(&self, pattern: &ASTPattern, bindings: &mut Vec<(Token, Type)>) {
+        for identifier in pattern.identifiers() {
+            let literal = &identifier.span.literal;
+            if self.find_variant(literal).is_none() && bindings.iter().all(|(bound, _)| bound.span.literal != *literal) {
+                bindings.push((identifier.clone(), Type::Error));
+            }
+        }
+    }
+
+    // Reports arms matching no value the arms before them leave, then values
+    // no arm matches
+    fn check_exhaustiveness(&mut self, match_expr: &ASTMatchExpression, patterns: Vec<Pattern>, scrutinee: &Type) {
+        let scrutinee = self.resolve(scrutinee);
+        if scrutinee == Type::Error {
+            return;
+        }
+        let usefulness = Usefulness::new(&self.enums, &self.structs, false);
+        let mut rows: Vec<Pattern> = Vec::new();
+        for (arm, pattern) in match_expr.arms.iter().zip(patterns) {
+            if !usefulness.is_useful(&rows, &pattern, &scrutinee) {
+                self.diagnostics_bag.borrow_mut().report_unreachable_pattern(&arm.pattern.span);
+            }
+            rows.push(pattern);
+        }
+        let missing: Vec<_> = usefulness
+            .missing(&rows, &scrutinee)
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+        if !missing.is_empty() {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_non_exhaustive_match(&scrutinee, &missing, &match_expr.scrutinee.span);
+        }
+    }
+
+    // Reports values of type `ty` that the pattern of a `let` does not match
+    fn check_refutability(&mut self, let_pattern: &ASTPattern, pattern: Pattern, ty: &Type) {
+        let ty = self.resolve(ty);
+        if ty == Type::Error {
+            return;
+        }
+        let missing: Vec<_> = Usefulness::new(&self.enums, &self.structs, true)
+            .missing(slice::from_ref(&pattern), &ty)
+            .iter()
+            .map(|pattern| pattern.to_string())
+            .collect();
+        if !missing.is_empty() {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_refutable_pattern(&ty, &missing, &let_pattern.span);
+        }
+    }
+
+    fn variable(&self, name: &str) -> Option<&Type> {
+        self.scopes.iter().rev().find_map(|scope| scope.get(name))
+    }
+
+    fn declare_variable(&mut self, name: String, ty: Type) {
+        self.scopes.last_mut().unwrap().insert(name, ty);
+    }
+
+    fn lookup_variable(&mut self, name: &str, span: &TextSpan) -> Type {
+        match self.variable(name) {
+            Some(ty) => ty.clone(),
+            None => {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_undeclared_variable(name, span);
+                Type::Error
+            }
+        }
+    }
+}
+
+impl ASTVisitor for TypeChecker {
+    fn visit_expression(&mut self, expr: &ASTExpression) {
+        let ty = self.fresh();
+        self.do_visit_expression(expr);
+        let found = self.last_type.clone();
+        let _ = self.unify(&ty, &found);
+        self.types.insert(expr.id, ty.clone());
+        self.last_type = ty;
+    }
+
+    fn visit_let_statement(&mut self, let_statement: &ASTLetStatement) {
+        self.visit_expression(&let_statement.initializer);
+        let mut ty = self.last_type.clone();
+        if let Some(type_annotation) = &let_statement.type_annotation {
+            let expected = self.resolve_type_expression(type_annotation);
+            self.check_assignable(
+                &expected,
+                &ty,
+                &let_statement.initializer.span,
+                Some(&type_annotation.span),
+            );
+            ty = expected;
+        }
+        let mut bindings = Vec::new();
+        if let Some(pattern) = self.check_pattern(&let_statement.pattern, &ty, &mut bindings) {
+            self.check_refutability(&let_statement.pattern, pattern, &ty);
+        }
+        for (identifier, ty) in bindings {
+            self.declare_variable(identifier.span.literal, ty);
+        }
+    }
+
+    // The variable takes each item of the iterable in turn and, like the
+    // variables declared in the body, is only visible there
+    fn visit_for_statement(&mut self, for_statement: &ASTForStatement) {
+        self.visit_expression(&for_statement.iterable);
+        let item = match self.resolve(&self.last_type) {
+            Type::Range => Type::Int,
+            Type::Array(element) => *element,
+            Type::String => Type::String,
+            Type::Map(key, _) => *key,
+            Type::Error => Type::Error,
+            ty => {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_not_iterable(&ty, &for_statement.iterable.span);
+                Type::Error
+            }
+        };
+        self.scopes.push(HashMap::from([(for_statement.variable.span.literal.clone(), item)]));
+        for statement in &for_statement.body {
+            self.visit_statement(statement);
+        }
+        self.scopes.pop();
+    }
+
+    // The struct is known from here on; fields may refer to it
+    fn visit_struct_statement(&mut self, struct_statement: &ASTStructStatement) {
+        let name = &struct_statement.name;
+        if let Some(previous) = self.declared_type(&name.span.literal) {
+            self.diagnostics_bag.borrow_mut().report_duplicate_name("type", "declared", name, &previous);
+            // Later uses keep checking against the first declaration
+            return;
+        }
+        let struct_type = StructType { name: name.clone(), fields: Vec::new() };
+        self.structs.insert(name.span.literal.clone(), struct_type);
+        let mut fields: Vec<(Token, Type)> = Vec::new();
+        for field in &struct_statement.fields {
+            let ty = self.resolve_type_expression(&field.type_annotation);
+            match fields.iter().find(|(previous, _)| previous.span.literal == field.name.span.literal) {
+                Some((previous, _)) => self.diagnostics_bag.borrow_mut().report_duplicate_name(
+                    "field",
+                    "declared",
+                    &field.name,
+                    &previous.span,
+                ),
+                None => fields.push((field.name.clone(), ty)),
+            }
+        }
+        self.structs.get_mut(&name.span.literal).unwrap().fields = fields;
+    }
+
+    // The enum is known from here on; payloads may refer to it
+    fn visit_enum_statement(&mut self, enum_statement: &ASTEnumStatement) {
+        let name = &enum_statement.name;
+        if let Some(previous) = self.declared_type(&name.span.literal) {
+            self.diagnostics_bag.borrow_mut().report_duplicate_name("type", "declared", name, &previous);
+            return;
+        }
+        let enum_type = EnumType { name: name.clone(), variants: Vec::new() };
+        self.enums.insert(name.span.literal.clone(), enum_type);
+        let mut variants: Vec<VariantType> = Vec::new();
+        for variant in &enum_statement.variants {
+            let payload = variant
+                .payload
+                .iter()
+                .map(|ty| self.resolve_type_expression(ty))
+                .collect();
+            let literal = &variant.name.span.literal;
+            let previous = variants
+                .iter()
+                .find(|previous| previous.name.span.literal == *literal)
+                .or_else(|| self.find_variant(literal).map(|(_, previous)| previous));
+            match previous {
+                Some(previous) => self.diagnostics_bag.borrow_mut().report_duplicate_name(
+                    "variant",
+                    "declared",
+                    &variant.name,
+                    &previous.name.span,
+                ),
+                None => variants.push(VariantType { name: variant.name.clone(), payload }),
+            }
+        }
+        self.enums.get_mut(&name.span.literal).unwrap().variants = variants;
+    }
+
+    fn visit_binary_expression(&mut self, binary_expr: &ASTBinaryExpression) {
+        self.visit_expression(&binary_expr.left);
+        let left = self.last_type.clone();
+        self.visit_expression(&binary_expr.right);
+        let right = self.last_type.clone();
+
+        // Arithmetic is only defined on integers
+        for operand in [&binary_expr.left, &binary_expr.right] {
+            let ty = self.types[&operand.id].clone();
+            if self.unify(&Type::Int, &ty).is_err() {
+                self.diagnostics_bag.borrow_mut().report_invalid_operands(
+                    &binary_expr.operator.token,
+                    &self.resolve(&left),
+                    &self.resolve(&right),
+                    &operand.span,
+                    &self.resolve(&ty),
+                );
+                break;
+            }
+        }
+        self.last_type = Type::Int;
+    }
+
+    // Ranges are of integers
+    fn visit_range_expression(&mut self, range_expr: &ASTRangeExpression) {
+        self.visit_expression(&range_expr.start);
+        let start = self.last_type.clone();
+        self.visit_expression(&range_expr.end);
+        let end = self.last_type.clone();
+        for bound in [&range_expr.start, &range_expr.end] {
+            let ty = self.types[&bound.id].clone();
+            if self.unify(&Type::Int, &ty).is_err() {
+                self.diagnostics_bag.borrow_mut().report_invalid_operands(
+                    &range_expr.operator,
+                    &self.resolve(&start),
+                    &self.resolve(&end),
+                    &bound.span,
+                    &self.resolve(&ty),
+                );
+                break;
+            }
+        }
+        self.last_type = Type::Range;
+    }
+
+    fn visit_assignment_expression(&mut self, assignment_expr: &ASTAssignmentExpression) {
+        self.visit_expression(&assignment_expr.expression);
+        let found = self.last_type.clone();
+        let identifier = &assignment_expr.identifier;
+        let expected = self.lookup_variable(&identifier.span.literal, &identifier.span);
+        self.check_assignable(&expected, &found, &assignment_expr.expression.span, None);
+        self.last_type = found;
+    }
+
+    // The elements take the type of the first one; `[]` gets a fresh one
+    fn visit_list_expression(&mut self, list_expr: &ASTListExpression) {
+        let element = self.fresh();
+        for expr in &list_expr.elements {
+            self.check_element(&element, expr, list_expr.elements.first());
+        }
+        self.last_type = Type::Array(Box::new(element));
+    }
+
+    // Entries take the key and value types of the first one
+    fn visit_map_expression(&mut self, map_expr: &ASTMapExpression) {
+        let (key, value) = (self.fresh(), self.fresh());
+        let first = map_expr.entries.first();
+        for entry in &map_expr.entries {
+            self.check_element(&key, &entry.key, first.map(|first| &first.key));
+            self.check_element(&value, &entry.value, first.map(|first| &first.value));
+        }
+        if let Some(first) = first {
+            self.check_key(&key, &first.key.span);
+        }
+        self.last_type = Type::Map(Box::new(key), Box::new(value));
+    }
+
+    fn visit_string_expression(&mut self, _string: &ASTStringExpression) {
+        self.last_type = Type::String;
+    }
+
+    fn visit_tuple_expression(&mut self, tuple_expr: &ASTTupleExpression) {
+        let elements = tuple_expr
+            .elements
+            .iter()
+            .map(|element| {
+                self.visit_expression(element);
+                self.last_type.clone()
+            })
+            .collect();
+        self.last_type = Type::Tuple(elements);
+    }
+
+    fn visit_index_expression(&mut self, index_expr: &ASTIndexExpression) {
+        self.last_type = self.check_index(&index_expr.target, &index_expr.index);
+    }
+
+    fn visit_index_assignment_expression(&mut self, assignment_expr: &ASTIndexAssignmentExpression) {
+        let element = self.check_index(&assignment_expr.target, &assignment_expr.index);
+        self.visit_expression(&assignment_expr.expression);
+        let found = self.last_type.clone();
+        self.check_assignable(&element, &found, &assignment_expr.expression.span, None);
+        self.last_type = found;
+    }
+
+    fn visit_slice_expression(&mut self, slice_expr: &ASTSliceExpression) {
+        let list = Type::Array(Box::new(self.fresh()));
+        self.visit_expression(&slice_expr.target);
+        self.expect(&list, &slice_expr.target);
+        for bound in [&slice_expr.start, &slice_expr.end].into_iter().flatten() {
+            self.visit_expression(bound);
+            self.expect(&Type::Int, bound);
+        }
+        self.last_type = list;
+    }
+
+    fn visit_struct_expression(&mut self, struct_expr: &ASTStructExpression) {
+        for field in &struct_expr.fields {
+            self.visit_expression(&field.value);
+        }
+        let name = &struct_expr.name;
+        let Some(struct_type) = self.structs.get(&name.span.literal) else {
+            self.diagnostics_bag.borrow_mut().report_unknown_type(name);
+            self.last_type = Type::Error;
+            return;
+        };
+        let (declared, names) = (struct_type.fields.clone(), struct_type.field_names());
+        let ty = Type::Struct(name.span.literal.clone());
+        let mut initialized: Vec<&Token> = Vec::new();
+        for field in &struct_expr.fields {
+            let literal = &field.name.span.literal;
+            if let Some(previous) = initialized.iter().find(|previous| previous.span.literal == *literal) {
+                self.diagnostics_bag
+                    .borrow_mut()
+                    .report_duplicate_name("field", "initialized", &field.name, &previous.span);
+                continue;
+            }
+            initialized.push(&field.name);
+            match declared.iter().find(|(declaration, _)| declaration.span.literal == *literal) {
+                Some((declaration, expected)) => {
+                    let found = self.types[&field.value.id].clone();
+                    self.check_assignable(expected, &found, &field.value.span, Some(&declaration.span));
+                }
+                None => self.diagnostics_bag.borrow_mut().report_unknown_field(&ty, &field.name, &names),
+            }
+        }
+        let missing: Vec<_> = names
+            .into_iter()
+            .filter(|field| !initialized.iter().any(|initialized| initialized.span.literal == *field))
+            .collect();
+        if !missing.is_empty() {
+            self.diagnostics_bag.borrow_mut().report_missing_fields(name, &missing, false);
+        }
+        self.last_type = ty;
+    }
+
+    fn visit_field_expression(&mut self, field_expr: &ASTFieldExpression) {
+        self.visit_expression(&field_expr.target);
+        self.last_type = self.field_type(&field_expr.target, &field_expr.field);
+    }
+
+    fn visit_field_assignment_expression(&mut self, assignment_expr: &ASTFieldAssignmentExpression) {
+        self.visit_expression(&assignment_expr.target);
+        let expected = self.field_type(&assignment_expr.target, &assignment_expr.field);
+        self.visit_expression(&assignment_expr.expression);
+        let found = self.last_type.clone();
+        self.check_assignable(&expected, &found, &assignment_expr.expression.span, None);
+        self.last_type = found;
+    }
+
+    fn visit_call_expression(&mut self, call_expr: &ASTCallExpression) {
+        for argument in &call_expr.arguments {
+            self.visit_expression(argument);
+        }
+        let callee = &call_expr.callee;
+        if let Some((ty, payload)) = self.variant_signature(&callee.span.literal) {
+            if call_expr.arguments.len() != payload.len() {
+                self.diagnostics_bag.borrow_mut().report_payload_count_mismatch(
+                    callee,
+                    payload.len(),
+                    call_expr.arguments.len(),
+                    false,
+                );
+            }
+            for (expected, argument) in payload.iter().zip(&call_expr.arguments) {
+                let found = self.types[&argument.id].clone();
+                self.check_assignable(expected, &found, &argument.span, None);
+            }
+            self.last_type = ty;
+            return;
+        }
+        let Some(builtin) = Builtin::from_name(&callee.span.literal) else {
+            self.diagnostics_bag.borrow_mut().report_unknown_function(callee);
+            self.last_type = Type::Error;
+            return;
+        };
+        if call_expr.arguments.len() != builtin.arity() {
+            self.diagnostics_bag
+                .borrow_mut()
+                .report_argument_count_mismatch(builtin, call_expr.arguments.len(), &callee.span);
+            self.last_type = Type::Error;
+            return;
+        }
+        let (parameters, return_type) = self.signature(builtin);
+        for (parameter, argument) in parameters.iter().zip(&call_expr.arguments) {
+            let found = self.types[&argument.id].clone();
+            self.check_assignable(parameter, &found, &argument.span, None);
+        }
+        self.last_type = return_type;
+    }
+
+    fn visit_variable_expression(&mut self, variable_expression: &ASTVariableExpression) {
+        let identifier = &variable_expression.identifier;
+        let literal = &identifier.span.literal;
+        // Variables hide unit variants of the same name
+        if self.variable(literal).is_none() {
+            if let Some((ty, payload)) = self.variant_signature(literal) {
+                if !payload.is_empty() {
+                    self.diagnostics_bag
+                        .borrow_mut()
+                        .report_payload_count_mismatch(identifier, payload.len(), 0, false);
+                }
+                self.last_type = ty;
+                return;
+            }
+        }
+        self.last_type = self.lookup_variable(literal, &identifier.span);
+    }
+
+    // Every arm has the type of the first one. Bindings of a pattern are only
+    // visible in its arm.
+    fn visit_match_expression(&mut self, match_expr: &ASTMatchExpression) {
+        self.visit_expression(&match_expr.scrutinee);
+        let scrutinee = self.last_type.clone();
+        let result = self.fresh();
+        let mut patterns = Vec::new();
+        for arm in &match_expr.arms {
+            let mut bindings = Vec::new();
+            patterns.push(self.check_pattern(&arm.pattern, &scrutinee, &mut bindings));
+            let scope = bindings
+                .into_iter()
+                .map(|(identifier, ty)| (identifier.span.literal, ty))
+                .collect();
+            self.scopes.push(scope);
+            self.check_element(&result, &arm.body, match_expr.arms.first().map(|first| &first.body));
+            self.scopes.pop();
+        }
+        // Coverage is only meaningful once every pattern checked
+        if let Some(patterns) = patterns.into_iter().collect::<Option<Vec<_>>>() {
+            self.check_exhaustiveness(match_expr, patterns, &scrutinee);
+        }
+        self.last_type = result;
+    }
+
+    fn visit_number_expression(&mut self, _number: &ASTNumberExpression) {
+        self.last_type = Type::Int;
+    }
+
+    fn visit_error(&mut self, _span: &TextSpan) {
+        self.last_type = Type::Error;
+    }
+}
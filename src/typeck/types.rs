@@ -0,0 +1,73 @@
+use std::fmt::Display;
+
+#[derive(Debug, PartialEq, Clone)]
+pub enum Type {
+    Int,
+    Float,
+    Bool,
+    String,
+    Array(Box<Type>),
+    Map(Box<Type>, Box<Type>), // Key and value
+    Function(Vec<Type>, Box<Type>),
+    Optional(Box<Type>),
+    Tuple(Vec<Type>),
+    Struct(String), // Declared with `struct`, compared by name
+    Enum(String),   // Declared with `enum`, compared by name
+    Range, // Of integers, as built by `..` and `..=`
+    Var(usize), // Inference variable, resolved through unification
+    Error,      // Type of an expression that already failed to check
+}
+
+impl Type {
+    // Primitive types that can be named in annotations
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "int" => Some(Type::Int),
+            "float" => Some(Type::Float),
+            "bool" => Some(Type::Bool),
+            "string" => Some(Type::String),
+            "range" => Some(Type::Range),
+            _ => None,
+        }
+    }
+}
+
+/* DISPLAY */
+
+impl Display for Type {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Type::Int => write!(f, "int"),
+            Type::Float => write!(f, "float"),
+            Type::Bool => write!(f, "bool"),
+            Type::String => write!(f, "string"),
+            Type::Array(element) => write!(f, "[{}]", element),
+            Type::Map(key, value) => write!(f, "{{{}: {}}}", key, value),
+            Type::Function(parameters, return_type) => {
+                write!(f, "fn(")?;
+                for (index, parameter) in parameters.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", parameter)?;
+                }
+                write!(f, ") -> {}", return_type)
+            }
+            Type::Optional(inner) => write!(f, "{}?", inner),
+            Type::Tuple(elements) => {
+                write!(f, "(")?;
+                for (index, element) in elements.iter().enumerate() {
+                    if index > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", element)?;
+                }
+                write!(f, "{})", if elements.len() == 1 { "," } else { "" })
+            }
+            Type::Struct(name) | Type::Enum(name) => write!(f, "{}", name),
+            Type::Range => write!(f, "range"),
+            Type::Var(_) => write!(f, "_"),
+            Type::Error => write!(f, "{{error}}"),
+        }
+    }
+}
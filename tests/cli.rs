@@ -0,0 +1,99 @@
+use std::{
+    env, fs,
+    path::PathBuf,
+    process::{Command, Output},
+};
+
+fn work_dir() -> PathBuf {
+    let dir = env::temp_dir().join(format!("sirin-cli-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+// Writes `source` to a file of its own and runs the `sirin` binary on it
+fn sirin(name: &str, args: &[&str], source: &str) -> Output {
+    let file = work_dir().join(name);
+    fs::write(&file, source).unwrap();
+    Command::new(env!("CARGO_BIN_EXE_sirin"))
+        .args(args)
+        .arg(&file)
+        .output()
+        .unwrap()
+}
+
+fn has_tool(name: &str) -> bool {
+    Command::new(name).arg("--version").output().is_ok_and(|output| output.status.success())
+}
+
+fn stdout(output: &Output) -> String {
+    String::from_utf8(output.stdout.clone()).unwrap()
+}
+
+fn stderr(output: &Output) -> String {
+    String::from_utf8(output.stderr.clone()).unwrap()
+}
+
+// Backends are compared with the interpreter on many programs by their own
+// tests; this only checks that `sirin build` drives `as` and `ld`
+#[test]
+fn build_links_an_executable() {
+    if !has_tool("as") || !has_tool("ld") {
+        eprintln!("skipped: `as` or `ld` is not available");
+        return;
+    }
+    let executable = work_dir().join("build");
+    let build = sirin("build.sn", &["build", "-o", executable.to_str().unwrap()], "let x = 300\nx = x + 1");
+    assert!(build.status.success(), "{}", stderr(&build));
+    let output = Command::new(&executable).output().unwrap();
+    assert_eq!(stdout(&output), "Some(301)\n");
+    assert_eq!(output.status.code(), Some(301 & 0xff));
+}
+
+#[test]
+fn structured_formats_still_run_the_program() {
+    for format in ["json", "sarif"] {
+        let name = format!("runs-{}.sn", format);
+        let output = sirin(&name, &["run", "--format", format], "let x = 2\nx * 21");
+        assert!(output.status.success(), "{}", stderr(&output));
+        assert_eq!(stdout(&output), "Some(42)\n");
+    }
+}
+
+#[test]
+fn json_lines_include_runtime_errors_on_stderr() {
+    let output = sirin("runtime-error.sn", &["run", "--format", "json"], "let xs = [1]\nxs[3]");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(stdout(&output), "");
+    let lines: Vec<_> = stderr(&output).lines().map(str::to_string).collect();
+    assert_eq!(lines.len(), 1, "{:?}", lines);
+    assert!(lines[0].starts_with("{\"severity\":\"error\",\"code\":\"E0014\""), "{}", lines[0]);
+}
+
+#[test]
+fn sarif_is_a_single_document_with_compile_and_runtime_diagnostics() {
+    let output = sirin("sarif.sn", &["run", "--format", "sarif"], "let unused = 1\nlet xs = [1]\nxs[3]");
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = stderr(&output);
+    assert_eq!(stderr.lines().count(), 1);
+    assert!(stderr.contains("\"ruleId\":\"W0003\"") && stderr.contains("\"ruleId\":\"E0014\""), "{}", stderr);
+}
+
+#[test]
+fn structured_formats_count_columns_in_characters() {
+    let source = "let s = \"ééé\" + 1";
+    let json = stderr(&sirin("columns.sn", &["run", "--format", "json"], source));
+    let plus = "\"range\":{\"start\":{\"line\":1,\"column\":15},\"end\":{\"line\":1,\"column\":16}}";
+    assert!(json.contains(plus), "{}", json);
+    let sarif = stderr(&sirin("columns.sn", &["run", "--format", "sarif"], source));
+    assert!(sarif.contains("\"columnKind\":\"unicodeCodePoints\""), "{}", sarif);
+    let string = "\"startLine\":1,\"startColumn\":9,\"endLine\":1,\"endColumn\":14,\"byteOffset\":8";
+    assert!(sarif.contains(string), "{}", sarif);
+}
+
+#[test]
+fn fix_closes_an_unclosed_parenthesis_in_place() {
+    let output = sirin("fix.sn", &["fix"], "let a = (1 + 2\na * 3");
+    assert!(output.status.success(), "{}", stderr(&output));
+    assert!(stdout(&output).starts_with("Applied 1 fix(es) to "), "{}", stdout(&output));
+    assert_eq!(fs::read_to_string(work_dir().join("fix.sn")).unwrap(), "let a = (1 + 2)\na * 3");
+}